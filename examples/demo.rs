@@ -0,0 +1,62 @@
+//! Withdrawal with hard-coded witnesses, checked with `MockProver`
+use halo2_proofs::{circuit::Value, dev::MockProver, halo2curves::pasta::Fp};
+use tornado_halo2::{
+    circuits::{merkle::MerklePathWitness, tornado::TornadoCircuit},
+    codec::to_hex,
+    ext_data::{address_to_field, asset_id, ext_data_hash},
+    native::{compute_root, hash_value, hash_values},
+};
+
+fn main() {
+    let nullifier = Fp::from(0x456);
+    let secret = Fp::from(0xabc);
+    let path_elements = [2, 5, 7, 14, 23].map(Fp::from);
+    let path_indices: Vec<Fp> = vec![0, 0, 1, 1, 0].iter().map(|e| Fp::from(*e)).collect();
+    // the same path indices, as the bits of the leaf index
+    let leaf_index = Fp::from(0b01100);
+    let ext_data_hash: Fp = ext_data_hash(&[0x11; 20], &[]);
+    let recipient: Fp = address_to_field(&[0x11; 20]);
+    let relayer: Fp = address_to_field(&[0x22; 20]);
+    let (fee, refund) = (Fp::from(50), Fp::from(0));
+    // 0.1 ETH, in gwei
+    let amount = Fp::from(100_000_000);
+    let asset_id: Fp = asset_id("eth");
+
+    let circuit = TornadoCircuit::<Fp, 5>::new(
+        Value::known(nullifier),
+        Value::known(secret),
+        MerklePathWitness::known(path_elements, leaf_index),
+        Value::known(ext_data_hash),
+        Value::known(recipient),
+        Value::known(relayer),
+        Value::known(fee),
+        Value::known(refund),
+        Value::known(amount),
+        Value::known(asset_id),
+    );
+
+    let commitment = hash_values(&[nullifier, secret, amount, asset_id]);
+    println!("commitment {}", to_hex(&commitment));
+
+    let root = compute_root(commitment, &path_elements, &path_indices)
+        .expect("path_elements and path_indices have the same length");
+    println!("root {}", to_hex(&root));
+
+    let nullifier_hash = hash_value(nullifier);
+    println!("nullifier_hash {}", to_hex(&nullifier_hash));
+
+    let public_input = vec![
+        nullifier_hash,
+        root,
+        ext_data_hash,
+        recipient,
+        relayer,
+        fee,
+        refund,
+        amount,
+        asset_id,
+    ];
+    let prover = MockProver::run(10, &circuit, vec![public_input]).unwrap();
+
+    println!("prover: {:?}", prover.verify());
+}
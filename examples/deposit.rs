@@ -0,0 +1,31 @@
+use halo2_proofs::{
+    halo2curves::bn256::{Bn256, Fr},
+    poly::kzg::commitment::ParamsKZG,
+};
+use rand_core::OsRng;
+use tornado_halo2::{
+    circuits::commitment::{CommitmentCircuit, CommitmentPublicInputs},
+    codec::to_hex,
+    note::Note,
+    prover::{keygen, prove, verify},
+};
+
+fn main() {
+    let note = Note::<Fr>::random("eth", "0.1", 1, OsRng).expect("valid note");
+    let circuit = CommitmentCircuit::from_note(&note);
+    // what the pool sees on deposit: the commitment, and the amount and asset paid
+    let public_inputs = CommitmentPublicInputs::from_note(&note);
+    let instances = public_inputs.to_instances();
+
+    let params = ParamsKZG::<Bn256>::setup(6, OsRng);
+    let pk = keygen(&params, &circuit).expect("keygen");
+    let proof = prove(&params, &pk, circuit, &instances).expect("prove");
+    println!(
+        "deposit of {}: {} bytes of proof",
+        to_hex(&public_inputs.commitment),
+        proof.len()
+    );
+
+    verify(&params, pk.get_vk(), &proof, &instances).expect("verify");
+    println!("commitment opening verified");
+}
@@ -0,0 +1,82 @@
+use halo2_proofs::{circuit::Value, halo2curves::bn256::Fr};
+use tornado_halo2::{codec::to_hex, note, params::load_or_generate, prelude::*};
+
+const DEPTH: usize = 20;
+
+fn main() {
+    // deposit
+    let nullifier = Fr::from(0x456);
+    let secret = Fr::from(0xabc);
+    // 0.1 ETH, in gwei
+    let amount = Fr::from(100_000_000);
+    let asset_id: Fr = asset_id("eth");
+    let commitment = note::commitment(nullifier, secret, amount, asset_id);
+
+    let mut tree = IncrementalMerkleTree::new(DEPTH);
+    for leaf in 1..=10u64 {
+        tree.insert(Fr::from(leaf)).expect("tree has room");
+    }
+    let index = tree.insert(commitment).expect("tree has room");
+    println!("deposited {} at index {index}", to_hex(&commitment));
+
+    // withdraw
+    let path = MerklePathWitness::from_tree(&tree, index).expect("the tree is DEPTH deep");
+    let ext_data_hash: Fr = ext_data_hash(&[0x11; 20], &[]);
+    // withdrawing through a relayer, which keeps a fee
+    let recipient: Fr = address_to_field(&[0x11; 20]);
+    let relayer: Fr = address_to_field(&[0x22; 20]);
+    let (fee, refund) = (Fr::from(50), Fr::from(0));
+    let circuit = TornadoCircuit::<Fr, DEPTH>::new(
+        Value::known(nullifier),
+        Value::known(secret),
+        path,
+        Value::known(ext_data_hash),
+        Value::known(recipient),
+        Value::known(relayer),
+        Value::known(fee),
+        Value::known(refund),
+        Value::known(amount),
+        Value::known(asset_id),
+    );
+    let public_inputs = TornadoPublicInputs {
+        nullifier_hash: nullifier * nullifier,
+        root: tree.root(),
+        ext_data_hash,
+        recipient,
+        relayer,
+        fee,
+        refund,
+        amount,
+        asset_id,
+    };
+    let instances = public_inputs.to_instances();
+
+    let k = TornadoCircuit::<Fr, DEPTH>::min_k();
+    let params_path = std::env::temp_dir().join(format!("tornado-halo2/kzg_bn256_{k}.params"));
+    let params = load_or_generate(&params_path, k).expect("params");
+    let pk = keygen(&params, &circuit).expect("keygen");
+    let transcript = prove(&params, &pk, circuit, &instances).expect("prove");
+
+    let circuit_id = tornado_circuit_id::<DEPTH>(pk.get_vk());
+    let proof = Proof::new(CurveId::Bn256, k, &instances, transcript).with_circuit_id(circuit_id);
+    let bytes = proof.to_bytes().expect("serialize proof");
+    println!("withdrawal proof: {} bytes serialized", bytes.len());
+
+    let proof = Proof::from_bytes(&bytes).expect("deserialize proof");
+    proof
+        .check_circuit_id(&circuit_id)
+        .expect("a proof of this circuit");
+    let public_inputs = proof
+        .public_inputs::<Fr>()
+        .ok()
+        .and_then(|instances| TornadoPublicInputs::from_instances(&instances))
+        .expect("withdrawal public inputs");
+    verify(
+        &params,
+        pk.get_vk(),
+        &proof.transcript,
+        &public_inputs.to_instances(),
+    )
+    .expect("verify");
+    println!("withdrawal proof verified");
+}
@@ -0,0 +1,28 @@
+use halo2_proofs::{
+    circuit::Value,
+    halo2curves::bn256::{Bn256, Fr},
+    poly::kzg::commitment::ParamsKZG,
+};
+use rand_core::OsRng;
+use tornado_halo2::{
+    circuits::hash::HashCircuit,
+    prover::{keygen, prove, verify},
+};
+
+fn main() {
+    let a = Fr::from(11);
+    let b = Fr::from(7);
+    let circuit = HashCircuit {
+        a: Value::known(a),
+        b: Value::known(b),
+    };
+    let instances = vec![vec![a * b]];
+
+    let params = ParamsKZG::<Bn256>::setup(4, OsRng);
+    let pk = keygen(&params, &circuit).expect("keygen");
+    let proof = prove(&params, &pk, circuit, &instances).expect("prove");
+    println!("hash proof: {} bytes", proof.len());
+
+    verify(&params, pk.get_vk(), &proof, &instances).expect("verify");
+    println!("hash proof verified");
+}
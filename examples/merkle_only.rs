@@ -0,0 +1,33 @@
+use halo2_proofs::{
+    circuit::Value,
+    halo2curves::bn256::{Bn256, Fr},
+    poly::kzg::commitment::ParamsKZG,
+};
+use rand_core::OsRng;
+use tornado_halo2::{
+    circuits::merkle::{MerkleCircuit, MerklePathWitness},
+    prover::{keygen, prove, verify},
+    tree::IncrementalMerkleTree,
+};
+
+const DEPTH: usize = 20;
+
+fn main() {
+    let mut tree = IncrementalMerkleTree::new(DEPTH);
+    for leaf in 1..=100u64 {
+        tree.insert(Fr::from(leaf)).expect("tree has room");
+    }
+    let leaf = tree.leaves()[42];
+    let path = MerklePathWitness::from_tree(&tree, 42).expect("the tree is DEPTH deep");
+
+    let circuit = MerkleCircuit::<Fr, DEPTH>::new(Value::known(leaf), path);
+    let instances = vec![vec![leaf, tree.root()]];
+
+    let params = ParamsKZG::<Bn256>::setup(8, OsRng);
+    let pk = keygen(&params, &circuit).expect("keygen");
+    let proof = prove(&params, &pk, circuit, &instances).expect("prove");
+    println!("depth {DEPTH} merkle proof: {} bytes", proof.len());
+
+    verify(&params, pk.get_vk(), &proof, &instances).expect("verify");
+    println!("merkle proof verified");
+}
@@ -0,0 +1,12 @@
+//! Every proof and note read from disk or the network is unwrapped first: decoding must fail
+//! cleanly, and an envelope that decodes is the only encoding of its contents.
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use tornado_halo2::envelope::Envelope;
+
+fuzz_target!(|data: &[u8]| {
+    if let Ok(envelope) = Envelope::from_bytes(data) {
+        assert_eq!(envelope.to_bytes(), data);
+    }
+});
@@ -0,0 +1,18 @@
+//! Notes are pasted or scanned by users: parsing either encoding must fail cleanly, and a note
+//! that parses must encode back to a note equal to it.
+#![no_main]
+
+use halo2_proofs::halo2curves::bn256::Fr;
+use libfuzzer_sys::fuzz_target;
+use tornado_halo2::note::Note;
+
+fuzz_target!(|data: &[u8]| {
+    if let Ok(s) = std::str::from_utf8(data) {
+        if let Ok(note) = s.parse::<Note<Fr>>() {
+            assert_eq!(note.to_string().parse::<Note<Fr>>().unwrap(), note);
+        }
+    }
+    if let Ok(note) = Note::<Fr>::from_bytes(data) {
+        assert_eq!(Note::<Fr>::from_bytes(&note.to_bytes()).unwrap(), note);
+    }
+});
@@ -0,0 +1,16 @@
+//! Relayers decode proofs sent by anyone, before verifying them
+#![no_main]
+
+use halo2_proofs::halo2curves::bn256::Fr;
+use libfuzzer_sys::fuzz_target;
+use tornado_halo2::proof::Proof;
+
+fuzz_target!(|data: &[u8]| {
+    if let Ok(proof) = Proof::from_bytes(data) {
+        let _ = proof.public_inputs::<Fr>();
+        assert_eq!(
+            Proof::from_bytes(&proof.to_bytes().unwrap()).unwrap(),
+            proof
+        );
+    }
+});
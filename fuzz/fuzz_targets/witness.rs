@@ -0,0 +1,15 @@
+//! `prove-witness` reads JSON written by other tools, see `src/witness.rs`
+#![no_main]
+
+use halo2_proofs::halo2curves::bn256::Fr;
+use libfuzzer_sys::fuzz_target;
+use tornado_halo2::witness::{PublicFile, WitnessFile};
+
+fuzz_target!(|data: &[u8]| {
+    if let Ok(witness) = serde_json::from_slice::<WitnessFile>(data) {
+        let _ = witness.parse::<Fr>();
+    }
+    if let Ok(public) = serde_json::from_slice::<PublicFile>(data) {
+        let _ = public.public_inputs::<Fr>();
+    }
+});
@@ -0,0 +1,121 @@
+#ifndef TORNADO_HALO2_H
+#define TORNADO_HALO2_H
+
+/* Generated with cbindgen from src/ffi.rs, do not edit by hand */
+
+#include <stdarg.h>
+#include <stdbool.h>
+#include <stdint.h>
+#include <stdlib.h>
+
+// Depth of the pool's tree
+#define TREE_DEPTH 20
+
+typedef enum TornadoStatus {
+  TORNADO_STATUS_OK = 0,
+  // A pointer is null or a string isn't UTF-8
+  TORNADO_STATUS_INVALID_ARGUMENT = 1,
+  // A note, JSON file or proof couldn't be parsed
+  TORNADO_STATUS_INVALID_INPUT = 2,
+  // The params file is missing, doesn't match its `.sha256` sidecar or has the wrong size
+  TORNADO_STATUS_INVALID_PARAMS = 3,
+  TORNADO_STATUS_PROVING_FAILED = 4,
+  TORNADO_STATUS_VERIFICATION_FAILED = 5,
+  // A bug: the call panicked
+  TORNADO_STATUS_PANIC = 6,
+  // The proof is valid for other public inputs than the expected ones
+  TORNADO_STATUS_WRONG_PUBLIC_INPUTS = 7,
+} TornadoStatus;
+
+// The proving key of the withdrawal circuit, built once by `tornado_prover_new`. Building it
+// costs about as much as a proof at `TREE_DEPTH`, so wallets proving more than once should
+// keep a prover rather than call `tornado_prove`.
+typedef struct TornadoProver TornadoProver;
+
+// Bytes allocated by this library, see the ownership rules above
+typedef struct TornadoBuffer {
+  uint8_t *data;
+  uintptr_t len;
+} TornadoBuffer;
+
+// `k` of the withdrawal circuit for `TREE_DEPTH`, i.e. the smallest one it fits in. The params
+// file must be generated for this `k`.
+uint32_t tornado_k(void);
+
+// Generates a random note for the `currency` pool, e.g. `eth`. On success, `note_out` holds
+// the note string (UTF-8, not NUL-terminated) and `commitment_out` the 32-byte big-endian
+// commitment to deposit.
+//
+// # Safety
+//
+// `currency` and `denomination` must be valid NUL-terminated strings, `note_out` must point
+// to a writable `TornadoBuffer` and `commitment_out` to 32 writable bytes.
+TornadoStatus tornado_note_new(const char *currency,
+                               const char *denomination,
+                               uint64_t chain_id,
+                               TornadoBuffer *note_out,
+                               uint8_t (*commitment_out)[32]);
+
+// Loads the params and builds the proving key. On success, `prover_out` holds a prover to
+// pass to `tornado_prover_prove` and release with `tornado_prover_free`.
+//
+// # Safety
+//
+// `params_path` must be a valid NUL-terminated string and `prover_out` must point to a
+// writable pointer.
+TornadoStatus tornado_prover_new(const char *params_path, TornadoProver **prover_out);
+
+// Proves a withdrawal from the JSON witness and public inputs described in `crate::witness`.
+// On success, `proof_out` holds the proof in the `Proof` binary format.
+//
+// # Safety
+//
+// `prover` must come from `tornado_prover_new` and not have been freed, `witness_json` and
+// `public_json` must be valid NUL-terminated strings and `proof_out` must point to a writable
+// `TornadoBuffer`.
+TornadoStatus tornado_prover_prove(const TornadoProver *prover,
+                                   const char *witness_json,
+                                   const char *public_json,
+                                   TornadoBuffer *proof_out);
+
+// Releases a prover. Freeing a null prover is a no-op.
+//
+// # Safety
+//
+// `prover` must come from `tornado_prover_new` and must not be used or freed again afterwards.
+void tornado_prover_free(TornadoProver *prover);
+
+// `tornado_prover_prove` with a prover built for this call only. Building the proving key
+// roughly doubles the cost of the proof, see `TornadoProver`.
+//
+// # Safety
+//
+// `params_path`, `witness_json` and `public_json` must be valid NUL-terminated strings and
+// `proof_out` must point to a writable `TornadoBuffer`.
+TornadoStatus tornado_prove(const char *params_path,
+                            const char *witness_json,
+                            const char *public_json,
+                            TornadoBuffer *proof_out);
+
+// Verifies that a proof written by `tornado_prove` is valid for the public inputs of
+// `public_json`, in the format described in `crate::witness`. Returns `Ok` if it is,
+// `WrongPublicInputs` if the proof is for other public inputs, e.g. another note or
+// recipient, and `VerificationFailed` if it isn't valid.
+//
+// # Safety
+//
+// `params_path` and `public_json` must be valid NUL-terminated strings and `proof` must point
+// to `proof_len` readable bytes.
+TornadoStatus tornado_verify(const char *params_path,
+                             const char *public_json,
+                             const uint8_t *proof,
+                             uintptr_t proof_len);
+
+// Releases a buffer filled in by this library. Freeing a buffer with a null `data` is a no-op.
+//
+// # Safety
+//
+// `buffer` must come from this library and must not be used or freed again afterwards.
+void tornado_buffer_free(TornadoBuffer buffer);
+
+#endif /* TORNADO_HALO2_H */
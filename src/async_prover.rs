@@ -0,0 +1,324 @@
+//! `prover::prove` off the caller's thread, for applications that must stay responsive during
+//! a multi-second proof, e.g. a GUI wallet. [`prove_async`] reports the coarse [`Stage`] the
+//! proof is in and stops early once its [`CancellationToken`] is cancelled.
+//!
+//! halo2 doesn't report its progress, so it is read from the calls it makes: synthesis starts
+//! when the circuit is synthesized, commitments when the first point is written to the
+//! transcript, and the opening when the first evaluation is. Cancellation is checked at each of
+//! these calls, so a proof stops within one commitment of being cancelled.
+use crate::{error::TornadoError, oneshot, prover};
+use halo2_proofs::{
+    circuit::Layouter,
+    halo2curves::bn256::{Bn256, Fr, G1Affine},
+    plonk::{self, Circuit, ConstraintSystem, ProvingKey},
+    poly::kzg::commitment::ParamsKZG,
+    transcript::{Blake2bWrite, Challenge255, Transcript, TranscriptWrite, TranscriptWriterBuffer},
+};
+use std::{
+    future::Future,
+    io, panic,
+    pin::Pin,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, Mutex, PoisonError,
+    },
+    task::{Context, Poll},
+    thread::{self, JoinHandle},
+};
+
+/// Stages of a proof, in the order they run
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Stage {
+    /// Witness generation: the circuit assigns its cells
+    Synthesis,
+    /// Commitments to the advice columns, the lookups, the permutation and the quotient
+    Commit,
+    /// Evaluations of the committed polynomials and the opening proof
+    Open,
+}
+
+/// Shared by the caller and a proof: once cancelled, the proof fails with
+/// `TornadoError::Cancelled` at its next stage or commitment
+#[derive(Debug, Clone, Default)]
+pub struct CancellationToken(Arc<AtomicBool>);
+
+impl CancellationToken {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn cancel(&self) {
+        self.0.store(true, Ordering::Relaxed);
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.0.load(Ordering::Relaxed)
+    }
+}
+
+/// The stage a proof reached, reported to `on_progress` when it changes
+struct Progress {
+    stage: Mutex<Option<Stage>>,
+    on_progress: Box<dyn Fn(Stage) + Send + Sync>,
+    cancel: CancellationToken,
+}
+
+impl Progress {
+    /// Fails once the proof is cancelled, otherwise moves it to `stage` if it isn't past it
+    fn checkpoint(&self, stage: Stage) -> io::Result<()> {
+        if self.cancel.is_cancelled() {
+            return Err(io::Error::new(
+                io::ErrorKind::Interrupted,
+                "proof cancelled",
+            ));
+        }
+        let mut current = self.stage.lock().unwrap_or_else(PoisonError::into_inner);
+        if current.map_or(true, |current| current < stage) {
+            *current = Some(stage);
+            (self.on_progress)(stage);
+        }
+        Ok(())
+    }
+}
+
+/// `circuit`, reporting `Stage::Synthesis` when it is synthesized. Its configuration is the
+/// circuit's, so it proves with the circuit's proving key.
+struct TrackedCircuit<C> {
+    circuit: C,
+    progress: Arc<Progress>,
+}
+
+impl<C: Circuit<Fr>> Circuit<Fr> for TrackedCircuit<C> {
+    type Config = C::Config;
+    type FloorPlanner = C::FloorPlanner;
+
+    fn without_witnesses(&self) -> Self {
+        Self {
+            circuit: self.circuit.without_witnesses(),
+            progress: self.progress.clone(),
+        }
+    }
+
+    fn configure(meta: &mut ConstraintSystem<Fr>) -> Self::Config {
+        C::configure(meta)
+    }
+
+    fn synthesize(
+        &self,
+        config: Self::Config,
+        layouter: impl Layouter<Fr>,
+    ) -> Result<(), plonk::Error> {
+        self.progress
+            .checkpoint(Stage::Synthesis)
+            .map_err(|_| plonk::Error::Synthesis)?;
+        self.circuit.synthesize(config, layouter)
+    }
+}
+
+/// `transcript`, reporting `Stage::Commit` at the first point written and `Stage::Open` at the
+/// first scalar
+struct TrackedTranscript<'a, T> {
+    transcript: T,
+    progress: &'a Progress,
+}
+
+impl<T> Transcript<G1Affine, Challenge255<G1Affine>> for TrackedTranscript<'_, T>
+where
+    T: Transcript<G1Affine, Challenge255<G1Affine>>,
+{
+    fn squeeze_challenge(&mut self) -> Challenge255<G1Affine> {
+        self.transcript.squeeze_challenge()
+    }
+
+    fn common_point(&mut self, point: G1Affine) -> io::Result<()> {
+        self.transcript.common_point(point)
+    }
+
+    fn common_scalar(&mut self, scalar: Fr) -> io::Result<()> {
+        self.transcript.common_scalar(scalar)
+    }
+}
+
+impl<T> TranscriptWrite<G1Affine, Challenge255<G1Affine>> for TrackedTranscript<'_, T>
+where
+    T: TranscriptWrite<G1Affine, Challenge255<G1Affine>>,
+{
+    fn write_point(&mut self, point: G1Affine) -> io::Result<()> {
+        self.progress.checkpoint(Stage::Commit)?;
+        self.transcript.write_point(point)
+    }
+
+    fn write_scalar(&mut self, scalar: Fr) -> io::Result<()> {
+        self.progress.checkpoint(Stage::Open)?;
+        self.transcript.write_scalar(scalar)
+    }
+}
+
+fn prove_tracked<C: Circuit<Fr>>(
+    params: &ParamsKZG<Bn256>,
+    pk: &ProvingKey<G1Affine>,
+    circuit: C,
+    instances: &[Vec<Fr>],
+    progress: Arc<Progress>,
+) -> Result<Vec<u8>, TornadoError> {
+    let instances: Vec<&[Fr]> = instances.iter().map(|column| column.as_slice()).collect();
+    let mut transcript = TrackedTranscript {
+        transcript: Blake2bWrite::<_, G1Affine, Challenge255<_>>::init(vec![]),
+        progress: &progress,
+    };
+    let circuit = TrackedCircuit {
+        circuit,
+        progress: progress.clone(),
+    };
+    match prover::write_proof(params, pk, circuit, &instances, &mut transcript) {
+        Ok(()) => Ok(transcript.transcript.finalize()),
+        // the error is whichever halo2 made of the failed checkpoint
+        Err(_) if progress.cancel.is_cancelled() => Err(TornadoError::Cancelled),
+        Err(e) => Err(e),
+    }
+}
+
+/// Same as `prover::prove`, on a thread of its own: halo2 still spreads the proof over its
+/// rayon pool, but the caller's thread (or its executor) is free meanwhile. `on_progress` is
+/// called from the proving thread when the proof enters a stage. Dropping the returned future
+/// cancels the proof.
+pub fn prove_async<C>(
+    params: Arc<ParamsKZG<Bn256>>,
+    pk: Arc<ProvingKey<G1Affine>>,
+    circuit: C,
+    instances: Vec<Vec<Fr>>,
+    on_progress: impl Fn(Stage) + Send + Sync + 'static,
+    cancel: CancellationToken,
+) -> ProofTask
+where
+    C: Circuit<Fr> + Send + 'static,
+{
+    let progress = Arc::new(Progress {
+        stage: Mutex::new(None),
+        on_progress: Box::new(on_progress),
+        cancel: cancel.clone(),
+    });
+    let (sender, receiver) = oneshot::channel();
+    let thread = thread::spawn(move || {
+        sender.send(prove_tracked(&params, &pk, circuit, &instances, progress));
+    });
+    ProofTask {
+        receiver,
+        thread: Some(thread),
+        cancel,
+    }
+}
+
+/// A proof running on its own thread, resolving to the transcript bytes of `prover::prove`
+pub struct ProofTask {
+    receiver: oneshot::Receiver<Result<Vec<u8>, TornadoError>>,
+    thread: Option<JoinHandle<()>>,
+    cancel: CancellationToken,
+}
+
+impl Future for ProofTask {
+    type Output = Result<Vec<u8>, TornadoError>;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        match Pin::new(&mut self.receiver).poll(cx) {
+            Poll::Ready(Some(result)) => Poll::Ready(result),
+            Poll::Ready(None) => {
+                // the proving thread panicked, e.g. in the circuit's synthesis
+                let thread = self.thread.take().expect("polled after completion");
+                match thread.join() {
+                    Err(payload) => panic::resume_unwind(payload),
+                    Ok(()) => unreachable!("the thread sends a result before returning"),
+                }
+            }
+            Poll::Pending => Poll::Pending,
+        }
+    }
+}
+
+impl Drop for ProofTask {
+    fn drop(&mut self) {
+        // a no-op once the proof is done
+        self.cancel.cancel();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{prove_async, CancellationToken, Stage};
+    use crate::{
+        error::TornadoError,
+        prover::{keygen, verify},
+        test_utils::{block_on, random_withdrawal},
+    };
+    use halo2_proofs::{
+        halo2curves::bn256::{Bn256, Fr},
+        poly::kzg::commitment::ParamsKZG,
+    };
+    use rand_core::OsRng;
+    use std::sync::{Arc, Mutex};
+
+    #[test]
+    fn test_prove_async() {
+        let params = Arc::new(ParamsKZG::<Bn256>::setup(8, OsRng));
+        let (circuit, public_inputs) = random_withdrawal::<Fr, 5>(OsRng);
+        let pk = Arc::new(keygen(&params, &circuit).unwrap());
+        let instances = public_inputs.to_instances();
+
+        let stages = Arc::new(Mutex::new(vec![]));
+        let reported = stages.clone();
+        let proof = block_on(prove_async(
+            params.clone(),
+            pk.clone(),
+            circuit,
+            instances.clone(),
+            move |stage| reported.lock().unwrap().push(stage),
+            CancellationToken::new(),
+        ))
+        .unwrap();
+        assert!(verify(&params, pk.get_vk(), &proof, &instances).is_ok());
+        assert_eq!(
+            *stages.lock().unwrap(),
+            [Stage::Synthesis, Stage::Commit, Stage::Open]
+        );
+
+        let (circuit, public_inputs) = random_withdrawal::<Fr, 5>(OsRng);
+        let cancel = CancellationToken::new();
+        cancel.cancel();
+        let cancelled = prove_async(
+            params,
+            pk,
+            circuit,
+            public_inputs.to_instances(),
+            |_| panic!("a cancelled proof doesn't start"),
+            cancel,
+        );
+        assert!(matches!(block_on(cancelled), Err(TornadoError::Cancelled)));
+    }
+
+    #[test]
+    fn test_cancel_while_proving() {
+        let params = Arc::new(ParamsKZG::<Bn256>::setup(8, OsRng));
+        let (circuit, public_inputs) = random_withdrawal::<Fr, 5>(OsRng);
+        let pk = Arc::new(keygen(&params, &circuit).unwrap());
+
+        let cancel = CancellationToken::new();
+        let stages = Arc::new(Mutex::new(vec![]));
+        let (reported, canceller) = (stages.clone(), cancel.clone());
+        let on_progress = move |stage| {
+            reported.lock().unwrap().push(stage);
+            if stage == Stage::Commit {
+                canceller.cancel();
+            }
+        };
+        let task = prove_async(
+            params,
+            pk,
+            circuit,
+            public_inputs.to_instances(),
+            on_progress,
+            cancel,
+        );
+        assert!(matches!(block_on(task), Err(TornadoError::Cancelled)));
+        assert_eq!(*stages.lock().unwrap(), [Stage::Synthesis, Stage::Commit]);
+    }
+}
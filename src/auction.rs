@@ -0,0 +1,352 @@
+//! Optional coordination layer between users and relayers.
+//!
+//! Users post sealed withdrawal intents (only a digest and the maximum fee they accept are public),
+//! relayers bid a per-withdrawal fee, and once bidding closes the lowest bid wins every intent
+//! whose maximum fee covers it. The relayer and fee are public inputs of a withdrawal, so users
+//! only prove once the winner is known, then reveal their intent along with a proof paying it.
+//! The winner gets back a `Batch` of those proofs to submit. The auction doesn't aggregate them
+//! nor verify their transcripts, which is left to the relayer (see `relayer::Relayer`).
+use crate::{
+    circuits::tornado::TornadoPublicInputs,
+    ext_data::address_to_field,
+    proof::{CurveId, Proof},
+};
+use halo2_proofs::halo2curves::{bn256::Fr, ff::PrimeField};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::{collections::BTreeMap, fmt};
+
+#[derive(Debug)]
+pub enum Error {
+    WrongPhase,
+    NoBids,
+    UnknownIntent(usize),
+    /// The revealed intent doesn't hash to the sealed digest
+    DigestMismatch(usize),
+    /// The proof isn't a bn256 withdrawal
+    InvalidProof(usize),
+    /// The proof withdraws another note than the intent
+    NoteMismatch(usize),
+    /// The proof doesn't pay the winning relayer
+    RelayerMismatch(usize),
+    /// The proof's fee isn't the winning bid
+    FeeMismatch(usize),
+    Encoding(bincode::Error),
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::WrongPhase => write!(f, "action not allowed in the current auction phase"),
+            Error::NoBids => write!(f, "no relayer bid on the auction"),
+            Error::UnknownIntent(id) => write!(f, "intent {id} is not part of the winning batch"),
+            Error::DigestMismatch(id) => write!(f, "intent {id} doesn't match its sealed digest"),
+            Error::InvalidProof(id) => write!(f, "proof of intent {id} is not a withdrawal"),
+            Error::NoteMismatch(id) => write!(f, "proof of intent {id} withdraws another note"),
+            Error::RelayerMismatch(id) => {
+                write!(f, "proof of intent {id} doesn't pay the winning relayer")
+            }
+            Error::FeeMismatch(id) => write!(f, "proof of intent {id} doesn't pay the winning fee"),
+            Error::Encoding(e) => write!(f, "intent encoding error: {e}"),
+        }
+    }
+}
+
+impl std::error::Error for Error {}
+
+impl From<bincode::Error> for Error {
+    fn from(e: bincode::Error) -> Self {
+        Error::Encoding(e)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct WithdrawalIntent {
+    /// Representation of the nullifier hash of the note to withdraw
+    pub nullifier_hash: [u8; 32],
+    pub max_fee: u64,
+}
+
+impl WithdrawalIntent {
+    /// `sha256(bincode(intent) || salt)`. The salt keeps low-entropy intents from being brute-forced.
+    pub fn seal(&self, salt: &[u8; 32]) -> Result<SealedIntent, Error> {
+        Ok(SealedIntent {
+            digest: self.digest(salt)?,
+            max_fee: self.max_fee,
+        })
+    }
+
+    fn digest(&self, salt: &[u8; 32]) -> Result<[u8; 32], Error> {
+        let mut hasher = Sha256::new();
+        hasher.update(bincode::serialize(self)?);
+        hasher.update(salt);
+        Ok(hasher.finalize().into())
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SealedIntent {
+    pub digest: [u8; 32],
+    pub max_fee: u64,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Bid {
+    /// The address the relayer is paid at, the `relayer` public input of the withdrawals it wins
+    pub relayer: [u8; 20],
+    pub fee: u64,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Batch {
+    pub relayer: [u8; 20],
+    pub fee: u64,
+    pub withdrawals: Vec<Proof>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Phase {
+    Bidding,
+    Revealing,
+    Settled,
+}
+
+#[derive(Debug)]
+pub struct Auction {
+    phase: Phase,
+    intents: Vec<SealedIntent>,
+    bids: Vec<Bid>,
+    winner: Option<Bid>,
+    revealed: BTreeMap<usize, Proof>,
+}
+
+impl Default for Auction {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Auction {
+    pub fn new() -> Self {
+        Self {
+            phase: Phase::Bidding,
+            intents: vec![],
+            bids: vec![],
+            winner: None,
+            revealed: BTreeMap::new(),
+        }
+    }
+
+    pub fn phase(&self) -> Phase {
+        self.phase
+    }
+
+    pub fn winner(&self) -> Option<&Bid> {
+        self.winner.as_ref()
+    }
+
+    /// Returns the intent id, used to reveal it later
+    pub fn post_intent(&mut self, intent: SealedIntent) -> Result<usize, Error> {
+        if self.phase != Phase::Bidding {
+            return Err(Error::WrongPhase);
+        }
+        self.intents.push(intent);
+        Ok(self.intents.len() - 1)
+    }
+
+    pub fn bid(&mut self, bid: Bid) -> Result<(), Error> {
+        if self.phase != Phase::Bidding {
+            return Err(Error::WrongPhase);
+        }
+        self.bids.push(bid);
+        Ok(())
+    }
+
+    /// Picks the lowest fee (earliest bid wins ties) and returns the ids of the intents it covers
+    pub fn close_bidding(&mut self) -> Result<Vec<usize>, Error> {
+        if self.phase != Phase::Bidding {
+            return Err(Error::WrongPhase);
+        }
+        let winner = self
+            .bids
+            .iter()
+            .enumerate()
+            .min_by_key(|(i, bid)| (bid.fee, *i))
+            .map(|(_, bid)| bid.clone())
+            .ok_or(Error::NoBids)?;
+        self.winner = Some(winner);
+        self.phase = Phase::Revealing;
+        Ok(self.winning_intents())
+    }
+
+    fn winning_intents(&self) -> Vec<usize> {
+        let fee = match &self.winner {
+            Some(winner) => winner.fee,
+            None => return vec![],
+        };
+        (0..self.intents.len())
+            .filter(|id| self.intents[*id].max_fee >= fee)
+            .collect()
+    }
+
+    /// Opens intent `id` and records `proof`, which must withdraw its note and pay the winner
+    pub fn reveal(
+        &mut self,
+        id: usize,
+        intent: &WithdrawalIntent,
+        salt: &[u8; 32],
+        proof: Proof,
+    ) -> Result<(), Error> {
+        if self.phase != Phase::Revealing {
+            return Err(Error::WrongPhase);
+        }
+        if !self.winning_intents().contains(&id) {
+            return Err(Error::UnknownIntent(id));
+        }
+        if intent.seal(salt)? != self.intents[id] {
+            return Err(Error::DigestMismatch(id));
+        }
+        let winner = self.winner.as_ref().ok_or(Error::NoBids)?;
+        if proof.curve != CurveId::Bn256 {
+            return Err(Error::InvalidProof(id));
+        }
+        let public_inputs = proof
+            .public_inputs::<Fr>()
+            .ok()
+            .and_then(|instances| TornadoPublicInputs::from_instances(&instances))
+            .ok_or(Error::InvalidProof(id))?;
+        if public_inputs.nullifier_hash.to_repr() != intent.nullifier_hash {
+            return Err(Error::NoteMismatch(id));
+        }
+        if public_inputs.relayer != address_to_field(&winner.relayer) {
+            return Err(Error::RelayerMismatch(id));
+        }
+        if public_inputs.fee != Fr::from(winner.fee) {
+            return Err(Error::FeeMismatch(id));
+        }
+        self.revealed.insert(id, proof);
+        Ok(())
+    }
+
+    /// Hands the revealed withdrawals to the winning relayer, in intent order.
+    /// Intents that were never revealed are left out.
+    pub fn settle(&mut self) -> Result<Batch, Error> {
+        if self.phase != Phase::Revealing {
+            return Err(Error::WrongPhase);
+        }
+        let winner = self.winner.clone().ok_or(Error::NoBids)?;
+        self.phase = Phase::Settled;
+        Ok(Batch {
+            relayer: winner.relayer,
+            fee: winner.fee,
+            withdrawals: std::mem::take(&mut self.revealed).into_values().collect(),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Auction, Bid, Error, Phase, WithdrawalIntent};
+    use crate::{
+        circuits::tornado::TornadoPublicInputs,
+        ext_data::address_to_field,
+        proof::{CurveId, Proof},
+    };
+    use halo2_proofs::halo2curves::{bn256::Fr, ff::PrimeField, pasta::Fp};
+
+    fn intent(nullifier_hash: u64, max_fee: u64) -> WithdrawalIntent {
+        WithdrawalIntent {
+            nullifier_hash: Fr::from(nullifier_hash).to_repr(),
+            max_fee,
+        }
+    }
+
+    fn proof(nullifier_hash: u64, relayer: [u8; 20], fee: u64) -> Proof {
+        let public_inputs = TornadoPublicInputs {
+            nullifier_hash: Fr::from(nullifier_hash),
+            root: Fr::from(0xaaa),
+            ext_data_hash: Fr::from(0),
+            recipient: address_to_field(&[0x11; 20]),
+            relayer: address_to_field(&relayer),
+            fee: Fr::from(fee),
+            refund: Fr::from(0),
+            amount: Fr::from(1),
+            asset_id: Fr::from(1),
+        };
+        Proof::new(
+            CurveId::Bn256,
+            10,
+            &public_inputs.to_instances(),
+            vec![nullifier_hash as u8],
+        )
+    }
+
+    #[test]
+    fn test_auction() {
+        let mut auction = Auction::new();
+        let salt = [7u8; 32];
+        let cheap = intent(1, 5);
+        let generous = intent(2, 50);
+        let cheap_id = auction.post_intent(cheap.seal(&salt).unwrap()).unwrap();
+        let generous_id = auction.post_intent(generous.seal(&salt).unwrap()).unwrap();
+
+        let [alice, bob, carol] = [[0xa1; 20], [0xb0; 20], [0xca; 20]];
+        for (relayer, fee) in [(alice, 20), (bob, 10), (carol, 10)] {
+            auction.bid(Bid { relayer, fee }).unwrap();
+        }
+
+        let covered = auction.close_bidding().unwrap();
+        assert_eq!(covered, vec![generous_id]);
+        assert_eq!(auction.winner().unwrap().relayer, bob);
+        assert_eq!(auction.phase(), Phase::Revealing);
+
+        // proofs are made once the winner is known, paying it its fee
+        let paid = proof(2, bob, 10);
+        assert!(matches!(
+            auction.reveal(cheap_id, &cheap, &salt, proof(1, bob, 10)),
+            Err(Error::UnknownIntent(_))
+        ));
+        assert!(matches!(
+            auction.reveal(generous_id, &generous, &[0u8; 32], paid.clone()),
+            Err(Error::DigestMismatch(_))
+        ));
+        assert!(matches!(
+            auction.reveal(generous_id, &generous, &salt, proof(1, bob, 10)),
+            Err(Error::NoteMismatch(_))
+        ));
+        assert!(matches!(
+            auction.reveal(generous_id, &generous, &salt, proof(2, alice, 10)),
+            Err(Error::RelayerMismatch(_))
+        ));
+        assert!(matches!(
+            auction.reveal(generous_id, &generous, &salt, proof(2, bob, 20)),
+            Err(Error::FeeMismatch(_))
+        ));
+        let pasta = Proof::new(CurveId::Pasta, 10, &[vec![Fp::from(2)]], vec![]);
+        assert!(matches!(
+            auction.reveal(generous_id, &generous, &salt, pasta),
+            Err(Error::InvalidProof(_))
+        ));
+        auction
+            .reveal(generous_id, &generous, &salt, paid.clone())
+            .unwrap();
+
+        let batch = auction.settle().unwrap();
+        assert_eq!(batch.relayer, bob);
+        assert_eq!(batch.fee, 10);
+        assert_eq!(batch.withdrawals, vec![paid]);
+        assert!(matches!(
+            auction.bid(Bid {
+                relayer: [0xda; 20],
+                fee: 1
+            }),
+            Err(Error::WrongPhase)
+        ));
+    }
+
+    #[test]
+    fn test_auction_without_bids() {
+        let mut auction = Auction::new();
+        assert!(matches!(auction.close_bidding(), Err(Error::NoBids)));
+    }
+}
@@ -0,0 +1,303 @@
+//! Deliberately stressful layouts, generic over the floor planner, used to compare
+//! `SimpleFloorPlanner` and `V1` (and future layout options) on rows used and minimum `k`.
+use crate::chips::{
+    merkle::{MerkleChip, MerkleConfig},
+    merkle4::{Merkle4Chip, Merkle4Config},
+    tornado::{TornadoChip, TornadoConfig},
+};
+use halo2_proofs::{
+    circuit::{FloorPlanner, Layouter, Value},
+    halo2curves::ff::PrimeField,
+    plonk::{Advice, Circuit, Column, ConstraintSystem, Error},
+};
+use std::marker::PhantomData;
+
+/// The advice columns, next to the single, unconstrained instance column `tune` proves with
+fn advice_columns<F: PrimeField>(meta: &mut ConstraintSystem<F>) -> [Column<Advice>; 3] {
+    let advice = [
+        meta.advice_column(),
+        meta.advice_column(),
+        meta.advice_column(),
+    ];
+    meta.instance_column();
+    advice
+}
+
+/// A single Merkle path of `DEPTH` layers
+#[derive(Debug)]
+pub struct DeepTreeCircuit<F, P, const DEPTH: usize> {
+    _marker: PhantomData<(F, P)>,
+}
+
+impl<F, P, const DEPTH: usize> DeepTreeCircuit<F, P, DEPTH> {
+    pub fn new() -> Self {
+        Self {
+            _marker: PhantomData,
+        }
+    }
+}
+
+impl<F, P, const DEPTH: usize> Default for DeepTreeCircuit<F, P, DEPTH> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<F: PrimeField, P: FloorPlanner, const DEPTH: usize> Circuit<F>
+    for DeepTreeCircuit<F, P, DEPTH>
+{
+    type Config = MerkleConfig;
+    type FloorPlanner = P;
+
+    fn without_witnesses(&self) -> Self {
+        Self::new()
+    }
+
+    fn configure(meta: &mut ConstraintSystem<F>) -> Self::Config {
+        let advice = advice_columns(meta);
+        MerkleChip::configure(meta, advice)
+    }
+
+    fn synthesize(
+        &self,
+        config: Self::Config,
+        mut layouter: impl Layouter<F>,
+    ) -> Result<(), Error> {
+        let leaf_cell = layouter.assign_region(
+            || "assign leaf",
+            |mut region| {
+                region.assign_advice(
+                    || "assign leaf",
+                    config.advice[0],
+                    0,
+                    || Value::known(F::from(3)),
+                )
+            },
+        )?;
+
+        let chip = MerkleChip::construct(config);
+        chip.prove_tree_root(
+            layouter.namespace(|| "prove tree"),
+            leaf_cell,
+            std::array::from_fn::<_, DEPTH, _>(|i| Value::known(F::from(i as u64 + 2))),
+            Value::known(F::ZERO),
+        )?;
+        Ok(())
+    }
+}
+
+/// A single arity-4 Merkle path of `DEPTH` layers, i.e. a tree of `4^DEPTH` leaves
+#[derive(Debug)]
+pub struct QuadTreeCircuit<F, P, const DEPTH: usize> {
+    _marker: PhantomData<(F, P)>,
+}
+
+impl<F, P, const DEPTH: usize> QuadTreeCircuit<F, P, DEPTH> {
+    pub fn new() -> Self {
+        Self {
+            _marker: PhantomData,
+        }
+    }
+}
+
+impl<F, P, const DEPTH: usize> Default for QuadTreeCircuit<F, P, DEPTH> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<F: PrimeField, P: FloorPlanner, const DEPTH: usize> Circuit<F>
+    for QuadTreeCircuit<F, P, DEPTH>
+{
+    type Config = Merkle4Config;
+    type FloorPlanner = P;
+
+    fn without_witnesses(&self) -> Self {
+        Self::new()
+    }
+
+    fn configure(meta: &mut ConstraintSystem<F>) -> Self::Config {
+        let advice = advice_columns(meta);
+        Merkle4Chip::configure(meta, advice)
+    }
+
+    fn synthesize(
+        &self,
+        config: Self::Config,
+        mut layouter: impl Layouter<F>,
+    ) -> Result<(), Error> {
+        let leaf_cell = layouter.assign_region(
+            || "assign leaf",
+            |mut region| {
+                region.assign_advice(
+                    || "assign leaf",
+                    config.advice[0],
+                    0,
+                    || Value::known(F::from(3)),
+                )
+            },
+        )?;
+
+        let chip = Merkle4Chip::construct(config);
+        chip.prove_tree_root(
+            layouter.namespace(|| "prove quad tree"),
+            leaf_cell,
+            std::array::from_fn::<_, DEPTH, _>(|i| {
+                [0, 1, 2].map(|j| Value::known(F::from((3 * i + j) as u64 + 2)))
+            }),
+            std::array::from_fn(|i| Value::known(F::from(i as u64 % 4))),
+        )?;
+        Ok(())
+    }
+}
+
+/// `width` independent hashes, each in its own region
+#[derive(Debug)]
+pub struct WideHashCircuit<F, P> {
+    pub width: usize,
+    _marker: PhantomData<(F, P)>,
+}
+
+impl<F, P> WideHashCircuit<F, P> {
+    pub fn new(width: usize) -> Self {
+        Self {
+            width,
+            _marker: PhantomData,
+        }
+    }
+}
+
+impl<F: PrimeField, P: FloorPlanner> Circuit<F> for WideHashCircuit<F, P> {
+    type Config = TornadoConfig;
+    type FloorPlanner = P;
+
+    fn without_witnesses(&self) -> Self {
+        Self::new(self.width)
+    }
+
+    fn configure(meta: &mut ConstraintSystem<F>) -> Self::Config {
+        let advice = advice_columns(meta);
+        TornadoChip::configure(meta, advice)
+    }
+
+    fn synthesize(
+        &self,
+        config: Self::Config,
+        mut layouter: impl Layouter<F>,
+    ) -> Result<(), Error> {
+        let chip = TornadoChip::construct(config);
+        for i in 0..self.width {
+            chip.compute_hash(
+                layouter.namespace(|| format!("hash {i}")),
+                Value::known(F::from(i as u64)),
+                Value::known(F::from(i as u64 + 1)),
+            )?;
+        }
+        Ok(())
+    }
+}
+
+/// `notes` full withdrawals (nullifier hash, commitment and a `DEPTH` Merkle path),
+/// interleaving hash-only regions with Merkle layers
+#[derive(Debug)]
+pub struct MixedCircuit<F, P, const DEPTH: usize> {
+    pub notes: usize,
+    _marker: PhantomData<(F, P)>,
+}
+
+impl<F, P, const DEPTH: usize> MixedCircuit<F, P, DEPTH> {
+    pub fn new(notes: usize) -> Self {
+        Self {
+            notes,
+            _marker: PhantomData,
+        }
+    }
+}
+
+impl<F: PrimeField, P: FloorPlanner, const DEPTH: usize> Circuit<F> for MixedCircuit<F, P, DEPTH> {
+    type Config = TornadoConfig;
+    type FloorPlanner = P;
+
+    fn without_witnesses(&self) -> Self {
+        Self::new(self.notes)
+    }
+
+    fn configure(meta: &mut ConstraintSystem<F>) -> Self::Config {
+        let advice = advice_columns(meta);
+        TornadoChip::configure(meta, advice)
+    }
+
+    fn synthesize(
+        &self,
+        config: Self::Config,
+        mut layouter: impl Layouter<F>,
+    ) -> Result<(), Error> {
+        let tornado_chip = TornadoChip::construct(config.clone());
+        let merkle_chip = MerkleChip::construct(config.merkle_config);
+        for i in 0..self.notes {
+            let nullifier = Value::known(F::from(i as u64 + 1));
+            let secret = Value::known(F::from(i as u64 + 2));
+            tornado_chip.compute_hash(
+                layouter.namespace(|| format!("nullifier hash {i}")),
+                nullifier,
+                nullifier,
+            )?;
+            let commitment = tornado_chip.compute_hash(
+                layouter.namespace(|| format!("commitment {i}")),
+                nullifier,
+                secret,
+            )?;
+            merkle_chip.prove_tree_root(
+                layouter.namespace(|| format!("prove tree {i}")),
+                commitment,
+                std::array::from_fn::<_, DEPTH, _>(|j| Value::known(F::from(j as u64 + 2))),
+                Value::known(F::from(i as u64)),
+            )?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{DeepTreeCircuit, MixedCircuit, QuadTreeCircuit, WideHashCircuit};
+    use crate::dev::stats;
+    use halo2_proofs::{
+        circuit::{floor_planner::V1, SimpleFloorPlanner},
+        halo2curves::bn256::Fr,
+    };
+
+    #[test]
+    fn test_deep_tree_layouts() {
+        // one region per layer: V1 fills the gaps `SimpleFloorPlanner` leaves between them
+        let simple = stats(&DeepTreeCircuit::<Fr, SimpleFloorPlanner, 20>::new()).unwrap();
+        let v1 = stats(&DeepTreeCircuit::<Fr, V1, 20>::new()).unwrap();
+        assert!(v1.rows <= simple.rows);
+        assert!(v1.min_k <= simple.min_k);
+        assert!(simple.min_k <= 12);
+        // the quad tree has half the layers of the binary one for the same anonymity set
+        let quad_simple = stats(&QuadTreeCircuit::<Fr, SimpleFloorPlanner, 10>::new()).unwrap();
+        let quad_v1 = stats(&QuadTreeCircuit::<Fr, V1, 10>::new()).unwrap();
+        assert!(quad_v1.rows <= quad_simple.rows);
+        assert!(quad_v1.min_k <= quad_simple.min_k);
+        assert!(quad_simple.min_k <= 12);
+    }
+
+    #[test]
+    fn test_wide_and_mixed_layouts() {
+        for (simple, v1) in [
+            (
+                stats(&WideHashCircuit::<Fr, SimpleFloorPlanner>::new(64)),
+                stats(&WideHashCircuit::<Fr, V1>::new(64)),
+            ),
+            (
+                stats(&MixedCircuit::<Fr, SimpleFloorPlanner, 8>::new(4)),
+                stats(&MixedCircuit::<Fr, V1, 8>::new(4)),
+            ),
+        ] {
+            // not a single chip repeated, so neither planner is expected to win: both must fit
+            let (simple, v1) = (simple.unwrap(), v1.unwrap());
+            assert!(simple.min_k <= 12 && v1.min_k <= 12);
+        }
+    }
+}
@@ -0,0 +1,310 @@
+use halo2_proofs::halo2curves::ff::PrimeField;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::{
+    fmt, fs, io,
+    path::{Path, PathBuf},
+};
+
+#[derive(Debug)]
+pub enum Error {
+    Io(io::Error),
+    Encoding(bincode::Error),
+    /// The bytes returned by the store don't hash to the requested CID
+    CidMismatch,
+    /// The root recomputed from the snapshot leaves differs from the snapshot root
+    RootMismatch,
+    /// The contract doesn't know the snapshot root
+    UnknownRoot,
+    InvalidFieldElement,
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::Io(e) => write!(f, "checkpoint store error: {e}"),
+            Error::Encoding(e) => write!(f, "checkpoint encoding error: {e}"),
+            Error::CidMismatch => write!(f, "checkpoint content doesn't match its CID"),
+            Error::RootMismatch => write!(f, "checkpoint leaves don't match its root"),
+            Error::UnknownRoot => write!(f, "checkpoint root is unknown to the contract"),
+            Error::InvalidFieldElement => {
+                write!(f, "checkpoint contains a non-canonical field element")
+            }
+        }
+    }
+}
+
+impl std::error::Error for Error {}
+
+impl From<io::Error> for Error {
+    fn from(e: io::Error) -> Self {
+        Error::Io(e)
+    }
+}
+
+impl From<bincode::Error> for Error {
+    fn from(e: bincode::Error) -> Self {
+        Error::Encoding(e)
+    }
+}
+
+/// CIDv1 (raw codec, sha2-256 multihash), in its base32 string form.
+/// This is what `ipfs add --cid-version 1 --raw-leaves` returns for a single block.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct Cid(pub String);
+
+impl Cid {
+    pub fn of(bytes: &[u8]) -> Self {
+        // version 1, raw codec, sha2-256, 32 byte digest
+        let mut cid = vec![0x01, 0x55, 0x12, 0x20];
+        cid.extend_from_slice(&Sha256::digest(bytes));
+        Self(format!("b{}", base32_lower(&cid)))
+    }
+}
+
+impl fmt::Display for Cid {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+fn base32_lower(bytes: &[u8]) -> String {
+    const ALPHABET: &[u8; 32] = b"abcdefghijklmnopqrstuvwxyz234567";
+    let mut out = String::with_capacity((bytes.len() * 8 + 4) / 5);
+    let mut buffer: u16 = 0;
+    let mut bits = 0;
+    for byte in bytes {
+        buffer = (buffer << 8) | *byte as u16;
+        bits += 8;
+        while bits >= 5 {
+            bits -= 5;
+            out.push(ALPHABET[((buffer >> bits) & 0x1f) as usize] as char);
+        }
+    }
+    if bits > 0 {
+        out.push(ALPHABET[((buffer << (5 - bits)) & 0x1f) as usize] as char);
+    }
+    out
+}
+
+/// Content-addressed storage. An IPFS node's HTTP API (`/api/v0/block/put`, `/api/v0/block/get`)
+/// satisfies this contract; `FsStore` is the local equivalent.
+pub trait ContentStore {
+    fn put(&mut self, bytes: &[u8]) -> io::Result<Cid>;
+    fn get(&self, cid: &Cid) -> io::Result<Vec<u8>>;
+}
+
+/// Stores each block in `dir/<cid>`
+pub struct FsStore {
+    dir: PathBuf,
+}
+
+impl FsStore {
+    pub fn new(dir: impl AsRef<Path>) -> io::Result<Self> {
+        fs::create_dir_all(dir.as_ref())?;
+        Ok(Self {
+            dir: dir.as_ref().to_path_buf(),
+        })
+    }
+}
+
+impl ContentStore for FsStore {
+    fn put(&mut self, bytes: &[u8]) -> io::Result<Cid> {
+        let cid = Cid::of(bytes);
+        fs::write(self.dir.join(&cid.0), bytes)?;
+        Ok(cid)
+    }
+
+    fn get(&self, cid: &Cid) -> io::Result<Vec<u8>> {
+        fs::read(self.dir.join(&cid.0))
+    }
+}
+
+/// Source of truth for roots, i.e. the pool contract's `isKnownRoot`
+pub trait RootOracle<F> {
+    fn is_known_root(&self, root: &F) -> bool;
+}
+
+/// Every deposit commitment up to `block`, in insertion order, and the resulting root
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TreeSnapshot {
+    pub block: u64,
+    pub root: [u8; 32],
+    pub leaves: Vec<[u8; 32]>,
+}
+
+impl TreeSnapshot {
+    pub fn new<F: PrimeField<Repr = [u8; 32]>>(block: u64, root: F, leaves: &[F]) -> Self {
+        Self {
+            block,
+            root: root.to_repr(),
+            leaves: leaves.iter().map(|leaf| leaf.to_repr()).collect(),
+        }
+    }
+
+    pub fn root<F: PrimeField<Repr = [u8; 32]>>(&self) -> Result<F, Error> {
+        Option::from(F::from_repr(self.root)).ok_or(Error::InvalidFieldElement)
+    }
+
+    pub fn leaves<F: PrimeField<Repr = [u8; 32]>>(&self) -> Result<Vec<F>, Error> {
+        self.leaves
+            .iter()
+            .map(|leaf| Option::from(F::from_repr(*leaf)).ok_or(Error::InvalidFieldElement))
+            .collect()
+    }
+}
+
+/// Publishes a snapshot every `interval` blocks
+pub struct Exporter<S> {
+    pub store: S,
+    pub interval: u64,
+    last_exported: Option<u64>,
+}
+
+impl<S: ContentStore> Exporter<S> {
+    pub fn new(store: S, interval: u64) -> Self {
+        Self {
+            store,
+            interval,
+            last_exported: None,
+        }
+    }
+
+    pub fn export<F: PrimeField<Repr = [u8; 32]>>(
+        &mut self,
+        block: u64,
+        root: F,
+        leaves: &[F],
+    ) -> Result<Cid, Error> {
+        let snapshot = TreeSnapshot::new(block, root, leaves);
+        let cid = self.store.put(&bincode::serialize(&snapshot)?)?;
+        self.last_exported = Some(block);
+        Ok(cid)
+    }
+
+    /// Exports only if at least `interval` blocks went by since the last export
+    pub fn maybe_export<F: PrimeField<Repr = [u8; 32]>>(
+        &mut self,
+        block: u64,
+        root: F,
+        leaves: &[F],
+    ) -> Result<Option<Cid>, Error> {
+        match self.last_exported {
+            Some(last) if block < last.saturating_add(self.interval) => Ok(None),
+            _ => self.export(block, root, leaves).map(Some),
+        }
+    }
+}
+
+/// Fetches a snapshot and only returns it if
+/// - the content matches `cid`
+/// - `compute_root` over the leaves gives back the snapshot root
+/// - the contract knows that root
+pub fn import<F, S, O>(
+    store: &S,
+    cid: &Cid,
+    oracle: &O,
+    compute_root: impl Fn(&[F]) -> F,
+) -> Result<TreeSnapshot, Error>
+where
+    F: PrimeField<Repr = [u8; 32]>,
+    S: ContentStore,
+    O: RootOracle<F>,
+{
+    let bytes = store.get(cid)?;
+    if &Cid::of(&bytes) != cid {
+        return Err(Error::CidMismatch);
+    }
+
+    let snapshot: TreeSnapshot = bincode::deserialize(&bytes)?;
+    let root = snapshot.root::<F>()?;
+    if compute_root(&snapshot.leaves::<F>()?) != root {
+        return Err(Error::RootMismatch);
+    }
+    if !oracle.is_known_root(&root) {
+        return Err(Error::UnknownRoot);
+    }
+    Ok(snapshot)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{import, Cid, ContentStore, Error, Exporter, FsStore, RootOracle};
+    use halo2_proofs::halo2curves::pasta::Fp;
+
+    struct KnownRoots(Vec<Fp>);
+
+    impl RootOracle<Fp> for KnownRoots {
+        fn is_known_root(&self, root: &Fp) -> bool {
+            self.0.contains(root)
+        }
+    }
+
+    fn product(leaves: &[Fp]) -> Fp {
+        leaves.iter().product()
+    }
+
+    fn store(name: &str) -> FsStore {
+        let dir = std::env::temp_dir().join(format!("tornado-checkpoint-{name}"));
+        let _ = std::fs::remove_dir_all(&dir);
+        FsStore::new(dir).unwrap()
+    }
+
+    #[test]
+    fn test_cid_of_empty_block() {
+        assert_eq!(
+            Cid::of(&[]).0,
+            "bafkreihdwdcefgh4dqkjv67uzcmw7ojee6xedzdetojuzjevtenxquvyku"
+        );
+    }
+
+    #[test]
+    fn test_export_import() {
+        let leaves = vec![Fp::from(2), Fp::from(3), Fp::from(5)];
+        let root = product(&leaves);
+
+        let mut exporter = Exporter::new(store("roundtrip"), 100);
+        let cid = exporter.export(1000, root, &leaves).unwrap();
+        assert!(exporter
+            .maybe_export(1050, root, &leaves)
+            .unwrap()
+            .is_none());
+
+        let snapshot = import(&exporter.store, &cid, &KnownRoots(vec![root]), product).unwrap();
+        assert_eq!(snapshot.block, 1000);
+        assert_eq!(snapshot.leaves::<Fp>().unwrap(), leaves);
+
+        let err = import(&exporter.store, &cid, &KnownRoots(vec![]), product).unwrap_err();
+        assert!(matches!(err, Error::UnknownRoot));
+    }
+
+    #[test]
+    fn test_import_rejects_tampered_content() {
+        let leaves = vec![Fp::from(2), Fp::from(3)];
+        let mut exporter = Exporter::new(store("tampered"), 100);
+        let cid = exporter.export(1, product(&leaves), &leaves).unwrap();
+
+        let other = exporter.store.put(b"not a snapshot").unwrap();
+        std::fs::rename(
+            exporter.store.dir.join(&other.0),
+            exporter.store.dir.join(&cid.0),
+        )
+        .unwrap();
+
+        let oracle = KnownRoots(vec![product(&leaves)]);
+        let err = import(&exporter.store, &cid, &oracle, product).unwrap_err();
+        assert!(matches!(err, Error::CidMismatch));
+    }
+
+    #[test]
+    fn test_import_rejects_wrong_root() {
+        let leaves = vec![Fp::from(2), Fp::from(3)];
+        let bogus_root = Fp::from(7);
+        let mut exporter = Exporter::new(store("wrong-root"), 100);
+        let cid = exporter.export(1, bogus_root, &leaves).unwrap();
+
+        let oracle = KnownRoots(vec![bogus_root]);
+        let err = import(&exporter.store, &cid, &oracle, product).unwrap_err();
+        assert!(matches!(err, Error::RootMismatch));
+    }
+}
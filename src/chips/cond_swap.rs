@@ -0,0 +1,141 @@
+use halo2_proofs::{
+    circuit::{AssignedCell, Layouter, Region, Value},
+    halo2curves::ff::PrimeField,
+    plonk::{Advice, Column, ConstraintSystem, Error, Expression, Selector},
+    poly::Rotation,
+};
+use std::marker::PhantomData;
+
+/// Conditional swap: `(a, b)` if `bit` is 0, `(b, a)` if it's 1.
+///
+/// Region layout, `swap_selector` on the first row:
+///   | a    | b     | bit |
+///   | left | right |     |
+#[derive(Debug, Clone, Copy)]
+pub struct CondSwapConfig {
+    pub advice: [Column<Advice>; 3],
+    pub(crate) swap_selector: Selector,
+}
+
+pub struct CondSwapChip<F> {
+    pub(crate) config: CondSwapConfig,
+    _marker: PhantomData<F>,
+}
+
+impl<F: PrimeField> CondSwapChip<F> {
+    pub fn construct(config: CondSwapConfig) -> Self {
+        Self {
+            config,
+            _marker: PhantomData,
+        }
+    }
+
+    pub fn configure(
+        meta: &mut ConstraintSystem<F>,
+        advice: [Column<Advice>; 3],
+    ) -> CondSwapConfig {
+        let swap_selector = meta.selector();
+
+        meta.enable_equality(advice[0]);
+        meta.enable_equality(advice[1]);
+        meta.enable_equality(advice[2]);
+
+        meta.create_gate("conditional swap", |meta| {
+            let s = meta.query_selector(swap_selector);
+            let a = meta.query_advice(advice[0], Rotation::cur());
+            let b = meta.query_advice(advice[1], Rotation::cur());
+            let bit = meta.query_advice(advice[2], Rotation::cur());
+            let left = meta.query_advice(advice[0], Rotation::next());
+            let right = meta.query_advice(advice[1], Rotation::next());
+
+            // the bit is either 0 or 1
+            let bool_check = bit.clone() * (Expression::Constant(F::ONE) - bit.clone());
+            // left = a + bit * (b - a), right = b + bit * (a - b)
+            let left_check = (b.clone() - a.clone()) * bit.clone() + a.clone() - left;
+            let right_check = (a - b.clone()) * bit + b - right;
+            vec![
+                s.clone() * bool_check,
+                s.clone() * left_check,
+                s * right_check,
+            ]
+        });
+
+        CondSwapConfig {
+            advice,
+            swap_selector,
+        }
+    }
+
+    /// Returns the constrained `(left, right)` cells
+    pub fn swap(
+        &self,
+        layouter: impl Layouter<F>,
+        a: &AssignedCell<F, F>,
+        b: Value<F>,
+        bit: &AssignedCell<F, F>,
+    ) -> Result<(AssignedCell<F, F>, AssignedCell<F, F>), Error> {
+        self.swap_inner(layouter, a, b, None, bit)
+    }
+
+    /// Like `swap`, with `b` copied from an assigned cell, e.g. a sibling shared by two paths
+    pub fn swap_cells(
+        &self,
+        layouter: impl Layouter<F>,
+        a: &AssignedCell<F, F>,
+        b: &AssignedCell<F, F>,
+        bit: &AssignedCell<F, F>,
+    ) -> Result<(AssignedCell<F, F>, AssignedCell<F, F>), Error> {
+        self.swap_inner(layouter, a, b.value().cloned(), Some(b), bit)
+    }
+
+    fn swap_inner(
+        &self,
+        mut layouter: impl Layouter<F>,
+        a: &AssignedCell<F, F>,
+        b: Value<F>,
+        b_cell: Option<&AssignedCell<F, F>>,
+        bit: &AssignedCell<F, F>,
+    ) -> Result<(AssignedCell<F, F>, AssignedCell<F, F>), Error> {
+        layouter.assign_region(
+            || "conditional swap",
+            |mut region| self.assign_swap(&mut region, 0, a, b, b_cell, bit),
+        )
+    }
+
+    /// Lays out the swap on rows `offset` and `offset + 1` of `region`, for a chip that uses
+    /// `(left, right)` on the second row, e.g. as the inputs of a hash
+    pub(crate) fn assign_swap(
+        &self,
+        region: &mut Region<'_, F>,
+        offset: usize,
+        a: &AssignedCell<F, F>,
+        b: Value<F>,
+        b_cell: Option<&AssignedCell<F, F>>,
+        bit: &AssignedCell<F, F>,
+    ) -> Result<(AssignedCell<F, F>, AssignedCell<F, F>), Error> {
+        self.config.swap_selector.enable(region, offset)?;
+
+        a.copy_advice(|| "a", region, self.config.advice[0], offset)?;
+        match b_cell {
+            Some(cell) => cell.copy_advice(|| "b", region, self.config.advice[1], offset)?,
+            None => region.assign_advice(|| "b", self.config.advice[1], offset, || b)?,
+        };
+        bit.copy_advice(|| "swap bit", region, self.config.advice[2], offset)?;
+
+        let mut left = a.value().cloned();
+        let mut right = b;
+        bit.value().map(|f| {
+            (left, right) = if *f == F::ZERO {
+                (left, right)
+            } else {
+                (right, left)
+            }
+        });
+
+        let left_cell =
+            region.assign_advice(|| "left", self.config.advice[0], offset + 1, || left)?;
+        let right_cell =
+            region.assign_advice(|| "right", self.config.advice[1], offset + 1, || right)?;
+        Ok((left_cell, right_cell))
+    }
+}
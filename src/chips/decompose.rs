@@ -0,0 +1,151 @@
+use halo2_proofs::{
+    circuit::{AssignedCell, Layouter, Value},
+    halo2curves::ff::PrimeField,
+    plonk::{Advice, Column, ConstraintSystem, Constraints, Error, Expression, Fixed, Selector},
+    poly::Rotation,
+};
+use std::marker::PhantomData;
+
+/// Little-endian bit decomposition of a value into a fixed number of bits, with a running sum:
+/// `z_0 = value`, `z_i = 2 * z_{i+1} + bit_i` and `z_n = 0`, so `value < 2^n`.
+///
+/// Region layout, `decompose_selector` on every row but the last:
+///   | z_0 | bit_0 |
+///   | z_1 | bit_1 |
+///   | ... | ...   |
+///   | z_n |       |
+#[derive(Debug, Clone, Copy)]
+pub struct DecomposeConfig {
+    pub advice: [Column<Advice>; 3],
+    pub(crate) decompose_selector: Selector,
+}
+
+pub struct DecomposeChip<F> {
+    pub(crate) config: DecomposeConfig,
+    _marker: PhantomData<F>,
+}
+
+impl<F: PrimeField> DecomposeChip<F> {
+    pub fn construct(config: DecomposeConfig) -> Self {
+        Self {
+            config,
+            _marker: PhantomData,
+        }
+    }
+
+    /// `constant` is used to pin the last running sum to 0
+    pub fn configure(
+        meta: &mut ConstraintSystem<F>,
+        advice: [Column<Advice>; 3],
+        constant: Column<Fixed>,
+    ) -> DecomposeConfig {
+        let decompose_selector = meta.selector();
+
+        meta.enable_constant(constant);
+        meta.enable_equality(advice[0]);
+        meta.enable_equality(advice[1]);
+
+        meta.create_gate("bit decomposition", |meta| {
+            let s = meta.query_selector(decompose_selector);
+            let z_cur = meta.query_advice(advice[0], Rotation::cur());
+            let z_next = meta.query_advice(advice[0], Rotation::next());
+            let bit = meta.query_advice(advice[1], Rotation::cur());
+
+            let bool_check = bit.clone() * (Expression::Constant(F::ONE) - bit.clone());
+            let running_sum = z_next * Expression::Constant(F::from(2)) + bit - z_cur;
+            Constraints::with_selector(s, [bool_check, running_sum])
+        });
+
+        DecomposeConfig {
+            advice,
+            decompose_selector,
+        }
+    }
+
+    /// Returns the `BITS` bits of `value`, least significant first. The proof fails if
+    /// `value >= 2^BITS`.
+    pub fn decompose<const BITS: usize>(
+        &self,
+        layouter: impl Layouter<F>,
+        value: Value<F>,
+    ) -> Result<[AssignedCell<F, F>; BITS], Error> {
+        self.decompose_inner(layouter, value, None)
+    }
+
+    /// Like `decompose`, with `value` copied from an assigned cell so the bits are bound to it
+    pub fn decompose_cell<const BITS: usize>(
+        &self,
+        layouter: impl Layouter<F>,
+        value: &AssignedCell<F, F>,
+    ) -> Result<[AssignedCell<F, F>; BITS], Error> {
+        self.decompose_inner(layouter, value.value().cloned(), Some(value))
+    }
+
+    /// The value of `bits`, least significant first: the inverse of `decompose`. The bits of an
+    /// integer above the modulus, e.g. a 256-bit digest, give its value modulo p.
+    pub fn compose(
+        &self,
+        mut layouter: impl Layouter<F>,
+        bits: &[AssignedCell<F, F>],
+    ) -> Result<AssignedCell<F, F>, Error> {
+        layouter.assign_region(
+            || "bit composition",
+            |mut region| {
+                let [z_column, bit_column, _] = self.config.advice;
+                let mut z = Value::known(F::ZERO);
+                let mut value = region.assign_advice(|| "z_n", z_column, bits.len(), || z)?;
+                region.constrain_constant(value.cell(), F::ZERO)?;
+
+                // the running sum of `decompose`, from the most significant bit down
+                for (i, bit) in bits.iter().enumerate().rev() {
+                    self.config.decompose_selector.enable(&mut region, i)?;
+                    bit.copy_advice(|| format!("bit {i}"), &mut region, bit_column, i)?;
+                    z = z * Value::known(F::from(2)) + bit.value().cloned();
+                    value = region.assign_advice(|| format!("z_{i}"), z_column, i, || z)?;
+                }
+                Ok(value)
+            },
+        )
+    }
+
+    fn decompose_inner<const BITS: usize>(
+        &self,
+        mut layouter: impl Layouter<F>,
+        value: Value<F>,
+        value_cell: Option<&AssignedCell<F, F>>,
+    ) -> Result<[AssignedCell<F, F>; BITS], Error> {
+        layouter.assign_region(
+            || "bit decomposition",
+            |mut region| {
+                let [z_column, bit_column, _] = self.config.advice;
+                if let Some(cell) = value_cell {
+                    cell.copy_advice(|| "z_0", &mut region, z_column, 0)?;
+                }
+
+                // an out-of-range value keeps a non-zero remainder and fails the last check
+                let mut z = value;
+                let mut bits = Vec::with_capacity(BITS);
+                for i in 0..BITS {
+                    self.config.decompose_selector.enable(&mut region, i)?;
+                    if i > 0 || value_cell.is_none() {
+                        region.assign_advice(|| format!("z_{i}"), z_column, i, || z)?;
+                    }
+
+                    let bit = z.map(|z| F::from(z.is_odd().unwrap_u8() as u64));
+                    bits.push(region.assign_advice(
+                        || format!("bit {i}"),
+                        bit_column,
+                        i,
+                        || bit,
+                    )?);
+                    z = (z - bit) * Value::known(F::TWO_INV);
+                }
+
+                let last = region.assign_advice(|| "remainder", z_column, BITS, || z)?;
+                region.constrain_constant(last.cell(), F::ZERO)?;
+
+                Ok(bits.try_into().expect("BITS bits were assigned"))
+            },
+        )
+    }
+}
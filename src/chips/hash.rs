@@ -1,20 +1,20 @@
+use crate::tree::{Hasher, ProductHasher};
 use halo2_proofs::{
-    circuit::{AssignedCell, Layouter},
+    circuit::{AssignedCell, Layouter, Region, Value},
     halo2curves::ff::PrimeField,
-    plonk::{Advice, Column, ConstraintSystem, Error, Instance, Selector},
+    plonk::{Advice, Column, ConstraintSystem, Error, Selector},
     poly::Rotation,
 };
-use std::marker::PhantomData;
+use std::{fmt::Debug, marker::PhantomData};
 
 #[derive(Debug, Clone, Copy)]
 pub struct HashConfig {
     pub advice: [Column<Advice>; 3],
-    pub instance: Column<Instance>,
-    pub hash_selector: Selector,
+    pub(crate) hash_selector: Selector,
 }
 
 pub struct HashChip<F> {
-    pub config: HashConfig,
+    pub(crate) config: HashConfig,
     _marker: PhantomData<F>,
 }
 
@@ -26,17 +26,12 @@ impl<F: PrimeField> HashChip<F> {
         }
     }
 
-    pub fn configure(
-        meta: &mut ConstraintSystem<F>,
-        advice: [Column<Advice>; 3],
-        instance: Column<Instance>,
-    ) -> HashConfig {
+    pub fn configure(meta: &mut ConstraintSystem<F>, advice: [Column<Advice>; 3]) -> HashConfig {
         let hash_selector = meta.selector();
 
         meta.enable_equality(advice[0]);
         meta.enable_equality(advice[1]);
         meta.enable_equality(advice[2]);
-        meta.enable_equality(instance);
 
         meta.create_gate("hash constraint", |meta| {
             let s = meta.query_selector(hash_selector);
@@ -48,44 +43,107 @@ impl<F: PrimeField> HashChip<F> {
 
         HashConfig {
             advice,
-            instance,
             hash_selector,
         }
     }
 
     pub fn hash(
+        &self,
+        layouter: impl Layouter<F>,
+        left_cell: AssignedCell<F, F>,
+        right_cell: AssignedCell<F, F>,
+    ) -> Result<AssignedCell<F, F>, Error> {
+        self.hash_inner(layouter, left_cell, right_cell, None)
+    }
+
+    /// Like `hash`, with the digest computed beforehand, see `WitnessCalculator`
+    pub fn hash_precomputed(
+        &self,
+        layouter: impl Layouter<F>,
+        left_cell: AssignedCell<F, F>,
+        right_cell: AssignedCell<F, F>,
+        digest: Value<F>,
+    ) -> Result<AssignedCell<F, F>, Error> {
+        self.hash_inner(layouter, left_cell, right_cell, Some(digest))
+    }
+
+    /// `None` computes the digest from the input cells
+    pub(crate) fn hash_inner(
         &self,
         mut layouter: impl Layouter<F>,
         left_cell: AssignedCell<F, F>,
         right_cell: AssignedCell<F, F>,
+        digest: Option<Value<F>>,
     ) -> Result<AssignedCell<F, F>, Error> {
         layouter.assign_region(
             || "hash row",
             |mut region| {
-                self.config.hash_selector.enable(&mut region, 0)?;
-
-                left_cell.copy_advice(
+                let left = left_cell.copy_advice(
                     || "copy left input",
                     &mut region,
                     self.config.advice[0],
                     0,
                 )?;
-                right_cell.copy_advice(
+                let right = right_cell.copy_advice(
                     || "copy right input",
                     &mut region,
                     self.config.advice[1],
                     0,
                 )?;
-
-                let hash_result_cell = region.assign_advice(
-                    || "output",
-                    self.config.advice[2],
-                    0,
-                    || left_cell.value().cloned() * right_cell.value().cloned(),
-                )?;
-
-                Ok(hash_result_cell)
+                self.assign_digest(&mut region, 0, &left, &right, digest)
             },
         )
     }
+
+    /// Hashes the inputs already in the first two advice columns at `offset`, so a chip laying
+    /// out its own region (e.g. a Merkle layer) doesn't spend a row copying them
+    pub(crate) fn assign_digest(
+        &self,
+        region: &mut Region<'_, F>,
+        offset: usize,
+        left: &AssignedCell<F, F>,
+        right: &AssignedCell<F, F>,
+        digest: Option<Value<F>>,
+    ) -> Result<AssignedCell<F, F>, Error> {
+        self.config.hash_selector.enable(region, offset)?;
+        region.assign_advice(
+            || "output",
+            self.config.advice[2],
+            offset,
+            || digest.unwrap_or_else(|| left.value().cloned() * right.value().cloned()),
+        )
+    }
+}
+
+/// A `Hasher` with a chip computing it, so a circuit can be generic over its hash function
+pub trait CircuitHasher<F: PrimeField>: Hasher<F> {
+    type Config: Clone + Debug;
+
+    /// Configures the chip on `advice`, allocating any other column it needs
+    fn configure(meta: &mut ConstraintSystem<F>, advice: [Column<Advice>; 3]) -> Self::Config;
+
+    /// The in-circuit `Hasher::hash` of the two cells
+    fn hash_cells(
+        config: &Self::Config,
+        layouter: impl Layouter<F>,
+        left: AssignedCell<F, F>,
+        right: AssignedCell<F, F>,
+    ) -> Result<AssignedCell<F, F>, Error>;
+}
+
+impl<F: PrimeField> CircuitHasher<F> for ProductHasher {
+    type Config = HashConfig;
+
+    fn configure(meta: &mut ConstraintSystem<F>, advice: [Column<Advice>; 3]) -> HashConfig {
+        HashChip::configure(meta, advice)
+    }
+
+    fn hash_cells(
+        config: &HashConfig,
+        layouter: impl Layouter<F>,
+        left: AssignedCell<F, F>,
+        right: AssignedCell<F, F>,
+    ) -> Result<AssignedCell<F, F>, Error> {
+        HashChip::construct(*config).hash(layouter, left, right)
+    }
 }
@@ -0,0 +1,129 @@
+use halo2_proofs::{
+    circuit::{AssignedCell, Layouter, Value},
+    halo2curves::ff::PrimeField,
+    plonk::{Advice, Column, ConstraintSystem, Constraints, Error, Expression, Selector},
+    poly::Rotation,
+};
+use std::marker::PhantomData;
+
+/// Boolean `value == 0`, with the inverse-witness trick: the prover supplies `inv`, the inverse
+/// of `value` (or anything if it's zero), and `out = 1 - value * inv` with `value * out = 0`.
+/// Either `value` is zero and `out` is 1, or `inv` must be its inverse and `out` is 0.
+///
+/// `is_zero` layout, `is_zero_selector` on its only row:
+///   | value | inv | out |
+///
+/// `is_equal` applies the same trick to `a - b`, `is_equal_selector` on the first row:
+///   | a   | b | inv |
+///   | out |   |     |
+#[derive(Debug, Clone, Copy)]
+pub struct IsZeroConfig {
+    pub advice: [Column<Advice>; 3],
+    pub(crate) is_zero_selector: Selector,
+    pub(crate) is_equal_selector: Selector,
+}
+
+pub struct IsZeroChip<F> {
+    pub(crate) config: IsZeroConfig,
+    _marker: PhantomData<F>,
+}
+
+fn is_zero_constraints<F: PrimeField>(
+    value: Expression<F>,
+    inv: Expression<F>,
+    out: Expression<F>,
+) -> [Expression<F>; 2] {
+    let out_check = out.clone() - (Expression::Constant(F::ONE) - value.clone() * inv);
+    let product_check = value * out;
+    [out_check, product_check]
+}
+
+/// `(inv, out)` witnesses for `value`
+fn is_zero_witness<F: PrimeField>(value: Value<F>) -> (Value<F>, Value<F>) {
+    let inv = value.map(|value| value.invert().unwrap_or(F::ZERO));
+    let out = value.map(|value| if value == F::ZERO { F::ONE } else { F::ZERO });
+    (inv, out)
+}
+
+impl<F: PrimeField> IsZeroChip<F> {
+    pub fn construct(config: IsZeroConfig) -> Self {
+        Self {
+            config,
+            _marker: PhantomData,
+        }
+    }
+
+    pub fn configure(meta: &mut ConstraintSystem<F>, advice: [Column<Advice>; 3]) -> IsZeroConfig {
+        let is_zero_selector = meta.selector();
+        let is_equal_selector = meta.selector();
+
+        meta.enable_equality(advice[0]);
+        meta.enable_equality(advice[1]);
+        meta.enable_equality(advice[2]);
+
+        meta.create_gate("is zero", |meta| {
+            let s = meta.query_selector(is_zero_selector);
+            let value = meta.query_advice(advice[0], Rotation::cur());
+            let inv = meta.query_advice(advice[1], Rotation::cur());
+            let out = meta.query_advice(advice[2], Rotation::cur());
+            Constraints::with_selector(s, is_zero_constraints(value, inv, out))
+        });
+
+        meta.create_gate("is equal", |meta| {
+            let s = meta.query_selector(is_equal_selector);
+            let a = meta.query_advice(advice[0], Rotation::cur());
+            let b = meta.query_advice(advice[1], Rotation::cur());
+            let inv = meta.query_advice(advice[2], Rotation::cur());
+            let out = meta.query_advice(advice[0], Rotation::next());
+            Constraints::with_selector(s, is_zero_constraints(a - b, inv, out))
+        });
+
+        IsZeroConfig {
+            advice,
+            is_zero_selector,
+            is_equal_selector,
+        }
+    }
+
+    /// Returns a cell constrained to 1 if `value` is zero, 0 otherwise
+    pub fn is_zero(
+        &self,
+        mut layouter: impl Layouter<F>,
+        value: &AssignedCell<F, F>,
+    ) -> Result<AssignedCell<F, F>, Error> {
+        layouter.assign_region(
+            || "is zero",
+            |mut region| {
+                self.config.is_zero_selector.enable(&mut region, 0)?;
+                let [a0, a1, a2] = self.config.advice;
+
+                value.copy_advice(|| "value", &mut region, a0, 0)?;
+                let (inv, out) = is_zero_witness(value.value().copied());
+                region.assign_advice(|| "inv", a1, 0, || inv)?;
+                region.assign_advice(|| "is zero", a2, 0, || out)
+            },
+        )
+    }
+
+    /// Returns a cell constrained to 1 if `a == b`, 0 otherwise
+    pub fn is_equal(
+        &self,
+        mut layouter: impl Layouter<F>,
+        a: &AssignedCell<F, F>,
+        b: &AssignedCell<F, F>,
+    ) -> Result<AssignedCell<F, F>, Error> {
+        layouter.assign_region(
+            || "is equal",
+            |mut region| {
+                self.config.is_equal_selector.enable(&mut region, 0)?;
+                let [a0, a1, a2] = self.config.advice;
+
+                a.copy_advice(|| "a", &mut region, a0, 0)?;
+                b.copy_advice(|| "b", &mut region, a1, 0)?;
+                let (inv, out) = is_zero_witness(a.value().copied() - b.value().copied());
+                region.assign_advice(|| "inv", a2, 0, || inv)?;
+                region.assign_advice(|| "is equal", a0, 1, || out)
+            },
+        )
+    }
+}
@@ -0,0 +1,366 @@
+use halo2_proofs::{
+    circuit::{AssignedCell, Layouter, Region, Value},
+    halo2curves::ff::PrimeField,
+    plonk::{Advice, Column, ConstraintSystem, Constraints, Error, Expression, Fixed, Selector},
+    poly::Rotation,
+};
+use std::marker::PhantomData;
+
+/// Bits absorbed per block. The chip hashes a single block, so inputs are shorter.
+pub const RATE_BITS: usize = 1088;
+const STATE_BITS: usize = 1600;
+const ROUNDS: usize = 24;
+/// Added to lane `(0, 0)` by ι, one per round
+const ROUND_CONSTANTS: [u64; ROUNDS] = [
+    0x0000000000000001,
+    0x0000000000008082,
+    0x800000000000808a,
+    0x8000000080008000,
+    0x000000000000808b,
+    0x0000000080000001,
+    0x8000000080008081,
+    0x8000000000008009,
+    0x000000000000008a,
+    0x0000000000000088,
+    0x0000000080008009,
+    0x000000008000000a,
+    0x000000008000808b,
+    0x800000000000008b,
+    0x8000000000008089,
+    0x8000000000008003,
+    0x8000000000008002,
+    0x8000000000000080,
+    0x000000000000800a,
+    0x800000008000000a,
+    0x8000000080008081,
+    0x8000000000008080,
+    0x0000000080000001,
+    0x8000000080008008,
+];
+
+/// Position of bit `z` of lane `(x, y)` in the state. Bytes are absorbed into the lanes
+/// little-endian, so it is also the position of the bit in the input.
+fn bit_index(x: usize, y: usize, z: usize) -> usize {
+    64 * (x + 5 * y) + z
+}
+
+/// ρ rotates lane `(x, y)` left by `offsets[x][y]`
+fn rho_offsets() -> [[usize; 5]; 5] {
+    let mut offsets = [[0; 5]; 5];
+    let (mut x, mut y) = (1, 0);
+    for t in 0..ROUNDS {
+        offsets[x][y] = (t + 1) * (t + 2) / 2 % 64;
+        (x, y) = (y, (2 * x + 3 * y) % 5);
+    }
+    offsets
+}
+
+fn bool_check<F: PrimeField>(value: Expression<F>) -> Expression<F> {
+    value.clone() * (Expression::Constant(F::ONE) - value)
+}
+
+/// Keccak-256 of a single block, one cell per bit of the state. Each step of a round is a
+/// row per output bit, checking that `out` is the parity of a sum of input bits:
+///   - θ's column sums: `i_0 + i_1 + i_2 + i_3 + i_4 = out + 2 * carry`
+///   - θ's update, with the two neighboring columns: `i_0 + i_1 + i_2 = out + 2 * carry`
+///   - χ and ι: `i_0 + (1 - i_1) * i_2 + rc = out + 2 * carry`, i.e. `a ⊕ (¬b ∧ c) ⊕ rc`
+///
+/// ρ and π only move bits, so they cost nothing. A permutation is 24 rounds of 3520 rows, so
+/// a circuit hashing with the chip needs `k = 17`.
+///
+/// Row layout:
+///   | i_0 | i_1 | i_2 | i_3 | i_4 | out | carry | rc |
+#[derive(Debug, Clone, Copy)]
+pub struct KeccakConfig {
+    pub advice: [Column<Advice>; 7],
+    pub(crate) round_constant: Column<Fixed>,
+    pub(crate) column_sum_selector: Selector,
+    pub(crate) theta_selector: Selector,
+    pub(crate) chi_selector: Selector,
+}
+
+pub struct KeccakChip<F> {
+    pub(crate) config: KeccakConfig,
+    _marker: PhantomData<F>,
+}
+
+impl<F: PrimeField> KeccakChip<F> {
+    pub fn construct(config: KeccakConfig) -> Self {
+        Self {
+            config,
+            _marker: PhantomData,
+        }
+    }
+
+    /// `constant` holds the padding and the zero capacity of the state
+    pub fn configure(
+        meta: &mut ConstraintSystem<F>,
+        advice: [Column<Advice>; 7],
+        constant: Column<Fixed>,
+    ) -> KeccakConfig {
+        let round_constant = meta.fixed_column();
+        let column_sum_selector = meta.selector();
+        let theta_selector = meta.selector();
+        let chi_selector = meta.selector();
+
+        meta.enable_constant(constant);
+        for column in advice {
+            meta.enable_equality(column);
+        }
+
+        let two = Expression::Constant(F::from(2));
+        meta.create_gate("keccak column sum", |meta| {
+            let s = meta.query_selector(column_sum_selector);
+            let sum = advice[..5]
+                .iter()
+                .map(|column| meta.query_advice(*column, Rotation::cur()))
+                .reduce(|a, b| a + b)
+                .expect("5 inputs");
+            let out = meta.query_advice(advice[5], Rotation::cur());
+            let carry = meta.query_advice(advice[6], Rotation::cur());
+
+            let carry_check = carry.clone()
+                * (carry.clone() - Expression::Constant(F::ONE))
+                * (carry.clone() - two.clone());
+            let parity = sum - out.clone() - carry * two.clone();
+            Constraints::with_selector(s, [bool_check(out), carry_check, parity])
+        });
+
+        meta.create_gate("keccak theta", |meta| {
+            let s = meta.query_selector(theta_selector);
+            let sum = advice[..3]
+                .iter()
+                .map(|column| meta.query_advice(*column, Rotation::cur()))
+                .reduce(|a, b| a + b)
+                .expect("3 inputs");
+            let out = meta.query_advice(advice[5], Rotation::cur());
+            let carry = meta.query_advice(advice[6], Rotation::cur());
+
+            let parity = sum - out.clone() - carry.clone() * two.clone();
+            Constraints::with_selector(s, [bool_check(out), bool_check(carry), parity])
+        });
+
+        meta.create_gate("keccak chi", |meta| {
+            let s = meta.query_selector(chi_selector);
+            let a = meta.query_advice(advice[0], Rotation::cur());
+            let b = meta.query_advice(advice[1], Rotation::cur());
+            let c = meta.query_advice(advice[2], Rotation::cur());
+            let rc = meta.query_fixed(round_constant, Rotation::cur());
+            let out = meta.query_advice(advice[5], Rotation::cur());
+            let carry = meta.query_advice(advice[6], Rotation::cur());
+
+            let sum = a + (Expression::Constant(F::ONE) - b) * c + rc;
+            let parity = sum - out.clone() - carry.clone() * two.clone();
+            Constraints::with_selector(s, [bool_check(out), bool_check(carry), parity])
+        });
+
+        KeccakConfig {
+            advice,
+            round_constant,
+            column_sum_selector,
+            theta_selector,
+            chi_selector,
+        }
+    }
+
+    /// The 256 bits of the Keccak-256 digest of `bits`, in the order of the input: byte by
+    /// byte, each least significant bit first. The input cells must be constrained to bits,
+    /// e.g. by `DecomposeChip`.
+    ///
+    /// Panics unless `bits` is whole bytes fitting in one block, i.e. fewer than `RATE_BITS`
+    pub fn digest(
+        &self,
+        mut layouter: impl Layouter<F>,
+        bits: &[AssignedCell<F, F>],
+    ) -> Result<[AssignedCell<F, F>; 256], Error> {
+        assert!(
+            bits.len().is_multiple_of(8) && bits.len() < RATE_BITS,
+            "the input must be whole bytes fitting in one block"
+        );
+        let (zero, one) = layouter.assign_region(
+            || "keccak padding",
+            |mut region| {
+                let out = self.config.advice[5];
+                let zero = region.assign_advice_from_constant(|| "zero", out, 0, F::ZERO)?;
+                let one = region.assign_advice_from_constant(|| "one", out, 1, F::ONE)?;
+                Ok((zero, one))
+            },
+        )?;
+
+        // pad10*1: a one after the input and at the end of the block, zeros elsewhere
+        let mut state: Vec<AssignedCell<F, F>> = (0..STATE_BITS)
+            .map(|i| match i {
+                i if i < bits.len() => bits[i].clone(),
+                i if i == bits.len() || i == RATE_BITS - 1 => one.clone(),
+                _ => zero.clone(),
+            })
+            .collect();
+        for round in 0..ROUNDS {
+            state = self.round(
+                layouter.namespace(|| format!("keccak round {round}")),
+                &state,
+                round,
+            )?;
+        }
+
+        state.truncate(256);
+        Ok(state.try_into().expect("256 bits were kept"))
+    }
+
+    fn round(
+        &self,
+        mut layouter: impl Layouter<F>,
+        state: &[AssignedCell<F, F>],
+        round: usize,
+    ) -> Result<Vec<AssignedCell<F, F>>, Error> {
+        layouter.assign_region(
+            || "keccak round",
+            |mut region| {
+                let mut offset = 0;
+
+                // θ: the parity of each column, then each bit flipped by the parities of the
+                // column on its left and of the column on its right, one bit lower
+                let mut columns = Vec::with_capacity(5 * 64);
+                for x in 0..5 {
+                    for z in 0..64 {
+                        let inputs = (0..5).map(|y| &state[bit_index(x, y, z)]);
+                        let selector = self.config.column_sum_selector;
+                        columns.push(self.assign_parity(&mut region, selector, offset, inputs)?);
+                        offset += 1;
+                    }
+                }
+                let column = |x: usize, z: usize| &columns[64 * x + z];
+                let mut theta = Vec::with_capacity(STATE_BITS);
+                for (i, bit) in state.iter().enumerate() {
+                    let (x, z) = (i / 64 % 5, i % 64);
+                    let inputs = [
+                        bit,
+                        column((x + 4) % 5, z),
+                        column((x + 1) % 5, (z + 63) % 64),
+                    ];
+                    let selector = self.config.theta_selector;
+                    theta.push(self.assign_parity(&mut region, selector, offset, inputs)?);
+                    offset += 1;
+                }
+
+                // ρ and π: lane (x, y) rotated to lane (y, 2x + 3y)
+                let offsets = rho_offsets();
+                let mut moved: Vec<&AssignedCell<F, F>> = theta.iter().collect();
+                for x in 0..5 {
+                    for y in 0..5 {
+                        for z in 0..64 {
+                            let from = bit_index(x, y, (z + 64 - offsets[x][y]) % 64);
+                            moved[bit_index(y, (2 * x + 3 * y) % 5, z)] = &theta[from];
+                        }
+                    }
+                }
+
+                // χ, and ι on lane (0, 0)
+                let mut next = Vec::with_capacity(STATE_BITS);
+                for (i, bit) in moved.iter().enumerate() {
+                    let (x, y, z) = (i / 64 % 5, i / 320, i % 64);
+                    let inputs = [
+                        *bit,
+                        moved[bit_index((x + 1) % 5, y, z)],
+                        moved[bit_index((x + 2) % 5, y, z)],
+                    ];
+                    let rc = if (x, y) == (0, 0) {
+                        (ROUND_CONSTANTS[round] >> z) & 1
+                    } else {
+                        0
+                    };
+                    next.push(self.assign_chi(&mut region, offset, inputs, rc)?);
+                    offset += 1;
+                }
+                Ok(next)
+            },
+        )
+    }
+
+    /// Copies `inputs` to the first input columns at `offset`, and assigns their parity
+    fn assign_parity<'a>(
+        &self,
+        region: &mut Region<'_, F>,
+        selector: Selector,
+        offset: usize,
+        inputs: impl IntoIterator<Item = &'a AssignedCell<F, F>>,
+    ) -> Result<AssignedCell<F, F>, Error>
+    where
+        F: 'a,
+    {
+        selector.enable(region, offset)?;
+        let sum = self
+            .copy_inputs(region, offset, inputs)?
+            .into_iter()
+            .fold(Value::known(0), |sum, bit| {
+                sum.zip(bit).map(|(sum, bit)| sum + bit)
+            });
+        self.assign_sum(region, offset, sum)
+    }
+
+    /// `a ⊕ (¬b ∧ c) ⊕ rc` for `inputs = [a, b, c]`
+    fn assign_chi(
+        &self,
+        region: &mut Region<'_, F>,
+        offset: usize,
+        inputs: [&AssignedCell<F, F>; 3],
+        rc: u64,
+    ) -> Result<AssignedCell<F, F>, Error> {
+        self.config.chi_selector.enable(region, offset)?;
+        region.assign_fixed(
+            || "round constant",
+            self.config.round_constant,
+            offset,
+            || Value::known(F::from(rc)),
+        )?;
+        let [a, b, c]: [Value<u64>; 3] = self
+            .copy_inputs(region, offset, inputs)?
+            .try_into()
+            .expect("3 inputs");
+        let sum = a.zip(b).zip(c).map(|((a, b), c)| a + (1 - b) * c + rc);
+        self.assign_sum(region, offset, sum)
+    }
+
+    /// Copies `inputs` to the input columns at `offset`, and returns them as bits. A cell that
+    /// isn't a bit fails the gates, whichever bit it is read as.
+    fn copy_inputs<'a>(
+        &self,
+        region: &mut Region<'_, F>,
+        offset: usize,
+        inputs: impl IntoIterator<Item = &'a AssignedCell<F, F>>,
+    ) -> Result<Vec<Value<u64>>, Error>
+    where
+        F: 'a,
+    {
+        inputs
+            .into_iter()
+            .zip(self.config.advice)
+            .enumerate()
+            .map(|(i, (input, column))| {
+                let input = input.copy_advice(|| format!("input {i}"), region, column, offset)?;
+                Ok(input.value().map(|bit| u64::from(*bit == F::ONE)))
+            })
+            .collect()
+    }
+
+    /// `out` and `carry` of a row whose inputs sum to `sum`
+    fn assign_sum(
+        &self,
+        region: &mut Region<'_, F>,
+        offset: usize,
+        sum: Value<u64>,
+    ) -> Result<AssignedCell<F, F>, Error> {
+        region.assign_advice(
+            || "carry",
+            self.config.advice[6],
+            offset,
+            || sum.map(|sum| F::from(sum / 2)),
+        )?;
+        region.assign_advice(
+            || "out",
+            self.config.advice[5],
+            offset,
+            || sum.map(|sum| F::from(sum % 2)),
+        )
+    }
+}
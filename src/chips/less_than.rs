@@ -0,0 +1,244 @@
+use super::range_check::{RangeCheckChip, RangeCheckConfig};
+use halo2_proofs::{
+    circuit::{AssignedCell, Layouter},
+    halo2curves::ff::PrimeField,
+    plonk::{Advice, Column, ConstraintSystem, Constraints, Error, Expression, Selector},
+    poly::Rotation,
+};
+use std::marker::PhantomData;
+
+/// Bits of the low limb
+const LO_BITS: usize = 128;
+
+/// A value split as `hi * 2^128 + lo`, both limbs range checked and the split canonical
+#[derive(Debug, Clone)]
+pub struct Limbs<F: PrimeField> {
+    pub hi: AssignedCell<F, F>,
+    pub lo: AssignedCell<F, F>,
+}
+
+/// Strict comparison of field elements as integers in `0..p`, for sorted structures such as the
+/// indexed nullifier tree. A value is split in a 128-bit low limb and a `F::NUM_BITS - 128`-bit
+/// high limb, and the split is checked below `p`: otherwise `v` and `v + p` would both be valid
+/// splits of `v`, and a value could be made to compare as larger than itself. `x > y` then holds
+/// iff `x - y - 1`, computed limb by limb with a borrow, has limbs in range.
+///
+/// Gates:
+///   split     | value  | hi   | lo   |  value = hi * 2^128 + lo
+///   subtract  | x_lo   | y_lo | d_lo |  x_lo - y_lo - 1 + borrow * 2^128 = d_lo
+///             | x_hi   | y_hi | d_hi |  x_hi - y_hi - borrow = d_hi
+///             | borrow |      |      |  borrow is boolean
+#[derive(Debug, Clone, Copy)]
+pub struct LessThanConfig {
+    pub advice: [Column<Advice>; 3],
+    pub(crate) split_selector: Selector,
+    pub(crate) subtract_selector: Selector,
+    pub(crate) range_check_config: RangeCheckConfig,
+}
+
+pub struct LessThanChip<F> {
+    pub(crate) config: LessThanConfig,
+    _marker: PhantomData<F>,
+}
+
+/// Left-hand side of a subtraction
+enum Minuend<'a, F: PrimeField> {
+    Limbs(&'a Limbs<F>),
+    /// The field modulus, which isn't a field element but whose limbs are
+    Modulus,
+}
+
+/// The limb of `f` starting at byte `offset`. `to_repr` is little-endian for every field we
+/// use.
+fn limb<F: PrimeField>(f: F, offset: usize) -> u128 {
+    let repr = f.to_repr();
+    u128::from_le_bytes(
+        repr.as_ref()[offset..offset + 16]
+            .try_into()
+            .expect("32-byte representation"),
+    )
+}
+
+/// `(hi, lo)` limbs of the modulus `p`. `p - 1` is even, so adding 1 to its low limb can't carry.
+fn modulus_limbs<F: PrimeField>() -> (F, F) {
+    let p_minus_1 = -F::ONE;
+    (
+        F::from_u128(limb(p_minus_1, 16)),
+        F::from_u128(limb(p_minus_1, 0) + 1),
+    )
+}
+
+impl<F: PrimeField> LessThanChip<F> {
+    pub fn construct(config: LessThanConfig) -> Self {
+        Self {
+            config,
+            _marker: PhantomData,
+        }
+    }
+
+    /// Bits of the high limb
+    fn hi_bits() -> usize {
+        F::NUM_BITS as usize - LO_BITS
+    }
+
+    /// The range check's table must be loaded by the circuit, which may share it
+    pub fn configure(
+        meta: &mut ConstraintSystem<F>,
+        advice: [Column<Advice>; 3],
+        range_check_config: RangeCheckConfig,
+    ) -> LessThanConfig {
+        let split_selector = meta.selector();
+        let subtract_selector = meta.selector();
+        let two_pow_lo = Expression::Constant(F::from_u128(1 << 64).square());
+
+        meta.enable_equality(advice[0]);
+        meta.enable_equality(advice[1]);
+        meta.enable_equality(advice[2]);
+
+        meta.create_gate("limb split", |meta| {
+            let s = meta.query_selector(split_selector);
+            let value = meta.query_advice(advice[0], Rotation::cur());
+            let hi = meta.query_advice(advice[1], Rotation::cur());
+            let lo = meta.query_advice(advice[2], Rotation::cur());
+            vec![s * (hi * two_pow_lo.clone() + lo - value)]
+        });
+
+        meta.create_gate("limb subtraction", |meta| {
+            let s = meta.query_selector(subtract_selector);
+            let x_lo = meta.query_advice(advice[0], Rotation::cur());
+            let y_lo = meta.query_advice(advice[1], Rotation::cur());
+            let d_lo = meta.query_advice(advice[2], Rotation::cur());
+            let x_hi = meta.query_advice(advice[0], Rotation::next());
+            let y_hi = meta.query_advice(advice[1], Rotation::next());
+            let d_hi = meta.query_advice(advice[2], Rotation::next());
+            let borrow = meta.query_advice(advice[0], Rotation(2));
+
+            let one = Expression::Constant(F::ONE);
+            let lo = x_lo - y_lo - one.clone() + borrow.clone() * two_pow_lo.clone() - d_lo;
+            let hi = x_hi - y_hi - borrow.clone() - d_hi;
+            let bool_check = borrow.clone() * (one - borrow);
+            Constraints::with_selector(s, [lo, hi, bool_check])
+        });
+
+        LessThanConfig {
+            advice,
+            split_selector,
+            subtract_selector,
+            range_check_config,
+        }
+    }
+
+    /// Splits `value` in range checked limbs, and checks that the split is the canonical one
+    pub fn split(
+        &self,
+        mut layouter: impl Layouter<F>,
+        value: &AssignedCell<F, F>,
+    ) -> Result<Limbs<F>, Error> {
+        let [value_column, hi_column, lo_column] = self.config.advice;
+        let limbs = layouter.assign_region(
+            || "limb split",
+            |mut region| {
+                self.config.split_selector.enable(&mut region, 0)?;
+                value.copy_advice(|| "value", &mut region, value_column, 0)?;
+                let hi = region.assign_advice(
+                    || "hi",
+                    hi_column,
+                    0,
+                    || value.value().map(|v| F::from_u128(limb(*v, 16))),
+                )?;
+                let lo = region.assign_advice(
+                    || "lo",
+                    lo_column,
+                    0,
+                    || value.value().map(|v| F::from_u128(limb(*v, 0))),
+                )?;
+                Ok(Limbs { hi, lo })
+            },
+        )?;
+        self.range_check_limbs(layouter.namespace(|| "range check limbs"), &limbs)?;
+
+        // p - value - 1 >= 0
+        self.subtract(
+            layouter.namespace(|| "check canonical split"),
+            Minuend::Modulus,
+            &limbs,
+        )?;
+        Ok(limbs)
+    }
+
+    /// Constrains `a < b`
+    pub fn check_less_than(
+        &self,
+        mut layouter: impl Layouter<F>,
+        a: &Limbs<F>,
+        b: &Limbs<F>,
+    ) -> Result<(), Error> {
+        // b - a - 1 >= 0
+        self.subtract(layouter.namespace(|| "b - a - 1"), Minuend::Limbs(b), a)
+    }
+
+    fn range_check_limbs(
+        &self,
+        mut layouter: impl Layouter<F>,
+        limbs: &Limbs<F>,
+    ) -> Result<(), Error> {
+        let range_check_chip = RangeCheckChip::construct(self.config.range_check_config);
+        range_check_chip.range_check(layouter.namespace(|| "lo"), &limbs.lo, LO_BITS)?;
+        range_check_chip.range_check(layouter.namespace(|| "hi"), &limbs.hi, Self::hi_bits())
+    }
+
+    /// Constrains `x - y - 1 >= 0` as integers, by range checking the limbs of the difference
+    fn subtract(
+        &self,
+        mut layouter: impl Layouter<F>,
+        x: Minuend<'_, F>,
+        y: &Limbs<F>,
+    ) -> Result<(), Error> {
+        let [x_column, y_column, d_column] = self.config.advice;
+        let difference = layouter.assign_region(
+            || "limb subtraction",
+            |mut region| {
+                self.config.subtract_selector.enable(&mut region, 0)?;
+                let (x_hi, x_lo) = match &x {
+                    Minuend::Limbs(limbs) => (
+                        limbs.hi.copy_advice(|| "x_hi", &mut region, x_column, 1)?,
+                        limbs.lo.copy_advice(|| "x_lo", &mut region, x_column, 0)?,
+                    ),
+                    Minuend::Modulus => {
+                        let (p_hi, p_lo) = modulus_limbs::<F>();
+                        (
+                            region.assign_advice_from_constant(|| "p_hi", x_column, 1, p_hi)?,
+                            region.assign_advice_from_constant(|| "p_lo", x_column, 0, p_lo)?,
+                        )
+                    }
+                };
+                y.lo.copy_advice(|| "y_lo", &mut region, y_column, 0)?;
+                y.hi.copy_advice(|| "y_hi", &mut region, y_column, 1)?;
+
+                // the low limbs are below 2^128 in an honest proof, and a dishonest one fails
+                // the range checks whatever is assigned here
+                let lo = x_lo.value().zip(y.lo.value()).map(|(x, y)| {
+                    let (d, under_y) = limb(*x, 0).overflowing_sub(limb(*y, 0));
+                    let (d, under_one) = d.overflowing_sub(1);
+                    (d, under_y || under_one)
+                });
+                let borrow = lo.map(|(_, borrow)| F::from(borrow as u64));
+                region.assign_advice(|| "borrow", x_column, 2, || borrow)?;
+                let d_lo = region.assign_advice(
+                    || "d_lo",
+                    d_column,
+                    0,
+                    || lo.map(|(d, _)| F::from_u128(d)),
+                )?;
+                let d_hi = region.assign_advice(
+                    || "d_hi",
+                    d_column,
+                    1,
+                    || x_hi.value().cloned() - y.hi.value().cloned() - borrow,
+                )?;
+                Ok(Limbs { hi: d_hi, lo: d_lo })
+            },
+        )?;
+        self.range_check_limbs(layouter.namespace(|| "range check difference"), &difference)
+    }
+}
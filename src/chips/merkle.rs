@@ -1,23 +1,45 @@
-use crate::chips::hash::{HashChip, HashConfig};
+use crate::{
+    chips::{
+        cond_swap::{CondSwapChip, CondSwapConfig},
+        decompose::{DecomposeChip, DecomposeConfig},
+        hash::{HashChip, HashConfig},
+    },
+    tree::{zeros, ProductHasher},
+};
 use halo2_proofs::{
-    circuit::{AssignedCell, Layouter, Value},
+    circuit::{AssignedCell, Layouter, Region, Value},
     halo2curves::ff::PrimeField,
-    plonk::{Advice, Column, ConstraintSystem, Error, Expression, Instance, Selector},
+    plonk::{Advice, Column, ConstraintSystem, Error, Expression, Fixed, Selector},
     poly::Rotation,
 };
 use std::marker::PhantomData;
 
+/// Layer region, the swap gate on the first row and the hash gate on the second, so the
+/// ordered nodes are hashed where the swap puts them:
+///   | node  | neighbor | bit    |
+///   | left  | right    | digest |
+///   | count | previous |        |
+/// `prove_tree_root` also counts the layers on the third row, `prove_tree_update` doesn't.
+///
+/// `prove_tree_append` loads each path element in its own region, with the layer's empty
+/// subtree in the `zeros` column:
+///   | | element | bit | zero |
 #[derive(Debug, Clone)]
 pub struct MerkleConfig {
     pub advice: [Column<Advice>; 3],
-    pub instance: Column<Instance>,
-    pub swap_selector: Selector,
-    pub swap_bit_bool_selector: Selector,
-    pub hash_config: HashConfig,
+    pub(crate) layer_counter_selector: Selector,
+    /// `element` is `zero` unless `bit` is 1
+    pub(crate) padding_selector: Selector,
+    pub(crate) constant: Column<Fixed>,
+    /// `zeros::<F, ProductHasher>(DEPTH)[layer]` on the path element row of each layer
+    pub(crate) zeros: Column<Fixed>,
+    pub(crate) cond_swap_config: CondSwapConfig,
+    pub(crate) decompose_config: DecomposeConfig,
+    pub(crate) hash_config: HashConfig,
 }
 
 pub struct MerkleChip<F> {
-    pub config: MerkleConfig,
+    pub(crate) config: MerkleConfig,
     _marker: PhantomData<F>,
 }
 
@@ -29,133 +51,278 @@ impl<F: PrimeField> MerkleChip<F> {
         }
     }
 
-    pub fn configure(
-        meta: &mut ConstraintSystem<F>,
-        advice: [Column<Advice>; 3],
-        instance: Column<Instance>,
-    ) -> MerkleConfig {
-        let swap_selector = meta.selector();
-        let swap_bit_bool_selector = meta.selector();
+    pub fn configure(meta: &mut ConstraintSystem<F>, advice: [Column<Advice>; 3]) -> MerkleConfig {
+        let layer_counter_selector = meta.selector();
+        let padding_selector = meta.selector();
+        let constant = meta.fixed_column();
+        meta.enable_constant(constant);
+        let zeros = meta.fixed_column();
 
         meta.enable_equality(advice[0]);
         meta.enable_equality(advice[1]);
         meta.enable_equality(advice[2]);
-        meta.enable_equality(instance);
 
-        // we check that the `swap_bit` (advice[2]) is either `0` or `1`
-        meta.create_gate("bool constraint", |meta| {
-            let s = meta.query_selector(swap_bit_bool_selector);
-            let swap_bit = meta.query_advice(advice[2], Rotation::cur());
-            vec![s * swap_bit.clone() * (Expression::Constant(F::from(1)) - swap_bit)]
+        // the number of layers applied including this one, next to the previous count.
+        // `prove_tree_root` pins the final count to `DEPTH`.
+        meta.create_gate("layer counter", |meta| {
+            let s = meta.query_selector(layer_counter_selector);
+            let count = meta.query_advice(advice[0], Rotation::cur());
+            let prev_count = meta.query_advice(advice[1], Rotation::cur());
+            vec![s * (prev_count + Expression::Constant(F::ONE) - count)]
         });
 
-        // if the swap selector is on (on the first row)
-        // then we check the `swap_bit`
-        // If it's on (1) -> we make sure the leaves are swapped on the next row
-        meta.create_gate("swap constraint", |meta| {
-            let s = meta.query_selector(swap_selector);
-            let swap_bit = meta.query_advice(advice[2], Rotation::cur());
-
-            let left_cur = meta.query_advice(advice[0], Rotation::cur());
-            let right_cur = meta.query_advice(advice[1], Rotation::cur());
-
-            let left_next = meta.query_advice(advice[0], Rotation::next());
-            let right_next = meta.query_advice(advice[1], Rotation::next());
-
-            let constraint1 = s.clone()
-                * ((right_cur.clone() - left_cur.clone()) * swap_bit.clone() + left_cur.clone()
-                    - left_next);
-            let constraint2 =
-                s * ((left_cur - right_cur.clone()) * swap_bit + right_cur - right_next);
-            vec![constraint1, constraint2]
+        // the sibling of a left child is on the right of the appended leaf, i.e. empty. The
+        // bit is copied from the decomposition of the index, which constrains it to 0 or 1.
+        meta.create_gate("zero padding", |meta| {
+            let s = meta.query_selector(padding_selector);
+            let element = meta.query_advice(advice[1], Rotation::cur());
+            let bit = meta.query_advice(advice[2], Rotation::cur());
+            let zero = meta.query_fixed(zeros, Rotation::cur());
+            vec![s * (Expression::Constant(F::ONE) - bit) * (element - zero)]
         });
 
-        let hash_config = HashChip::configure(meta, advice, instance);
+        let cond_swap_config = CondSwapChip::configure(meta, advice);
+        let decompose_config = DecomposeChip::configure(meta, advice, constant);
+        let hash_config = HashChip::configure(meta, advice);
 
         MerkleConfig {
             advice,
-            instance,
-            swap_selector,
-            swap_bit_bool_selector,
+            layer_counter_selector,
+            padding_selector,
+            constant,
+            zeros,
+            cond_swap_config,
+            decompose_config,
             hash_config,
         }
     }
 
+    /// Hashes `node_cell` with its neighbor. `layer` is the number of layers applied so far;
+    /// returns the parent node and the incremented count.
     pub fn merkle_prove_layer(
+        &self,
+        layouter: impl Layouter<F>,
+        node_cell: &AssignedCell<F, F>,
+        layer: &AssignedCell<F, F>,
+        neighbor: Value<F>,
+        swap_bit: &AssignedCell<F, F>,
+    ) -> Result<(AssignedCell<F, F>, AssignedCell<F, F>), Error> {
+        self.merkle_prove_layer_inner(layouter, node_cell, layer, neighbor, swap_bit, None)
+    }
+
+    fn merkle_prove_layer_inner(
         &self,
         mut layouter: impl Layouter<F>,
         node_cell: &AssignedCell<F, F>,
+        layer: &AssignedCell<F, F>,
         neighbor: Value<F>,
-        swap_bit: Value<F>,
-    ) -> Result<AssignedCell<F, F>, Error> {
-        let (left, right) = layouter.assign_region(
-            || "merkle prove",
+        swap_bit: &AssignedCell<F, F>,
+        digest: Option<Value<F>>,
+    ) -> Result<(AssignedCell<F, F>, AssignedCell<F, F>), Error> {
+        layouter.assign_region(
+            || "merkle layer",
             |mut region| {
-                self.config.swap_selector.enable(&mut region, 0)?;
-                self.config.swap_bit_bool_selector.enable(&mut region, 0)?;
-
-                node_cell.copy_advice(
-                    || "copy previous node cell",
-                    &mut region,
-                    self.config.advice[0],
-                    0,
-                )?;
-                region.assign_advice(
-                    || "set neighbor node",
-                    self.config.advice[1],
-                    0,
-                    || neighbor,
-                )?;
-                region.assign_advice(|| "set swap bit", self.config.advice[2], 0, || swap_bit)?;
-
-                let mut left = node_cell.value().cloned();
-                let mut right = neighbor;
-                swap_bit.map(|f| {
-                    (left, right) = if f == F::ZERO {
-                        (left, right)
-                    } else {
-                        (right, left)
-                    }
-                });
+                let parent =
+                    self.assign_layer(&mut region, node_cell, neighbor, None, swap_bit, digest)?;
 
-                let left_cell = region.assign_advice(
-                    || "left node to be hashed",
+                self.config.layer_counter_selector.enable(&mut region, 2)?;
+                layer.copy_advice(|| "layers so far", &mut region, self.config.advice[1], 2)?;
+                let next_layer = region.assign_advice(
+                    || "layers including this one",
                     self.config.advice[0],
-                    1,
-                    || left,
+                    2,
+                    || layer.value().map(|count| *count + F::ONE),
                 )?;
-                let right_cell = region.assign_advice(
-                    || "right node to be hashed",
-                    self.config.advice[1],
-                    1,
-                    || right,
-                )?;
-
-                Ok((left_cell, right_cell))
+                Ok((parent, next_layer))
             },
-        )?;
+        )
+    }
 
+    /// Orders `node` and its neighbor on the first two rows of `region` and hashes them on the
+    /// second. `neighbor_cell` copies the neighbor in, e.g. a sibling shared by two paths.
+    fn assign_layer(
+        &self,
+        region: &mut Region<'_, F>,
+        node: &AssignedCell<F, F>,
+        neighbor: Value<F>,
+        neighbor_cell: Option<&AssignedCell<F, F>>,
+        swap_bit: &AssignedCell<F, F>,
+        digest: Option<Value<F>>,
+    ) -> Result<AssignedCell<F, F>, Error> {
+        let swap_chip = CondSwapChip::construct(self.config.cond_swap_config);
         let hash_chip = HashChip::construct(self.config.hash_config);
-        let result_hash_cell = hash_chip.hash(layouter.namespace(|| "hash row"), left, right)?;
-        Ok(result_hash_cell)
+        let (left, right) =
+            swap_chip.assign_swap(region, 0, node, neighbor, neighbor_cell, swap_bit)?;
+        hash_chip.assign_digest(region, 1, &left, &right, digest)
+    }
+
+    /// Applies exactly `DEPTH` layers, so the tree depth is part of the circuit shape. The
+    /// layer counter is constrained from 0 to `DEPTH`, so a root can only be exposed once
+    /// every layer was hashed.
+    ///
+    /// The swap bits are the bits of `leaf_index`, least significant first, so the proof fails
+    /// unless `leaf_index < 2^DEPTH`.
+    pub fn prove_tree_root<const DEPTH: usize>(
+        &self,
+        layouter: impl Layouter<F>,
+        leaf: AssignedCell<F, F>,
+        path_elements: [Value<F>; DEPTH],
+        leaf_index: Value<F>,
+    ) -> Result<AssignedCell<F, F>, Error> {
+        self.prove_tree_root_inner(layouter, leaf, path_elements, leaf_index, None)
+    }
+
+    /// Like `prove_tree_root`, with the digest of each layer computed beforehand, from the
+    /// leaf's parent up to the root, see `WitnessCalculator`
+    pub fn prove_tree_root_precomputed<const DEPTH: usize>(
+        &self,
+        layouter: impl Layouter<F>,
+        leaf: AssignedCell<F, F>,
+        path_elements: [Value<F>; DEPTH],
+        leaf_index: Value<F>,
+        digests: [Value<F>; DEPTH],
+    ) -> Result<AssignedCell<F, F>, Error> {
+        self.prove_tree_root_inner(layouter, leaf, path_elements, leaf_index, Some(digests))
     }
 
-    pub fn prove_tree_root(
+    fn prove_tree_root_inner<const DEPTH: usize>(
         &self,
         mut layouter: impl Layouter<F>,
         leaf: AssignedCell<F, F>,
-        path_elements: Vec<Value<F>>,
-        path_indices: Vec<Value<F>>,
+        path_elements: [Value<F>; DEPTH],
+        leaf_index: Value<F>,
+        digests: Option<[Value<F>; DEPTH]>,
     ) -> Result<AssignedCell<F, F>, Error> {
+        let decompose_chip = DecomposeChip::construct(self.config.decompose_config);
+        let swap_bits = decompose_chip
+            .decompose::<DEPTH>(layouter.namespace(|| "decompose leaf index"), leaf_index)?;
+
+        let mut layer = layouter.assign_region(
+            || "layer counter start",
+            |mut region| {
+                region.assign_advice_from_constant(
+                    || "no layers",
+                    self.config.advice[2],
+                    0,
+                    F::ZERO,
+                )
+            },
+        )?;
+
         let mut digest: AssignedCell<F, F> = leaf;
-        for i in 0..path_elements.len() {
-            digest = self.merkle_prove_layer(
+        let digests = digests.map_or([None; DEPTH], |digests| digests.map(Some));
+        for ((element, swap_bit), precomputed) in
+            path_elements.into_iter().zip(&swap_bits).zip(digests)
+        {
+            (digest, layer) = self.merkle_prove_layer_inner(
                 layouter.namespace(|| "prove tree"),
                 &digest,
-                path_elements[i],
-                path_indices[i],
+                &layer,
+                element,
+                swap_bit,
+                precomputed,
             )?;
         }
+
+        layouter.assign_region(
+            || "layer counter end",
+            |mut region| region.constrain_constant(layer.cell(), F::from(DEPTH as u64)),
+        )?;
         Ok(digest)
     }
+
+    /// Returns the roots of the trees holding `old_leaf` and `new_leaf` at `leaf_index`, with
+    /// every other leaf the same: both paths are hashed over the same sibling cells and the
+    /// same bits of `leaf_index`.
+    pub fn prove_tree_update<const DEPTH: usize>(
+        &self,
+        layouter: impl Layouter<F>,
+        old_leaf: AssignedCell<F, F>,
+        new_leaf: AssignedCell<F, F>,
+        path_elements: [Value<F>; DEPTH],
+        leaf_index: &AssignedCell<F, F>,
+    ) -> Result<(AssignedCell<F, F>, AssignedCell<F, F>), Error> {
+        self.prove_tree_update_inner(
+            layouter,
+            old_leaf,
+            new_leaf,
+            path_elements,
+            leaf_index,
+            false,
+        )
+    }
+
+    /// Like `prove_tree_update`, for a leaf appended at the tree's next index: every sibling
+    /// on its right is constrained to be the empty subtree of its layer (for `ProductHasher`),
+    /// so the path can't be padded with anything else. These are the siblings of the layers
+    /// where the leaf's subtree is a left child, i.e. the 0 bits of `leaf_index`.
+    pub fn prove_tree_append<const DEPTH: usize>(
+        &self,
+        layouter: impl Layouter<F>,
+        old_leaf: AssignedCell<F, F>,
+        new_leaf: AssignedCell<F, F>,
+        path_elements: [Value<F>; DEPTH],
+        leaf_index: &AssignedCell<F, F>,
+    ) -> Result<(AssignedCell<F, F>, AssignedCell<F, F>), Error> {
+        self.prove_tree_update_inner(
+            layouter,
+            old_leaf,
+            new_leaf,
+            path_elements,
+            leaf_index,
+            true,
+        )
+    }
+
+    fn prove_tree_update_inner<const DEPTH: usize>(
+        &self,
+        mut layouter: impl Layouter<F>,
+        old_leaf: AssignedCell<F, F>,
+        new_leaf: AssignedCell<F, F>,
+        path_elements: [Value<F>; DEPTH],
+        leaf_index: &AssignedCell<F, F>,
+        padded: bool,
+    ) -> Result<(AssignedCell<F, F>, AssignedCell<F, F>), Error> {
+        let decompose_chip = DecomposeChip::construct(self.config.decompose_config);
+        let swap_bits = decompose_chip
+            .decompose_cell::<DEPTH>(layouter.namespace(|| "decompose leaf index"), leaf_index)?;
+        let zeros = zeros::<F, ProductHasher>(DEPTH);
+
+        let (mut old_digest, mut new_digest) = (old_leaf, new_leaf);
+        for ((element, swap_bit), zero) in path_elements.into_iter().zip(&swap_bits).zip(zeros) {
+            let neighbor = layouter.assign_region(
+                || "path element",
+                |mut region| {
+                    if padded {
+                        self.config.padding_selector.enable(&mut region, 0)?;
+                        swap_bit.copy_advice(|| "bit", &mut region, self.config.advice[2], 0)?;
+                        region.assign_fixed(
+                            || "empty subtree",
+                            self.config.zeros,
+                            0,
+                            || Value::known(zero),
+                        )?;
+                    }
+                    region.assign_advice(|| "path element", self.config.advice[1], 0, || element)
+                },
+            )?;
+            for digest in [&mut old_digest, &mut new_digest] {
+                let parent = layouter.assign_region(
+                    || "merkle layer",
+                    |mut region| {
+                        self.assign_layer(
+                            &mut region,
+                            digest,
+                            neighbor.value().cloned(),
+                            Some(&neighbor),
+                            swap_bit,
+                            None,
+                        )
+                    },
+                )?;
+                *digest = parent;
+            }
+        }
+        Ok((old_digest, new_digest))
+    }
 }
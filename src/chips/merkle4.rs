@@ -0,0 +1,189 @@
+use halo2_proofs::{
+    circuit::{AssignedCell, Layouter, Value},
+    halo2curves::ff::PrimeField,
+    plonk::{Advice, Column, ConstraintSystem, Constraints, Error, Expression, Selector},
+    poly::Rotation,
+};
+use std::marker::PhantomData;
+
+/// Arity-4 Merkle chip: each layer hashes the node with its 3 siblings, so a tree of `4^DEPTH`
+/// leaves needs half the layers of the binary tree. The 4-to-1 hash is the product of the
+/// children, like the `HashChip` gate.
+#[derive(Debug, Clone)]
+pub struct Merkle4Config {
+    pub advice: [Column<Advice>; 3],
+    pub(crate) layer_selector: Selector,
+}
+
+pub struct Merkle4Chip<F> {
+    pub(crate) config: Merkle4Config,
+    _marker: PhantomData<F>,
+}
+
+/// Position of the node among its siblings, from the layer's index in `0..4`
+fn node_position<F: PrimeField>(index: F) -> Option<u64> {
+    (0..4).find(|i| F::from(*i) == index)
+}
+
+impl<F: PrimeField> Merkle4Chip<F> {
+    pub fn construct(config: Merkle4Config) -> Self {
+        Self {
+            config,
+            _marker: PhantomData,
+        }
+    }
+
+    pub fn configure(meta: &mut ConstraintSystem<F>, advice: [Column<Advice>; 3]) -> Merkle4Config {
+        let layer_selector = meta.selector();
+
+        meta.enable_equality(advice[0]);
+        meta.enable_equality(advice[1]);
+        meta.enable_equality(advice[2]);
+
+        // Layer layout, `layer_selector` on the first row:
+        //   | node   | sibling 0 | sibling 1 |
+        //   | sib. 2 | bit 0     | bit 1     |
+        //   | index  | child 0   | child 1   |
+        //   | ch. 2  | child 3   | c0*c1*c2  |
+        //   | parent |           |           |
+        meta.create_gate("quad merkle layer", |meta| {
+            let s = meta.query_selector(layer_selector);
+            let one = Expression::Constant(F::ONE);
+            let two = Expression::Constant(F::from(2));
+
+            let node = meta.query_advice(advice[0], Rotation(0));
+            let sibling0 = meta.query_advice(advice[1], Rotation(0));
+            let sibling1 = meta.query_advice(advice[2], Rotation(0));
+            let sibling2 = meta.query_advice(advice[0], Rotation(1));
+            let bit0 = meta.query_advice(advice[1], Rotation(1));
+            let bit1 = meta.query_advice(advice[2], Rotation(1));
+            let index = meta.query_advice(advice[0], Rotation(2));
+            let child0 = meta.query_advice(advice[1], Rotation(2));
+            let child1 = meta.query_advice(advice[2], Rotation(2));
+            let child2 = meta.query_advice(advice[0], Rotation(3));
+            let child3 = meta.query_advice(advice[1], Rotation(3));
+            let partial = meta.query_advice(advice[2], Rotation(3));
+            let parent = meta.query_advice(advice[0], Rotation(4));
+
+            // index decomposition into 2 bits
+            let bool0 = bit0.clone() * (one.clone() - bit0.clone());
+            let bool1 = bit1.clone() * (one.clone() - bit1.clone());
+            let decomposition = bit0.clone() + two * bit1.clone() - index;
+
+            // is_at[i] is 1 iff the node goes in position i
+            let is_at0 = (one.clone() - bit0.clone()) * (one.clone() - bit1.clone());
+            let is_at1 = bit0.clone() * (one.clone() - bit1.clone());
+            let is_at2 = (one.clone() - bit0.clone()) * bit1.clone();
+            let is_at3 = bit0 * bit1;
+
+            // 4-way conditional rotate: the node is inserted at its position and the siblings
+            // keep their order around it
+            let rotate0 = is_at0.clone() * node.clone()
+                + (one.clone() - is_at0.clone()) * sibling0.clone()
+                - child0.clone();
+            let rotate1 = is_at0.clone() * sibling0
+                + is_at1.clone() * node.clone()
+                + (is_at2.clone() + is_at3.clone()) * sibling1.clone()
+                - child1.clone();
+            let rotate2 = (is_at0 + is_at1) * sibling1
+                + is_at2 * node.clone()
+                + is_at3.clone() * sibling2.clone()
+                - child2.clone();
+            let rotate3 = (one - is_at3.clone()) * sibling2 + is_at3 * node - child3.clone();
+
+            let hash_partial = child0 * child1 * child2 - partial.clone();
+            let hash = partial * child3 - parent;
+
+            Constraints::with_selector(
+                s,
+                [
+                    bool0,
+                    bool1,
+                    decomposition,
+                    rotate0,
+                    rotate1,
+                    rotate2,
+                    rotate3,
+                    hash_partial,
+                    hash,
+                ],
+            )
+        });
+
+        Merkle4Config {
+            advice,
+            layer_selector,
+        }
+    }
+
+    /// Hashes `node_cell` with its `siblings`, `index` in `0..4` being the node's position
+    pub fn prove_layer(
+        &self,
+        mut layouter: impl Layouter<F>,
+        node_cell: &AssignedCell<F, F>,
+        siblings: [Value<F>; 3],
+        index: Value<F>,
+    ) -> Result<AssignedCell<F, F>, Error> {
+        layouter.assign_region(
+            || "quad merkle layer",
+            |mut region| {
+                self.config.layer_selector.enable(&mut region, 0)?;
+                let [a0, a1, a2] = self.config.advice;
+
+                node_cell.copy_advice(|| "node", &mut region, a0, 0)?;
+                region.assign_advice(|| "sibling 0", a1, 0, || siblings[0])?;
+                region.assign_advice(|| "sibling 1", a2, 0, || siblings[1])?;
+                region.assign_advice(|| "sibling 2", a0, 1, || siblings[2])?;
+
+                // an out-of-range index gets position 0 and fails the decomposition
+                let position = index.map(|index| node_position(index).unwrap_or(0));
+                region.assign_advice(|| "bit 0", a1, 1, || position.map(|p| F::from(p & 1)))?;
+                region.assign_advice(|| "bit 1", a2, 1, || position.map(|p| F::from(p >> 1)))?;
+                region.assign_advice(|| "index", a0, 2, || index)?;
+
+                let children: Value<[F; 4]> = node_cell
+                    .value()
+                    .copied()
+                    .zip(siblings[0].zip(siblings[1]).zip(siblings[2]))
+                    .zip(position)
+                    .map(|((node, ((s0, s1), s2)), position)| {
+                        let mut children = vec![s0, s1, s2];
+                        children.insert(position as usize, node);
+                        [children[0], children[1], children[2], children[3]]
+                    });
+                let child = |i: usize| children.map(|children| children[i]);
+                region.assign_advice(|| "child 0", a1, 2, || child(0))?;
+                region.assign_advice(|| "child 1", a2, 2, || child(1))?;
+                region.assign_advice(|| "child 2", a0, 3, || child(2))?;
+                region.assign_advice(|| "child 3", a1, 3, || child(3))?;
+                region.assign_advice(
+                    || "c0 * c1 * c2",
+                    a2,
+                    3,
+                    || child(0) * child(1) * child(2),
+                )?;
+                region.assign_advice(|| "parent", a0, 4, || children.map(|c| c.iter().product()))
+            },
+        )
+    }
+
+    /// Applies exactly `DEPTH` quad layers
+    pub fn prove_tree_root<const DEPTH: usize>(
+        &self,
+        mut layouter: impl Layouter<F>,
+        leaf: AssignedCell<F, F>,
+        path_elements: [[Value<F>; 3]; DEPTH],
+        path_indices: [Value<F>; DEPTH],
+    ) -> Result<AssignedCell<F, F>, Error> {
+        let mut digest = leaf;
+        for (siblings, index) in path_elements.into_iter().zip(path_indices) {
+            digest = self.prove_layer(
+                layouter.namespace(|| "prove quad layer"),
+                &digest,
+                siblings,
+                index,
+            )?;
+        }
+        Ok(digest)
+    }
+}
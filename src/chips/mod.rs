@@ -1,3 +1,11 @@
+pub mod cond_swap;
+pub mod decompose;
 pub mod hash;
+pub mod is_zero;
+pub mod keccak;
+pub mod less_than;
 pub mod merkle;
+pub mod merkle4;
+pub mod range_check;
+pub mod sha256;
 pub mod tornado;
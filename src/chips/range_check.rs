@@ -0,0 +1,164 @@
+use halo2_proofs::{
+    circuit::{AssignedCell, Layouter, Value},
+    halo2curves::ff::PrimeField,
+    plonk::{
+        Advice, Column, ConstraintSystem, Error, Expression, Fixed, Selector, TableColumn,
+        VirtualCells,
+    },
+    poly::Rotation,
+};
+use std::marker::PhantomData;
+
+/// Largest chunk looked up directly: the table has `2^lookup_bits` rows, so it must fit in the
+/// circuit next to everything else
+pub const MAX_LOOKUP_BITS: usize = 16;
+
+/// Range check of a value to `num_bits` bits, by splitting it in `lookup_bits`-bit chunks with a
+/// running sum: `z_0 = value`, `z_i = 2^lookup_bits * z_{i+1} + chunk_i` and `z_n = 0`. Each
+/// chunk is looked up in a table of `0..2^lookup_bits`. When `num_bits` isn't a multiple of
+/// `lookup_bits`, the last chunk is also looked up shifted left by the missing bits, so it can
+/// only be short.
+///
+/// Region layout, `lookup_selector` on every row but the last:
+///   | z_0 | shift_0 |
+///   | z_1 | shift_1 |
+///   | ... | ...     |
+///   | z_n |         |
+#[derive(Debug, Clone, Copy)]
+pub struct RangeCheckConfig {
+    pub advice: [Column<Advice>; 3],
+    pub(crate) shift: Column<Fixed>,
+    pub(crate) lookup_selector: Selector,
+    pub(crate) table: TableColumn,
+    pub lookup_bits: usize,
+}
+
+pub struct RangeCheckChip<F> {
+    pub(crate) config: RangeCheckConfig,
+    _marker: PhantomData<F>,
+}
+
+/// Low `bits` bits of `f`. `to_repr` is little-endian for every field we use.
+fn low_bits<F: PrimeField>(f: F, bits: usize) -> u64 {
+    let repr = f.to_repr();
+    let low = u32::from_le_bytes([repr.as_ref()[0], repr.as_ref()[1], 0, 0]) as u64;
+    low & ((1 << bits) - 1)
+}
+
+impl<F: PrimeField> RangeCheckChip<F> {
+    pub fn construct(config: RangeCheckConfig) -> Self {
+        Self {
+            config,
+            _marker: PhantomData,
+        }
+    }
+
+    /// `constant` is used to pin the last running sum to 0. Panics unless
+    /// `0 < lookup_bits <= MAX_LOOKUP_BITS`.
+    pub fn configure(
+        meta: &mut ConstraintSystem<F>,
+        advice: [Column<Advice>; 3],
+        constant: Column<Fixed>,
+        lookup_bits: usize,
+    ) -> RangeCheckConfig {
+        assert!(
+            lookup_bits > 0 && lookup_bits <= MAX_LOOKUP_BITS,
+            "lookup_bits must be in 1..={MAX_LOOKUP_BITS}"
+        );
+        let lookup_selector = meta.complex_selector();
+        let shift = meta.fixed_column();
+        let table = meta.lookup_table_column();
+
+        meta.enable_constant(constant);
+        meta.enable_equality(advice[0]);
+
+        // a disabled row looks up 0, which is in the table
+        let chunk = |meta: &mut VirtualCells<F>| {
+            let s = meta.query_selector(lookup_selector);
+            let z_cur = meta.query_advice(advice[0], Rotation::cur());
+            let z_next = meta.query_advice(advice[0], Rotation::next());
+            s * (z_cur - z_next * Expression::Constant(F::from(1 << lookup_bits)))
+        };
+        meta.lookup("range check chunk", |meta| vec![(chunk(meta), table)]);
+        meta.lookup("range check shifted chunk", |meta| {
+            let shift = meta.query_fixed(shift, Rotation::cur());
+            vec![(chunk(meta) * shift, table)]
+        });
+
+        RangeCheckConfig {
+            advice,
+            shift,
+            lookup_selector,
+            table,
+            lookup_bits,
+        }
+    }
+
+    /// Fills the lookup table, once per circuit
+    pub fn load_table(&self, mut layouter: impl Layouter<F>) -> Result<(), Error> {
+        layouter.assign_table(
+            || "range check table",
+            |mut table| {
+                for value in 0..1 << self.config.lookup_bits {
+                    table.assign_cell(
+                        || "value",
+                        self.config.table,
+                        value,
+                        || Value::known(F::from(value as u64)),
+                    )?;
+                }
+                Ok(())
+            },
+        )
+    }
+
+    /// Constrains `value < 2^num_bits`
+    pub fn range_check(
+        &self,
+        mut layouter: impl Layouter<F>,
+        value: &AssignedCell<F, F>,
+        num_bits: usize,
+    ) -> Result<(), Error> {
+        let lookup_bits = self.config.lookup_bits;
+        let num_chunks = num_bits.div_ceil(lookup_bits);
+        let last_shift = F::from(1 << (num_chunks * lookup_bits - num_bits));
+        let chunk_size_inv = F::from(1 << lookup_bits).invert().unwrap();
+
+        layouter.assign_region(
+            || "range check",
+            |mut region| {
+                let z_column = self.config.advice[0];
+                let mut z = value.copy_advice(|| "z_0", &mut region, z_column, 0)?;
+
+                // an out-of-range value keeps a non-zero remainder and fails the last check
+                for i in 0..num_chunks {
+                    self.config.lookup_selector.enable(&mut region, i)?;
+                    let shift = if i + 1 == num_chunks {
+                        last_shift
+                    } else {
+                        F::ONE
+                    };
+                    region.assign_fixed(
+                        || format!("shift {i}"),
+                        self.config.shift,
+                        i,
+                        || Value::known(shift),
+                    )?;
+
+                    let next = z.value().map(|z| {
+                        let chunk = F::from(low_bits(*z, lookup_bits));
+                        (*z - chunk) * chunk_size_inv
+                    });
+                    z = region.assign_advice(
+                        || format!("z_{}", i + 1),
+                        z_column,
+                        i + 1,
+                        || next,
+                    )?;
+                }
+
+                region.constrain_constant(z.cell(), F::ZERO)
+            },
+        )
+    }
+}
@@ -0,0 +1,630 @@
+use super::{
+    decompose::{DecomposeChip, DecomposeConfig},
+    hash::CircuitHasher,
+};
+use crate::{codec::to_bytes_be, tree::Sha256Hasher};
+use halo2_proofs::{
+    circuit::{AssignedCell, Layouter, Region, Value},
+    halo2curves::ff::{FromUniformBytes, PrimeField},
+    plonk::{Advice, Column, ConstraintSystem, Constraints, Error, Expression, Fixed, Selector},
+    poly::Rotation,
+};
+use std::marker::PhantomData;
+
+/// Added to the state in each round of the compression
+const ROUND_CONSTANTS: [u32; 64] = [
+    0x428a2f98, 0x71374491, 0xb5c0fbcf, 0xe9b5dba5, 0x3956c25b, 0x59f111f1, 0x923f82a4, 0xab1c5ed5,
+    0xd807aa98, 0x12835b01, 0x243185be, 0x550c7dc3, 0x72be5d74, 0x80deb1fe, 0x9bdc06a7, 0xc19bf174,
+    0xe49b69c1, 0xefbe4786, 0x0fc19dc6, 0x240ca1cc, 0x2de92c6f, 0x4a7484aa, 0x5cb0a9dc, 0x76f988da,
+    0x983e5152, 0xa831c66d, 0xb00327c8, 0xbf597fc7, 0xc6e00bf3, 0xd5a79147, 0x06ca6351, 0x14292967,
+    0x27b70a85, 0x2e1b2138, 0x4d2c6dfc, 0x53380d13, 0x650a7354, 0x766a0abb, 0x81c2c92e, 0x92722c85,
+    0xa2bfe8a1, 0xa81a664b, 0xc24b8b70, 0xc76c51a3, 0xd192e819, 0xd6990624, 0xf40e3585, 0x106aa070,
+    0x19a4c116, 0x1e376c08, 0x2748774c, 0x34b0bcb5, 0x391c0cb3, 0x4ed8aa4a, 0x5b9cca4f, 0x682e6ff3,
+    0x748f82ee, 0x78a5636f, 0x84c87814, 0x8cc70208, 0x90befffa, 0xa4506ceb, 0xbef9a3f7, 0xc67178f2,
+];
+/// The state before the first block
+const INITIAL_STATE: [u32; 8] = [
+    0x6a09e667, 0xbb67ae85, 0x3c6ef372, 0xa54ff53a, 0x510e527f, 0x9b05688c, 0x1f83d9ab, 0x5be0cd19,
+];
+/// Summands of the widest addition, the next `a`
+const ADD_INPUTS: usize = 6;
+
+/// A 32-bit word, least significant bit first
+type Word<F> = [AssignedCell<F, F>; 32];
+
+fn bool_check<F: PrimeField>(value: Expression<F>) -> Expression<F> {
+    value.clone() * (Expression::Constant(F::ONE) - value)
+}
+
+/// SHA-256, one cell per bit of the words. Each word operation is 32 rows, one per output bit:
+///   - the three-way XORs of Σ and σ: `i_0 + i_1 + i_2 = out + 2 * c_0`
+///   - `Ch(e, f, g) = i_0 * i_1 + (1 - i_0) * i_2`
+///   - `Maj(a, b, c)`, the carry of their sum: `i_0 + i_1 + i_2 = c_0 + 2 * out`
+///   - additions modulo `2^32`, least significant bit first, with the carry of the previous
+///     row: `i_0 + ... + i_5 + k + carry_prev = out + 2 * carry` for `carry = c_0 + 2 * c_1 +
+///     4 * c_2`, and `k` the bit of a round constant
+///
+/// A block is about 17k rows, so hashing two field elements (two blocks) needs `k = 16`.
+///
+/// Row layout:
+///   | i_0 | ... | i_5 | out | c_0 | c_1 | c_2 | k |
+#[derive(Debug, Clone, Copy)]
+pub struct Sha256Config {
+    pub advice: [Column<Advice>; 10],
+    pub(crate) decompose_config: DecomposeConfig,
+    pub(crate) constant_bit: Column<Fixed>,
+    pub(crate) xor_selector: Selector,
+    pub(crate) ch_selector: Selector,
+    pub(crate) maj_selector: Selector,
+    pub(crate) add_first_selector: Selector,
+    pub(crate) add_selector: Selector,
+    pub(crate) canonical_selector: Selector,
+}
+
+pub struct Sha256Chip<F> {
+    pub(crate) config: Sha256Config,
+    _marker: PhantomData<F>,
+}
+
+impl<F: PrimeField<Repr = [u8; 32]>> Sha256Chip<F> {
+    pub fn construct(config: Sha256Config) -> Self {
+        Self {
+            config,
+            _marker: PhantomData,
+        }
+    }
+
+    /// `constant` holds the padding, the initial state and the ends of the canonicity check
+    pub fn configure(
+        meta: &mut ConstraintSystem<F>,
+        advice: [Column<Advice>; 10],
+        constant: Column<Fixed>,
+    ) -> Sha256Config {
+        let constant_bit = meta.fixed_column();
+        let xor_selector = meta.selector();
+        let ch_selector = meta.selector();
+        let maj_selector = meta.selector();
+        let add_first_selector = meta.selector();
+        let add_selector = meta.selector();
+        let canonical_selector = meta.selector();
+
+        meta.enable_constant(constant);
+        for column in advice {
+            meta.enable_equality(column);
+        }
+        let decompose_config =
+            DecomposeChip::configure(meta, [advice[0], advice[1], advice[2]], constant);
+
+        let one = || Expression::Constant(F::ONE);
+        let two = || Expression::Constant(F::from(2));
+        let [out, c_0, c_1, c_2] = [6, 7, 8, 9].map(|i| advice[i]);
+
+        meta.create_gate("sha256 xor", |meta| {
+            let s = meta.query_selector(xor_selector);
+            let [a, b, c] = [0, 1, 2].map(|i| meta.query_advice(advice[i], Rotation::cur()));
+            let out = meta.query_advice(out, Rotation::cur());
+            let carry = meta.query_advice(c_0, Rotation::cur());
+            let parity = a + b + c - out.clone() - carry.clone() * two();
+            Constraints::with_selector(s, [bool_check(out), bool_check(carry), parity])
+        });
+
+        meta.create_gate("sha256 ch", |meta| {
+            let s = meta.query_selector(ch_selector);
+            let [e, f, g] = [0, 1, 2].map(|i| meta.query_advice(advice[i], Rotation::cur()));
+            let out = meta.query_advice(out, Rotation::cur());
+            Constraints::with_selector(s, [e.clone() * f + (one() - e) * g - out])
+        });
+
+        meta.create_gate("sha256 maj", |meta| {
+            let s = meta.query_selector(maj_selector);
+            let [a, b, c] = [0, 1, 2].map(|i| meta.query_advice(advice[i], Rotation::cur()));
+            let out = meta.query_advice(out, Rotation::cur());
+            let parity = meta.query_advice(c_0, Rotation::cur());
+            let majority = a + b + c - parity.clone() - out.clone() * two();
+            Constraints::with_selector(s, [bool_check(out), bool_check(parity), majority])
+        });
+
+        for (name, selector, with_carry) in [
+            ("sha256 add first bit", add_first_selector, false),
+            ("sha256 add", add_selector, true),
+        ] {
+            meta.create_gate(name, |meta| {
+                let s = meta.query_selector(selector);
+                let k = meta.query_fixed(constant_bit, Rotation::cur());
+                let mut sum = advice[..ADD_INPUTS]
+                    .iter()
+                    .map(|column| meta.query_advice(*column, Rotation::cur()))
+                    .fold(k, |a, b| a + b);
+                if with_carry {
+                    let [c_0, c_1, c_2] =
+                        [c_0, c_1, c_2].map(|column| meta.query_advice(column, Rotation::prev()));
+                    sum = sum + c_0 + c_1 * two() + c_2 * Expression::Constant(F::from(4));
+                }
+                let [c_0, c_1, c_2] =
+                    [c_0, c_1, c_2].map(|column| meta.query_advice(column, Rotation::cur()));
+                let out = meta.query_advice(out, Rotation::cur());
+                let carry = c_0.clone()
+                    + c_1.clone() * two()
+                    + c_2.clone() * Expression::Constant(F::from(4));
+                let addition = sum - out.clone() - carry * two();
+                Constraints::with_selector(
+                    s,
+                    [
+                        bool_check(out),
+                        bool_check(c_0),
+                        bool_check(c_1),
+                        bool_check(c_2),
+                        addition,
+                    ],
+                )
+            });
+        }
+
+        // from the most significant bit down: `eq` while the bits match the modulus', `lt` once
+        // a bit is below it
+        meta.create_gate("sha256 canonical", |meta| {
+            let s = meta.query_selector(canonical_selector);
+            let bit = meta.query_advice(advice[0], Rotation::cur());
+            let modulus_bit = meta.query_fixed(constant_bit, Rotation::cur());
+            let [eq, eq_next] = [Rotation::cur(), Rotation::next()]
+                .map(|rotation| meta.query_advice(advice[1], rotation));
+            let [lt, lt_next] = [Rotation::cur(), Rotation::next()]
+                .map(|rotation| meta.query_advice(advice[2], rotation));
+            let matches = bit.clone() * modulus_bit.clone()
+                + (one() - bit.clone()) * (one() - modulus_bit.clone());
+            Constraints::with_selector(
+                s,
+                [
+                    lt_next - lt - eq.clone() * (one() - bit) * modulus_bit,
+                    eq_next - eq * matches,
+                ],
+            )
+        });
+
+        Sha256Config {
+            advice,
+            decompose_config,
+            constant_bit,
+            xor_selector,
+            ch_selector,
+            maj_selector,
+            add_first_selector,
+            add_selector,
+            canonical_selector,
+        }
+    }
+
+    /// `Sha256Hasher` in-circuit: SHA-256 of the 32-byte big-endian encodings of `left` and
+    /// `right`, reduced modulo p. The encodings are checked to be canonical, so a value has a
+    /// single hash, e.g. a nullifier a single nullifier hash.
+    pub fn hash(
+        &self,
+        mut layouter: impl Layouter<F>,
+        left: AssignedCell<F, F>,
+        right: AssignedCell<F, F>,
+    ) -> Result<AssignedCell<F, F>, Error> {
+        let decompose_chip = DecomposeChip::construct(self.config.decompose_config);
+        let mut message = Vec::with_capacity(512);
+        for (name, value) in [("left", left), ("right", right)] {
+            let bits = decompose_chip.decompose_cell::<256>(
+                layouter.namespace(|| format!("decompose {name}")),
+                &value,
+            )?;
+            self.check_canonical(layouter.namespace(|| format!("canonical {name}")), &bits)?;
+            message.extend(big_endian_bytes(&bits));
+        }
+        let digest = self.digest(layouter.namespace(|| "sha256"), &message)?;
+        decompose_chip.compose(
+            layouter.namespace(|| "digest modulo p"),
+            &big_endian_bytes(&digest),
+        )
+    }
+
+    /// The 256 bits of the SHA-256 digest of `bits`, in the order of the input: byte by byte,
+    /// each least significant bit first. The input cells must be constrained to bits, e.g. by
+    /// `DecomposeChip`.
+    ///
+    /// Panics unless `bits` is whole bytes
+    pub fn digest(
+        &self,
+        mut layouter: impl Layouter<F>,
+        bits: &[AssignedCell<F, F>],
+    ) -> Result<[AssignedCell<F, F>; 256], Error> {
+        assert!(
+            bits.len().is_multiple_of(8),
+            "the input must be whole bytes"
+        );
+        let (zero, one) = self.load_constants(&mut layouter)?;
+
+        // a one bit (the top bit of a byte), zeros, then the big-endian length in bits on the
+        // last 8 bytes of a block
+        let len = bits.len();
+        let blocks = (len + 64) / 512 + 1;
+        let mut padded: Vec<AssignedCell<F, F>> = bits.to_vec();
+        padded.resize(512 * blocks, zero.clone());
+        padded[len + 7] = one.clone();
+        for i in 0..64 {
+            if (len >> i) & 1 == 1 {
+                let byte = 64 * blocks - 1 - i / 8;
+                padded[8 * byte + i % 8] = one.clone();
+            }
+        }
+
+        let constant_word =
+            |value: u32| -> Word<F> { word_bits(value).map(|bit| [&zero, &one][bit].clone()) };
+        let mut state = INITIAL_STATE.map(constant_word);
+        for (i, block) in padded.chunks(512).enumerate() {
+            // big-endian words
+            let words = std::array::from_fn(|t| {
+                std::array::from_fn(|j| block[8 * (4 * t + 3 - j / 8) + j % 8].clone())
+            });
+            state = self.compress(
+                layouter.namespace(|| format!("block {i}")),
+                &state,
+                words,
+                &zero,
+            )?;
+        }
+
+        Ok(std::array::from_fn(|i| {
+            let byte = i / 8;
+            state[byte / 4][8 * (3 - byte % 4) + i % 8].clone()
+        }))
+    }
+
+    fn load_constants(
+        &self,
+        layouter: &mut impl Layouter<F>,
+    ) -> Result<(AssignedCell<F, F>, AssignedCell<F, F>), Error> {
+        layouter.assign_region(
+            || "sha256 constants",
+            |mut region| {
+                let out = self.config.advice[6];
+                let zero = region.assign_advice_from_constant(|| "zero", out, 0, F::ZERO)?;
+                let one = region.assign_advice_from_constant(|| "one", out, 1, F::ONE)?;
+                Ok((zero, one))
+            },
+        )
+    }
+
+    /// The state after `block`
+    fn compress(
+        &self,
+        mut layouter: impl Layouter<F>,
+        state: &[Word<F>; 8],
+        block: [Word<F>; 16],
+        zero: &AssignedCell<F, F>,
+    ) -> Result<[Word<F>; 8], Error> {
+        let schedule = layouter.assign_region(
+            || "message schedule",
+            |mut region| {
+                let offset = &mut 0;
+                let mut schedule = block.to_vec();
+                for t in 16..64 {
+                    let (w_15, w_2) = (&schedule[t - 15], &schedule[t - 2]);
+                    let sigma_0 = self.xor(&mut region, offset, |j| {
+                        [
+                            &w_15[(j + 7) % 32],
+                            &w_15[(j + 18) % 32],
+                            w_15.get(j + 3).unwrap_or(zero),
+                        ]
+                    })?;
+                    let sigma_1 = self.xor(&mut region, offset, |j| {
+                        [
+                            &w_2[(j + 17) % 32],
+                            &w_2[(j + 19) % 32],
+                            w_2.get(j + 10).unwrap_or(zero),
+                        ]
+                    })?;
+                    let word = self.add(
+                        &mut region,
+                        offset,
+                        &[&sigma_1, &schedule[t - 7], &sigma_0, &schedule[t - 16]],
+                        0,
+                        zero,
+                    )?;
+                    schedule.push(word);
+                }
+                Ok(schedule)
+            },
+        )?;
+
+        let mut working = state.clone();
+        for (t, (word, k)) in schedule.iter().zip(ROUND_CONSTANTS).enumerate() {
+            working = layouter.assign_region(
+                || format!("round {t}"),
+                |mut region| {
+                    let offset = &mut 0;
+                    let [a, b, c, d, e, f, g, h] = &working;
+                    let big_sigma_1 = self.xor(&mut region, offset, |j| {
+                        [&e[(j + 6) % 32], &e[(j + 11) % 32], &e[(j + 25) % 32]]
+                    })?;
+                    let ch = self.bitwise(
+                        &mut region,
+                        offset,
+                        self.config.ch_selector,
+                        |j| [&e[j], &f[j], &g[j]],
+                        |e, f, g| (if e == 1 { f } else { g }, 0),
+                    )?;
+                    let big_sigma_0 = self.xor(&mut region, offset, |j| {
+                        [&a[(j + 2) % 32], &a[(j + 13) % 32], &a[(j + 22) % 32]]
+                    })?;
+                    let maj = self.bitwise(
+                        &mut region,
+                        offset,
+                        self.config.maj_selector,
+                        |j| [&a[j], &b[j], &c[j]],
+                        |a, b, c| ((a + b + c) / 2, (a + b + c) % 2),
+                    )?;
+
+                    // e + T1 and T1 + T2, with T1 = h + Σ1 + Ch + k + w and T2 = Σ0 + Maj
+                    let next_e = self.add(
+                        &mut region,
+                        offset,
+                        &[d, h, &big_sigma_1, &ch, word],
+                        k,
+                        zero,
+                    )?;
+                    let next_a = self.add(
+                        &mut region,
+                        offset,
+                        &[h, &big_sigma_1, &ch, word, &big_sigma_0, &maj],
+                        k,
+                        zero,
+                    )?;
+                    Ok([
+                        next_a,
+                        a.clone(),
+                        b.clone(),
+                        c.clone(),
+                        next_e,
+                        e.clone(),
+                        f.clone(),
+                        g.clone(),
+                    ])
+                },
+            )?;
+        }
+
+        layouter.assign_region(
+            || "add the compressed block",
+            |mut region| {
+                let offset = &mut 0;
+                let mut next = Vec::with_capacity(8);
+                for (word, compressed) in state.iter().zip(&working) {
+                    next.push(self.add(&mut region, offset, &[word, compressed], 0, zero)?);
+                }
+                Ok(next.try_into().expect("8 words"))
+            },
+        )
+    }
+
+    /// The XOR of the three bits `inputs(j)`, for each bit `j` of a word
+    fn xor<'a>(
+        &self,
+        region: &mut Region<'_, F>,
+        offset: &mut usize,
+        inputs: impl Fn(usize) -> [&'a AssignedCell<F, F>; 3],
+    ) -> Result<Word<F>, Error>
+    where
+        F: 'a,
+    {
+        self.bitwise(
+            region,
+            offset,
+            self.config.xor_selector,
+            inputs,
+            |a, b, c| ((a + b + c) % 2, (a + b + c) / 2),
+        )
+    }
+
+    /// The bits `inputs(j)` in a row of `selector`'s gate, for each bit `j` of a word. `op`
+    /// gives the `out` and `c_0` bits of a row.
+    fn bitwise<'a>(
+        &self,
+        region: &mut Region<'_, F>,
+        offset: &mut usize,
+        selector: Selector,
+        inputs: impl Fn(usize) -> [&'a AssignedCell<F, F>; 3],
+        op: impl Fn(u64, u64, u64) -> (u64, u64),
+    ) -> Result<Word<F>, Error>
+    where
+        F: 'a,
+    {
+        let mut word = Vec::with_capacity(32);
+        for j in 0..32 {
+            selector.enable(region, *offset)?;
+            let [a, b, c] = self.copy_inputs(region, *offset, inputs(j))?;
+            let bits = a.zip(b).zip(c).map(|((a, b), c)| op(a, b, c));
+            let (out, carry) = (bits.map(|bits| bits.0), bits.map(|bits| bits.1));
+            region.assign_advice(
+                || "c_0",
+                self.config.advice[7],
+                *offset,
+                || carry.map(F::from),
+            )?;
+            word.push(region.assign_advice(
+                || "out",
+                self.config.advice[6],
+                *offset,
+                || out.map(F::from),
+            )?);
+            *offset += 1;
+        }
+        Ok(word.try_into().expect("32 bits"))
+    }
+
+    /// `words` and `k` added modulo `2^32`. Missing summands are copies of `zero`.
+    fn add(
+        &self,
+        region: &mut Region<'_, F>,
+        offset: &mut usize,
+        words: &[&Word<F>],
+        k: u32,
+        zero: &AssignedCell<F, F>,
+    ) -> Result<Word<F>, Error> {
+        assert!(words.len() <= ADD_INPUTS);
+        let k = word_bits(k);
+        let mut carry = Value::known(0);
+        let mut word = Vec::with_capacity(32);
+        for j in 0..32 {
+            let selector = if j == 0 {
+                self.config.add_first_selector
+            } else {
+                self.config.add_selector
+            };
+            selector.enable(region, *offset)?;
+            region.assign_fixed(
+                || "k",
+                self.config.constant_bit,
+                *offset,
+                || Value::known(F::from(k[j] as u64)),
+            )?;
+            let inputs = (0..ADD_INPUTS).map(|i| words.get(i).map_or(zero, |word| &word[j]));
+            let sum = self
+                .copy_inputs::<ADD_INPUTS>(region, *offset, inputs)?
+                .into_iter()
+                .fold(carry.map(|carry| carry + k[j] as u64), |sum, bit| {
+                    sum.zip(bit).map(|(sum, bit)| sum + bit)
+                });
+            carry = sum.map(|sum| sum / 2);
+            for (i, column) in self.config.advice[7..].iter().enumerate() {
+                region.assign_advice(
+                    || format!("c_{i}"),
+                    *column,
+                    *offset,
+                    || carry.map(|carry| F::from((carry >> i) & 1)),
+                )?;
+            }
+            word.push(region.assign_advice(
+                || "out",
+                self.config.advice[6],
+                *offset,
+                || sum.map(|sum| F::from(sum % 2)),
+            )?);
+            *offset += 1;
+        }
+        Ok(word.try_into().expect("32 bits"))
+    }
+
+    /// Copies `inputs` to the input columns at `offset`, and returns them as bits. A cell that
+    /// isn't a bit fails the gates, whichever bit it is read as.
+    fn copy_inputs<'a, const N: usize>(
+        &self,
+        region: &mut Region<'_, F>,
+        offset: usize,
+        inputs: impl IntoIterator<Item = &'a AssignedCell<F, F>>,
+    ) -> Result<[Value<u64>; N], Error>
+    where
+        F: 'a,
+    {
+        let bits = inputs
+            .into_iter()
+            .zip(self.config.advice)
+            .enumerate()
+            .map(|(i, (input, column))| {
+                let input = input.copy_advice(|| format!("i_{i}"), region, column, offset)?;
+                Ok(input.value().map(|bit| u64::from(*bit == F::ONE)))
+            })
+            .collect::<Result<Vec<_>, Error>>()?;
+        Ok(bits.try_into().expect("N inputs"))
+    }
+
+    /// Constrains the 256 `bits`, least significant first, to be an integer below the modulus
+    fn check_canonical(
+        &self,
+        mut layouter: impl Layouter<F>,
+        bits: &[AssignedCell<F, F>; 256],
+    ) -> Result<(), Error> {
+        let modulus = modulus_bits::<F>();
+        layouter.assign_region(
+            || "canonical bits",
+            |mut region| {
+                let [_, eq_column, lt_column] = [0, 1, 2].map(|i| self.config.advice[i]);
+                let mut eq = Value::known(true);
+                let mut lt = Value::known(false);
+                let eq_cell =
+                    region.assign_advice(|| "eq", eq_column, 0, || Value::known(F::ONE))?;
+                region.constrain_constant(eq_cell.cell(), F::ONE)?;
+                let lt_cell =
+                    region.assign_advice(|| "lt", lt_column, 0, || Value::known(F::ZERO))?;
+                region.constrain_constant(lt_cell.cell(), F::ZERO)?;
+
+                for (row, i) in (0..256).rev().enumerate() {
+                    self.config.canonical_selector.enable(&mut region, row)?;
+                    region.assign_fixed(
+                        || "modulus bit",
+                        self.config.constant_bit,
+                        row,
+                        || Value::known(F::from(modulus[i] as u64)),
+                    )?;
+                    let [bit] = self.copy_inputs(&mut region, row, [&bits[i]])?;
+                    let bit = bit.map(|bit| bit == 1);
+                    lt = lt
+                        .zip(eq)
+                        .zip(bit)
+                        .map(|((lt, eq), bit)| lt || (eq && !bit && modulus[i]));
+                    eq = eq.zip(bit).map(|(eq, bit)| eq && bit == modulus[i]);
+                    let [eq_value, lt_value] =
+                        [eq, lt].map(|bit| bit.map(|bit| F::from(bit as u64)));
+                    region.assign_advice(|| "eq", eq_column, row + 1, || eq_value)?;
+                    let lt_cell = region.assign_advice(|| "lt", lt_column, row + 1, || lt_value)?;
+                    if row == 255 {
+                        region.constrain_constant(lt_cell.cell(), F::ONE)?;
+                    }
+                }
+                Ok(())
+            },
+        )
+    }
+}
+
+impl<F: PrimeField<Repr = [u8; 32]> + FromUniformBytes<64>> CircuitHasher<F> for Sha256Hasher {
+    type Config = Sha256Config;
+
+    /// Allocates the chip's 7 other advice columns and its constant column
+    fn configure(meta: &mut ConstraintSystem<F>, advice: [Column<Advice>; 3]) -> Sha256Config {
+        let advice = std::array::from_fn(|i| match advice.get(i) {
+            Some(column) => *column,
+            None => meta.advice_column(),
+        });
+        let constant = meta.fixed_column();
+        Sha256Chip::configure(meta, advice, constant)
+    }
+
+    fn hash_cells(
+        config: &Sha256Config,
+        layouter: impl Layouter<F>,
+        left: AssignedCell<F, F>,
+        right: AssignedCell<F, F>,
+    ) -> Result<AssignedCell<F, F>, Error> {
+        Sha256Chip::construct(*config).hash(layouter, left, right)
+    }
+}
+
+/// The bits of `value`, least significant first
+fn word_bits(value: u32) -> [usize; 32] {
+    std::array::from_fn(|j| ((value >> j) & 1) as usize)
+}
+
+/// The bits of the modulus, least significant first
+fn modulus_bits<F: PrimeField<Repr = [u8; 32]>>() -> [bool; 256] {
+    // p - 1 plus one, in big-endian bytes
+    let mut modulus = to_bytes_be(&-F::ONE);
+    for byte in modulus.iter_mut().rev() {
+        let (sum, overflow) = byte.overflowing_add(1);
+        *byte = sum;
+        if !overflow {
+            break;
+        }
+    }
+    std::array::from_fn(|i| (modulus[31 - i / 8] >> (i % 8)) & 1 == 1)
+}
+
+/// The bits of a 32-byte big-endian integer given least significant bit first, in the byte
+/// order of `Sha256Chip::digest`, or the other way around
+fn big_endian_bytes<F: PrimeField>(bits: &[AssignedCell<F, F>]) -> Vec<AssignedCell<F, F>> {
+    (0..256)
+        .map(|i| bits[8 * (31 - i / 8) + i % 8].clone())
+        .collect()
+}
@@ -1,24 +1,38 @@
 use super::{
     hash::{HashChip, HashConfig},
     merkle::{MerkleChip, MerkleConfig},
+    range_check::{RangeCheckChip, RangeCheckConfig},
+};
+use crate::{
+    circuits::{merkle::MerklePathWitness, tornado::AMOUNT_BITS},
+    trace::{trace_event, trace_span},
+    tree::ProductHasher,
+    witness_calculator::WitnessCalculator,
 };
 use halo2_proofs::{
     circuit::{AssignedCell, Layouter, Value},
     halo2curves::ff::PrimeField,
-    plonk::{Advice, Column, ConstraintSystem, Error, Instance},
+    plonk::{Advice, Column, ConstraintSystem, Error, Selector},
+    poly::Rotation,
 };
 use std::marker::PhantomData;
 
+/// Small enough for the table to fit next to a shallow tree in `k = 8`
+const LOOKUP_BITS: usize = 4;
+
 #[derive(Debug, Clone)]
 pub struct TornadoConfig {
     pub advice: [Column<Advice>; 3],
-    pub instance: Column<Instance>,
-    pub merkle_config: MerkleConfig,
-    pub hash_config: HashConfig,
+    pub(crate) merkle_config: MerkleConfig,
+    pub(crate) hash_config: HashConfig,
+    pub(crate) history_first_selector: Selector,
+    pub(crate) history_step_selector: Selector,
+    pub(crate) history_zero_selector: Selector,
+    pub(crate) square_selector: Selector,
 }
 
 pub struct TornadoChip<F> {
-    pub config: TornadoConfig,
+    pub(crate) config: TornadoConfig,
     _marker: PhantomData<F>,
 }
 
@@ -30,32 +44,93 @@ impl<F: PrimeField> TornadoChip<F> {
         }
     }
 
-    pub fn configure(
-        meta: &mut ConstraintSystem<F>,
-        advice: [Column<Advice>; 3],
-        instance: Column<Instance>,
-    ) -> TornadoConfig {
+    pub fn configure(meta: &mut ConstraintSystem<F>, advice: [Column<Advice>; 3]) -> TornadoConfig {
         meta.enable_equality(advice[0]);
         meta.enable_equality(advice[1]);
         meta.enable_equality(advice[2]);
-        meta.enable_equality(instance);
 
-        let merkle_config = MerkleChip::configure(meta, advice, instance);
-        let hash_config = HashChip::configure(meta, advice, instance);
+        // the Merkle chip's hash gate is on the same columns, a second one would only add a
+        // selector and a duplicate gate
+        let merkle_config = MerkleChip::configure(meta, advice);
+        let hash_config = merkle_config.hash_config;
+
+        let history_first_selector = meta.selector();
+        let history_step_selector = meta.selector();
+        let history_zero_selector = meta.selector();
+        let square_selector = meta.selector();
+
+        // root history membership: on each row advice[0] is the root, advice[1] a history entry
+        // and advice[2] the running product of (root - entry). The root is in the history iff
+        // the product over every entry is 0.
+        meta.create_gate("history product start", |meta| {
+            let s = meta.query_selector(history_first_selector);
+            let root = meta.query_advice(advice[0], Rotation::cur());
+            let entry = meta.query_advice(advice[1], Rotation::cur());
+            let product = meta.query_advice(advice[2], Rotation::cur());
+            vec![s * (root - entry - product)]
+        });
+
+        meta.create_gate("history product step", |meta| {
+            let s = meta.query_selector(history_step_selector);
+            let root = meta.query_advice(advice[0], Rotation::cur());
+            let entry = meta.query_advice(advice[1], Rotation::cur());
+            let product_prev = meta.query_advice(advice[2], Rotation::prev());
+            let product = meta.query_advice(advice[2], Rotation::cur());
+            vec![s * (product_prev * (root - entry) - product)]
+        });
+
+        meta.create_gate("history product is zero", |meta| {
+            let s = meta.query_selector(history_zero_selector);
+            let product = meta.query_advice(advice[2], Rotation::cur());
+            vec![s * product]
+        });
+
+        // public values that nothing else constrains are squared, like Tornado's
+        // `recipientSquare`, so each one appears in a gate and can't be stripped from the proof
+        meta.create_gate("public value square", |meta| {
+            let s = meta.query_selector(square_selector);
+            let value = meta.query_advice(advice[0], Rotation::cur());
+            let square = meta.query_advice(advice[1], Rotation::cur());
+            vec![s * (value.clone() * value - square)]
+        });
 
         TornadoConfig {
             advice,
-            instance,
             merkle_config,
             hash_config,
+            history_first_selector,
+            history_step_selector,
+            history_zero_selector,
+            square_selector,
         }
     }
 
     pub fn compute_hash(
+        &self,
+        layouter: impl Layouter<F>,
+        left_value: Value<F>,
+        right_value: Value<F>,
+    ) -> Result<AssignedCell<F, F>, Error> {
+        self.compute_hash_inner(layouter, left_value, right_value, None)
+    }
+
+    /// Like `compute_hash`, with the digest computed beforehand, see `WitnessCalculator`
+    pub fn compute_hash_precomputed(
+        &self,
+        layouter: impl Layouter<F>,
+        left_value: Value<F>,
+        right_value: Value<F>,
+        digest: Value<F>,
+    ) -> Result<AssignedCell<F, F>, Error> {
+        self.compute_hash_inner(layouter, left_value, right_value, Some(digest))
+    }
+
+    fn compute_hash_inner(
         &self,
         mut layouter: impl Layouter<F>,
         left_value: Value<F>,
         right_value: Value<F>,
+        digest: Option<Value<F>>,
     ) -> Result<AssignedCell<F, F>, Error> {
         let (left, right) = layouter.assign_region(
             || "compute hash",
@@ -69,7 +144,308 @@ impl<F: PrimeField> TornadoChip<F> {
         )?;
 
         let hash_chip = HashChip::construct(self.config.hash_config);
-        let hash_result = hash_chip.hash(layouter.namespace(|| "hash values"), left, right)?;
-        Ok(hash_result)
+        hash_chip.hash_inner(layouter.namespace(|| "hash values"), left, right, digest)
+    }
+
+    /// `H(H(H(nullifier, secret), amount), asset_id)`, the commitment of a note worth `amount`
+    /// of the asset `asset_id`. Range checking `amount` is up to the caller.
+    pub fn compute_commitment(
+        &self,
+        layouter: impl Layouter<F>,
+        nullifier: Value<F>,
+        secret: Value<F>,
+        amount: &AssignedCell<F, F>,
+        asset_id: &AssignedCell<F, F>,
+    ) -> Result<AssignedCell<F, F>, Error> {
+        self.compute_commitment_inner(layouter, nullifier, secret, amount, asset_id, None)
+    }
+
+    /// Like `compute_commitment`, with the digests computed beforehand, innermost first, see
+    /// `WitnessCalculator`
+    pub fn compute_commitment_precomputed(
+        &self,
+        layouter: impl Layouter<F>,
+        nullifier: Value<F>,
+        secret: Value<F>,
+        amount: &AssignedCell<F, F>,
+        asset_id: &AssignedCell<F, F>,
+        digests: [Value<F>; 3],
+    ) -> Result<AssignedCell<F, F>, Error> {
+        self.compute_commitment_inner(layouter, nullifier, secret, amount, asset_id, Some(digests))
+    }
+
+    fn compute_commitment_inner(
+        &self,
+        mut layouter: impl Layouter<F>,
+        nullifier: Value<F>,
+        secret: Value<F>,
+        amount: &AssignedCell<F, F>,
+        asset_id: &AssignedCell<F, F>,
+        digests: Option<[Value<F>; 3]>,
+    ) -> Result<AssignedCell<F, F>, Error> {
+        let [inner_digest, amount_digest, asset_digest] =
+            digests.map_or([None; 3], |d| d.map(Some));
+        let inner = self.compute_hash_inner(
+            layouter.namespace(|| "hash nullifier and secret"),
+            nullifier,
+            secret,
+            inner_digest,
+        )?;
+        let hash_chip = HashChip::construct(self.config.hash_config);
+        let inner = hash_chip.hash_inner(
+            layouter.namespace(|| "hash amount"),
+            inner,
+            amount.clone(),
+            amount_digest,
+        )?;
+        hash_chip.hash_inner(
+            layouter.namespace(|| "hash asset id"),
+            inner,
+            asset_id.clone(),
+            asset_digest,
+        )
+    }
+
+    /// Assigns a value that is only exposed as a public input, e.g. `ext_data_hash`, next to
+    /// its square
+    pub fn load_public_value(
+        &self,
+        mut layouter: impl Layouter<F>,
+        value: Value<F>,
+    ) -> Result<AssignedCell<F, F>, Error> {
+        layouter.assign_region(
+            || "load public value",
+            |mut region| {
+                self.config.square_selector.enable(&mut region, 0)?;
+                region.assign_advice(|| "square", self.config.advice[1], 0, || value * value)?;
+                region.assign_advice(|| "public value", self.config.advice[0], 0, || value)
+            },
+        )
+    }
+
+    /// Constrains `root` to be one of the `history` roots, like `isKnownRoot` checking the
+    /// contract's root history. The caller binds the history, e.g. to public inputs.
+    pub fn prove_root_in_history(
+        &self,
+        mut layouter: impl Layouter<F>,
+        root: AssignedCell<F, F>,
+        history: &[AssignedCell<F, F>],
+    ) -> Result<(), Error> {
+        if history.is_empty() {
+            return Err(Error::Synthesis);
+        }
+
+        layouter.assign_region(
+            || "root history membership",
+            |mut region| {
+                let mut product = Value::known(F::ONE);
+                for (row, entry) in history.iter().enumerate() {
+                    if row == 0 {
+                        self.config
+                            .history_first_selector
+                            .enable(&mut region, row)?;
+                    } else {
+                        self.config.history_step_selector.enable(&mut region, row)?;
+                    }
+
+                    root.copy_advice(|| "root", &mut region, self.config.advice[0], row)?;
+                    let entry = entry.copy_advice(
+                        || "history entry",
+                        &mut region,
+                        self.config.advice[1],
+                        row,
+                    )?;
+                    product = product * (root.value().cloned() - entry.value().cloned());
+                    region.assign_advice(
+                        || "running product",
+                        self.config.advice[2],
+                        row,
+                        || product,
+                    )?;
+                }
+                self.config
+                    .history_zero_selector
+                    .enable(&mut region, history.len() - 1)
+            },
+        )
+    }
+}
+
+/// The witness of a withdrawal, see `TornadoGadget::assert_valid_withdrawal`
+#[derive(Debug, Clone)]
+pub struct WithdrawalInputs<F, const DEPTH: usize> {
+    pub nullifier: Value<F>,
+    pub secret: Value<F>,
+    pub path: MerklePathWitness<F, DEPTH>,
+    pub ext_data_hash: Value<F>,
+    pub recipient: Value<F>,
+    pub relayer: Value<F>,
+    pub fee: Value<F>,
+    pub refund: Value<F>,
+    pub amount: Value<F>,
+    pub asset_id: Value<F>,
+    /// Hashed with the note commitment into the leaf, e.g. the note's owner (see
+    /// `circuits::owned`) or its unlock time (see `circuits::timelock`)
+    pub binding: Option<AssignedCell<F, F>>,
+}
+
+/// The cells of a withdrawal's public values, and of its leaf. `TornadoCircuit` constrains
+/// them to the rows of `PublicInputs`; an embedding circuit exposes them where it wants, or
+/// constrains them against its own cells (e.g. the amount against a private balance).
+#[derive(Debug, Clone)]
+pub struct WithdrawalCells<F> {
+    pub nullifier_hash: AssignedCell<F, F>,
+    /// The note commitment, hashed with the binding if there is one
+    pub leaf: AssignedCell<F, F>,
+    pub root: AssignedCell<F, F>,
+    pub ext_data_hash: AssignedCell<F, F>,
+    pub recipient: AssignedCell<F, F>,
+    pub relayer: AssignedCell<F, F>,
+    pub fee: AssignedCell<F, F>,
+    pub refund: AssignedCell<F, F>,
+    /// Range checked to `AMOUNT_BITS`
+    pub amount: AssignedCell<F, F>,
+    pub asset_id: AssignedCell<F, F>,
+}
+
+/// The withdrawal circuit's logic, for circuits embedding it next to their own: it returns the
+/// cells of the withdrawal's public values and never reads or constrains the instance column.
+pub struct TornadoGadget<F> {
+    tornado_config: TornadoConfig,
+    range_check_config: RangeCheckConfig,
+    _marker: PhantomData<F>,
+}
+
+impl<F: PrimeField> TornadoGadget<F> {
+    pub fn construct(tornado_config: TornadoConfig, range_check_config: RangeCheckConfig) -> Self {
+        Self {
+            tornado_config,
+            range_check_config,
+            _marker: PhantomData,
+        }
+    }
+
+    /// The chips of a withdrawal over `advice`, with the range check of the amount sharing the
+    /// Merkle chip's constant column
+    pub fn configure(
+        meta: &mut ConstraintSystem<F>,
+        advice: [Column<Advice>; 3],
+    ) -> (TornadoConfig, RangeCheckConfig) {
+        let tornado_config = TornadoChip::configure(meta, advice);
+        let range_check_config = RangeCheckChip::configure(
+            meta,
+            advice,
+            tornado_config.merkle_config.constant,
+            LOOKUP_BITS,
+        );
+        (tornado_config, range_check_config)
+    }
+
+    /// Proves the withdrawal of a note of the tree whose root is returned: the nullifier hash
+    /// and the commitment are of the same note, and the commitment (or the leaf it is bound
+    /// into) is at `leaf_index`. The public values are assigned next to their squares, see
+    /// `TornadoChip::load_public_value`.
+    ///
+    /// Loads the range check table, which a circuit may only load once: circuits with more
+    /// range checks share it, as `circuits::timelock` does.
+    pub fn assert_valid_withdrawal<const DEPTH: usize>(
+        &self,
+        mut layouter: impl Layouter<F>,
+        inputs: &WithdrawalInputs<F, DEPTH>,
+    ) -> Result<WithdrawalCells<F>, Error> {
+        let tornado_chip = TornadoChip::construct(self.tornado_config.clone());
+        let witness = {
+            trace_span!(DEBUG, "witness");
+            WitnessCalculator::<ProductHasher>::compute_inputs(inputs)
+        };
+
+        // step 1: nullifier hash
+        let nullifier_hash = {
+            trace_span!(DEBUG, "nullifier hash");
+            tornado_chip.compute_hash_precomputed(
+                layouter.namespace(|| "get nullifier hash"),
+                inputs.nullifier,
+                inputs.nullifier,
+                witness.nullifier_hash,
+            )?
+        };
+        trace_event!(cell = ?nullifier_hash.cell(), "nullifier hash assigned");
+
+        // step 2: compute commitment, for a public asset and an amount that fits in 64 bits
+        let (amount, asset_id, commitment) = {
+            trace_span!(DEBUG, "commitment");
+            let amount = tornado_chip
+                .load_public_value(layouter.namespace(|| "load amount"), inputs.amount)?;
+            let asset_id = tornado_chip
+                .load_public_value(layouter.namespace(|| "load asset id"), inputs.asset_id)?;
+            let range_check_chip = RangeCheckChip::construct(self.range_check_config);
+            range_check_chip.load_table(layouter.namespace(|| "range check table"))?;
+            range_check_chip.range_check(
+                layouter.namespace(|| "range check amount"),
+                &amount,
+                AMOUNT_BITS,
+            )?;
+            let commitment = tornado_chip.compute_commitment_precomputed(
+                layouter.namespace(|| "get commitment"),
+                inputs.nullifier,
+                inputs.secret,
+                &amount,
+                &asset_id,
+                witness.commitment,
+            )?;
+            (amount, asset_id, commitment)
+        };
+        trace_event!(cell = ?commitment.cell(), "commitment assigned");
+
+        let merkle_chip = MerkleChip::construct(self.tornado_config.merkle_config.clone());
+        let (leaf, root) = {
+            trace_span!(DEBUG, "merkle proof");
+            match &inputs.binding {
+                None => {
+                    let root = merkle_chip.prove_tree_root_precomputed(
+                        layouter.namespace(|| "prove merkle tree"),
+                        commitment.clone(),
+                        inputs.path.path_elements,
+                        inputs.path.leaf_index,
+                        witness.path_digests,
+                    )?;
+                    (commitment, root)
+                }
+                // the precomputed digests are those of the unbound commitment
+                Some(binding) => {
+                    let leaf = HashChip::construct(self.tornado_config.hash_config).hash(
+                        layouter.namespace(|| "bind commitment"),
+                        commitment,
+                        binding.clone(),
+                    )?;
+                    let root = merkle_chip.prove_tree_root(
+                        layouter.namespace(|| "prove merkle tree"),
+                        leaf.clone(),
+                        inputs.path.path_elements,
+                        inputs.path.leaf_index,
+                    )?;
+                    (leaf, root)
+                }
+            }
+        };
+        trace_event!(cell = ?root.cell(), "merkle root assigned");
+
+        trace_span!(DEBUG, "public values");
+        // step 3: bind the withdrawal's external data, recipient, relayer, fee and refund
+        let mut load = |name: &str, value| {
+            tornado_chip.load_public_value(layouter.namespace(|| format!("load {name}")), value)
+        };
+        Ok(WithdrawalCells {
+            nullifier_hash,
+            leaf,
+            root,
+            ext_data_hash: load("ext data hash", inputs.ext_data_hash)?,
+            recipient: load("recipient", inputs.recipient)?,
+            relayer: load("relayer", inputs.relayer)?,
+            fee: load("fee", inputs.fee)?,
+            refund: load("refund", inputs.refund)?,
+            amount,
+            asset_id,
+        })
     }
 }
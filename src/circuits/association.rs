@@ -0,0 +1,217 @@
+//! Withdrawals proving their deposit belongs to an association set, as in Privacy Pools.
+//!
+//! An association set provider publishes the root of a tree of deposit commitments it vouches
+//! for, e.g. every deposit not linked to a hack. The withdrawal proves its note is both in the
+//! pool's tree and in the association tree, so the pool (or a compliant front-end) can require
+//! a root from a provider it trusts without learning which deposit is withdrawn. A blocklist is
+//! served the same way, with an association set of every deposit not on it: this is how
+//! Privacy Pools' providers work, and it keeps the proof one more Merkle path instead of a
+//! sorted non-membership proof like `nullifier_exclusion`.
+use crate::{
+    chips::merkle::MerkleChip,
+    circuits::{
+        merkle::MerklePathWitness,
+        tornado::{PublicInputs, TornadoCircuit, TornadoPublicInputs},
+    },
+};
+use halo2_proofs::{
+    circuit::{floor_planner::V1, Layouter},
+    halo2curves::ff::PrimeField,
+    plonk::{Circuit, ConstraintSystem, Error},
+};
+
+/// Row of the association root, after the rows of `PublicInputs`
+pub const ASSOCIATION_ROOT: usize = PublicInputs::LEN;
+
+/// The public inputs of `AssociationCircuit`: the withdrawal's, and the association root
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct AssociationPublicInputs<F> {
+    pub withdrawal: TornadoPublicInputs<F>,
+    pub association_root: F,
+}
+
+impl<F: PrimeField> AssociationPublicInputs<F> {
+    /// The rows of `PublicInputs`, then the association root
+    pub fn to_instances(&self) -> Vec<Vec<F>> {
+        let mut instances = self.withdrawal.to_instances();
+        instances[0].push(self.association_root);
+        instances
+    }
+
+    /// Returns `None` unless `instances` is a single column of `PublicInputs::LEN + 1` values
+    pub fn from_instances(instances: &[Vec<F>]) -> Option<Self> {
+        let [column] = instances else {
+            return None;
+        };
+        let (association_root, withdrawal) = column.split_last()?;
+        Some(Self {
+            withdrawal: TornadoPublicInputs::from_instances(&[withdrawal.to_vec()])?,
+            association_root: *association_root,
+        })
+    }
+}
+
+/// `TornadoCircuit`, also proving that the note commitment is a leaf of the association tree
+/// of `ASSOCIATION_DEPTH` layers. The association path is private like the pool's.
+///
+/// Instance layout: the rows of `PublicInputs`, then row `ASSOCIATION_ROOT` = association root.
+#[derive(Debug)]
+pub struct AssociationCircuit<F, const DEPTH: usize, const ASSOCIATION_DEPTH: usize> {
+    pub withdrawal: TornadoCircuit<F, DEPTH>,
+    /// Path of the note commitment in the association tree
+    pub association_path: MerklePathWitness<F, ASSOCIATION_DEPTH>,
+}
+
+impl<F: PrimeField, const DEPTH: usize, const ASSOCIATION_DEPTH: usize>
+    AssociationCircuit<F, DEPTH, ASSOCIATION_DEPTH>
+{
+    pub fn new(
+        withdrawal: TornadoCircuit<F, DEPTH>,
+        association_path: MerklePathWitness<F, ASSOCIATION_DEPTH>,
+    ) -> Self {
+        Self {
+            withdrawal,
+            association_path,
+        }
+    }
+}
+
+impl<F: PrimeField, const DEPTH: usize, const ASSOCIATION_DEPTH: usize> Circuit<F>
+    for AssociationCircuit<F, DEPTH, ASSOCIATION_DEPTH>
+{
+    type Config = <TornadoCircuit<F, DEPTH> as Circuit<F>>::Config;
+    type FloorPlanner = V1;
+
+    fn without_witnesses(&self) -> Self {
+        Self {
+            withdrawal: TornadoCircuit::default(),
+            association_path: MerklePathWitness::unknown(),
+        }
+    }
+
+    fn configure(meta: &mut ConstraintSystem<F>) -> Self::Config {
+        TornadoCircuit::<F, DEPTH>::configure(meta)
+    }
+
+    fn synthesize(
+        &self,
+        config: Self::Config,
+        mut layouter: impl Layouter<F>,
+    ) -> Result<(), Error> {
+        let merkle_chip = MerkleChip::construct(config.0.merkle_config.clone());
+        let instance = config.2;
+
+        let commitment = self
+            .withdrawal
+            .synthesize_withdrawal(config, &mut layouter, None)?;
+        let association_root = merkle_chip.prove_tree_root(
+            layouter.namespace(|| "prove association set membership"),
+            commitment,
+            self.association_path.path_elements,
+            self.association_path.leaf_index,
+        )?;
+        layouter.constrain_instance(association_root.cell(), instance, ASSOCIATION_ROOT)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{AssociationCircuit, AssociationPublicInputs};
+    use crate::{
+        circuits::{
+            merkle::MerklePathWitness,
+            tornado::{TornadoCircuit, TornadoPublicInputs},
+        },
+        note::Note,
+        tree::IncrementalMerkleTree,
+    };
+    use halo2_proofs::{dev::MockProver, halo2curves::pasta::Fp};
+
+    /// A withdrawal of `note` from a pool holding `[3, 5, note, 7]`, with the public inputs
+    /// but the association root
+    fn withdrawal(note: &Note<Fp>) -> (TornadoCircuit<Fp, 5>, TornadoPublicInputs<Fp>) {
+        let mut pool = IncrementalMerkleTree::new(5);
+        pool.insert_batch(&[Fp::from(3), Fp::from(5)]).unwrap();
+        let index = pool.insert(note.commitment()).unwrap();
+        pool.insert(Fp::from(7)).unwrap();
+        let circuit = TornadoCircuit::builder(note, pool.proof(index).unwrap().into())
+            .build()
+            .unwrap();
+        let public_inputs = TornadoPublicInputs {
+            nullifier_hash: note.nullifier_hash(),
+            root: pool.root(),
+            ext_data_hash: Fp::from(0),
+            recipient: Fp::from(0),
+            relayer: Fp::from(0),
+            fee: Fp::from(0),
+            refund: Fp::from(0),
+            amount: Fp::from(note.amount()),
+            asset_id: note.asset_id(),
+        };
+        (circuit, public_inputs)
+    }
+
+    fn circuit(
+        note: &Note<Fp>,
+        association_set: &IncrementalMerkleTree<Fp>,
+        index: usize,
+    ) -> (AssociationCircuit<Fp, 5, 4>, AssociationPublicInputs<Fp>) {
+        let (withdrawal, public_inputs) = withdrawal(note);
+        let circuit = AssociationCircuit::new(
+            withdrawal,
+            MerklePathWitness::from_tree(association_set, index).unwrap(),
+        );
+        let public_inputs = AssociationPublicInputs {
+            withdrawal: public_inputs,
+            association_root: association_set.root(),
+        };
+        (circuit, public_inputs)
+    }
+
+    fn verify(circuit: &AssociationCircuit<Fp, 5, 4>, instances: Vec<Vec<Fp>>) -> bool {
+        MockProver::run(10, circuit, instances)
+            .unwrap()
+            .verify()
+            .is_ok()
+    }
+
+    #[test]
+    fn test_associated_deposit() {
+        let note = Note::new(Fp::from(0x456), Fp::from(0xabc), "eth", "0.1", 1).unwrap();
+        let mut association_set = IncrementalMerkleTree::new(4);
+        association_set.insert(Fp::from(3)).unwrap();
+        let index = association_set.insert(note.commitment()).unwrap();
+
+        let (circuit, public_inputs) = circuit(&note, &association_set, index);
+        let instances = public_inputs.to_instances();
+        assert_eq!(
+            AssociationPublicInputs::from_instances(&instances),
+            Some(public_inputs)
+        );
+        assert!(verify(&circuit, instances));
+
+        // the association root is public
+        let mut other_root = public_inputs;
+        other_root.association_root += Fp::from(1);
+        assert!(!verify(&circuit, other_root.to_instances()));
+        // and so are the withdrawal's
+        let mut other_recipient = public_inputs;
+        other_recipient.withdrawal.recipient = Fp::from(0x11);
+        assert!(!verify(&circuit, other_recipient.to_instances()));
+    }
+
+    #[test]
+    fn test_unassociated_deposit() {
+        let note = Note::new(Fp::from(0x456), Fp::from(0xabc), "eth", "0.1", 1).unwrap();
+        // the association set holds the pool's other deposits, not this one
+        let mut association_set = IncrementalMerkleTree::new(4);
+        association_set
+            .insert_batch(&[Fp::from(3), Fp::from(5), Fp::from(7)])
+            .unwrap();
+
+        for index in 0..3 {
+            let (circuit, public_inputs) = circuit(&note, &association_set, index);
+            assert!(!verify(&circuit, public_inputs.to_instances()));
+        }
+    }
+}
@@ -0,0 +1,295 @@
+use crate::{
+    chips::{
+        hash::HashChip,
+        merkle::{MerkleChip, MerkleConfig},
+    },
+    circuits::merkle::MerklePathWitness,
+    native,
+    tree::{self, IncrementalMerkleTree, ZERO_VALUE},
+};
+use halo2_proofs::{
+    circuit::{AssignedCell, Layouter, SimpleFloorPlanner, Value},
+    halo2curves::ff::PrimeField,
+    plonk::{Circuit, Column, ConstraintSystem, Error, Expression, Instance, Selector},
+    poly::Rotation,
+};
+
+/// `H(...H(H(c_0, c_1), c_2)..., c_{N-1})`, the public digest of a batch of commitments
+pub fn commitments_hash<F: PrimeField>(commitments: &[F]) -> F {
+    native::hash_values(commitments)
+}
+
+#[derive(Debug, Clone)]
+pub struct BatchDepositConfig {
+    pub(crate) merkle_config: MerkleConfig,
+    /// `advice[0]` on the next row is `advice[0] + 1`
+    pub(crate) increment_selector: Selector,
+    pub instance: Column<Instance>,
+}
+
+/// Proves that appending `N` commitments one after the other, from `start_index`, takes the
+/// tree from `root_0` to `root_N`. Each insertion is checked like `InsertCircuit`, and the new
+/// root of one is the old root of the next. The commitments themselves stay private: a rollup
+/// posts them as calldata, and the contract checks them against their `commitments_hash`.
+///
+/// Instance layout: row 0 = root_0, row 1 = root_N, row 2 = commitments hash, row 3 = start
+/// index. The contract must check the start index against its next index, otherwise a batch
+/// could skip leaves.
+#[derive(Debug)]
+pub struct BatchDepositCircuit<F, const DEPTH: usize, const N: usize> {
+    pub commitments: [Value<F>; N],
+    /// Siblings of each new leaf when it is inserted, i.e. after the previous ones
+    pub path_elements: [[Value<F>; DEPTH]; N],
+    pub start_index: Value<F>,
+}
+
+impl<F: PrimeField, const DEPTH: usize, const N: usize> BatchDepositCircuit<F, DEPTH, N> {
+    pub fn new(
+        commitments: [Value<F>; N],
+        path_elements: [[Value<F>; DEPTH]; N],
+        start_index: Value<F>,
+    ) -> Self {
+        Self {
+            commitments,
+            path_elements,
+            start_index,
+        }
+    }
+
+    /// Appends `commitments` to `tree` and returns the circuit proving it, with its public
+    /// inputs. The tree is left untouched if it isn't `DEPTH` levels deep or the batch doesn't
+    /// fit.
+    pub fn insert_into(
+        tree: &mut IncrementalMerkleTree<F>,
+        commitments: [F; N],
+    ) -> Result<(Self, Vec<F>), tree::Error> {
+        if tree.depth() != DEPTH {
+            return Err(tree::Error::WrongDepth {
+                expected: DEPTH,
+                found: tree.depth(),
+            });
+        }
+        if tree.len() + N > 1 << DEPTH {
+            return Err(tree::Error::TreeFull);
+        }
+        let old_root = tree.root();
+        let start_index = tree.len();
+        let mut path_elements = [[Value::unknown(); DEPTH]; N];
+        for (path, commitment) in path_elements.iter_mut().zip(commitments) {
+            let index = tree.insert(commitment)?;
+            *path = MerklePathWitness::<F, DEPTH>::from_tree(tree, index)
+                .expect("the leaf was just inserted in a DEPTH tree")
+                .path_elements;
+        }
+
+        let circuit = Self {
+            commitments: commitments.map(Value::known),
+            path_elements,
+            start_index: Value::known(F::from(start_index as u64)),
+        };
+        let public_inputs = vec![
+            old_root,
+            tree.root(),
+            commitments_hash(&commitments),
+            F::from(start_index as u64),
+        ];
+        Ok((circuit, public_inputs))
+    }
+}
+
+impl<F: PrimeField, const DEPTH: usize, const N: usize> Circuit<F>
+    for BatchDepositCircuit<F, DEPTH, N>
+{
+    type Config = BatchDepositConfig;
+    type FloorPlanner = SimpleFloorPlanner;
+
+    fn without_witnesses(&self) -> Self {
+        Self {
+            commitments: [Value::unknown(); N],
+            path_elements: [[Value::unknown(); DEPTH]; N],
+            start_index: Value::unknown(),
+        }
+    }
+
+    fn configure(meta: &mut ConstraintSystem<F>) -> Self::Config {
+        let advice = [
+            meta.advice_column(),
+            meta.advice_column(),
+            meta.advice_column(),
+        ];
+        let instance = meta.instance_column();
+        meta.enable_equality(instance);
+        let merkle_config = MerkleChip::configure(meta, advice);
+
+        let increment_selector = meta.selector();
+        meta.create_gate("increment", |meta| {
+            let s = meta.query_selector(increment_selector);
+            let cur = meta.query_advice(advice[0], Rotation::cur());
+            let next = meta.query_advice(advice[0], Rotation::next());
+            vec![s * (cur + Expression::Constant(F::ONE) - next)]
+        });
+
+        BatchDepositConfig {
+            merkle_config,
+            increment_selector,
+            instance,
+        }
+    }
+
+    fn synthesize(
+        &self,
+        config: Self::Config,
+        mut layouter: impl Layouter<F>,
+    ) -> Result<(), Error> {
+        let merkle_chip = MerkleChip::construct(config.merkle_config.clone());
+        let hash_chip = HashChip::construct(config.merkle_config.hash_config);
+        let advice = config.merkle_config.advice;
+        let instance = config.instance;
+
+        let mut index = layouter.assign_region(
+            || "load start index",
+            |mut region| region.assign_advice(|| "start index", advice[0], 0, || self.start_index),
+        )?;
+        layouter.constrain_instance(index.cell(), instance, 3)?;
+
+        let mut root: Option<AssignedCell<F, F>> = None;
+        let mut list_hash: Option<AssignedCell<F, F>> = None;
+        for (i, (commitment, path_elements)) in
+            self.commitments.iter().zip(self.path_elements).enumerate()
+        {
+            if i > 0 {
+                index = layouter.assign_region(
+                    || format!("index {i}"),
+                    |mut region| {
+                        config.increment_selector.enable(&mut region, 0)?;
+                        index.copy_advice(|| "previous index", &mut region, advice[0], 0)?;
+                        region.assign_advice(
+                            || "index",
+                            advice[0],
+                            1,
+                            || index.value().map(|index| *index + F::ONE),
+                        )
+                    },
+                )?;
+            }
+
+            let (empty_leaf, commitment) = layouter.assign_region(
+                || format!("commitment {i}: load leaves"),
+                |mut region| {
+                    let empty_leaf = region.assign_advice_from_constant(
+                        || "empty leaf",
+                        advice[0],
+                        0,
+                        F::from(ZERO_VALUE),
+                    )?;
+                    let commitment =
+                        region.assign_advice(|| "commitment", advice[1], 0, || *commitment)?;
+                    Ok((empty_leaf, commitment))
+                },
+            )?;
+            let (old_root, new_root) = merkle_chip.prove_tree_append(
+                layouter.namespace(|| format!("commitment {i}: prove insertion")),
+                empty_leaf,
+                commitment.clone(),
+                path_elements,
+                &index,
+            )?;
+
+            // each insertion starts from the root left by the previous one
+            match root {
+                None => layouter.constrain_instance(old_root.cell(), instance, 0)?,
+                Some(previous) => layouter.assign_region(
+                    || format!("commitment {i}: chain roots"),
+                    |mut region| region.constrain_equal(previous.cell(), old_root.cell()),
+                )?,
+            }
+            root = Some(new_root);
+
+            list_hash = Some(match list_hash {
+                None => commitment,
+                Some(list_hash) => hash_chip.hash(
+                    layouter.namespace(|| format!("commitment {i}: hash list")),
+                    list_hash,
+                    commitment,
+                )?,
+            });
+        }
+
+        if let (Some(root), Some(list_hash)) = (root, list_hash) {
+            layouter.constrain_instance(root.cell(), instance, 1)?;
+            layouter.constrain_instance(list_hash.cell(), instance, 2)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{commitments_hash, BatchDepositCircuit};
+    use crate::{
+        circuits::merkle::MerklePathWitness,
+        tree::{self, IncrementalMerkleTree},
+    };
+    use halo2_proofs::{dev::MockProver, halo2curves::pasta::Fp};
+
+    #[test]
+    fn test_batch_deposit_circuit() {
+        let mut tree = IncrementalMerkleTree::new(5);
+        for leaf in [3, 5, 7] {
+            tree.insert(Fp::from(leaf)).unwrap();
+        }
+        let commitments = [11, 13, 17, 19].map(Fp::from);
+        let (circuit, public_input) =
+            BatchDepositCircuit::<Fp, 5, 4>::insert_into(&mut tree, commitments).unwrap();
+
+        let mut sequential = IncrementalMerkleTree::new(5);
+        for leaf in [3, 5, 7, 11, 13, 17, 19] {
+            sequential.insert(Fp::from(leaf)).unwrap();
+        }
+        assert_eq!(public_input[1], sequential.root());
+        assert_eq!(public_input[2], commitments_hash(&commitments));
+        assert_eq!(public_input[3], Fp::from(3));
+
+        let prover = MockProver::run(10, &circuit, vec![public_input.clone()]).unwrap();
+        assert!(prover.verify().is_ok());
+
+        for row in 0..public_input.len() {
+            let mut tampered = public_input.clone();
+            tampered[row] += Fp::from(1);
+            let prover = MockProver::run(10, &circuit, vec![tampered]).unwrap();
+            assert!(prover.verify().is_err());
+        }
+    }
+
+    #[test]
+    fn test_batch_deposit_paths() {
+        let mut tree = IncrementalMerkleTree::new(5);
+        let commitments = [11, 13, 17].map(Fp::from);
+        let (mut circuit, public_input) =
+            BatchDepositCircuit::<Fp, 5, 3>::insert_into(&mut tree, commitments).unwrap();
+
+        // the final tree's path of the first leaf has the later leaves as siblings, so it
+        // doesn't start from the empty tree
+        circuit.path_elements[0] = MerklePathWitness::<Fp, 5>::from_tree(&tree, 0)
+            .unwrap()
+            .path_elements;
+        let prover = MockProver::run(10, &circuit, vec![public_input]).unwrap();
+        assert!(prover.verify().is_err());
+
+        let mut full = IncrementalMerkleTree::new(2);
+        full.insert(Fp::from(3)).unwrap();
+        assert_eq!(
+            BatchDepositCircuit::<Fp, 2, 4>::insert_into(&mut full, [Fp::from(5); 4]).unwrap_err(),
+            tree::Error::TreeFull
+        );
+        assert_eq!(full.len(), 1);
+        assert_eq!(
+            BatchDepositCircuit::<Fp, 3, 1>::insert_into(&mut full, [Fp::from(5)]).unwrap_err(),
+            tree::Error::WrongDepth {
+                expected: 3,
+                found: 2
+            }
+        );
+        assert_eq!(full.len(), 1);
+    }
+}
@@ -0,0 +1,198 @@
+//! Deposits: knowledge of the note behind a commitment, without the tree.
+//!
+//! A deposit only inserts `commitment(nullifier, secret, amount, asset_id)`, so nothing stops a
+//! depositor from inserting a value they can't open, or the commitment of another amount than
+//! the one paid. A pool, or a front end checking deposits before sending them, can require a
+//! proof that the commitment opens to the deposit's amount and asset. The nullifier and the
+//! secret stay private, so the proof doesn't link the deposit to its withdrawal.
+//!
+//! It is also the smallest circuit built from `TornadoChip`: the withdrawal circuit is this
+//! commitment, proven to be in the tree, plus its nullifier hash.
+use crate::{
+    chips::tornado::{TornadoChip, TornadoConfig},
+    note::Note,
+};
+use halo2_proofs::{
+    circuit::{Layouter, SimpleFloorPlanner, Value},
+    halo2curves::ff::PrimeField,
+    plonk::{Circuit, Column, ConstraintSystem, Error, Instance},
+};
+
+const COMMITMENT: usize = 0;
+const AMOUNT: usize = 1;
+const ASSET_ID: usize = 2;
+
+/// The public inputs of `CommitmentCircuit`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CommitmentPublicInputs<F> {
+    pub commitment: F,
+    pub amount: F,
+    pub asset_id: F,
+}
+
+impl<F: PrimeField> CommitmentPublicInputs<F> {
+    /// Row 0 = commitment, row 1 = amount, row 2 = asset id
+    pub fn to_instances(&self) -> Vec<Vec<F>> {
+        vec![vec![self.commitment, self.amount, self.asset_id]]
+    }
+
+    /// Returns `None` unless `instances` is a single column of 3 values
+    pub fn from_instances(instances: &[Vec<F>]) -> Option<Self> {
+        let [column] = instances else {
+            return None;
+        };
+        let [commitment, amount, asset_id] = column.as_slice() else {
+            return None;
+        };
+        Some(Self {
+            commitment: *commitment,
+            amount: *amount,
+            asset_id: *asset_id,
+        })
+    }
+}
+
+impl<F: PrimeField<Repr = [u8; 32]>> CommitmentPublicInputs<F> {
+    pub fn from_note(note: &Note<F>) -> Self {
+        Self {
+            commitment: note.commitment(),
+            amount: F::from(note.amount()),
+            asset_id: note.asset_id(),
+        }
+    }
+}
+
+/// Proves knowledge of a nullifier and a secret committed to with the public amount and asset
+/// id. The amount and the asset id are read from the instance column, so only the nullifier and
+/// the secret are witnesses.
+///
+/// Instance layout: see `CommitmentPublicInputs::to_instances`.
+#[derive(Debug, Clone, Copy)]
+pub struct CommitmentCircuit<F> {
+    pub nullifier: Value<F>,
+    pub secret: Value<F>,
+}
+
+impl<F: PrimeField> CommitmentCircuit<F> {
+    pub fn from_note(note: &Note<F>) -> Self {
+        Self {
+            nullifier: Value::known(note.nullifier),
+            secret: Value::known(note.secret),
+        }
+    }
+}
+
+impl<F: PrimeField> Circuit<F> for CommitmentCircuit<F> {
+    type Config = (TornadoConfig, Column<Instance>);
+    type FloorPlanner = SimpleFloorPlanner;
+
+    fn without_witnesses(&self) -> Self {
+        Self {
+            nullifier: Value::unknown(),
+            secret: Value::unknown(),
+        }
+    }
+
+    fn configure(meta: &mut ConstraintSystem<F>) -> Self::Config {
+        let advice = [
+            meta.advice_column(),
+            meta.advice_column(),
+            meta.advice_column(),
+        ];
+        let instance = meta.instance_column();
+        meta.enable_equality(instance);
+        (TornadoChip::configure(meta, advice), instance)
+    }
+
+    fn synthesize(
+        &self,
+        (config, instance): Self::Config,
+        mut layouter: impl Layouter<F>,
+    ) -> Result<(), Error> {
+        let (amount, asset_id) = layouter.assign_region(
+            || "load amount and asset id",
+            |mut region| {
+                let advice = config.advice;
+                let amount = region.assign_advice_from_instance(
+                    || "amount",
+                    instance,
+                    AMOUNT,
+                    advice[0],
+                    0,
+                )?;
+                let asset_id = region.assign_advice_from_instance(
+                    || "asset id",
+                    instance,
+                    ASSET_ID,
+                    advice[1],
+                    0,
+                )?;
+                Ok((amount, asset_id))
+            },
+        )?;
+
+        let tornado_chip = TornadoChip::construct(config);
+        let commitment = tornado_chip.compute_commitment(
+            layouter.namespace(|| "commitment"),
+            self.nullifier,
+            self.secret,
+            &amount,
+            &asset_id,
+        )?;
+        layouter.constrain_instance(commitment.cell(), instance, COMMITMENT)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{CommitmentCircuit, CommitmentPublicInputs};
+    use crate::note::Note;
+    use halo2_proofs::{circuit::Value, dev::MockProver, halo2curves::pasta::Fp};
+
+    fn verify(circuit: &CommitmentCircuit<Fp>, public_inputs: CommitmentPublicInputs<Fp>) -> bool {
+        MockProver::run(6, circuit, public_inputs.to_instances())
+            .unwrap()
+            .verify()
+            .is_ok()
+    }
+
+    #[test]
+    fn test_opening() {
+        let note = Note::new(Fp::from(0x456), Fp::from(0xabc), "eth", "0.1", 1).unwrap();
+        let circuit = CommitmentCircuit::from_note(&note);
+        let public_inputs = CommitmentPublicInputs::from_note(&note);
+        assert_eq!(
+            CommitmentPublicInputs::from_instances(&public_inputs.to_instances()),
+            Some(public_inputs)
+        );
+        assert!(verify(&circuit, public_inputs));
+    }
+
+    #[test]
+    fn test_wrong_opening() {
+        let note = Note::new(Fp::from(0x456), Fp::from(0xabc), "eth", "0.1", 1).unwrap();
+        let public_inputs = CommitmentPublicInputs::from_note(&note);
+
+        // the commitment opens to the deposit's amount and asset only
+        let larger = Note::new(Fp::from(0x456), Fp::from(0xabc), "eth", "1", 1).unwrap();
+        let mut other_amount = public_inputs;
+        other_amount.amount = CommitmentPublicInputs::from_note(&larger).amount;
+        assert!(!verify(&CommitmentCircuit::from_note(&note), other_amount));
+        let dai = Note::new(Fp::from(0x456), Fp::from(0xabc), "dai", "0.1", 1).unwrap();
+        let mut other_asset = public_inputs;
+        other_asset.asset_id = dai.asset_id();
+        assert!(!verify(&CommitmentCircuit::from_note(&note), other_asset));
+
+        // and to its own nullifier and secret
+        let other = Note::new(Fp::from(0x789), Fp::from(0xdef), "eth", "0.1", 1).unwrap();
+        assert!(!verify(
+            &CommitmentCircuit::from_note(&other),
+            public_inputs
+        ));
+        let other_secret = CommitmentCircuit {
+            secret: Value::known(other.secret),
+            ..CommitmentCircuit::from_note(&note)
+        };
+        assert!(!verify(&other_secret, public_inputs));
+    }
+}
@@ -1,9 +1,10 @@
-use crate::chips::hash::{HashChip, HashConfig};
+use crate::chips::hash::{CircuitHasher, HashChip, HashConfig};
 use halo2_proofs::{
     circuit::{Layouter, SimpleFloorPlanner, Value},
     halo2curves::ff::PrimeField,
-    plonk::{Circuit, ConstraintSystem},
+    plonk::{Advice, Circuit, Column, ConstraintSystem, Instance},
 };
+use std::marker::PhantomData;
 
 #[derive(Debug, Default)]
 pub struct HashCircuit<F> {
@@ -12,7 +13,7 @@ pub struct HashCircuit<F> {
 }
 
 impl<F: PrimeField> Circuit<F> for HashCircuit<F> {
-    type Config = HashConfig;
+    type Config = (HashConfig, Column<Instance>);
     type FloorPlanner = SimpleFloorPlanner;
 
     fn without_witnesses(&self) -> Self {
@@ -26,12 +27,13 @@ impl<F: PrimeField> Circuit<F> for HashCircuit<F> {
             meta.advice_column(),
         ];
         let instance = meta.instance_column();
-        HashChip::configure(meta, advice, instance)
+        meta.enable_equality(instance);
+        (HashChip::configure(meta, advice), instance)
     }
 
     fn synthesize(
         &self,
-        config: Self::Config,
+        (config, instance): Self::Config,
         mut layouter: impl Layouter<F>,
     ) -> Result<(), halo2_proofs::plonk::Error> {
         let (left, right) = layouter.assign_region(
@@ -56,14 +58,79 @@ impl<F: PrimeField> Circuit<F> for HashCircuit<F> {
         let chip = HashChip::construct(config);
         let hash_result_cell = chip.hash(layouter.namespace(|| "hasher"), left, right)?;
 
-        layouter.constrain_instance(hash_result_cell.cell(), config.instance, 0)
+        layouter.constrain_instance(hash_result_cell.cell(), instance, 0)
+    }
+}
+
+/// `HashCircuit` for any `CircuitHasher`, e.g. `Sha256Hasher` (with `k = 16`)
+#[derive(Debug)]
+pub struct HasherCircuit<F, H> {
+    pub a: Value<F>,
+    pub b: Value<F>,
+    _marker: PhantomData<H>,
+}
+
+impl<F, H> HasherCircuit<F, H> {
+    pub fn new(a: Value<F>, b: Value<F>) -> Self {
+        Self {
+            a,
+            b,
+            _marker: PhantomData,
+        }
+    }
+}
+
+impl<F: PrimeField, H: CircuitHasher<F>> Circuit<F> for HasherCircuit<F, H> {
+    type Config = (H::Config, [Column<Advice>; 3], Column<Instance>);
+    type FloorPlanner = SimpleFloorPlanner;
+
+    fn without_witnesses(&self) -> Self {
+        Self::new(Value::unknown(), Value::unknown())
+    }
+
+    fn configure(meta: &mut ConstraintSystem<F>) -> Self::Config {
+        let advice = [
+            meta.advice_column(),
+            meta.advice_column(),
+            meta.advice_column(),
+        ];
+        let instance = meta.instance_column();
+        meta.enable_equality(instance);
+        (H::configure(meta, advice), advice, instance)
+    }
+
+    fn synthesize(
+        &self,
+        (config, advice, instance): Self::Config,
+        mut layouter: impl Layouter<F>,
+    ) -> Result<(), halo2_proofs::plonk::Error> {
+        let (left, right) = layouter.assign_region(
+            || "private inputs",
+            |mut region| {
+                let left =
+                    region.assign_advice(|| "private input left", advice[0], 0, || self.a)?;
+                let right =
+                    region.assign_advice(|| "private input right", advice[1], 0, || self.b)?;
+                Ok((left, right))
+            },
+        )?;
+
+        let hash_result_cell =
+            H::hash_cells(&config, layouter.namespace(|| "hasher"), left, right)?;
+
+        layouter.constrain_instance(hash_result_cell.cell(), instance, 0)
     }
 }
 
 #[cfg(test)]
 mod tests {
-    use super::HashCircuit;
-    use halo2_proofs::{circuit::Value, dev::MockProver, halo2curves::pasta::Fp};
+    use super::{HashCircuit, HasherCircuit};
+    use crate::tree::{Hasher, ProductHasher, Sha256Hasher};
+    use halo2_proofs::{
+        circuit::Value,
+        dev::MockProver,
+        halo2curves::{bn256::Fr, pasta::Fp},
+    };
 
     #[test]
     fn test_hash_circuit() {
@@ -82,4 +149,32 @@ mod tests {
         let prover2 = MockProver::run(4, &circuit, vec![public_inputs2.clone()]).unwrap();
         assert!(prover2.verify().is_err());
     }
+
+    #[test]
+    fn test_hasher_circuit() {
+        let (a, b) = (Fp::from(11), Fp::from(7));
+        let circuit = HasherCircuit::<Fp, ProductHasher>::new(Value::known(a), Value::known(b));
+        let prover = MockProver::run(4, &circuit, vec![vec![ProductHasher::hash(a, b)]]).unwrap();
+        assert!(prover.verify().is_ok());
+    }
+
+    #[test]
+    fn test_sha256_hasher_circuit() {
+        let verify = |a: Fr, b: Fr, digest: Fr| {
+            let circuit = HasherCircuit::<Fr, Sha256Hasher>::new(Value::known(a), Value::known(b));
+            MockProver::run(16, &circuit, vec![vec![digest]])
+                .unwrap()
+                .verify()
+                .is_ok()
+        };
+        let (a, b) = (Fr::from(1), Fr::from(2));
+        let digest = Sha256Hasher::hash(a, b);
+        assert!(verify(a, b, digest));
+        assert!(!verify(a, b, digest + Fr::from(1)));
+        assert!(!verify(b, a, digest));
+
+        // inputs just below the modulus, the largest canonical encodings
+        let (a, b) = (-Fr::from(1), -Fr::from(2));
+        assert!(verify(a, b, Sha256Hasher::hash(a, b)));
+    }
 }
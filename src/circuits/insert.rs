@@ -0,0 +1,184 @@
+use crate::{
+    chips::merkle::{MerkleChip, MerkleConfig},
+    circuits::merkle::MerklePathWitness,
+    tree::ZERO_VALUE,
+};
+use halo2_proofs::{
+    circuit::{Layouter, SimpleFloorPlanner, Value},
+    halo2curves::ff::PrimeField,
+    plonk::{Circuit, Column, ConstraintSystem, Error, Instance},
+};
+
+/// Proves that inserting `commitment` at `index` takes the tree from `old_root` to `new_root`:
+/// the leaf at `index` is empty under `old_root` and is `commitment` under `new_root`, with
+/// the same siblings. A contract can then accept a new root without hashing the path itself,
+/// as long as it checks `index` against its own next index and `old_root` against its current
+/// root. The siblings on the right of `index` are constrained to be empty subtrees, see
+/// `MerkleChip::prove_tree_append`.
+///
+/// Instance layout: row 0 = old root, row 1 = new root, row 2 = commitment, row 3 = index.
+#[derive(Debug)]
+pub struct InsertCircuit<F, const DEPTH: usize> {
+    pub commitment: Value<F>,
+    /// Path of the new leaf, its leaf index being the insertion index
+    pub path: MerklePathWitness<F, DEPTH>,
+}
+
+impl<F: PrimeField, const DEPTH: usize> InsertCircuit<F, DEPTH> {
+    pub fn new(commitment: Value<F>, path: MerklePathWitness<F, DEPTH>) -> Self {
+        Self { commitment, path }
+    }
+}
+
+impl<F: PrimeField, const DEPTH: usize> Circuit<F> for InsertCircuit<F, DEPTH> {
+    type Config = (MerkleConfig, Column<Instance>);
+    type FloorPlanner = SimpleFloorPlanner;
+
+    fn without_witnesses(&self) -> Self {
+        Self {
+            commitment: Value::unknown(),
+            path: MerklePathWitness::unknown(),
+        }
+    }
+
+    fn configure(meta: &mut ConstraintSystem<F>) -> Self::Config {
+        let advice = [
+            meta.advice_column(),
+            meta.advice_column(),
+            meta.advice_column(),
+        ];
+        let instance = meta.instance_column();
+        meta.enable_equality(instance);
+        (MerkleChip::configure(meta, advice), instance)
+    }
+
+    fn synthesize(
+        &self,
+        (config, instance): Self::Config,
+        mut layouter: impl Layouter<F>,
+    ) -> Result<(), Error> {
+        let (empty_leaf, commitment, index) = layouter.assign_region(
+            || "load leaves",
+            |mut region| {
+                let empty_leaf = region.assign_advice_from_constant(
+                    || "empty leaf",
+                    config.advice[0],
+                    0,
+                    F::from(ZERO_VALUE),
+                )?;
+                let commitment = region.assign_advice(
+                    || "commitment",
+                    config.advice[1],
+                    0,
+                    || self.commitment,
+                )?;
+                let index = region.assign_advice(
+                    || "index",
+                    config.advice[2],
+                    0,
+                    || self.path.leaf_index,
+                )?;
+                Ok((empty_leaf, commitment, index))
+            },
+        )?;
+        layouter.constrain_instance(commitment.cell(), instance, 2)?;
+        layouter.constrain_instance(index.cell(), instance, 3)?;
+
+        let chip = MerkleChip::construct(config.clone());
+        let (old_root, new_root) = chip.prove_tree_append(
+            layouter.namespace(|| "prove insertion"),
+            empty_leaf,
+            commitment,
+            self.path.path_elements,
+            &index,
+        )?;
+        layouter.constrain_instance(old_root.cell(), instance, 0)?;
+        layouter.constrain_instance(new_root.cell(), instance, 1)?;
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::InsertCircuit;
+    use crate::{circuits::merkle::MerklePathWitness, tree::IncrementalMerkleTree};
+    use halo2_proofs::{
+        circuit::Value,
+        dev::MockProver,
+        halo2curves::{ff::Field, pasta::Fp},
+    };
+
+    fn circuit(commitment: Fp, path_elements: Vec<Fp>, index: usize) -> InsertCircuit<Fp, 5> {
+        InsertCircuit::new(
+            Value::known(commitment),
+            MerklePathWitness::known(path_elements.try_into().unwrap(), Fp::from(index as u64)),
+        )
+    }
+
+    #[test]
+    fn test_insert_circuit() {
+        let mut tree = IncrementalMerkleTree::new(5);
+        for leaf in [3, 5, 7] {
+            tree.insert(Fp::from(leaf)).unwrap();
+        }
+        let old_root = tree.root();
+        let commitment = Fp::from(0xc0ffee);
+        let index = tree.insert(commitment).unwrap();
+        // appending doesn't change the siblings of the new leaf
+        let (path_elements, _) = tree.proof(index).unwrap();
+
+        let circuit = circuit(commitment, path_elements, index);
+        let public_input = vec![old_root, tree.root(), commitment, Fp::from(index as u64)];
+        let prover = MockProver::run(10, &circuit, vec![public_input.clone()]).unwrap();
+        assert!(prover.verify().is_ok());
+
+        for row in 0..public_input.len() {
+            let mut tampered = public_input.clone();
+            tampered[row] += Fp::from(1);
+            let prover = MockProver::run(10, &circuit, vec![tampered]).unwrap();
+            assert!(prover.verify().is_err());
+        }
+    }
+
+    #[test]
+    fn test_insert_bogus_padding() {
+        // index 3 = 0b00011: the siblings of layers 2 to 4 are empty subtrees. Moving a factor
+        // from one of them to a sibling on the left keeps both roots under the product hash, but
+        // not the padding.
+        let mut tree = IncrementalMerkleTree::new(5);
+        for leaf in [3, 5, 7] {
+            tree.insert(Fp::from(leaf)).unwrap();
+        }
+        let old_root = tree.root();
+        let commitment = Fp::from(0xc0ffee);
+        let index = tree.insert(commitment).unwrap();
+        let (mut path_elements, _) = tree.proof(index).unwrap();
+        assert_eq!(path_elements[2..], tree.zeros()[2..5]);
+        path_elements[0] *= Fp::from(2).invert().unwrap();
+        path_elements[3] *= Fp::from(2);
+
+        let circuit = circuit(commitment, path_elements, index);
+        let public_input = vec![old_root, tree.root(), commitment, Fp::from(index as u64)];
+        let prover = MockProver::run(10, &circuit, vec![public_input]).unwrap();
+        assert!(prover.verify().is_err());
+    }
+
+    #[test]
+    fn test_insert_over_leaf() {
+        // overwriting a leaf is not an insertion: the old root doesn't have an empty leaf there
+        let mut tree = IncrementalMerkleTree::new(5);
+        for leaf in [3, 5, 7] {
+            tree.insert(Fp::from(leaf)).unwrap();
+        }
+        let old_root = tree.root();
+        let commitment = Fp::from(0xc0ffee);
+        tree.update(1, commitment);
+        let (path_elements, _) = tree.proof(1).unwrap();
+
+        let circuit = circuit(commitment, path_elements, 1);
+        let public_input = vec![old_root, tree.root(), commitment, Fp::from(1)];
+        let prover = MockProver::run(10, &circuit, vec![public_input]).unwrap();
+        assert!(prover.verify().is_err());
+    }
+}
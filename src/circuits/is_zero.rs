@@ -0,0 +1,83 @@
+use crate::chips::is_zero::{IsZeroChip, IsZeroConfig};
+use halo2_proofs::{
+    circuit::{Layouter, SimpleFloorPlanner, Value},
+    halo2curves::ff::PrimeField,
+    plonk::{Circuit, Column, ConstraintSystem, Error, Instance},
+};
+
+/// Instance layout: row 0 = `a == 0`, row 1 = `a == b`
+#[derive(Debug, Default)]
+pub struct IsZeroCircuit<F> {
+    pub a: Value<F>,
+    pub b: Value<F>,
+}
+
+impl<F: PrimeField> Circuit<F> for IsZeroCircuit<F> {
+    type Config = (IsZeroConfig, Column<Instance>);
+    type FloorPlanner = SimpleFloorPlanner;
+
+    fn without_witnesses(&self) -> Self {
+        Self::default()
+    }
+
+    fn configure(meta: &mut ConstraintSystem<F>) -> Self::Config {
+        let advice = [
+            meta.advice_column(),
+            meta.advice_column(),
+            meta.advice_column(),
+        ];
+        let instance = meta.instance_column();
+        meta.enable_equality(instance);
+        (IsZeroChip::configure(meta, advice), instance)
+    }
+
+    fn synthesize(
+        &self,
+        (config, instance): Self::Config,
+        mut layouter: impl Layouter<F>,
+    ) -> Result<(), Error> {
+        let (a, b) = layouter.assign_region(
+            || "private inputs",
+            |mut region| {
+                let a = region.assign_advice(|| "a", config.advice[0], 0, || self.a)?;
+                let b = region.assign_advice(|| "b", config.advice[1], 0, || self.b)?;
+                Ok((a, b))
+            },
+        )?;
+
+        let chip = IsZeroChip::construct(config);
+        let is_zero = chip.is_zero(layouter.namespace(|| "a == 0"), &a)?;
+        layouter.constrain_instance(is_zero.cell(), instance, 0)?;
+        let is_equal = chip.is_equal(layouter.namespace(|| "a == b"), &a, &b)?;
+        layouter.constrain_instance(is_equal.cell(), instance, 1)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::IsZeroCircuit;
+    use halo2_proofs::{circuit::Value, dev::MockProver, halo2curves::pasta::Fp};
+
+    fn verify(a: u64, b: u64, is_zero: u64, is_equal: u64) -> bool {
+        let circuit = IsZeroCircuit {
+            a: Value::known(Fp::from(a)),
+            b: Value::known(Fp::from(b)),
+        };
+        let public_inputs = vec![Fp::from(is_zero), Fp::from(is_equal)];
+        let prover = MockProver::run(4, &circuit, vec![public_inputs]).unwrap();
+        prover.verify().is_ok()
+    }
+
+    #[test]
+    fn test_is_zero_circuit() {
+        assert!(verify(0, 0, 1, 1));
+        assert!(verify(0, 5, 1, 0));
+        assert!(verify(5, 5, 0, 1));
+        assert!(verify(5, 7, 0, 0));
+
+        assert!(!verify(0, 5, 0, 0));
+        assert!(!verify(5, 7, 1, 0));
+        assert!(!verify(5, 7, 0, 1));
+        assert!(!verify(5, 5, 0, 0));
+    }
+}
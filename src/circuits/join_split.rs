@@ -0,0 +1,434 @@
+use super::tornado::AMOUNT_BITS;
+use crate::{
+    chips::{
+        merkle::MerkleChip,
+        range_check::{RangeCheckChip, RangeCheckConfig},
+        tornado::{TornadoChip, TornadoConfig},
+    },
+    circuits::merkle::MerklePathWitness,
+};
+use halo2_proofs::{
+    circuit::{AssignedCell, Layouter, SimpleFloorPlanner, Value},
+    halo2curves::ff::PrimeField,
+    plonk::{Circuit, Column, ConstraintSystem, Error, Instance, Selector},
+    poly::Rotation,
+};
+use std::array;
+
+const LOOKUP_BITS: usize = 8;
+
+/// A note spent by `JoinSplitCircuit`, of the transaction's asset
+#[derive(Debug, Clone)]
+pub struct InputNote<F, const DEPTH: usize> {
+    pub amount: Value<F>,
+    pub nullifier: Value<F>,
+    pub secret: Value<F>,
+    pub path: MerklePathWitness<F, DEPTH>,
+}
+
+impl<F: PrimeField, const DEPTH: usize> InputNote<F, DEPTH> {
+    pub fn new(
+        amount: Value<F>,
+        nullifier: Value<F>,
+        secret: Value<F>,
+        path: MerklePathWitness<F, DEPTH>,
+    ) -> Self {
+        Self {
+            amount,
+            nullifier,
+            secret,
+            path,
+        }
+    }
+
+    fn unknown() -> Self {
+        Self {
+            amount: Value::unknown(),
+            nullifier: Value::unknown(),
+            secret: Value::unknown(),
+            path: MerklePathWitness::unknown(),
+        }
+    }
+}
+
+/// A note created by `JoinSplitCircuit`, e.g. the change of a transfer
+#[derive(Debug, Clone, Copy)]
+pub struct OutputNote<F> {
+    pub amount: Value<F>,
+    pub nullifier: Value<F>,
+    pub secret: Value<F>,
+}
+
+impl<F: PrimeField> OutputNote<F> {
+    fn unknown() -> Self {
+        Self {
+            amount: Value::unknown(),
+            nullifier: Value::unknown(),
+            secret: Value::unknown(),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct JoinSplitConfig {
+    pub(crate) tornado_config: TornadoConfig,
+    pub(crate) range_check_config: RangeCheckConfig,
+    /// `advice[0] + advice[1] = advice[2]`
+    pub(crate) add_selector: Selector,
+    pub instance: Column<Instance>,
+}
+
+/// Shielded-pool transaction with arbitrary amounts: spends `INPUTS` notes of the tree and
+/// creates `OUTPUTS` new ones, with `sum(inputs) + public_amount = sum(outputs)`.
+/// `public_amount` is what the transaction deposits into the pool, so a withdrawal of `x` has
+/// `public_amount = -x`. Every amount is below `2^AMOUNT_BITS`, so that a sum of notes can't
+/// wrap around the modulus and mint value. Notes are committed as `note::commitment`, all of
+/// them with the transaction's `asset_id`: value can't move between assets.
+///
+/// Instance layout: rows `0..INPUTS` = the input nullifier hashes, rows
+/// `INPUTS..INPUTS + OUTPUTS` = the output commitments, then the root, the public amount, the
+/// ext data hash and the asset id.
+/// Every input must be in the tree, there are no dummy inputs: a transaction with fewer notes
+/// uses a circuit with a smaller `INPUTS`. The contract must reject repeated nullifier hashes.
+#[derive(Debug)]
+pub struct JoinSplitCircuit<F, const DEPTH: usize, const INPUTS: usize, const OUTPUTS: usize> {
+    pub inputs: [InputNote<F, DEPTH>; INPUTS],
+    pub outputs: [OutputNote<F>; OUTPUTS],
+    pub public_amount: Value<F>,
+    pub ext_data_hash: Value<F>,
+    pub asset_id: Value<F>,
+}
+
+impl<F: PrimeField, const DEPTH: usize, const INPUTS: usize, const OUTPUTS: usize>
+    JoinSplitCircuit<F, DEPTH, INPUTS, OUTPUTS>
+{
+    pub fn new(
+        inputs: [InputNote<F, DEPTH>; INPUTS],
+        outputs: [OutputNote<F>; OUTPUTS],
+        public_amount: Value<F>,
+        ext_data_hash: Value<F>,
+        asset_id: Value<F>,
+    ) -> Self {
+        Self {
+            inputs,
+            outputs,
+            public_amount,
+            ext_data_hash,
+            asset_id,
+        }
+    }
+}
+
+/// Assigns a range checked amount and returns it with the note commitment
+fn load_note<F: PrimeField>(
+    config: &JoinSplitConfig,
+    mut layouter: impl Layouter<F>,
+    amount: Value<F>,
+    nullifier: Value<F>,
+    secret: Value<F>,
+    asset_id: &AssignedCell<F, F>,
+) -> Result<(AssignedCell<F, F>, AssignedCell<F, F>), Error> {
+    let amount_cell = layouter.assign_region(
+        || "load amount",
+        |mut region| {
+            region.assign_advice(|| "amount", config.tornado_config.advice[0], 0, || amount)
+        },
+    )?;
+    RangeCheckChip::construct(config.range_check_config).range_check(
+        layouter.namespace(|| "range check amount"),
+        &amount_cell,
+        AMOUNT_BITS,
+    )?;
+
+    let tornado_chip = TornadoChip::construct(config.tornado_config.clone());
+    let commitment = tornado_chip.compute_commitment(
+        layouter.namespace(|| "compute commitment"),
+        nullifier,
+        secret,
+        &amount_cell,
+        asset_id,
+    )?;
+    Ok((amount_cell, commitment))
+}
+
+fn add<F: PrimeField>(
+    config: &JoinSplitConfig,
+    mut layouter: impl Layouter<F>,
+    a: &AssignedCell<F, F>,
+    b: &AssignedCell<F, F>,
+) -> Result<AssignedCell<F, F>, Error> {
+    let advice = config.tornado_config.advice;
+    layouter.assign_region(
+        || "add",
+        |mut region| {
+            config.add_selector.enable(&mut region, 0)?;
+            a.copy_advice(|| "a", &mut region, advice[0], 0)?;
+            b.copy_advice(|| "b", &mut region, advice[1], 0)?;
+            region.assign_advice(
+                || "a + b",
+                advice[2],
+                0,
+                || a.value().cloned() + b.value().cloned(),
+            )
+        },
+    )
+}
+
+fn sum<F: PrimeField>(
+    config: &JoinSplitConfig,
+    mut layouter: impl Layouter<F>,
+    amounts: Vec<AssignedCell<F, F>>,
+) -> Result<AssignedCell<F, F>, Error> {
+    let mut amounts = amounts.into_iter();
+    let mut total = amounts.next().ok_or(Error::Synthesis)?;
+    for amount in amounts {
+        total = add(config, layouter.namespace(|| "add amount"), &total, &amount)?;
+    }
+    Ok(total)
+}
+
+impl<F: PrimeField, const DEPTH: usize, const INPUTS: usize, const OUTPUTS: usize> Circuit<F>
+    for JoinSplitCircuit<F, DEPTH, INPUTS, OUTPUTS>
+{
+    type Config = JoinSplitConfig;
+    type FloorPlanner = SimpleFloorPlanner;
+
+    fn without_witnesses(&self) -> Self {
+        Self {
+            inputs: array::from_fn(|_| InputNote::unknown()),
+            outputs: [OutputNote::unknown(); OUTPUTS],
+            public_amount: Value::unknown(),
+            ext_data_hash: Value::unknown(),
+            asset_id: Value::unknown(),
+        }
+    }
+
+    fn configure(meta: &mut ConstraintSystem<F>) -> Self::Config {
+        let advice = [
+            meta.advice_column(),
+            meta.advice_column(),
+            meta.advice_column(),
+        ];
+        let instance = meta.instance_column();
+        meta.enable_equality(instance);
+        let tornado_config = TornadoChip::configure(meta, advice);
+        let range_check_config = RangeCheckChip::configure(
+            meta,
+            advice,
+            tornado_config.merkle_config.constant,
+            LOOKUP_BITS,
+        );
+
+        let add_selector = meta.selector();
+        meta.create_gate("add", |meta| {
+            let s = meta.query_selector(add_selector);
+            let a = meta.query_advice(advice[0], Rotation::cur());
+            let b = meta.query_advice(advice[1], Rotation::cur());
+            let c = meta.query_advice(advice[2], Rotation::cur());
+            vec![s * (a + b - c)]
+        });
+
+        JoinSplitConfig {
+            tornado_config,
+            range_check_config,
+            add_selector,
+            instance,
+        }
+    }
+
+    fn synthesize(
+        &self,
+        config: Self::Config,
+        mut layouter: impl Layouter<F>,
+    ) -> Result<(), Error> {
+        let tornado_chip = TornadoChip::construct(config.tornado_config.clone());
+        let merkle_chip = MerkleChip::construct(config.tornado_config.merkle_config.clone());
+        let instance = config.instance;
+        RangeCheckChip::construct(config.range_check_config)
+            .load_table(layouter.namespace(|| "range check table"))?;
+        let root_row = INPUTS + OUTPUTS;
+
+        let asset_id = tornado_chip
+            .load_public_value(layouter.namespace(|| "load asset id"), self.asset_id)?;
+        layouter.constrain_instance(asset_id.cell(), instance, root_row + 3)?;
+
+        let mut input_amounts = vec![];
+        for (i, note) in self.inputs.iter().enumerate() {
+            let nullifier_hash_cell = tornado_chip.compute_hash(
+                layouter.namespace(|| format!("input {i}: get nullifier hash")),
+                note.nullifier,
+                note.nullifier,
+            )?;
+            layouter.constrain_instance(nullifier_hash_cell.cell(), instance, i)?;
+
+            let (amount, commitment) = load_note(
+                &config,
+                layouter.namespace(|| format!("input {i}: get commitment")),
+                note.amount,
+                note.nullifier,
+                note.secret,
+                &asset_id,
+            )?;
+            let merkle_root_cell = merkle_chip.prove_tree_root(
+                layouter.namespace(|| format!("input {i}: prove merkle tree")),
+                commitment,
+                note.path.path_elements,
+                note.path.leaf_index,
+            )?;
+            layouter.constrain_instance(merkle_root_cell.cell(), instance, root_row)?;
+            input_amounts.push(amount);
+        }
+
+        let mut output_amounts = vec![];
+        for (i, note) in self.outputs.iter().enumerate() {
+            let (amount, commitment) = load_note(
+                &config,
+                layouter.namespace(|| format!("output {i}: get commitment")),
+                note.amount,
+                note.nullifier,
+                note.secret,
+                &asset_id,
+            )?;
+            layouter.constrain_instance(commitment.cell(), instance, INPUTS + i)?;
+            output_amounts.push(amount);
+        }
+
+        // value balance: sum(inputs) + public_amount = sum(outputs)
+        let public_amount = layouter.assign_region(
+            || "load public amount",
+            |mut region| {
+                region.assign_advice_from_instance(
+                    || "public amount",
+                    instance,
+                    root_row + 1,
+                    config.tornado_config.advice[0],
+                    0,
+                )
+            },
+        )?;
+        let inputs_total = sum(&config, layouter.namespace(|| "sum inputs"), input_amounts)?;
+        let inputs_total = add(
+            &config,
+            layouter.namespace(|| "add public amount"),
+            &inputs_total,
+            &public_amount,
+        )?;
+        let outputs_total = sum(
+            &config,
+            layouter.namespace(|| "sum outputs"),
+            output_amounts,
+        )?;
+        layouter.assign_region(
+            || "value balance",
+            |mut region| region.constrain_equal(inputs_total.cell(), outputs_total.cell()),
+        )?;
+
+        let ext_data_hash_cell = tornado_chip.load_public_value(
+            layouter.namespace(|| "load ext data hash"),
+            self.ext_data_hash,
+        )?;
+        layouter.constrain_instance(ext_data_hash_cell.cell(), instance, root_row + 2)?;
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{InputNote, JoinSplitCircuit, OutputNote};
+    use crate::{
+        circuits::merkle::MerklePathWitness, note::commitment, tree::IncrementalMerkleTree,
+    };
+    use halo2_proofs::{
+        circuit::Value,
+        dev::MockProver,
+        halo2curves::{ff::Field, pasta::Fp},
+    };
+    use std::array;
+
+    struct Transaction {
+        inputs: [(u64, Fp, Fp); 2],
+        outputs: [(Fp, Fp, Fp); 2],
+        public_amount: Fp,
+        asset_id: Fp,
+    }
+
+    fn verify(tx: &Transaction) -> bool {
+        let mut tree = IncrementalMerkleTree::new(5);
+        tree.insert(Fp::from(3)).unwrap();
+        let indices = tx.inputs.map(|(amount, nullifier, secret)| {
+            tree.insert(commitment(nullifier, secret, Fp::from(amount), tx.asset_id))
+                .unwrap()
+        });
+        let inputs = array::from_fn(|i| {
+            let (amount, nullifier, secret) = tx.inputs[i];
+            InputNote::new(
+                Value::known(Fp::from(amount)),
+                Value::known(nullifier),
+                Value::known(secret),
+                MerklePathWitness::from_tree(&tree, indices[i]).unwrap(),
+            )
+        });
+        let outputs = tx.outputs.map(|(amount, nullifier, secret)| OutputNote {
+            amount: Value::known(amount),
+            nullifier: Value::known(nullifier),
+            secret: Value::known(secret),
+        });
+        let circuit = JoinSplitCircuit::<Fp, 5, 2, 2>::new(
+            inputs,
+            outputs,
+            Value::known(tx.public_amount),
+            Value::known(Fp::from(0xe47)),
+            Value::known(tx.asset_id),
+        );
+
+        let mut public_input: Vec<Fp> = tx.inputs.iter().map(|(_, n, _)| n * n).collect();
+        public_input.extend(
+            tx.outputs
+                .iter()
+                .map(|(a, n, s)| commitment(*n, *s, *a, tx.asset_id)),
+        );
+        public_input.extend([tree.root(), tx.public_amount, Fp::from(0xe47), tx.asset_id]);
+        let prover = MockProver::run(10, &circuit, vec![public_input]).unwrap();
+        prover.verify().is_ok()
+    }
+
+    fn transaction(outputs: [Fp; 2], public_amount: Fp) -> Transaction {
+        Transaction {
+            inputs: [
+                (30, Fp::from(0x456), Fp::from(0xabc)),
+                (20, Fp::from(7), Fp::from(11)),
+            ],
+            outputs: [
+                (outputs[0], Fp::from(0x123), Fp::from(0x789)),
+                (outputs[1], Fp::from(13), Fp::from(17)),
+            ],
+            public_amount,
+            asset_id: Fp::from(0xda1),
+        }
+    }
+
+    #[test]
+    fn test_join_split_circuit() {
+        // a private transfer of 35, with 15 of change
+        assert!(verify(&transaction([Fp::from(35), Fp::from(15)], Fp::ZERO)));
+        // withdrawing 10 and keeping 40
+        assert!(verify(&transaction(
+            [Fp::from(40), Fp::ZERO],
+            -Fp::from(10)
+        )));
+        // depositing 5 on top
+        assert!(verify(&transaction([Fp::from(55), Fp::ZERO], Fp::from(5))));
+
+        // creating value
+        assert!(!verify(&transaction(
+            [Fp::from(36), Fp::from(15)],
+            Fp::ZERO
+        )));
+        // a "negative" output balancing a larger one
+        assert!(!verify(&transaction(
+            [Fp::from(51), -Fp::from(1)],
+            Fp::ZERO
+        )));
+    }
+}
@@ -0,0 +1,130 @@
+use crate::chips::{
+    decompose::{DecomposeChip, DecomposeConfig},
+    keccak::{KeccakChip, KeccakConfig},
+};
+use halo2_proofs::{
+    circuit::{Layouter, SimpleFloorPlanner, Value},
+    halo2curves::ff::PrimeField,
+    plonk::{Circuit, Column, ConstraintSystem, Error, Instance},
+};
+
+/// Proves knowledge of `BYTES` bytes with a given Keccak-256 digest.
+///
+/// Instance layout: rows 0 to 31 = the bytes of the digest.
+#[derive(Debug)]
+pub struct KeccakCircuit<F, const BYTES: usize> {
+    /// One byte per value
+    pub input: [Value<F>; BYTES],
+}
+
+impl<F: PrimeField, const BYTES: usize> KeccakCircuit<F, BYTES> {
+    pub fn new(input: [u8; BYTES]) -> Self {
+        Self {
+            input: input.map(|byte| Value::known(F::from(byte as u64))),
+        }
+    }
+}
+
+impl<F: PrimeField, const BYTES: usize> Circuit<F> for KeccakCircuit<F, BYTES> {
+    type Config = (KeccakConfig, DecomposeConfig, Column<Instance>);
+    type FloorPlanner = SimpleFloorPlanner;
+
+    fn without_witnesses(&self) -> Self {
+        Self {
+            input: [Value::unknown(); BYTES],
+        }
+    }
+
+    fn configure(meta: &mut ConstraintSystem<F>) -> Self::Config {
+        let advice = [(); 7].map(|_| meta.advice_column());
+        let constant = meta.fixed_column();
+        let instance = meta.instance_column();
+        meta.enable_equality(instance);
+        let keccak_config = KeccakChip::configure(meta, advice, constant);
+        let decompose_config =
+            DecomposeChip::configure(meta, [advice[0], advice[1], advice[2]], constant);
+        (keccak_config, decompose_config, instance)
+    }
+
+    fn synthesize(
+        &self,
+        (keccak_config, decompose_config, instance): Self::Config,
+        mut layouter: impl Layouter<F>,
+    ) -> Result<(), Error> {
+        let decompose_chip = DecomposeChip::construct(decompose_config);
+        let mut input = Vec::with_capacity(8 * BYTES);
+        for (i, byte) in self.input.iter().enumerate() {
+            input.extend(
+                decompose_chip
+                    .decompose::<8>(layouter.namespace(|| format!("input byte {i}")), *byte)?,
+            );
+        }
+
+        let digest =
+            KeccakChip::construct(keccak_config).digest(layouter.namespace(|| "keccak"), &input)?;
+
+        for (i, digest_bits) in digest.chunks(8).enumerate() {
+            let byte = layouter.assign_region(
+                || format!("load digest byte {i}"),
+                |mut region| {
+                    region.assign_advice_from_instance(
+                        || "digest byte",
+                        instance,
+                        i,
+                        decompose_config.advice[0],
+                        0,
+                    )
+                },
+            )?;
+            let bits = decompose_chip
+                .decompose_cell::<8>(layouter.namespace(|| format!("digest byte {i}")), &byte)?;
+            layouter.assign_region(
+                || format!("bind digest byte {i}"),
+                |mut region| {
+                    for (bit, digest_bit) in bits.iter().zip(digest_bits) {
+                        region.constrain_equal(bit.cell(), digest_bit.cell())?;
+                    }
+                    Ok(())
+                },
+            )?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::KeccakCircuit;
+    use halo2_proofs::{dev::MockProver, halo2curves::pasta::Fp};
+    use sha3::{Digest, Keccak256};
+
+    fn verify<const BYTES: usize>(input: [u8; BYTES], digest: &[u8]) -> bool {
+        let circuit = KeccakCircuit::<Fp, BYTES>::new(input);
+        let instances = digest.iter().map(|byte| Fp::from(*byte as u64)).collect();
+        MockProver::run(17, &circuit, vec![instances])
+            .unwrap()
+            .verify()
+            .is_ok()
+    }
+
+    #[test]
+    fn test_keccak_circuit() {
+        let digest = Keccak256::digest(b"abc");
+        assert_eq!(
+            hex::encode(digest),
+            "4e03657aea45a94fc7d47ba826c8d667c0d1e6e33a64a036ec44f58fa12d6c45"
+        );
+        assert!(verify(*b"abc", &digest));
+
+        let mut tampered = digest.to_vec();
+        tampered[31] ^= 1;
+        assert!(!verify(*b"abc", &tampered));
+    }
+
+    #[test]
+    fn test_keccak_circuit_word() {
+        // a 32-byte word, e.g. a field element hashed the way Solidity hashes a uint256
+        let input: [u8; 32] = std::array::from_fn(|i| i as u8 * 7);
+        assert!(verify(input, &Keccak256::digest(input)));
+    }
+}
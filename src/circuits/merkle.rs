@@ -1,23 +1,127 @@
-use crate::chips::merkle::{MerkleChip, MerkleConfig};
+use crate::{
+    chips::merkle::{MerkleChip, MerkleConfig},
+    circuits::tornado::BuildError,
+    tree::{Hasher, IncrementalMerkleTree, MerkleProof},
+};
 use halo2_proofs::{
     circuit::{Layouter, SimpleFloorPlanner, Value},
     halo2curves::ff::PrimeField,
-    plonk::{Circuit, ConstraintSystem},
+    plonk::{Circuit, Column, ConstraintSystem, Instance},
 };
 
-#[derive(Debug, Default)]
-pub struct MerkleCircuit<F> {
+/// The witness of a Merkle path: the siblings of the leaf from the leaf up, and its position.
+/// The depth is part of the type, so a path without witnesses (for keygen) has the same shape
+/// as the one proven, and a path of another depth doesn't convert.
+#[derive(Debug, Clone, Copy)]
+pub struct MerklePathWitness<F, const DEPTH: usize> {
+    pub path_elements: [Value<F>; DEPTH],
+    /// Position of the leaf in the tree, its bits are the path indices
+    pub leaf_index: Value<F>,
+}
+
+impl<F: PrimeField, const DEPTH: usize> MerklePathWitness<F, DEPTH> {
+    /// A path without witnesses
+    pub fn unknown() -> Self {
+        Self {
+            path_elements: [Value::unknown(); DEPTH],
+            leaf_index: Value::unknown(),
+        }
+    }
+
+    /// A path whose elements and leaf index are all known
+    pub fn known(path_elements: [F; DEPTH], leaf_index: F) -> Self {
+        Self {
+            path_elements: path_elements.map(Value::known),
+            leaf_index: Value::known(leaf_index),
+        }
+    }
+
+    /// Returns `None` unless the path has exactly `DEPTH` layers
+    pub fn new(path_elements: Vec<Value<F>>, leaf_index: Value<F>) -> Option<Self> {
+        Some(Self {
+            path_elements: path_elements.try_into().ok()?,
+            leaf_index,
+        })
+    }
+
+    /// The path of the leaf at `index`. Returns `None` if there is no leaf there, or the tree
+    /// isn't `DEPTH` deep.
+    pub fn from_tree<H: Hasher<F>>(
+        tree: &IncrementalMerkleTree<F, H>,
+        index: usize,
+    ) -> Option<Self> {
+        let (path_elements, _) = tree.proof(index)?;
+        Self::new(
+            path_elements.into_iter().map(Value::known).collect(),
+            Value::known(F::from(index as u64)),
+        )
+    }
+}
+
+impl<F: PrimeField, const DEPTH: usize> Default for MerklePathWitness<F, DEPTH> {
+    fn default() -> Self {
+        Self::unknown()
+    }
+}
+
+/// Checks the shape of the proof: `DEPTH` path elements, and as many path indices, each 0 or 1
+impl<F: PrimeField, const DEPTH: usize> TryFrom<MerkleProof<F>> for MerklePathWitness<F, DEPTH> {
+    type Error = BuildError;
+
+    fn try_from(proof: MerkleProof<F>) -> Result<Self, BuildError> {
+        let MerkleProof {
+            path_elements,
+            path_indices,
+        } = proof;
+        let path_elements: [F; DEPTH] =
+            path_elements.try_into().map_err(|path_elements: Vec<F>| {
+                BuildError::WrongPathElements {
+                    expected: DEPTH,
+                    found: path_elements.len(),
+                }
+            })?;
+        if path_indices.len() != DEPTH {
+            return Err(BuildError::WrongPathIndices {
+                expected: DEPTH,
+                found: path_indices.len(),
+            });
+        }
+        // the leaf index has the path indices as bits, least significant first
+        let mut leaf_index = F::ZERO;
+        for (layer, index) in path_indices.iter().enumerate().rev() {
+            if *index != F::ZERO && *index != F::ONE {
+                return Err(BuildError::InvalidPathIndex { layer });
+            }
+            leaf_index = leaf_index.double() + index;
+        }
+        Ok(Self {
+            path_elements: path_elements.map(Value::known),
+            leaf_index: Value::known(leaf_index),
+        })
+    }
+}
+
+#[derive(Debug)]
+pub struct MerkleCircuit<F, const DEPTH: usize> {
     pub leaf: Value<F>,
-    pub path_elements: Vec<Value<F>>,
-    pub path_indices: Vec<Value<F>>,
+    pub path: MerklePathWitness<F, DEPTH>,
+}
+
+impl<F: PrimeField, const DEPTH: usize> MerkleCircuit<F, DEPTH> {
+    pub fn new(leaf: Value<F>, path: MerklePathWitness<F, DEPTH>) -> Self {
+        Self { leaf, path }
+    }
 }
 
-impl<F: PrimeField> Circuit<F> for MerkleCircuit<F> {
-    type Config = MerkleConfig;
+impl<F: PrimeField, const DEPTH: usize> Circuit<F> for MerkleCircuit<F, DEPTH> {
+    type Config = (MerkleConfig, Column<Instance>);
     type FloorPlanner = SimpleFloorPlanner;
 
     fn without_witnesses(&self) -> Self {
-        Self::default()
+        Self {
+            leaf: Value::unknown(),
+            path: MerklePathWitness::unknown(),
+        }
     }
 
     fn configure(meta: &mut ConstraintSystem<F>) -> Self::Config {
@@ -27,28 +131,29 @@ impl<F: PrimeField> Circuit<F> for MerkleCircuit<F> {
             meta.advice_column(),
         ];
         let instance = meta.instance_column();
-        MerkleChip::configure(meta, advice, instance)
+        meta.enable_equality(instance);
+        (MerkleChip::configure(meta, advice), instance)
     }
 
     fn synthesize(
         &self,
-        config: Self::Config,
+        (config, instance): Self::Config,
         mut layouter: impl Layouter<F>,
     ) -> Result<(), halo2_proofs::plonk::Error> {
         let leaf_cell = layouter.assign_region(
             || "assign leaf",
             |mut region| region.assign_advice(|| "assign leaf", config.advice[0], 0, || self.leaf),
         )?;
-        layouter.constrain_instance(leaf_cell.cell(), config.clone().instance, 0)?;
+        layouter.constrain_instance(leaf_cell.cell(), instance, 0)?;
 
         let chip = MerkleChip::construct(config.clone());
         let root_cell = chip.prove_tree_root(
             layouter.namespace(|| "prove tree"),
             leaf_cell,
-            self.path_elements.clone(),
-            self.path_indices.clone(),
+            self.path.path_elements,
+            self.path.leaf_index,
         )?;
-        layouter.constrain_instance(root_cell.cell(), config.instance, 1)?;
+        layouter.constrain_instance(root_cell.cell(), instance, 1)?;
 
         Ok(())
     }
@@ -56,24 +161,114 @@ impl<F: PrimeField> Circuit<F> for MerkleCircuit<F> {
 
 #[cfg(test)]
 mod tests {
-    use super::MerkleCircuit;
-    use halo2_proofs::{circuit::Value, dev::MockProver, halo2curves::pasta::Fp};
+    use super::{MerkleCircuit, MerklePathWitness};
+    use crate::{
+        circuits::tornado::BuildError,
+        native::compute_root,
+        test_utils::{
+            assert_keygen_round_trip,
+            proptest_strategies::{
+                mutated_tree_case, path_case, path_indices, tree_case, PathMutation,
+            },
+            random_tree,
+        },
+        tree::{zeros, IncrementalMerkleTree, MerkleProof, ProductHasher},
+    };
+    use halo2_proofs::{
+        circuit::Value,
+        dev::MockProver,
+        halo2curves::{bn256::Fr, pasta::Fp},
+    };
+    use proptest::prelude::*;
+    use rand_core::OsRng;
+
+    /// Whether `MerkleCircuit` accepts the path, for the depths of the property tests
+    fn accepts(leaf: Fp, path_elements: &[Fp], leaf_index: usize, root: Fp) -> bool {
+        fn run<const DEPTH: usize>(
+            leaf: Fp,
+            path_elements: &[Fp],
+            leaf_index: usize,
+            root: Fp,
+        ) -> bool {
+            let path = MerklePathWitness::known(
+                path_elements.try_into().unwrap(),
+                Fp::from(leaf_index as u64),
+            );
+            let circuit = MerkleCircuit::<Fp, DEPTH>::new(Value::known(leaf), path);
+            MockProver::run(10, &circuit, vec![vec![leaf, root]])
+                .unwrap()
+                .verify()
+                .is_ok()
+        }
+        match path_elements.len() {
+            1 => run::<1>(leaf, path_elements, leaf_index, root),
+            2 => run::<2>(leaf, path_elements, leaf_index, root),
+            3 => run::<3>(leaf, path_elements, leaf_index, root),
+            4 => run::<4>(leaf, path_elements, leaf_index, root),
+            5 => run::<5>(leaf, path_elements, leaf_index, root),
+            6 => run::<6>(leaf, path_elements, leaf_index, root),
+            depth => unreachable!("no property test has depth {depth}"),
+        }
+    }
+
+    proptest! {
+        #![proptest_config(ProptestConfig::with_cases(32))]
+
+        #[test]
+        fn test_merkle_circuit_tree_paths(case in tree_case::<Fp>(1..=6)) {
+            let tree = case.tree();
+            let (path_elements, _) = tree.proof(case.index).unwrap();
+            prop_assert!(accepts(case.leaf(), &path_elements, case.index, tree.root()));
+            let wrong_root = tree.root() + Fp::from(1);
+            prop_assert!(!accepts(case.leaf(), &path_elements, case.index, wrong_root));
+        }
+
+        #[test]
+        fn test_merkle_circuit_arbitrary_paths(case in path_case::<Fp>(1..=6)) {
+            let depth = case.path_elements.len();
+            let root = compute_root(
+                case.leaf,
+                &case.path_elements,
+                &path_indices(case.leaf_index(), depth),
+            )
+            .unwrap();
+            prop_assert!(accepts(case.leaf, &case.path_elements, case.leaf_index(), root));
+        }
+
+        #[test]
+        fn test_merkle_circuit_mutated_paths((case, mutation) in mutated_tree_case::<Fp>(1..=6)) {
+            let tree = case.tree();
+            let (mut path_elements, _) = tree.proof(case.index).unwrap();
+            let mut leaf_index = case.index;
+            mutation.apply(&mut path_elements, &mut leaf_index);
+
+            let native = leaf_index < 1 << case.depth
+                && compute_root(
+                    case.leaf(),
+                    &path_elements,
+                    &path_indices(leaf_index, case.depth),
+                ) == Some(tree.root());
+            prop_assert_eq!(accepts(case.leaf(), &path_elements, leaf_index, tree.root()), native);
+            // a flipped bit within the path only swaps two inputs of the hash, which the product
+            // hash doesn't see; any other mutation changes the root
+            if !matches!(mutation, PathMutation::Bit { layer } if layer < case.depth) {
+                prop_assert!(!native);
+            }
+        }
+    }
 
     #[test]
     fn test_merkle_circuit() {
         let leaf = 123;
-        let elements = vec![2, 7, 6, 5, 5, 4];
-        let indices = vec![0, 1, 1, 0, 1, 0];
+        let elements = [2, 7, 6, 5, 5, 4];
+        // path indices 0, 1, 1, 0, 1, 0
+        let leaf_index = 0b010110;
         let root = leaf * elements.iter().product::<u64>();
 
-        let circuit = MerkleCircuit {
-            leaf: Value::known(Fp::from(leaf)),
-            path_elements: elements
-                .iter()
-                .map(|e| Value::known(Fp::from(*e)))
-                .collect(),
-            path_indices: indices.iter().map(|e| Value::known(Fp::from(*e))).collect(),
-        };
+        let circuit = MerkleCircuit::<Fp, 6>::new(
+            Value::known(Fp::from(leaf)),
+            MerklePathWitness::known(elements.map(Fp::from), Fp::from(leaf_index)),
+        );
         let public_input = vec![Fp::from(leaf), Fp::from(root)];
         let prover = MockProver::run(10, &circuit, vec![public_input]).unwrap();
         assert!(prover.verify().is_ok());
@@ -82,18 +277,15 @@ mod tests {
     #[test]
     fn test_merkle_circuit_err() {
         let leaf = 123;
-        let elements = vec![2, 7, 6, 5, 5, 4];
-        let indices = vec![0, 1, 1, 0, 1, 2];
+        let elements = [2, 7, 6, 5, 5, 4];
+        // doesn't fit in 6 bits
+        let leaf_index = 1 << 6;
         let root = leaf * elements.iter().product::<u64>();
 
-        let circuit = MerkleCircuit {
-            leaf: Value::known(Fp::from(leaf)),
-            path_elements: elements
-                .iter()
-                .map(|e| Value::known(Fp::from(*e)))
-                .collect(),
-            path_indices: indices.iter().map(|e| Value::known(Fp::from(*e))).collect(),
-        };
+        let circuit = MerkleCircuit::<Fp, 6>::new(
+            Value::known(Fp::from(leaf)),
+            MerklePathWitness::known(elements.map(Fp::from), Fp::from(leaf_index)),
+        );
         let public_input = vec![Fp::from(leaf), Fp::from(root)];
         let prover = MockProver::run(10, &circuit, vec![public_input]).unwrap();
         assert!(prover.verify().is_err());
@@ -102,4 +294,92 @@ mod tests {
         let prover2 = MockProver::run(10, &circuit, vec![public_input2]).unwrap();
         assert!(prover2.verify().is_err());
     }
+
+    #[test]
+    fn test_merkle_circuit_mismatched_path() {
+        let elements = vec![Value::known(Fp::from(2)), Value::known(Fp::from(7))];
+        let leaf_index = Value::known(Fp::from(2));
+
+        assert!(MerklePathWitness::<Fp, 2>::new(elements[..1].to_vec(), leaf_index).is_none());
+        assert!(MerklePathWitness::<Fp, 3>::new(elements.clone(), leaf_index).is_none());
+        assert!(MerklePathWitness::<Fp, 2>::new(elements, leaf_index).is_some());
+    }
+
+    #[test]
+    fn test_path_witness() {
+        let mut tree = IncrementalMerkleTree::<Fp>::new(4);
+        tree.insert_batch(&[3, 5, 7].map(Fp::from)).unwrap();
+        let from_tree = MerklePathWitness::<Fp, 4>::from_tree(&tree, 2).unwrap();
+        let from_proof: MerklePathWitness<Fp, 4> = MerkleProof::from(tree.proof(2).unwrap())
+            .try_into()
+            .unwrap();
+        from_tree
+            .leaf_index
+            .zip(from_proof.leaf_index)
+            .assert_if_known(|(a, b)| a == b && *a == Fp::from(2));
+        let circuit = MerkleCircuit {
+            leaf: Value::known(Fp::from(7)),
+            path: from_proof,
+        };
+        let prover = MockProver::run(10, &circuit, vec![vec![Fp::from(7), tree.root()]]).unwrap();
+        assert!(prover.verify().is_ok());
+
+        // a path of another depth doesn't convert
+        assert!(MerklePathWitness::<Fp, 5>::from_tree(&tree, 2).is_none());
+        assert!(MerklePathWitness::<Fp, 4>::from_tree(&tree, 3).is_none());
+        let proof = MerkleProof::from(tree.proof(2).unwrap());
+        assert_eq!(
+            MerklePathWitness::<Fp, 5>::try_from(proof.clone()).err(),
+            Some(BuildError::WrongPathElements {
+                expected: 5,
+                found: 4
+            })
+        );
+        let mut wrong_indices = proof.clone();
+        wrong_indices.path_indices.pop();
+        assert_eq!(
+            MerklePathWitness::<Fp, 4>::try_from(wrong_indices).err(),
+            Some(BuildError::WrongPathIndices {
+                expected: 4,
+                found: 3
+            })
+        );
+        let mut invalid_index = proof;
+        invalid_index.path_indices[1] = Fp::from(2);
+        assert_eq!(
+            MerklePathWitness::<Fp, 4>::try_from(invalid_index).err(),
+            Some(BuildError::InvalidPathIndex { layer: 1 })
+        );
+    }
+
+    #[test]
+    fn test_keygen_round_trip() {
+        // keys come from `MerklePathWitness::unknown`, proofs from a tree's path
+        let tree = random_tree::<Fr>(5, 11, OsRng);
+        let leaf = tree.leaves()[6];
+        let circuit = MerkleCircuit::<Fr, 5> {
+            leaf: Value::known(leaf),
+            path: MerklePathWitness::from_tree(&tree, 6).unwrap(),
+        };
+        assert_keygen_round_trip(circuit, &[vec![leaf, tree.root()]]);
+    }
+
+    #[test]
+    fn test_merkle_circuit_zero_padded_path() {
+        // first leaf of an otherwise empty tree: every sibling is an empty subtree
+        const DEPTH: usize = 8;
+        let table = zeros::<Fp, ProductHasher>(DEPTH);
+        let leaf = Fp::from(123);
+        let root = table[..DEPTH].iter().fold(leaf, |node, zero| node * zero);
+
+        let circuit = MerkleCircuit::<Fp, DEPTH> {
+            leaf: Value::known(leaf),
+            path: MerklePathWitness {
+                path_elements: std::array::from_fn(|i| Value::known(table[i])),
+                leaf_index: Value::known(Fp::from(0)),
+            },
+        };
+        let prover = MockProver::run(10, &circuit, vec![vec![leaf, root]]).unwrap();
+        assert!(prover.verify().is_ok());
+    }
 }
@@ -0,0 +1,108 @@
+use crate::chips::merkle4::{Merkle4Chip, Merkle4Config};
+use halo2_proofs::{
+    circuit::{Layouter, SimpleFloorPlanner, Value},
+    halo2curves::ff::PrimeField,
+    plonk::{Circuit, Column, ConstraintSystem, Error, Instance},
+};
+
+/// Membership in an arity-4 tree. Instance layout: row 0 = leaf, row 1 = root
+#[derive(Debug)]
+pub struct Merkle4Circuit<F, const DEPTH: usize> {
+    pub leaf: Value<F>,
+    pub path_elements: [[Value<F>; 3]; DEPTH],
+    /// Position of the node in each layer, in `0..4`
+    pub path_indices: [Value<F>; DEPTH],
+}
+
+impl<F: PrimeField, const DEPTH: usize> Circuit<F> for Merkle4Circuit<F, DEPTH> {
+    type Config = (Merkle4Config, Column<Instance>);
+    type FloorPlanner = SimpleFloorPlanner;
+
+    fn without_witnesses(&self) -> Self {
+        Self {
+            leaf: Value::unknown(),
+            path_elements: [[Value::unknown(); 3]; DEPTH],
+            path_indices: [Value::unknown(); DEPTH],
+        }
+    }
+
+    fn configure(meta: &mut ConstraintSystem<F>) -> Self::Config {
+        let advice = [
+            meta.advice_column(),
+            meta.advice_column(),
+            meta.advice_column(),
+        ];
+        let instance = meta.instance_column();
+        meta.enable_equality(instance);
+        (Merkle4Chip::configure(meta, advice), instance)
+    }
+
+    fn synthesize(
+        &self,
+        (config, instance): Self::Config,
+        mut layouter: impl Layouter<F>,
+    ) -> Result<(), Error> {
+        let leaf_cell = layouter.assign_region(
+            || "assign leaf",
+            |mut region| region.assign_advice(|| "assign leaf", config.advice[0], 0, || self.leaf),
+        )?;
+        layouter.constrain_instance(leaf_cell.cell(), instance, 0)?;
+
+        let chip = Merkle4Chip::construct(config.clone());
+        let root_cell = chip.prove_tree_root(
+            layouter.namespace(|| "prove quad tree"),
+            leaf_cell,
+            self.path_elements,
+            self.path_indices,
+        )?;
+        layouter.constrain_instance(root_cell.cell(), instance, 1)?;
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Merkle4Circuit;
+    use halo2_proofs::{circuit::Value, dev::MockProver, halo2curves::pasta::Fp};
+
+    fn circuit(leaf: u64, siblings: [[u64; 3]; 3], indices: [u64; 3]) -> Merkle4Circuit<Fp, 3> {
+        Merkle4Circuit {
+            leaf: Value::known(Fp::from(leaf)),
+            path_elements: siblings.map(|layer| layer.map(|s| Value::known(Fp::from(s)))),
+            path_indices: indices.map(|i| Value::known(Fp::from(i))),
+        }
+    }
+
+    #[test]
+    fn test_merkle4_circuit() {
+        let siblings = [[2, 3, 5], [7, 11, 13], [17, 19, 23]];
+        let root = 123 * siblings.iter().flatten().product::<u64>();
+
+        for indices in [[0, 1, 2], [3, 3, 0], [1, 0, 3]] {
+            let prover = MockProver::run(
+                10,
+                &circuit(123, siblings, indices),
+                vec![vec![Fp::from(123), Fp::from(root)]],
+            )
+            .unwrap();
+            assert!(prover.verify().is_ok());
+        }
+
+        let prover = MockProver::run(
+            10,
+            &circuit(123, siblings, [0, 4, 2]),
+            vec![vec![Fp::from(123), Fp::from(root)]],
+        )
+        .unwrap();
+        assert!(prover.verify().is_err());
+
+        let prover = MockProver::run(
+            10,
+            &circuit(123, siblings, [0, 1, 2]),
+            vec![vec![Fp::from(123), Fp::from(root + 1)]],
+        )
+        .unwrap();
+        assert!(prover.verify().is_err());
+    }
+}
@@ -1,2 +1,21 @@
+pub mod association;
+pub mod batch_deposit;
+pub mod commitment;
 pub mod hash;
+pub mod insert;
+pub mod is_zero;
+pub mod join_split;
+pub mod keccak;
 pub mod merkle;
+pub mod merkle4;
+pub mod multi_withdraw;
+pub mod nullifier_exclusion;
+pub mod owned;
+pub mod range_check;
+pub mod rln;
+pub mod screening;
+pub mod semaphore;
+pub mod timelock;
+pub mod tornado;
+pub mod tornado_history;
+pub mod viewing;
@@ -0,0 +1,206 @@
+use crate::{
+    chips::{
+        merkle::MerkleChip,
+        tornado::{TornadoChip, TornadoConfig},
+    },
+    circuits::merkle::MerklePathWitness,
+};
+use halo2_proofs::{
+    circuit::{Layouter, SimpleFloorPlanner, Value},
+    halo2curves::ff::PrimeField,
+    plonk::{Circuit, Column, ConstraintSystem, Error, Instance},
+};
+use std::array;
+
+/// One of the notes spent by `MultiWithdrawCircuit`
+#[derive(Debug, Clone)]
+pub struct SpentNote<F, const DEPTH: usize> {
+    pub nullifier: Value<F>,
+    pub secret: Value<F>,
+    pub path: MerklePathWitness<F, DEPTH>,
+}
+
+impl<F: PrimeField, const DEPTH: usize> SpentNote<F, DEPTH> {
+    pub fn new(nullifier: Value<F>, secret: Value<F>, path: MerklePathWitness<F, DEPTH>) -> Self {
+        Self {
+            nullifier,
+            secret,
+            path,
+        }
+    }
+
+    fn unknown() -> Self {
+        Self {
+            nullifier: Value::unknown(),
+            secret: Value::unknown(),
+            path: MerklePathWitness::unknown(),
+        }
+    }
+}
+
+/// Withdraws `NOTES` notes of the same tree in one proof, consolidating small deposits.
+/// Every branch runs on the same `TornadoChip` columns, one after the other.
+///
+/// Instance layout: rows `0..NOTES` = the nullifier hashes, row `NOTES` = root,
+/// row `NOTES + 1` = ext data hash, row `NOTES + 2` = total amount withdrawn, row `NOTES + 3`
+/// = relayer, row `NOTES + 4` = fee, row `NOTES + 5` = refund. The recipient is bound through
+/// the ext data hash, the relayer and its fee and refund by their own rows.
+/// The circuit doesn't check that the notes are distinct: the contract must reject a
+/// nullifier hash that is spent or appears twice in the same withdrawal, and check the total
+/// against `NOTES` times the pool's denomination. Like `TornadoHistoryCircuit`, it serves
+/// fixed-denomination pools: commitments are `H(nullifier, secret)`.
+#[derive(Debug)]
+pub struct MultiWithdrawCircuit<F, const DEPTH: usize, const NOTES: usize> {
+    pub notes: [SpentNote<F, DEPTH>; NOTES],
+    pub ext_data_hash: Value<F>,
+    pub total_amount: Value<F>,
+    /// See `ext_data::address_to_field`
+    pub relayer: Value<F>,
+    pub fee: Value<F>,
+    pub refund: Value<F>,
+}
+
+impl<F: PrimeField, const DEPTH: usize, const NOTES: usize> MultiWithdrawCircuit<F, DEPTH, NOTES> {
+    pub fn new(
+        notes: [SpentNote<F, DEPTH>; NOTES],
+        ext_data_hash: Value<F>,
+        total_amount: Value<F>,
+        relayer: Value<F>,
+        fee: Value<F>,
+        refund: Value<F>,
+    ) -> Self {
+        Self {
+            notes,
+            ext_data_hash,
+            total_amount,
+            relayer,
+            fee,
+            refund,
+        }
+    }
+}
+
+impl<F: PrimeField, const DEPTH: usize, const NOTES: usize> Circuit<F>
+    for MultiWithdrawCircuit<F, DEPTH, NOTES>
+{
+    type Config = (TornadoConfig, Column<Instance>);
+    type FloorPlanner = SimpleFloorPlanner;
+
+    fn without_witnesses(&self) -> Self {
+        Self {
+            notes: array::from_fn(|_| SpentNote::unknown()),
+            ext_data_hash: Value::unknown(),
+            total_amount: Value::unknown(),
+            relayer: Value::unknown(),
+            fee: Value::unknown(),
+            refund: Value::unknown(),
+        }
+    }
+
+    fn configure(meta: &mut ConstraintSystem<F>) -> Self::Config {
+        let advice = [
+            meta.advice_column(),
+            meta.advice_column(),
+            meta.advice_column(),
+        ];
+        let instance = meta.instance_column();
+        meta.enable_equality(instance);
+        (TornadoChip::configure(meta, advice), instance)
+    }
+
+    fn synthesize(
+        &self,
+        (config, instance): Self::Config,
+        mut layouter: impl Layouter<F>,
+    ) -> Result<(), Error> {
+        let tornado_chip = TornadoChip::construct(config.clone());
+        let merkle_chip = MerkleChip::construct(config.merkle_config.clone());
+
+        for (i, note) in self.notes.iter().enumerate() {
+            let nullifier_hash_cell = tornado_chip.compute_hash(
+                layouter.namespace(|| format!("note {i}: get nullifier hash")),
+                note.nullifier,
+                note.nullifier,
+            )?;
+            layouter.constrain_instance(nullifier_hash_cell.cell(), instance, i)?;
+
+            let commitment_hash_cell = tornado_chip.compute_hash(
+                layouter.namespace(|| format!("note {i}: get commitment")),
+                note.nullifier,
+                note.secret,
+            )?;
+            let merkle_root_cell = merkle_chip.prove_tree_root(
+                layouter.namespace(|| format!("note {i}: prove merkle tree")),
+                commitment_hash_cell,
+                note.path.path_elements,
+                note.path.leaf_index,
+            )?;
+            layouter.constrain_instance(merkle_root_cell.cell(), instance, NOTES)?;
+        }
+
+        for (row, (name, value)) in [
+            ("ext data hash", self.ext_data_hash),
+            ("total amount", self.total_amount),
+            ("relayer", self.relayer),
+            ("fee", self.fee),
+            ("refund", self.refund),
+        ]
+        .into_iter()
+        .enumerate()
+        {
+            let cell = tornado_chip
+                .load_public_value(layouter.namespace(|| format!("load {name}")), value)?;
+            layouter.constrain_instance(cell.cell(), instance, NOTES + 1 + row)?;
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{MultiWithdrawCircuit, SpentNote};
+    use crate::{circuits::merkle::MerklePathWitness, tree::IncrementalMerkleTree};
+    use halo2_proofs::{circuit::Value, dev::MockProver, halo2curves::pasta::Fp};
+    use std::array;
+
+    #[test]
+    fn test_multi_withdraw_circuit() {
+        let notes =
+            [(0x456, 0xabc), (0x123, 0x789), (7, 11)].map(|(n, s)| (Fp::from(n), Fp::from(s)));
+        let mut tree = IncrementalMerkleTree::new(5);
+        tree.insert(Fp::from(3)).unwrap();
+        let indices = notes.map(|(nullifier, secret)| tree.insert(nullifier * secret).unwrap());
+        // paths are taken once every note is in, so they all lead to the same root
+        let spent: [SpentNote<Fp, 5>; 3] = array::from_fn(|i| {
+            let (nullifier, secret) = notes[i];
+            SpentNote::new(
+                Value::known(nullifier),
+                Value::known(secret),
+                MerklePathWitness::from_tree(&tree, indices[i]).unwrap(),
+            )
+        });
+        let total_amount = Fp::from(3 * 100);
+        let circuit = MultiWithdrawCircuit::<Fp, 5, 3>::new(
+            spent,
+            Value::known(Fp::from(0xe47)),
+            Value::known(total_amount),
+            Value::known(Fp::from(0x22)),
+            Value::known(Fp::from(50)),
+            Value::known(Fp::from(0)),
+        );
+
+        let mut public_input: Vec<Fp> = notes.iter().map(|(n, _)| *n * *n).collect();
+        public_input.extend([tree.root(), Fp::from(0xe47), total_amount]);
+        public_input.extend([0x22, 50, 0].map(Fp::from));
+        let prover = MockProver::run(10, &circuit, vec![public_input.clone()]).unwrap();
+        assert!(prover.verify().is_ok());
+
+        for row in 0..public_input.len() {
+            let mut tampered = public_input.clone();
+            tampered[row] += Fp::from(1);
+            let prover = MockProver::run(10, &circuit, vec![tampered]).unwrap();
+            assert!(prover.verify().is_err());
+        }
+    }
+}
@@ -0,0 +1,236 @@
+use crate::{
+    chips::{
+        hash::HashChip,
+        less_than::{LessThanChip, LessThanConfig},
+        merkle::MerkleChip,
+        range_check::RangeCheckChip,
+        tornado::{TornadoChip, TornadoConfig},
+    },
+    circuits::merkle::MerklePathWitness,
+};
+use halo2_proofs::{
+    circuit::{Layouter, SimpleFloorPlanner, Value},
+    halo2curves::ff::PrimeField,
+    plonk::{Circuit, Column, ConstraintSystem, Error, Instance},
+};
+
+const LOOKUP_BITS: usize = 8;
+
+#[derive(Debug, Clone)]
+pub struct NullifierExclusionConfig {
+    pub(crate) tornado_config: TornadoConfig,
+    pub(crate) less_than_config: LessThanConfig,
+    pub instance: Column<Instance>,
+}
+
+/// Proves that a nullifier hash is not spent, i.e. not in the `IndexedNullifierTree` with a
+/// given root: some leaf `(low_value, low_next)` of the tree has
+/// `low_value < nullifier_hash < low_next`. The leaves form a sorted linked list, so that leaf
+/// is the only one whose range could hold the nullifier hash, and it doesn't.
+///
+/// Instance layout: row 0 = nullifier hash, row 1 = root of the nullifier tree.
+#[derive(Debug, Clone)]
+pub struct NullifierExclusionCircuit<F, const DEPTH: usize> {
+    pub low_value: Value<F>,
+    pub low_next: Value<F>,
+    /// Path of the low leaf
+    pub path: MerklePathWitness<F, DEPTH>,
+}
+
+impl<F: PrimeField, const DEPTH: usize> NullifierExclusionCircuit<F, DEPTH> {
+    pub fn new(low_value: Value<F>, low_next: Value<F>, path: MerklePathWitness<F, DEPTH>) -> Self {
+        Self {
+            low_value,
+            low_next,
+            path,
+        }
+    }
+}
+
+impl<F: PrimeField, const DEPTH: usize> Circuit<F> for NullifierExclusionCircuit<F, DEPTH> {
+    type Config = NullifierExclusionConfig;
+    type FloorPlanner = SimpleFloorPlanner;
+
+    fn without_witnesses(&self) -> Self {
+        Self {
+            low_value: Value::unknown(),
+            low_next: Value::unknown(),
+            path: MerklePathWitness::unknown(),
+        }
+    }
+
+    fn configure(meta: &mut ConstraintSystem<F>) -> Self::Config {
+        let advice = [
+            meta.advice_column(),
+            meta.advice_column(),
+            meta.advice_column(),
+        ];
+        let instance = meta.instance_column();
+        meta.enable_equality(instance);
+        let tornado_config = TornadoChip::configure(meta, advice);
+        let range_check_config = RangeCheckChip::configure(
+            meta,
+            advice,
+            tornado_config.merkle_config.constant,
+            LOOKUP_BITS,
+        );
+        let less_than_config = LessThanChip::configure(meta, advice, range_check_config);
+
+        NullifierExclusionConfig {
+            tornado_config,
+            less_than_config,
+            instance,
+        }
+    }
+
+    fn synthesize(
+        &self,
+        config: Self::Config,
+        mut layouter: impl Layouter<F>,
+    ) -> Result<(), Error> {
+        let hash_chip = HashChip::construct(config.tornado_config.hash_config);
+        let merkle_chip = MerkleChip::construct(config.tornado_config.merkle_config.clone());
+        let less_than_chip = LessThanChip::construct(config.less_than_config);
+        let advice = config.tornado_config.advice;
+        let instance = config.instance;
+        RangeCheckChip::construct(config.less_than_config.range_check_config)
+            .load_table(layouter.namespace(|| "range check table"))?;
+
+        let (nullifier_hash, low_value, low_next) = layouter.assign_region(
+            || "load values",
+            |mut region| {
+                let nullifier_hash = region.assign_advice_from_instance(
+                    || "nullifier hash",
+                    instance,
+                    0,
+                    advice[0],
+                    0,
+                )?;
+                let low_value =
+                    region.assign_advice(|| "low value", advice[1], 0, || self.low_value)?;
+                let low_next =
+                    region.assign_advice(|| "low next", advice[2], 0, || self.low_next)?;
+                Ok((nullifier_hash, low_value, low_next))
+            },
+        )?;
+
+        let low_leaf = hash_chip.hash(
+            layouter.namespace(|| "hash low leaf"),
+            low_value.clone(),
+            low_next.clone(),
+        )?;
+        let root = merkle_chip.prove_tree_root(
+            layouter.namespace(|| "prove low leaf"),
+            low_leaf,
+            self.path.path_elements,
+            self.path.leaf_index,
+        )?;
+        layouter.constrain_instance(root.cell(), instance, 1)?;
+
+        let nullifier_hash = less_than_chip.split(
+            layouter.namespace(|| "split nullifier hash"),
+            &nullifier_hash,
+        )?;
+        let low_value =
+            less_than_chip.split(layouter.namespace(|| "split low value"), &low_value)?;
+        let low_next = less_than_chip.split(layouter.namespace(|| "split low next"), &low_next)?;
+        less_than_chip.check_less_than(
+            layouter.namespace(|| "low value < nullifier hash"),
+            &low_value,
+            &nullifier_hash,
+        )?;
+        less_than_chip.check_less_than(
+            layouter.namespace(|| "nullifier hash < low next"),
+            &nullifier_hash,
+            &low_next,
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::NullifierExclusionCircuit;
+    use crate::{
+        circuits::merkle::MerklePathWitness,
+        nullifier::{ExclusionProof, IndexedNullifierTree},
+    };
+    use halo2_proofs::{circuit::Value, dev::MockProver, halo2curves::pasta::Fp};
+
+    fn circuit(proof: &ExclusionProof<Fp>) -> NullifierExclusionCircuit<Fp, 4> {
+        let path_elements = proof.path_elements.clone().try_into().unwrap();
+        NullifierExclusionCircuit::new(
+            Value::known(proof.low_leaf.value),
+            Value::known(proof.low_leaf.next),
+            MerklePathWitness::known(path_elements, Fp::from(proof.leaf_index as u64)),
+        )
+    }
+
+    fn verify(circuit: &NullifierExclusionCircuit<Fp, 4>, nullifier_hash: Fp, root: Fp) -> bool {
+        MockProver::run(10, circuit, vec![vec![nullifier_hash, root]])
+            .unwrap()
+            .verify()
+            .is_ok()
+    }
+
+    #[test]
+    fn test_unspent_nullifier() {
+        let mut set = IndexedNullifierTree::<Fp>::new(4);
+        for value in [0x123 * 0x123, 0x456 * 0x456, 7 * 7] {
+            set.insert(Fp::from(value)).unwrap();
+        }
+
+        for unspent in [Fp::from(0x789 * 0x789), Fp::from(2), -Fp::from(2)] {
+            let proof = set.exclusion_proof(&unspent).unwrap();
+            assert!(verify(&circuit(&proof), unspent, set.root()));
+            // the root is public
+            assert!(!verify(&circuit(&proof), unspent, set.root() + Fp::from(1)));
+        }
+    }
+
+    #[test]
+    fn test_spent_nullifier() {
+        let spent = Fp::from(0x456 * 0x456);
+        let mut set = IndexedNullifierTree::<Fp>::new(4);
+        for value in [0x123 * 0x123, 7 * 7] {
+            set.insert(Fp::from(value)).unwrap();
+        }
+        let proof = set.exclusion_proof(&spent).unwrap();
+        set.insert(spent).unwrap();
+
+        // the low leaf of `spent` before it was inserted is no longer in the tree
+        assert!(!verify(&circuit(&proof), spent, set.root()));
+        // the leaf of `spent` itself brackets it non-strictly
+        let leaf_index = set
+            .leaves()
+            .iter()
+            .position(|leaf| leaf.value == spent)
+            .unwrap();
+        let own_leaf = ExclusionProof {
+            low_leaf: set.leaves()[leaf_index],
+            leaf_index,
+            path_elements: set
+                .exclusion_proof(&(spent + Fp::from(1)))
+                .unwrap()
+                .path_elements,
+        };
+        assert!(!verify(&circuit(&own_leaf), spent, set.root()));
+    }
+
+    #[test]
+    fn test_wrong_low_leaf() {
+        let mut set = IndexedNullifierTree::<Fp>::new(4);
+        for value in [10, 20, 30] {
+            set.insert(Fp::from(value)).unwrap();
+        }
+        let nullifier_hash = Fp::from(25);
+
+        // a leaf of the tree whose range doesn't hold the nullifier hash
+        let other = set.exclusion_proof(&Fp::from(15)).unwrap();
+        assert!(!verify(&circuit(&other), nullifier_hash, set.root()));
+
+        // a range that holds it, but isn't a leaf
+        let mut forged = set.exclusion_proof(&nullifier_hash).unwrap();
+        forged.low_leaf.next = Fp::from(40);
+        assert!(!verify(&circuit(&forged), nullifier_hash, set.root()));
+    }
+}
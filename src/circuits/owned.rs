@@ -0,0 +1,310 @@
+//! Withdrawals authorized by a spending key, for notes that must not be spendable by whoever
+//! learns the note string.
+//!
+//! The owner keeps a spending key `sk` and publishes `pubkey = H(sk, sk)`. An owned deposit
+//! inserts `owned_commitment(commitment, pubkey) = H(commitment, H(pubkey))` instead of the
+//! note commitment, and the withdrawal proves knowledge of `sk` on top of the note's nullifier
+//! and secret. The scheme is hash-based rather than a signature checked by an ECC chip: the
+//! proof is already bound to its public inputs, so knowing the preimage of the key is enough.
+//! The pubkey stays private, so withdrawals of the same owner can't be linked. The core pool
+//! and its circuit are unchanged; a pool of owned notes verifies `OwnedCircuit` instead.
+//!
+//! A pool can also require the recipient to be derived from the pubkey, as an Ethereum
+//! address is from a public key: `BoundRecipientCircuit` checks that the recipient is
+//! `recipient_address(pubkey)` with a Keccak-256 chip, so a note can only pay its owner.
+use crate::{
+    chips::{
+        decompose::{DecomposeChip, DecomposeConfig},
+        hash::HashChip,
+        keccak::{KeccakChip, KeccakConfig},
+        tornado::TornadoChip,
+    },
+    circuits::tornado::{PublicInputs, TornadoCircuit},
+    codec::to_bytes_be,
+    native::hash_value,
+    tree::{Hasher, ProductHasher},
+};
+use halo2_proofs::{
+    circuit::{floor_planner::V1, AssignedCell, Layouter, Value},
+    halo2curves::ff::PrimeField,
+    plonk::{Circuit, ConstraintSystem, Error},
+};
+use sha3::{Digest, Keccak256};
+
+/// `H(spending_key, spending_key)`, the key notes are bound to
+pub fn spending_pubkey<F: PrimeField>(spending_key: F) -> F {
+    hash_value(spending_key)
+}
+
+/// `H(commitment, H(pubkey))`, the leaf of a note commitment bound to `pubkey`
+pub fn owned_commitment<F: PrimeField>(commitment: F, pubkey: F) -> F {
+    ProductHasher::hash(commitment, hash_value(pubkey))
+}
+
+/// The last 20 bytes of the Keccak-256 of the 32 big-endian bytes of `pubkey`, the only
+/// recipient of its notes in `BoundRecipientCircuit`
+pub fn recipient_address<F: PrimeField<Repr = [u8; 32]>>(pubkey: F) -> [u8; 20] {
+    let digest = Keccak256::digest(to_bytes_be(&pubkey));
+    digest[12..].try_into().expect("20 bytes")
+}
+
+/// `TornadoCircuit` over an owned commitment, also proving knowledge of the spending key it is
+/// bound to.
+///
+/// Instance layout: see `PublicInputs`, the pubkey isn't public.
+#[derive(Debug)]
+pub struct OwnedCircuit<F, const DEPTH: usize> {
+    /// Its Merkle path is that of the owned commitment
+    pub withdrawal: TornadoCircuit<F, DEPTH>,
+    pub spending_key: Value<F>,
+}
+
+impl<F: PrimeField, const DEPTH: usize> Circuit<F> for OwnedCircuit<F, DEPTH> {
+    type Config = <TornadoCircuit<F, DEPTH> as Circuit<F>>::Config;
+    type FloorPlanner = V1;
+
+    fn without_witnesses(&self) -> Self {
+        Self {
+            withdrawal: TornadoCircuit::default(),
+            spending_key: Value::unknown(),
+        }
+    }
+
+    fn configure(meta: &mut ConstraintSystem<F>) -> Self::Config {
+        TornadoCircuit::<F, DEPTH>::configure(meta)
+    }
+
+    fn synthesize(
+        &self,
+        config: Self::Config,
+        mut layouter: impl Layouter<F>,
+    ) -> Result<(), Error> {
+        self.synthesize_owned(config, &mut layouter)?;
+        Ok(())
+    }
+}
+
+impl<F: PrimeField, const DEPTH: usize> OwnedCircuit<F, DEPTH> {
+    /// Lays out the withdrawal of the owned commitment, and returns the pubkey
+    fn synthesize_owned(
+        &self,
+        config: <Self as Circuit<F>>::Config,
+        layouter: &mut impl Layouter<F>,
+    ) -> Result<AssignedCell<F, F>, Error> {
+        let advice = config.0.advice;
+        let hash_chip = HashChip::construct(config.0.hash_config);
+
+        let spending_key = layouter.assign_region(
+            || "load spending key",
+            |mut region| {
+                region.assign_advice(|| "spending key", advice[0], 0, || self.spending_key)
+            },
+        )?;
+        let pubkey = hash_chip.hash(
+            layouter.namespace(|| "derive pubkey"),
+            spending_key.clone(),
+            spending_key,
+        )?;
+        let owner = hash_chip.hash(
+            layouter.namespace(|| "hash pubkey"),
+            pubkey.clone(),
+            pubkey.clone(),
+        )?;
+        self.withdrawal
+            .synthesize_withdrawal(config, layouter, Some(owner))?;
+        Ok(pubkey)
+    }
+}
+
+/// `OwnedCircuit`, also proving that the recipient is `recipient_address` of the pubkey. The
+/// Keccak-256 chip makes it a `k = 17` circuit.
+///
+/// The pubkey is hashed from 256 bits that aren't checked to be below the modulus, so the
+/// prover may hash `pubkey + p` instead when it fits. It already holds the spending key, so
+/// this only chooses between addresses derived from its own pubkey.
+///
+/// Instance layout: see `PublicInputs`.
+#[derive(Debug)]
+pub struct BoundRecipientCircuit<F, const DEPTH: usize> {
+    /// Its recipient is `address_to_field(&recipient_address(pubkey))`
+    pub owned: OwnedCircuit<F, DEPTH>,
+}
+
+impl<F: PrimeField, const DEPTH: usize> Circuit<F> for BoundRecipientCircuit<F, DEPTH> {
+    type Config = (
+        <OwnedCircuit<F, DEPTH> as Circuit<F>>::Config,
+        KeccakConfig,
+        DecomposeConfig,
+    );
+    type FloorPlanner = V1;
+
+    fn without_witnesses(&self) -> Self {
+        Self {
+            owned: self.owned.without_witnesses(),
+        }
+    }
+
+    fn configure(meta: &mut ConstraintSystem<F>) -> Self::Config {
+        let owned_config = OwnedCircuit::<F, DEPTH>::configure(meta);
+        let constant = owned_config.0.merkle_config.constant;
+        let advice = [(); 7].map(|_| meta.advice_column());
+        let keccak_config = KeccakChip::configure(meta, advice, constant);
+        let decompose_config =
+            DecomposeChip::configure(meta, [advice[0], advice[1], advice[2]], constant);
+        (owned_config, keccak_config, decompose_config)
+    }
+
+    fn synthesize(
+        &self,
+        (owned_config, keccak_config, decompose_config): Self::Config,
+        mut layouter: impl Layouter<F>,
+    ) -> Result<(), Error> {
+        let tornado_chip = TornadoChip::construct(owned_config.0.clone());
+        let decompose_chip = DecomposeChip::construct(decompose_config);
+        let instance = owned_config.2;
+
+        let pubkey = self.owned.synthesize_owned(owned_config, &mut layouter)?;
+        let pubkey_bits = decompose_chip
+            .decompose_cell::<256>(layouter.namespace(|| "decompose pubkey"), &pubkey)?;
+        // its 32 big-endian bytes, each least significant bit first
+        let message: Vec<_> = (0..256)
+            .map(|i| pubkey_bits[8 * (31 - i / 8) + i % 8].clone())
+            .collect();
+        let digest = KeccakChip::construct(keccak_config)
+            .digest(layouter.namespace(|| "hash pubkey bytes"), &message)?;
+
+        let recipient = tornado_chip.load_public_value(
+            layouter.namespace(|| "load recipient"),
+            self.owned.withdrawal.recipient,
+        )?;
+        layouter.constrain_instance(recipient.cell(), instance, PublicInputs::RECIPIENT)?;
+        let recipient_bits = decompose_chip
+            .decompose_cell::<160>(layouter.namespace(|| "decompose recipient"), &recipient)?;
+        layouter.assign_region(
+            || "bind recipient",
+            |mut region| {
+                // as for the pubkey, byte `31 - i / 8` of the digest holds bit `i` of the
+                // recipient's 32 big-endian bytes
+                for (i, bit) in recipient_bits.iter().enumerate() {
+                    let digest_bit = &digest[8 * (31 - i / 8) + i % 8];
+                    region.constrain_equal(bit.cell(), digest_bit.cell())?;
+                }
+                Ok(())
+            },
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        owned_commitment, recipient_address, spending_pubkey, BoundRecipientCircuit, OwnedCircuit,
+    };
+    use crate::{
+        circuits::tornado::{TornadoCircuit, TornadoPublicInputs},
+        ext_data::address_to_field,
+        note::Note,
+        tree::IncrementalMerkleTree,
+    };
+    use halo2_proofs::{circuit::Value, dev::MockProver, halo2curves::pasta::Fp, plonk::Circuit};
+
+    /// A withdrawal of `note` bound to the pubkey of `spending_key` to `recipient`, from a pool
+    /// holding `[3, 5, leaf, 7]`
+    fn withdrawal(
+        note: &Note<Fp>,
+        spending_key: Fp,
+        recipient: Fp,
+    ) -> (TornadoCircuit<Fp, 5>, TornadoPublicInputs<Fp>) {
+        let leaf = owned_commitment(note.commitment(), spending_pubkey(spending_key));
+        let mut pool = IncrementalMerkleTree::new(5);
+        pool.insert_batch(&[Fp::from(3), Fp::from(5)]).unwrap();
+        let index = pool.insert(leaf).unwrap();
+        pool.insert(Fp::from(7)).unwrap();
+        let circuit = TornadoCircuit::builder(note, pool.proof(index).unwrap().into())
+            .recipient(recipient)
+            .build()
+            .unwrap();
+        let public_inputs = TornadoPublicInputs {
+            nullifier_hash: note.nullifier_hash(),
+            root: pool.root(),
+            ext_data_hash: Fp::from(0),
+            recipient,
+            relayer: Fp::from(0),
+            fee: Fp::from(0),
+            refund: Fp::from(0),
+            amount: Fp::from(note.amount()),
+            asset_id: note.asset_id(),
+        };
+        (circuit, public_inputs)
+    }
+
+    fn verify<C: Circuit<Fp>>(circuit: &C, public_inputs: TornadoPublicInputs<Fp>) -> bool {
+        verify_k(10, circuit, public_inputs)
+    }
+
+    fn verify_k<C: Circuit<Fp>>(
+        k: u32,
+        circuit: &C,
+        public_inputs: TornadoPublicInputs<Fp>,
+    ) -> bool {
+        MockProver::run(k, circuit, public_inputs.to_instances())
+            .unwrap()
+            .verify()
+            .is_ok()
+    }
+
+    #[test]
+    fn test_owned_withdrawal() {
+        let note = Note::new(Fp::from(0x456), Fp::from(0xabc), "eth", "0.1", 1).unwrap();
+        let spending_key = Fp::from(0x5e);
+        let (withdrawal, public_inputs) = withdrawal(&note, spending_key, Fp::from(0x11));
+        let circuit = OwnedCircuit {
+            withdrawal,
+            spending_key: Value::known(spending_key),
+        };
+        assert!(verify(&circuit, public_inputs));
+
+        // the public inputs are the withdrawal's
+        let mut other_recipient = public_inputs;
+        other_recipient.recipient = Fp::from(0x22);
+        assert!(!verify(&circuit, other_recipient));
+    }
+
+    #[test]
+    fn test_note_string_is_not_enough() {
+        let note = Note::new(Fp::from(0x456), Fp::from(0xabc), "eth", "0.1", 1).unwrap();
+        let (withdrawal, public_inputs) = withdrawal(&note, Fp::from(0x5e), Fp::from(0x11));
+
+        // the plain withdrawal circuit proves the note commitment, not the owned leaf
+        assert!(!verify(&withdrawal, public_inputs));
+        // and the owned one needs the spending key
+        let circuit = OwnedCircuit {
+            withdrawal,
+            spending_key: Value::known(Fp::from(0x5f)),
+        };
+        assert!(!verify(&circuit, public_inputs));
+    }
+
+    #[test]
+    fn test_bound_recipient() {
+        let note = Note::new(Fp::from(0x456), Fp::from(0xabc), "eth", "0.1", 1).unwrap();
+        let spending_key = Fp::from(0x5e);
+        let address = recipient_address(spending_pubkey(spending_key));
+        let circuit = |recipient| {
+            let (withdrawal, public_inputs) = withdrawal(&note, spending_key, recipient);
+            let owned = OwnedCircuit {
+                withdrawal,
+                spending_key: Value::known(spending_key),
+            };
+            (BoundRecipientCircuit { owned }, public_inputs)
+        };
+
+        let (bound, public_inputs) = circuit(address_to_field(&address));
+        assert!(verify_k(17, &bound, public_inputs));
+
+        // any other recipient, even with a valid owned withdrawal
+        let (other, public_inputs) = circuit(Fp::from(0x11));
+        assert!(verify(&other.owned, public_inputs));
+        assert!(!verify_k(17, &other, public_inputs));
+    }
+}
@@ -0,0 +1,82 @@
+use crate::chips::range_check::{RangeCheckChip, RangeCheckConfig};
+use halo2_proofs::{
+    circuit::{Layouter, SimpleFloorPlanner, Value},
+    halo2curves::ff::PrimeField,
+    plonk::{Circuit, ConstraintSystem, Error},
+};
+
+/// Proves knowledge of a value below `2^NUM_BITS`, with `LOOKUP_BITS`-bit chunks
+#[derive(Debug)]
+pub struct RangeCheckCircuit<F, const NUM_BITS: usize, const LOOKUP_BITS: usize> {
+    pub value: Value<F>,
+}
+
+impl<F: PrimeField, const NUM_BITS: usize, const LOOKUP_BITS: usize> Circuit<F>
+    for RangeCheckCircuit<F, NUM_BITS, LOOKUP_BITS>
+{
+    type Config = RangeCheckConfig;
+    type FloorPlanner = SimpleFloorPlanner;
+
+    fn without_witnesses(&self) -> Self {
+        Self {
+            value: Value::unknown(),
+        }
+    }
+
+    fn configure(meta: &mut ConstraintSystem<F>) -> Self::Config {
+        let advice = [
+            meta.advice_column(),
+            meta.advice_column(),
+            meta.advice_column(),
+        ];
+        let constant = meta.fixed_column();
+        RangeCheckChip::configure(meta, advice, constant, LOOKUP_BITS)
+    }
+
+    fn synthesize(
+        &self,
+        config: Self::Config,
+        mut layouter: impl Layouter<F>,
+    ) -> Result<(), Error> {
+        let value_cell = layouter.assign_region(
+            || "assign value",
+            |mut region| region.assign_advice(|| "value", config.advice[0], 0, || self.value),
+        )?;
+
+        let chip = RangeCheckChip::construct(config);
+        chip.load_table(layouter.namespace(|| "range check table"))?;
+        chip.range_check(layouter.namespace(|| "range check"), &value_cell, NUM_BITS)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::RangeCheckCircuit;
+    use halo2_proofs::{circuit::Value, dev::MockProver, halo2curves::pasta::Fp};
+
+    fn verify<const NUM_BITS: usize>(value: Fp) -> bool {
+        let circuit = RangeCheckCircuit::<Fp, NUM_BITS, 8> {
+            value: Value::known(value),
+        };
+        let prover = MockProver::run(10, &circuit, vec![]).unwrap();
+        prover.verify().is_ok()
+    }
+
+    #[test]
+    fn test_range_check_circuit() {
+        // a single chunk
+        assert!(verify::<8>(Fp::from(255)));
+        assert!(!verify::<8>(Fp::from(256)));
+
+        // several full chunks
+        assert!(verify::<24>(Fp::from((1 << 24) - 1)));
+        assert!(!verify::<24>(Fp::from(1 << 24)));
+
+        // a short last chunk
+        assert!(verify::<20>(Fp::from((1 << 20) - 1)));
+        assert!(!verify::<20>(Fp::from(1 << 20)));
+
+        assert!(verify::<0>(Fp::from(0)));
+        assert!(!verify::<64>(-Fp::from(1)));
+    }
+}
@@ -0,0 +1,326 @@
+//! Rate-limiting nullifiers (RLN): anonymous signaling, at most once per epoch.
+//!
+//! A member registers `identity_commitment(a_0)` for an identity secret `a_0` in a membership
+//! tree. To signal in an epoch with external nullifier `e` (e.g. a hash of the epoch and the
+//! application), the member proves membership and reveals a point of the line
+//! `y = a_0 + a_1 * x`, with `a_1 = H(a_0, e)` and `x` the hash of the signal. The nullifier
+//! `H(a_1, a_1)` is the same for every signal of the epoch, so the application spots a second
+//! one, and its two points are a Shamir share of `a_0` each: `recover_secret` opens the line
+//! and reveals the secret, which lets anyone slash the spammer's registration. Signals of
+//! different epochs are on unrelated lines and stay unlinkable.
+use crate::{
+    chips::{
+        hash::HashChip,
+        merkle::MerkleChip,
+        tornado::{TornadoChip, TornadoConfig},
+    },
+    circuits::merkle::MerklePathWitness,
+    native::hash_value,
+    tree::{Hasher, ProductHasher},
+};
+use halo2_proofs::{
+    circuit::{Layouter, SimpleFloorPlanner, Value},
+    halo2curves::ff::PrimeField,
+    plonk::{Circuit, Column, ConstraintSystem, Error, Instance, Selector},
+    poly::Rotation,
+};
+
+const ROOT: usize = 0;
+const EXTERNAL_NULLIFIER: usize = 1;
+const X: usize = 2;
+const Y: usize = 3;
+const NULLIFIER: usize = 4;
+
+/// `H(a_0, a_0)`, the leaf of the member with identity secret `a_0`
+pub fn identity_commitment<F: PrimeField>(identity_secret: F) -> F {
+    hash_value(identity_secret)
+}
+
+/// The share `(y, nullifier)` of a signal hashing to `x` in the epoch of `external_nullifier`
+pub fn share<F: PrimeField>(identity_secret: F, external_nullifier: F, x: F) -> (F, F) {
+    let slope = ProductHasher::hash(identity_secret, external_nullifier);
+    (identity_secret + slope * x, hash_value(slope))
+}
+
+/// The identity secret behind two shares `(x, y)` of one epoch, i.e. with the same nullifier.
+/// Returns `None` for the same `x` twice, e.g. a signal sent twice.
+pub fn recover_secret<F: PrimeField>((x_1, y_1): (F, F), (x_2, y_2): (F, F)) -> Option<F> {
+    let slope = (y_1 - y_2) * Option::<F>::from((x_1 - x_2).invert())?;
+    Some(y_1 - slope * x_1)
+}
+
+/// The public inputs of `RlnCircuit`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RlnPublicInputs<F> {
+    /// Root of the membership tree
+    pub root: F,
+    pub external_nullifier: F,
+    /// Hash of the signal
+    pub x: F,
+    pub y: F,
+    pub nullifier: F,
+}
+
+impl<F: PrimeField> RlnPublicInputs<F> {
+    /// The public inputs of the signal `x` of the member with `identity_secret`
+    pub fn new(identity_secret: F, root: F, external_nullifier: F, x: F) -> Self {
+        let (y, nullifier) = share(identity_secret, external_nullifier, x);
+        Self {
+            root,
+            external_nullifier,
+            x,
+            y,
+            nullifier,
+        }
+    }
+
+    /// Row 0 = root, row 1 = external nullifier, row 2 = x, row 3 = y, row 4 = nullifier
+    pub fn to_instances(&self) -> Vec<Vec<F>> {
+        vec![vec![
+            self.root,
+            self.external_nullifier,
+            self.x,
+            self.y,
+            self.nullifier,
+        ]]
+    }
+
+    /// Returns `None` unless `instances` is a single column of 5 values
+    pub fn from_instances(instances: &[Vec<F>]) -> Option<Self> {
+        let [column] = instances else {
+            return None;
+        };
+        let [root, external_nullifier, x, y, nullifier] = column.as_slice() else {
+            return None;
+        };
+        Some(Self {
+            root: *root,
+            external_nullifier: *external_nullifier,
+            x: *x,
+            y: *y,
+            nullifier: *nullifier,
+        })
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct RlnConfig {
+    pub(crate) tornado_config: TornadoConfig,
+    /// `advice[0] + advice[1] * advice[2]` on the next row's `advice[0]`
+    pub(crate) share_selector: Selector,
+    pub instance: Column<Instance>,
+}
+
+/// Proves that a member of the tree signals `x` with the share `y` and the nullifier of its
+/// identity secret in the epoch. The external nullifier and `x` are read from the instance
+/// column, so only the member's secret and path are witnesses.
+///
+/// Instance layout: see `RlnPublicInputs::to_instances`.
+#[derive(Debug, Clone, Copy)]
+pub struct RlnCircuit<F, const DEPTH: usize> {
+    pub identity_secret: Value<F>,
+    /// Path of the identity commitment in the tree
+    pub path: MerklePathWitness<F, DEPTH>,
+}
+
+impl<F: PrimeField, const DEPTH: usize> RlnCircuit<F, DEPTH> {
+    pub fn new(identity_secret: Value<F>, path: MerklePathWitness<F, DEPTH>) -> Self {
+        Self {
+            identity_secret,
+            path,
+        }
+    }
+}
+
+impl<F: PrimeField, const DEPTH: usize> Circuit<F> for RlnCircuit<F, DEPTH> {
+    type Config = RlnConfig;
+    type FloorPlanner = SimpleFloorPlanner;
+
+    fn without_witnesses(&self) -> Self {
+        Self {
+            identity_secret: Value::unknown(),
+            path: MerklePathWitness::unknown(),
+        }
+    }
+
+    fn configure(meta: &mut ConstraintSystem<F>) -> Self::Config {
+        let advice = [
+            meta.advice_column(),
+            meta.advice_column(),
+            meta.advice_column(),
+        ];
+        let instance = meta.instance_column();
+        meta.enable_equality(instance);
+        let tornado_config = TornadoChip::configure(meta, advice);
+
+        let share_selector = meta.selector();
+        meta.create_gate("share", |meta| {
+            let s = meta.query_selector(share_selector);
+            let secret = meta.query_advice(advice[0], Rotation::cur());
+            let slope = meta.query_advice(advice[1], Rotation::cur());
+            let x = meta.query_advice(advice[2], Rotation::cur());
+            let y = meta.query_advice(advice[0], Rotation::next());
+            vec![s * (secret + slope * x - y)]
+        });
+
+        RlnConfig {
+            tornado_config,
+            share_selector,
+            instance,
+        }
+    }
+
+    fn synthesize(
+        &self,
+        config: Self::Config,
+        mut layouter: impl Layouter<F>,
+    ) -> Result<(), Error> {
+        let hash_chip = HashChip::construct(config.tornado_config.hash_config);
+        let merkle_chip = MerkleChip::construct(config.tornado_config.merkle_config.clone());
+        let advice = config.tornado_config.advice;
+        let instance = config.instance;
+
+        let (secret, external_nullifier) = layouter.assign_region(
+            || "load secret and external nullifier",
+            |mut region| {
+                let secret = region.assign_advice(
+                    || "identity secret",
+                    advice[0],
+                    0,
+                    || self.identity_secret,
+                )?;
+                let external_nullifier = region.assign_advice_from_instance(
+                    || "external nullifier",
+                    instance,
+                    EXTERNAL_NULLIFIER,
+                    advice[1],
+                    0,
+                )?;
+                Ok((secret, external_nullifier))
+            },
+        )?;
+
+        let commitment = hash_chip.hash(
+            layouter.namespace(|| "identity commitment"),
+            secret.clone(),
+            secret.clone(),
+        )?;
+        let root = merkle_chip.prove_tree_root(
+            layouter.namespace(|| "prove membership"),
+            commitment,
+            self.path.path_elements,
+            self.path.leaf_index,
+        )?;
+        layouter.constrain_instance(root.cell(), instance, ROOT)?;
+
+        let slope = hash_chip.hash(
+            layouter.namespace(|| "epoch slope"),
+            secret.clone(),
+            external_nullifier,
+        )?;
+        let nullifier = hash_chip.hash(
+            layouter.namespace(|| "nullifier"),
+            slope.clone(),
+            slope.clone(),
+        )?;
+        layouter.constrain_instance(nullifier.cell(), instance, NULLIFIER)?;
+
+        let y = layouter.assign_region(
+            || "share",
+            |mut region| {
+                config.share_selector.enable(&mut region, 0)?;
+                secret.copy_advice(|| "identity secret", &mut region, advice[0], 0)?;
+                slope.copy_advice(|| "slope", &mut region, advice[1], 0)?;
+                let x = region.assign_advice_from_instance(|| "x", instance, X, advice[2], 0)?;
+                region.assign_advice(
+                    || "y",
+                    advice[0],
+                    1,
+                    || secret.value().cloned() + slope.value().cloned() * x.value().cloned(),
+                )
+            },
+        )?;
+        layouter.constrain_instance(y.cell(), instance, Y)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{identity_commitment, recover_secret, share, RlnCircuit, RlnPublicInputs};
+    use crate::{circuits::merkle::MerklePathWitness, tree::IncrementalMerkleTree};
+    use halo2_proofs::{circuit::Value, dev::MockProver, halo2curves::pasta::Fp};
+
+    /// A membership tree holding `[3, member, 5]`, and the circuit of the member's signals
+    fn member(identity_secret: Fp) -> (RlnCircuit<Fp, 5>, Fp) {
+        let mut tree = IncrementalMerkleTree::new(5);
+        tree.insert(Fp::from(3)).unwrap();
+        let index = tree.insert(identity_commitment(identity_secret)).unwrap();
+        tree.insert(Fp::from(5)).unwrap();
+        let circuit = RlnCircuit::new(
+            Value::known(identity_secret),
+            MerklePathWitness::from_tree(&tree, index).unwrap(),
+        );
+        (circuit, tree.root())
+    }
+
+    fn verify(circuit: &RlnCircuit<Fp, 5>, public_inputs: RlnPublicInputs<Fp>) -> bool {
+        MockProver::run(10, circuit, public_inputs.to_instances())
+            .unwrap()
+            .verify()
+            .is_ok()
+    }
+
+    #[test]
+    fn test_signal() {
+        let secret = Fp::from(0x123);
+        let (circuit, root) = member(secret);
+        let public_inputs = RlnPublicInputs::new(secret, root, Fp::from(7), Fp::from(0xabc));
+        assert_eq!(
+            RlnPublicInputs::from_instances(&public_inputs.to_instances()),
+            Some(public_inputs)
+        );
+        assert!(verify(&circuit, public_inputs));
+
+        // the share is of the signal and the nullifier of the epoch
+        let mut other_y = public_inputs;
+        other_y.y = share(secret, Fp::from(7), Fp::from(0xdef)).0;
+        assert!(!verify(&circuit, other_y));
+        let mut other_nullifier = public_inputs;
+        other_nullifier.nullifier = share(secret, Fp::from(8), Fp::from(0xabc)).1;
+        assert!(!verify(&circuit, other_nullifier));
+        let mut other_root = public_inputs;
+        other_root.root += Fp::from(1);
+        assert!(!verify(&circuit, other_root));
+    }
+
+    #[test]
+    fn test_non_member() {
+        let (_, root) = member(Fp::from(0x123));
+        let (circuit, _) = member(Fp::from(0x456));
+        let public_inputs = RlnPublicInputs::new(Fp::from(0x456), root, Fp::from(7), Fp::from(1));
+        assert!(!verify(&circuit, public_inputs));
+    }
+
+    #[test]
+    fn test_double_signal() {
+        let secret = Fp::from(0x123);
+        let (circuit, root) = member(secret);
+        let first = RlnPublicInputs::new(secret, root, Fp::from(7), Fp::from(0xabc));
+        let second = RlnPublicInputs::new(secret, root, Fp::from(7), Fp::from(0xdef));
+        assert!(verify(&circuit, first));
+        assert!(verify(&circuit, second));
+
+        // the two signals of the epoch share a nullifier, and their shares open the secret
+        assert_eq!(first.nullifier, second.nullifier);
+        assert_eq!(
+            recover_secret((first.x, first.y), (second.x, second.y)),
+            Some(secret)
+        );
+        assert_eq!(recover_secret((first.x, first.y), (first.x, first.y)), None);
+
+        // a signal of the next epoch has another nullifier
+        let next = RlnPublicInputs::new(secret, root, Fp::from(8), Fp::from(0xdef));
+        assert!(verify(&circuit, next));
+        assert_ne!(first.nullifier, next.nullifier);
+    }
+}
@@ -0,0 +1,331 @@
+//! Semaphore-style signaling: a member of a group proves membership and broadcasts a signal,
+//! at most once per external nullifier, without revealing which member it is.
+//!
+//! The statement and the public inputs are those of Semaphore v3: the identity commitment
+//! `H(H(nullifier, trapdoor))` is a leaf of the group's tree, the nullifier hash is
+//! `H(external_nullifier, nullifier)`, and the signal hash is only bound to the proof. The
+//! signal and the external nullifier are hashed to the field as Semaphore's `hash`, see
+//! `hash_to_field`. The identity hashes go through the crate's hash, not Semaphore's Poseidon,
+//! so a group and its proofs live on this crate's verifier rather than Semaphore's contracts.
+use crate::{
+    chips::{
+        hash::HashChip,
+        merkle::MerkleChip,
+        tornado::{TornadoChip, TornadoConfig},
+    },
+    circuits::merkle::MerklePathWitness,
+    codec::from_bytes_be,
+    native::hash_value,
+    tree::{Hasher, ProductHasher},
+};
+use halo2_proofs::{
+    circuit::{Layouter, SimpleFloorPlanner, Value},
+    halo2curves::ff::PrimeField,
+    plonk::{Circuit, Column, ConstraintSystem, Error, Instance},
+};
+use sha3::{Digest, Keccak256};
+
+const ROOT: usize = 0;
+const NULLIFIER_HASH: usize = 1;
+const SIGNAL_HASH: usize = 2;
+const EXTERNAL_NULLIFIER: usize = 3;
+
+/// `keccak256(message) >> 8`, Semaphore's hash of a signal or of an external nullifier's scope.
+/// The shift keeps the value below any 254-bit modulus.
+pub fn hash_to_field<F: PrimeField<Repr = [u8; 32]>>(message: &[u8]) -> F {
+    let digest = Keccak256::digest(message);
+    let mut bytes = [0; 32];
+    bytes[1..].copy_from_slice(&digest[..31]);
+    from_bytes_be(&bytes).expect("248 bits are below the modulus")
+}
+
+/// A member's secrets
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Identity<F> {
+    pub nullifier: F,
+    pub trapdoor: F,
+}
+
+impl<F: PrimeField> Identity<F> {
+    /// `H(H(nullifier, trapdoor))`, the member's leaf in the group
+    pub fn commitment(&self) -> F {
+        hash_value(ProductHasher::hash(self.nullifier, self.trapdoor))
+    }
+
+    /// `H(external_nullifier, nullifier)`, the same for every signal with `external_nullifier`
+    pub fn nullifier_hash(&self, external_nullifier: F) -> F {
+        ProductHasher::hash(external_nullifier, self.nullifier)
+    }
+}
+
+/// The public inputs of `SemaphoreCircuit`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SemaphorePublicInputs<F> {
+    /// Root of the group's tree
+    pub root: F,
+    pub nullifier_hash: F,
+    pub signal_hash: F,
+    pub external_nullifier: F,
+}
+
+impl<F: PrimeField> SemaphorePublicInputs<F> {
+    /// Row 0 = root, row 1 = nullifier hash, row 2 = signal hash, row 3 = external nullifier,
+    /// in the order of Semaphore's verifier
+    pub fn to_instances(&self) -> Vec<Vec<F>> {
+        vec![vec![
+            self.root,
+            self.nullifier_hash,
+            self.signal_hash,
+            self.external_nullifier,
+        ]]
+    }
+
+    /// Returns `None` unless `instances` is a single column of 4 values
+    pub fn from_instances(instances: &[Vec<F>]) -> Option<Self> {
+        let [column] = instances else {
+            return None;
+        };
+        let [root, nullifier_hash, signal_hash, external_nullifier] = column.as_slice() else {
+            return None;
+        };
+        Some(Self {
+            root: *root,
+            nullifier_hash: *nullifier_hash,
+            signal_hash: *signal_hash,
+            external_nullifier: *external_nullifier,
+        })
+    }
+}
+
+/// Proves that the identity commitment of a member is in the group's tree, and that the
+/// nullifier hash is the member's for the external nullifier. The signal hash is squared so
+/// the proof is bound to it.
+///
+/// Instance layout: see `SemaphorePublicInputs::to_instances`.
+#[derive(Debug, Clone, Copy)]
+pub struct SemaphoreCircuit<F, const DEPTH: usize> {
+    pub identity_nullifier: Value<F>,
+    pub identity_trapdoor: Value<F>,
+    /// Path of the identity commitment in the tree
+    pub path: MerklePathWitness<F, DEPTH>,
+    pub signal_hash: Value<F>,
+    pub external_nullifier: Value<F>,
+}
+
+impl<F: PrimeField, const DEPTH: usize> SemaphoreCircuit<F, DEPTH> {
+    pub fn new(
+        identity: &Identity<F>,
+        path: MerklePathWitness<F, DEPTH>,
+        signal_hash: Value<F>,
+        external_nullifier: Value<F>,
+    ) -> Self {
+        Self {
+            identity_nullifier: Value::known(identity.nullifier),
+            identity_trapdoor: Value::known(identity.trapdoor),
+            path,
+            signal_hash,
+            external_nullifier,
+        }
+    }
+}
+
+impl<F: PrimeField, const DEPTH: usize> Circuit<F> for SemaphoreCircuit<F, DEPTH> {
+    type Config = (TornadoConfig, Column<Instance>);
+    type FloorPlanner = SimpleFloorPlanner;
+
+    fn without_witnesses(&self) -> Self {
+        Self {
+            identity_nullifier: Value::unknown(),
+            identity_trapdoor: Value::unknown(),
+            path: MerklePathWitness::unknown(),
+            signal_hash: Value::unknown(),
+            external_nullifier: Value::unknown(),
+        }
+    }
+
+    fn configure(meta: &mut ConstraintSystem<F>) -> Self::Config {
+        let advice = [
+            meta.advice_column(),
+            meta.advice_column(),
+            meta.advice_column(),
+        ];
+        let instance = meta.instance_column();
+        meta.enable_equality(instance);
+        (TornadoChip::configure(meta, advice), instance)
+    }
+
+    fn synthesize(
+        &self,
+        (config, instance): Self::Config,
+        mut layouter: impl Layouter<F>,
+    ) -> Result<(), Error> {
+        let tornado_chip = TornadoChip::construct(config.clone());
+        let hash_chip = HashChip::construct(config.hash_config);
+        let merkle_chip = MerkleChip::construct(config.merkle_config.clone());
+
+        let [nullifier, trapdoor, external_nullifier] = layouter.assign_region(
+            || "load identity and external nullifier",
+            |mut region| {
+                let advice = config.advice;
+                Ok([
+                    region.assign_advice(
+                        || "nullifier",
+                        advice[0],
+                        0,
+                        || self.identity_nullifier,
+                    )?,
+                    region.assign_advice(|| "trapdoor", advice[1], 0, || self.identity_trapdoor)?,
+                    region.assign_advice(
+                        || "external nullifier",
+                        advice[2],
+                        0,
+                        || self.external_nullifier,
+                    )?,
+                ])
+            },
+        )?;
+        layouter.constrain_instance(external_nullifier.cell(), instance, EXTERNAL_NULLIFIER)?;
+
+        let secret = hash_chip.hash(
+            layouter.namespace(|| "identity secret"),
+            nullifier.clone(),
+            trapdoor,
+        )?;
+        let commitment = hash_chip.hash(
+            layouter.namespace(|| "identity commitment"),
+            secret.clone(),
+            secret,
+        )?;
+        let root = merkle_chip.prove_tree_root(
+            layouter.namespace(|| "prove membership"),
+            commitment,
+            self.path.path_elements,
+            self.path.leaf_index,
+        )?;
+        layouter.constrain_instance(root.cell(), instance, ROOT)?;
+
+        let nullifier_hash = hash_chip.hash(
+            layouter.namespace(|| "nullifier hash"),
+            external_nullifier,
+            nullifier,
+        )?;
+        layouter.constrain_instance(nullifier_hash.cell(), instance, NULLIFIER_HASH)?;
+
+        let signal_hash = tornado_chip
+            .load_public_value(layouter.namespace(|| "load signal hash"), self.signal_hash)?;
+        layouter.constrain_instance(signal_hash.cell(), instance, SIGNAL_HASH)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{hash_to_field, Identity, SemaphoreCircuit, SemaphorePublicInputs};
+    use crate::{
+        circuits::merkle::MerklePathWitness, codec::from_hex, tree::IncrementalMerkleTree,
+    };
+    use halo2_proofs::{
+        circuit::Value,
+        dev::MockProver,
+        halo2curves::{bn256::Fr, pasta::Fp},
+    };
+
+    fn identity() -> Identity<Fp> {
+        Identity {
+            nullifier: Fp::from(0x123),
+            trapdoor: Fp::from(0x456),
+        }
+    }
+
+    /// A signal of `identity` in a group holding `[3, member, 5]`
+    fn signal(
+        identity: &Identity<Fp>,
+        signal_hash: Fp,
+        external_nullifier: Fp,
+    ) -> (SemaphoreCircuit<Fp, 5>, SemaphorePublicInputs<Fp>) {
+        let mut group = IncrementalMerkleTree::new(5);
+        group.insert(Fp::from(3)).unwrap();
+        let index = group.insert(identity.commitment()).unwrap();
+        group.insert(Fp::from(5)).unwrap();
+        let circuit = SemaphoreCircuit::new(
+            identity,
+            MerklePathWitness::from_tree(&group, index).unwrap(),
+            Value::known(signal_hash),
+            Value::known(external_nullifier),
+        );
+        let public_inputs = SemaphorePublicInputs {
+            root: group.root(),
+            nullifier_hash: identity.nullifier_hash(external_nullifier),
+            signal_hash,
+            external_nullifier,
+        };
+        (circuit, public_inputs)
+    }
+
+    fn verify(circuit: &SemaphoreCircuit<Fp, 5>, public_inputs: SemaphorePublicInputs<Fp>) -> bool {
+        MockProver::run(10, circuit, public_inputs.to_instances())
+            .unwrap()
+            .verify()
+            .is_ok()
+    }
+
+    #[test]
+    fn test_hash_to_field() {
+        // `hash` of @semaphore-protocol/utils, i.e. `keccak256(message) >> 8`
+        let expected: Fr =
+            from_hex("0x00c5d2460186f7233c927e7db2dcc703c0e500b653ca82273b7bfad8045d85a4").unwrap();
+        assert_eq!(hash_to_field::<Fr>(b""), expected);
+        let expected: Fr =
+            from_hex("0x004e03657aea45a94fc7d47ba826c8d667c0d1e6e33a64a036ec44f58fa12d6c").unwrap();
+        assert_eq!(hash_to_field::<Fr>(b"abc"), expected);
+    }
+
+    #[test]
+    fn test_signal() {
+        let (circuit, public_inputs) = signal(&identity(), Fp::from(0xabc), Fp::from(7));
+        assert_eq!(
+            SemaphorePublicInputs::from_instances(&public_inputs.to_instances()),
+            Some(public_inputs)
+        );
+        assert!(verify(&circuit, public_inputs));
+
+        let mut other_signal = public_inputs;
+        other_signal.signal_hash = Fp::from(0xdef);
+        assert!(!verify(&circuit, other_signal));
+        let mut other_external_nullifier = public_inputs;
+        other_external_nullifier.external_nullifier = Fp::from(8);
+        assert!(!verify(&circuit, other_external_nullifier));
+        let mut other_root = public_inputs;
+        other_root.root += Fp::from(1);
+        assert!(!verify(&circuit, other_root));
+    }
+
+    #[test]
+    fn test_nullifier_hash() {
+        // the nullifier hash is the identity's for the external nullifier
+        let (circuit, public_inputs) = signal(&identity(), Fp::from(0xabc), Fp::from(7));
+        let other = Identity {
+            nullifier: Fp::from(0x789),
+            ..identity()
+        };
+        let mut other_nullifier_hash = public_inputs;
+        other_nullifier_hash.nullifier_hash = other.nullifier_hash(Fp::from(7));
+        assert!(!verify(&circuit, other_nullifier_hash));
+
+        // signals sharing an external nullifier share a nullifier hash, others don't
+        let (_, same_scope) = signal(&identity(), Fp::from(0xdef), Fp::from(7));
+        let (_, other_scope) = signal(&identity(), Fp::from(0xabc), Fp::from(8));
+        assert_eq!(public_inputs.nullifier_hash, same_scope.nullifier_hash);
+        assert_ne!(public_inputs.nullifier_hash, other_scope.nullifier_hash);
+    }
+
+    #[test]
+    fn test_non_member() {
+        let (_, public_inputs) = signal(&identity(), Fp::from(0xabc), Fp::from(7));
+        let outsider = Identity {
+            trapdoor: Fp::from(0x789),
+            ..identity()
+        };
+        let (circuit, _) = signal(&outsider, Fp::from(0xabc), Fp::from(7));
+        assert!(!verify(&circuit, public_inputs));
+    }
+}
@@ -0,0 +1,226 @@
+//! Withdrawals of notes that can't be withdrawn before a time chosen on deposit.
+//!
+//! A timelocked deposit inserts `timelocked_commitment(commitment, unlock_time) =
+//! H(commitment, H(unlock_time))` instead of the note commitment. The withdrawal proves
+//! `unlock_time < time` with `LessThanChip`, where `time` is a public input the pool checks
+//! against the chain, e.g. `require(time <= block.timestamp)`. The unlock time stays private,
+//! so deposits with the same lock can't be matched with their withdrawals. A pool counting in
+//! blocks passes `block.number` as `time` and notes lock until a block instead. A note without
+//! a lock is given a past unlock time.
+use crate::{
+    chips::{
+        hash::HashChip,
+        less_than::{LessThanChip, LessThanConfig},
+        tornado::TornadoChip,
+    },
+    circuits::tornado::{PublicInputs, TornadoCircuit, TornadoPublicInputs},
+    native::hash_value,
+    tree::{Hasher, ProductHasher},
+};
+use halo2_proofs::{
+    circuit::{floor_planner::V1, Layouter, Value},
+    halo2curves::ff::PrimeField,
+    plonk::{Circuit, ConstraintSystem, Error},
+};
+
+/// Row of the withdrawal time, after the rows of `PublicInputs`
+pub const TIME: usize = PublicInputs::LEN;
+
+/// `H(commitment, H(unlock_time))`, the leaf of a note commitment locked until `unlock_time`
+pub fn timelocked_commitment<F: PrimeField>(commitment: F, unlock_time: F) -> F {
+    ProductHasher::hash(commitment, hash_value(unlock_time))
+}
+
+/// The public inputs of `TimelockCircuit`: the withdrawal's, and the time it happens at
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TimelockPublicInputs<F> {
+    pub withdrawal: TornadoPublicInputs<F>,
+    pub time: F,
+}
+
+impl<F: PrimeField> TimelockPublicInputs<F> {
+    /// The rows of `PublicInputs`, then the time
+    pub fn to_instances(&self) -> Vec<Vec<F>> {
+        let mut instances = self.withdrawal.to_instances();
+        instances[0].push(self.time);
+        instances
+    }
+
+    /// Returns `None` unless `instances` is a single column of `PublicInputs::LEN + 1` values
+    pub fn from_instances(instances: &[Vec<F>]) -> Option<Self> {
+        let [column] = instances else {
+            return None;
+        };
+        let (time, withdrawal) = column.split_last()?;
+        Some(Self {
+            withdrawal: TornadoPublicInputs::from_instances(&[withdrawal.to_vec()])?,
+            time: *time,
+        })
+    }
+}
+
+/// `TornadoCircuit` over a timelocked commitment, also proving that its unlock time is before
+/// the withdrawal's time. Times are compared as integers below the modulus.
+///
+/// Instance layout: the rows of `PublicInputs`, then row `TIME` = the withdrawal's time.
+#[derive(Debug)]
+pub struct TimelockCircuit<F, const DEPTH: usize> {
+    /// Its Merkle path is that of the timelocked commitment
+    pub withdrawal: TornadoCircuit<F, DEPTH>,
+    pub unlock_time: Value<F>,
+    pub time: Value<F>,
+}
+
+impl<F: PrimeField, const DEPTH: usize> Circuit<F> for TimelockCircuit<F, DEPTH> {
+    type Config = (
+        <TornadoCircuit<F, DEPTH> as Circuit<F>>::Config,
+        LessThanConfig,
+    );
+    type FloorPlanner = V1;
+
+    fn without_witnesses(&self) -> Self {
+        Self {
+            withdrawal: TornadoCircuit::default(),
+            unlock_time: Value::unknown(),
+            time: Value::unknown(),
+        }
+    }
+
+    fn configure(meta: &mut ConstraintSystem<F>) -> Self::Config {
+        let withdrawal_config = TornadoCircuit::<F, DEPTH>::configure(meta);
+        // the withdrawal loads the range check table, the comparison shares it
+        let less_than_config =
+            LessThanChip::configure(meta, withdrawal_config.0.advice, withdrawal_config.1);
+        (withdrawal_config, less_than_config)
+    }
+
+    fn synthesize(
+        &self,
+        (withdrawal_config, less_than_config): Self::Config,
+        mut layouter: impl Layouter<F>,
+    ) -> Result<(), Error> {
+        let tornado_chip = TornadoChip::construct(withdrawal_config.0.clone());
+        let hash_chip = HashChip::construct(withdrawal_config.0.hash_config);
+        let less_than_chip = LessThanChip::construct(less_than_config);
+        let advice = withdrawal_config.0.advice;
+        let instance = withdrawal_config.2;
+
+        let unlock_time = layouter.assign_region(
+            || "load unlock time",
+            |mut region| region.assign_advice(|| "unlock time", advice[0], 0, || self.unlock_time),
+        )?;
+        let lock = hash_chip.hash(
+            layouter.namespace(|| "hash unlock time"),
+            unlock_time.clone(),
+            unlock_time.clone(),
+        )?;
+        self.withdrawal
+            .synthesize_withdrawal(withdrawal_config, &mut layouter, Some(lock))?;
+
+        let time = tornado_chip.load_public_value(layouter.namespace(|| "load time"), self.time)?;
+        layouter.constrain_instance(time.cell(), instance, TIME)?;
+        let unlock_time =
+            less_than_chip.split(layouter.namespace(|| "split unlock time"), &unlock_time)?;
+        let time = less_than_chip.split(layouter.namespace(|| "split time"), &time)?;
+        less_than_chip.check_less_than(
+            layouter.namespace(|| "unlock time < time"),
+            &unlock_time,
+            &time,
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{timelocked_commitment, TimelockCircuit, TimelockPublicInputs};
+    use crate::{
+        circuits::tornado::{TornadoCircuit, TornadoPublicInputs},
+        note::Note,
+        tree::IncrementalMerkleTree,
+    };
+    use halo2_proofs::{circuit::Value, dev::MockProver, halo2curves::pasta::Fp};
+
+    /// A withdrawal at `time` of `note` locked until `unlock_time`, from a pool holding
+    /// `[3, 5, leaf, 7]`
+    fn timelocked_withdrawal(
+        note: &Note<Fp>,
+        unlock_time: u64,
+        time: u64,
+    ) -> (TimelockCircuit<Fp, 5>, TimelockPublicInputs<Fp>) {
+        let leaf = timelocked_commitment(note.commitment(), Fp::from(unlock_time));
+        let mut pool = IncrementalMerkleTree::new(5);
+        pool.insert_batch(&[Fp::from(3), Fp::from(5)]).unwrap();
+        let index = pool.insert(leaf).unwrap();
+        pool.insert(Fp::from(7)).unwrap();
+        let withdrawal = TornadoCircuit::builder(note, pool.proof(index).unwrap().into())
+            .build()
+            .unwrap();
+        let circuit = TimelockCircuit {
+            withdrawal,
+            unlock_time: Value::known(Fp::from(unlock_time)),
+            time: Value::known(Fp::from(time)),
+        };
+        let public_inputs = TimelockPublicInputs {
+            withdrawal: TornadoPublicInputs {
+                nullifier_hash: note.nullifier_hash(),
+                root: pool.root(),
+                ext_data_hash: Fp::from(0),
+                recipient: Fp::from(0),
+                relayer: Fp::from(0),
+                fee: Fp::from(0),
+                refund: Fp::from(0),
+                amount: Fp::from(note.amount()),
+                asset_id: note.asset_id(),
+            },
+            time: Fp::from(time),
+        };
+        (circuit, public_inputs)
+    }
+
+    fn verify(circuit: &TimelockCircuit<Fp, 5>, public_inputs: TimelockPublicInputs<Fp>) -> bool {
+        MockProver::run(10, circuit, public_inputs.to_instances())
+            .unwrap()
+            .verify()
+            .is_ok()
+    }
+
+    #[test]
+    fn test_unlocked() {
+        let note = Note::new(Fp::from(0x456), Fp::from(0xabc), "eth", "0.1", 1).unwrap();
+        let (circuit, public_inputs) = timelocked_withdrawal(&note, 1_700_000_000, 1_700_000_001);
+        let instances = public_inputs.to_instances();
+        assert_eq!(
+            TimelockPublicInputs::from_instances(&instances),
+            Some(public_inputs)
+        );
+        assert!(verify(&circuit, public_inputs));
+
+        // a note without a lock
+        let (circuit, public_inputs) = timelocked_withdrawal(&note, 1, 1_700_000_001);
+        assert!(verify(&circuit, public_inputs));
+    }
+
+    #[test]
+    fn test_locked() {
+        let note = Note::new(Fp::from(0x456), Fp::from(0xabc), "eth", "0.1", 1).unwrap();
+        for time in [1_600_000_000, 1_700_000_000] {
+            let (circuit, public_inputs) = timelocked_withdrawal(&note, 1_700_000_000, time);
+            assert!(!verify(&circuit, public_inputs));
+        }
+    }
+
+    #[test]
+    fn test_bound_unlock_time() {
+        // the unlock time is the one in the leaf, and the time the public one
+        let note = Note::new(Fp::from(0x456), Fp::from(0xabc), "eth", "0.1", 1).unwrap();
+        let (mut circuit, public_inputs) =
+            timelocked_withdrawal(&note, 1_700_000_000, 1_600_000_000);
+        circuit.unlock_time = Value::known(Fp::from(0));
+        assert!(!verify(&circuit, public_inputs));
+
+        let (circuit, mut public_inputs) =
+            timelocked_withdrawal(&note, 1_700_000_000, 1_700_000_001);
+        public_inputs.time = Fp::from(1_800_000_000);
+        assert!(!verify(&circuit, public_inputs));
+    }
+}
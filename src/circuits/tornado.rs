@@ -0,0 +1,773 @@
+use crate::{
+    chips::{
+        range_check::RangeCheckConfig,
+        tornado::{TornadoConfig, TornadoGadget, WithdrawalInputs},
+    },
+    circuits::merkle::MerklePathWitness,
+    dev,
+    note::Note,
+    trace::trace_span,
+    tree::MerkleProof,
+    witness::{self, PublicFile, WitnessFile},
+};
+use halo2_proofs::{
+    circuit::{floor_planner::V1, AssignedCell, FloorPlanner, Layouter, Value},
+    halo2curves::{
+        bn256::Fr,
+        ff::{FromUniformBytes, PrimeField},
+    },
+    plonk::{Circuit, Column, ConstraintSystem, Error, Instance},
+};
+use std::{fmt, marker::PhantomData, path::Path};
+
+/// Bits of a note amount, see `note::AMOUNT_DECIMALS`
+pub const AMOUNT_BITS: usize = 64;
+
+/// The instance layout of `TornadoCircuit`, mirroring Tornado's public signals: the row of
+/// each public input in the instance column. The chips only return cells, so a circuit
+/// embedding the withdrawal next to its own public inputs picks its own rows.
+#[derive(Debug, Clone, Copy)]
+pub struct PublicInputs;
+
+impl PublicInputs {
+    pub const NULLIFIER_HASH: usize = 0;
+    pub const ROOT: usize = 1;
+    pub const EXT_DATA_HASH: usize = 2;
+    /// See `ext_data::address_to_field`
+    pub const RECIPIENT: usize = 3;
+    pub const RELAYER: usize = 4;
+    pub const FEE: usize = 5;
+    pub const REFUND: usize = 6;
+    /// The amount withdrawn, in units of 10^-9 of the asset
+    pub const AMOUNT: usize = 7;
+    /// See `ext_data::asset_id`
+    pub const ASSET_ID: usize = 8;
+    /// Number of rows
+    pub const LEN: usize = 9;
+}
+
+/// The public inputs of a withdrawal, by name. Provers and verifiers go through
+/// `to_instances` and `from_instances` rather than indexing the instance column themselves.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TornadoPublicInputs<F> {
+    pub nullifier_hash: F,
+    pub root: F,
+    pub ext_data_hash: F,
+    pub recipient: F,
+    pub relayer: F,
+    pub fee: F,
+    pub refund: F,
+    pub amount: F,
+    pub asset_id: F,
+}
+
+impl<F: PrimeField> TornadoPublicInputs<F> {
+    /// The instances of `TornadoCircuit`: a single column, laid out as in `PublicInputs`
+    pub fn to_instances(&self) -> Vec<Vec<F>> {
+        let mut column = vec![F::ZERO; PublicInputs::LEN];
+        column[PublicInputs::NULLIFIER_HASH] = self.nullifier_hash;
+        column[PublicInputs::ROOT] = self.root;
+        column[PublicInputs::EXT_DATA_HASH] = self.ext_data_hash;
+        column[PublicInputs::RECIPIENT] = self.recipient;
+        column[PublicInputs::RELAYER] = self.relayer;
+        column[PublicInputs::FEE] = self.fee;
+        column[PublicInputs::REFUND] = self.refund;
+        column[PublicInputs::AMOUNT] = self.amount;
+        column[PublicInputs::ASSET_ID] = self.asset_id;
+        vec![column]
+    }
+
+    /// Returns `None` unless `instances` is a single column of `PublicInputs::LEN` values
+    pub fn from_instances(instances: &[Vec<F>]) -> Option<Self> {
+        let [column] = instances else {
+            return None;
+        };
+        if column.len() != PublicInputs::LEN {
+            return None;
+        }
+        Some(Self {
+            nullifier_hash: column[PublicInputs::NULLIFIER_HASH],
+            root: column[PublicInputs::ROOT],
+            ext_data_hash: column[PublicInputs::EXT_DATA_HASH],
+            recipient: column[PublicInputs::RECIPIENT],
+            relayer: column[PublicInputs::RELAYER],
+            fee: column[PublicInputs::FEE],
+            refund: column[PublicInputs::REFUND],
+            amount: column[PublicInputs::AMOUNT],
+            asset_id: column[PublicInputs::ASSET_ID],
+        })
+    }
+}
+
+/// Proves the withdrawal of a note, with the public inputs of `PublicInputs`. The amount and
+/// the asset id are part of the note commitment (see `note::commitment`), so one tree can hold
+/// every denomination of every asset, the contract paying out the token of the asset id.
+///
+/// `P` lays out the regions. `V1` measures every region before placing any, and fills the gaps
+/// `SimpleFloorPlanner` leaves by placing them in assignment order, which saves rows as the
+/// tree gets deeper. The layout is part of the verifying key, so provers and verifiers must
+/// use the same floor planner.
+#[derive(Debug)]
+pub struct TornadoCircuit<F, const DEPTH: usize, P = V1> {
+    pub nullifier: Value<F>,
+    pub secret: Value<F>,
+    pub path: MerklePathWitness<F, DEPTH>,
+    pub ext_data_hash: Value<F>,
+    pub recipient: Value<F>,
+    pub relayer: Value<F>,
+    pub fee: Value<F>,
+    pub refund: Value<F>,
+    pub amount: Value<F>,
+    pub asset_id: Value<F>,
+    _marker: PhantomData<P>,
+}
+
+impl<F: PrimeField, const DEPTH: usize, P> TornadoCircuit<F, DEPTH, P> {
+    #[allow(clippy::too_many_arguments)]
+    pub fn new(
+        nullifier: Value<F>,
+        secret: Value<F>,
+        path: MerklePathWitness<F, DEPTH>,
+        ext_data_hash: Value<F>,
+        recipient: Value<F>,
+        relayer: Value<F>,
+        fee: Value<F>,
+        refund: Value<F>,
+        amount: Value<F>,
+        asset_id: Value<F>,
+    ) -> Self {
+        Self {
+            nullifier,
+            secret,
+            path,
+            ext_data_hash,
+            recipient,
+            relayer,
+            fee,
+            refund,
+            amount,
+            asset_id,
+            _marker: PhantomData,
+        }
+    }
+
+    /// The same witness, laid out by `Q`
+    pub fn with_floor_planner<Q>(self) -> TornadoCircuit<F, DEPTH, Q> {
+        TornadoCircuit {
+            nullifier: self.nullifier,
+            secret: self.secret,
+            path: self.path,
+            ext_data_hash: self.ext_data_hash,
+            recipient: self.recipient,
+            relayer: self.relayer,
+            fee: self.fee,
+            refund: self.refund,
+            amount: self.amount,
+            asset_id: self.asset_id,
+            _marker: PhantomData,
+        }
+    }
+}
+
+impl<const DEPTH: usize, P: FloorPlanner> TornadoCircuit<Fr, DEPTH, P> {
+    /// The smallest `k` the circuit fits in, measured by laying it out without witnesses (see
+    /// `dev::stats`). Params and keys must be generated for this `k`.
+    pub fn min_k() -> u32 {
+        dev::stats(&Self::default())
+            .expect("the circuit lays out without witnesses")
+            .min_k
+    }
+}
+
+/// A circuit without witnesses, e.g. for keygen or to render its layout. Its path has `DEPTH`
+/// unknown elements, so it lays out the same regions as any withdrawal of that depth.
+impl<F: PrimeField, const DEPTH: usize, P> Default for TornadoCircuit<F, DEPTH, P> {
+    fn default() -> Self {
+        Self {
+            nullifier: Value::unknown(),
+            secret: Value::unknown(),
+            path: MerklePathWitness::unknown(),
+            ext_data_hash: Value::unknown(),
+            recipient: Value::unknown(),
+            relayer: Value::unknown(),
+            fee: Value::unknown(),
+            refund: Value::unknown(),
+            amount: Value::unknown(),
+            asset_id: Value::unknown(),
+            _marker: PhantomData,
+        }
+    }
+}
+
+/// Why `TornadoCircuitBuilder::build` rejected a witness
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum BuildError {
+    /// The Merkle proof doesn't have `DEPTH` path elements
+    WrongPathElements { expected: usize, found: usize },
+    /// The Merkle proof doesn't have `DEPTH` path indices
+    WrongPathIndices { expected: usize, found: usize },
+    /// The path index of this layer, counted from the leaf, is neither 0 nor 1
+    InvalidPathIndex { layer: usize },
+}
+
+impl fmt::Display for BuildError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            BuildError::WrongPathElements { expected, found } => {
+                write!(f, "expected {expected} path elements, found {found}")
+            }
+            BuildError::WrongPathIndices { expected, found } => {
+                write!(f, "expected {expected} path indices, found {found}")
+            }
+            BuildError::InvalidPathIndex { layer } => {
+                write!(f, "path index of layer {layer} is not 0 or 1")
+            }
+        }
+    }
+}
+
+impl std::error::Error for BuildError {}
+
+/// Witness of a `TornadoCircuit` withdrawing a note, see `TornadoCircuit::builder`. The
+/// recipient, relayer, fee, refund and ext data hash are 0 unless set.
+#[derive(Debug, Clone)]
+pub struct TornadoCircuitBuilder<F, const DEPTH: usize> {
+    nullifier: F,
+    secret: F,
+    amount: F,
+    asset_id: F,
+    merkle_proof: MerkleProof<F>,
+    ext_data_hash: F,
+    recipient: F,
+    relayer: F,
+    fee: F,
+    refund: F,
+}
+
+impl<F: PrimeField, const DEPTH: usize> TornadoCircuitBuilder<F, DEPTH> {
+    pub fn ext_data_hash(mut self, ext_data_hash: F) -> Self {
+        self.ext_data_hash = ext_data_hash;
+        self
+    }
+
+    /// See `ext_data::address_to_field`
+    pub fn recipient(mut self, recipient: F) -> Self {
+        self.recipient = recipient;
+        self
+    }
+
+    /// See `ext_data::address_to_field`
+    pub fn relayer(mut self, relayer: F) -> Self {
+        self.relayer = relayer;
+        self
+    }
+
+    pub fn fee(mut self, fee: F) -> Self {
+        self.fee = fee;
+        self
+    }
+
+    pub fn refund(mut self, refund: F) -> Self {
+        self.refund = refund;
+        self
+    }
+
+    /// Checks the shape of the Merkle proof (see `MerklePathWitness::try_from`): a wrong path
+    /// would only show as an unsatisfiable circuit
+    pub fn build(self) -> Result<TornadoCircuit<F, DEPTH>, BuildError> {
+        Ok(TornadoCircuit {
+            nullifier: Value::known(self.nullifier),
+            secret: Value::known(self.secret),
+            path: self.merkle_proof.try_into()?,
+            ext_data_hash: Value::known(self.ext_data_hash),
+            recipient: Value::known(self.recipient),
+            relayer: Value::known(self.relayer),
+            fee: Value::known(self.fee),
+            refund: Value::known(self.refund),
+            amount: Value::known(self.amount),
+            asset_id: Value::known(self.asset_id),
+            _marker: PhantomData,
+        })
+    }
+}
+
+impl<F: PrimeField<Repr = [u8; 32]> + FromUniformBytes<64>, const DEPTH: usize>
+    TornadoCircuit<F, DEPTH>
+{
+    /// Starts a withdrawal of `note`, whose commitment is at the end of `merkle_proof`
+    pub fn builder(
+        note: &Note<F>,
+        merkle_proof: MerkleProof<F>,
+    ) -> TornadoCircuitBuilder<F, DEPTH> {
+        TornadoCircuitBuilder {
+            nullifier: note.nullifier,
+            secret: note.secret,
+            amount: F::from(note.amount()),
+            asset_id: note.asset_id(),
+            merkle_proof,
+            ext_data_hash: F::ZERO,
+            recipient: F::ZERO,
+            relayer: F::ZERO,
+            fee: F::ZERO,
+            refund: F::ZERO,
+        }
+    }
+}
+
+impl<F: PrimeField<Repr = [u8; 32]>, const DEPTH: usize, P> TornadoCircuit<F, DEPTH, P> {
+    /// Builds the circuit from a `witness.json` and a `public.json` (see `witness`), and
+    /// returns it with its public inputs
+    pub fn from_witness_file(
+        witness_path: impl AsRef<Path>,
+        public_path: impl AsRef<Path>,
+    ) -> Result<(Self, TornadoPublicInputs<F>), witness::Error> {
+        Self::from_witness(
+            &WitnessFile::read(witness_path)?,
+            &PublicFile::read(public_path)?,
+        )
+    }
+
+    /// Same as `from_witness_file`, with the files already parsed
+    pub fn from_witness(
+        witness: &WitnessFile,
+        public: &PublicFile,
+    ) -> Result<(Self, TornadoPublicInputs<F>), witness::Error> {
+        let witness = witness.parse::<F>()?;
+        let public_inputs = public.public_inputs::<F>()?;
+        let found = witness.path_elements.len();
+        let path = MerklePathWitness::new(
+            witness
+                .path_elements
+                .into_iter()
+                .map(Value::known)
+                .collect(),
+            Value::known(F::from(witness.leaf_index)),
+        )
+        .ok_or(witness::Error::WrongDepth {
+            expected: DEPTH,
+            found,
+        })?;
+        let circuit = Self::new(
+            Value::known(witness.nullifier),
+            Value::known(witness.secret),
+            path,
+            Value::known(public_inputs.ext_data_hash),
+            Value::known(public_inputs.recipient),
+            Value::known(public_inputs.relayer),
+            Value::known(public_inputs.fee),
+            Value::known(public_inputs.refund),
+            Value::known(public_inputs.amount),
+            Value::known(public_inputs.asset_id),
+        );
+        Ok((circuit, public_inputs))
+    }
+}
+
+impl<F: PrimeField, const DEPTH: usize, P: FloorPlanner> Circuit<F>
+    for TornadoCircuit<F, DEPTH, P>
+{
+    type Config = (TornadoConfig, RangeCheckConfig, Column<Instance>);
+    type FloorPlanner = P;
+
+    fn without_witnesses(&self) -> Self {
+        Self::default()
+    }
+
+    fn configure(meta: &mut ConstraintSystem<F>) -> Self::Config {
+        let advice = [
+            meta.advice_column(),
+            meta.advice_column(),
+            meta.advice_column(),
+        ];
+        let instance = meta.instance_column();
+        meta.enable_equality(instance);
+        let (tornado_config, range_check_config) = TornadoGadget::configure(meta, advice);
+        (tornado_config, range_check_config, instance)
+    }
+
+    fn synthesize(
+        &self,
+        config: Self::Config,
+        mut layouter: impl Layouter<F>,
+    ) -> Result<(), Error> {
+        trace_span!(DEBUG, "synthesize", circuit = "tornado", depth = DEPTH);
+        self.synthesize_withdrawal(config, &mut layouter, None)?;
+        Ok(())
+    }
+}
+
+impl<F: PrimeField, const DEPTH: usize, P> TornadoCircuit<F, DEPTH, P> {
+    /// The witness of the withdrawal, for `TornadoGadget::assert_valid_withdrawal`
+    pub fn to_inputs(&self) -> WithdrawalInputs<F, DEPTH> {
+        WithdrawalInputs {
+            nullifier: self.nullifier,
+            secret: self.secret,
+            path: self.path,
+            ext_data_hash: self.ext_data_hash,
+            recipient: self.recipient,
+            relayer: self.relayer,
+            fee: self.fee,
+            refund: self.refund,
+            amount: self.amount,
+            asset_id: self.asset_id,
+            binding: None,
+        }
+    }
+
+    /// Lays out the withdrawal and constrains the rows of `PublicInputs`. The leaf proven in
+    /// the tree is the note commitment, hashed with `binding` if there is one: the note's owner
+    /// (see `owned`) or its unlock time (see `timelock`).
+    /// Returns the leaf, for circuits proving more about the deposit (see `association`).
+    pub(crate) fn synthesize_withdrawal(
+        &self,
+        (config, range_check_config, instance): (TornadoConfig, RangeCheckConfig, Column<Instance>),
+        layouter: &mut impl Layouter<F>,
+        binding: Option<AssignedCell<F, F>>,
+    ) -> Result<AssignedCell<F, F>, Error> {
+        let gadget = TornadoGadget::construct(config, range_check_config);
+        let inputs = WithdrawalInputs {
+            binding,
+            ..self.to_inputs()
+        };
+        let cells = gadget.assert_valid_withdrawal(layouter.namespace(|| "withdrawal"), &inputs)?;
+
+        for (cell, row) in [
+            (&cells.nullifier_hash, PublicInputs::NULLIFIER_HASH),
+            (&cells.root, PublicInputs::ROOT),
+            (&cells.ext_data_hash, PublicInputs::EXT_DATA_HASH),
+            (&cells.recipient, PublicInputs::RECIPIENT),
+            (&cells.relayer, PublicInputs::RELAYER),
+            (&cells.fee, PublicInputs::FEE),
+            (&cells.refund, PublicInputs::REFUND),
+            (&cells.amount, PublicInputs::AMOUNT),
+            (&cells.asset_id, PublicInputs::ASSET_ID),
+        ] {
+            layouter.constrain_instance(cell.cell(), instance, row)?;
+        }
+        Ok(cells.leaf)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{BuildError, PublicInputs, TornadoCircuit, TornadoPublicInputs};
+    use crate::{
+        chips::{
+            range_check::RangeCheckConfig,
+            tornado::{TornadoConfig, TornadoGadget},
+        },
+        circuits::merkle::MerklePathWitness,
+        codec::to_hex,
+        dev::stats,
+        ext_data::address_to_field,
+        note::Note,
+        test_utils::{assert_keygen_round_trip, random_withdrawal},
+        tree::{IncrementalMerkleTree, MerkleProof},
+        witness,
+    };
+    use halo2_proofs::{
+        circuit::{Layouter, SimpleFloorPlanner, Value},
+        dev::MockProver,
+        halo2curves::{bn256::Fr, pasta::Fp},
+        plonk::{Circuit, Column, ConstraintSystem, Error, Instance},
+    };
+    use rand_core::OsRng;
+    use std::fs;
+
+    /// An application embedding the withdrawal: it credits the withdrawn amount to a private
+    /// balance rather than exposing it, and only exposes the nullifier hash and the root
+    struct PrivateCredit {
+        withdrawal: TornadoCircuit<Fp, 5>,
+        balance: Value<Fp>,
+    }
+
+    impl Circuit<Fp> for PrivateCredit {
+        type Config = (TornadoConfig, RangeCheckConfig, Column<Instance>);
+        type FloorPlanner = SimpleFloorPlanner;
+
+        fn without_witnesses(&self) -> Self {
+            Self {
+                withdrawal: TornadoCircuit::default(),
+                balance: Value::unknown(),
+            }
+        }
+
+        fn configure(meta: &mut ConstraintSystem<Fp>) -> Self::Config {
+            let advice = [(); 3].map(|_| meta.advice_column());
+            let instance = meta.instance_column();
+            meta.enable_equality(instance);
+            let (tornado_config, range_check_config) = TornadoGadget::configure(meta, advice);
+            (tornado_config, range_check_config, instance)
+        }
+
+        fn synthesize(
+            &self,
+            (tornado_config, range_check_config, instance): Self::Config,
+            mut layouter: impl Layouter<Fp>,
+        ) -> Result<(), Error> {
+            let advice = tornado_config.advice;
+            let gadget = TornadoGadget::construct(tornado_config, range_check_config);
+            let cells = gadget.assert_valid_withdrawal(
+                layouter.namespace(|| "withdrawal"),
+                &self.withdrawal.to_inputs(),
+            )?;
+            layouter.assign_region(
+                || "credit balance",
+                |mut region| {
+                    let balance =
+                        region.assign_advice(|| "balance", advice[0], 0, || self.balance)?;
+                    region.constrain_equal(balance.cell(), cells.amount.cell())
+                },
+            )?;
+            layouter.constrain_instance(cells.nullifier_hash.cell(), instance, 0)?;
+            layouter.constrain_instance(cells.root.cell(), instance, 1)
+        }
+    }
+
+    fn circuit(amount: Fp) -> (TornadoCircuit<Fp, 5>, Vec<Fp>) {
+        let nullifier = Fp::from(0x456);
+        let secret = Fp::from(0xabc);
+        let elements = [2, 5, 7, 14, 23].map(Fp::from);
+        // path indices 0, 0, 1, 1, 0
+        let leaf_index = Fp::from(0b01100);
+        let asset_id = Fp::from(0xda1);
+        let commitment = nullifier * secret * amount * asset_id;
+        let root = elements.iter().fold(commitment, |node, e| node * e);
+        let [recipient, relayer, fee, refund] = [0x11, 0x22, 50, 0].map(Fp::from);
+
+        let circuit = TornadoCircuit::<Fp, 5>::new(
+            Value::known(nullifier),
+            Value::known(secret),
+            MerklePathWitness::known(elements, leaf_index),
+            Value::known(Fp::from(0xe47)),
+            Value::known(recipient),
+            Value::known(relayer),
+            Value::known(fee),
+            Value::known(refund),
+            Value::known(amount),
+            Value::known(asset_id),
+        );
+        let public_input = vec![
+            nullifier * nullifier,
+            root,
+            Fp::from(0xe47),
+            recipient,
+            relayer,
+            fee,
+            refund,
+            amount,
+            asset_id,
+        ];
+        (circuit, public_input)
+    }
+
+    #[test]
+    fn test_tornado_circuit() {
+        let (circuit, public_input) = circuit(Fp::from(100_000_000));
+        let prover = MockProver::run(10, &circuit, vec![public_input.clone()]).unwrap();
+        assert!(prover.verify().is_ok());
+
+        // changing any public input, e.g. a relayer redirecting the funds or raising its fee,
+        // invalidates the proof
+        for row in 0..public_input.len() {
+            let mut tampered = public_input.clone();
+            tampered[row] += Fp::from(1);
+            let prover = MockProver::run(10, &circuit, vec![tampered]).unwrap();
+            assert!(prover.verify().is_err());
+        }
+    }
+
+    #[test]
+    fn test_embedded_withdrawal() {
+        let amount = Fp::from(100_000_000);
+        let (withdrawal, public_input) = circuit(amount);
+        let instances = vec![vec![
+            public_input[PublicInputs::NULLIFIER_HASH],
+            public_input[PublicInputs::ROOT],
+        ]];
+        let credit = |withdrawal, balance| PrivateCredit {
+            withdrawal,
+            balance: Value::known(balance),
+        };
+        let prover = MockProver::run(10, &credit(withdrawal, amount), instances.clone()).unwrap();
+        assert!(prover.verify().is_ok());
+
+        // the gadget constrains the same withdrawal: the balance is the committed amount, and
+        // the root is the tree's
+        let (withdrawal, _) = circuit(amount);
+        let overcredited = credit(withdrawal, amount + Fp::from(1));
+        let prover = MockProver::run(10, &overcredited, instances.clone()).unwrap();
+        assert!(prover.verify().is_err());
+        let mut wrong_root = instances;
+        wrong_root[0][1] += Fp::from(1);
+        let (withdrawal, _) = circuit(amount);
+        let prover = MockProver::run(10, &credit(withdrawal, amount), wrong_root).unwrap();
+        assert!(prover.verify().is_err());
+    }
+
+    #[test]
+    fn test_floor_planners() {
+        let (circuit, public_input) = circuit(Fp::from(100_000_000));
+        let circuit = circuit.with_floor_planner::<SimpleFloorPlanner>();
+        let prover = MockProver::run(10, &circuit, vec![public_input]).unwrap();
+        assert!(prover.verify().is_ok());
+
+        let v1 = stats(&TornadoCircuit::<Fr, 20>::default()).unwrap();
+        let simple = stats(&TornadoCircuit::<Fr, 20, SimpleFloorPlanner>::default()).unwrap();
+        assert!(v1.rows <= simple.rows);
+        assert!(v1.min_k <= simple.min_k);
+    }
+
+    #[test]
+    fn test_keygen_round_trip() {
+        let (circuit, public_inputs) = random_withdrawal::<Fr, 5>(OsRng);
+        assert_keygen_round_trip(circuit, &public_inputs.to_instances());
+        let (circuit, public_inputs) = random_withdrawal::<Fr, 5>(OsRng);
+        let circuit = circuit.with_floor_planner::<SimpleFloorPlanner>();
+        assert_keygen_round_trip(circuit, &public_inputs.to_instances());
+    }
+
+    #[test]
+    fn test_min_k() {
+        assert!(TornadoCircuit::<Fr, 5>::min_k() <= TornadoCircuit::<Fr, 32>::min_k());
+        // the k the CLI used before it was measured
+        assert!(TornadoCircuit::<Fr, 20>::min_k() <= 10);
+    }
+
+    #[test]
+    fn test_public_inputs() {
+        let (_, public_input) = circuit(Fp::from(100_000_000));
+        let instances = vec![public_input];
+        let public_inputs = TornadoPublicInputs::from_instances(&instances).unwrap();
+        assert_eq!(public_inputs.root, instances[0][PublicInputs::ROOT]);
+        assert_eq!(public_inputs.fee, Fp::from(50));
+        assert_eq!(public_inputs.to_instances(), instances);
+
+        assert_eq!(TornadoPublicInputs::<Fp>::from_instances(&[]), None);
+        let short = vec![instances[0][..PublicInputs::LEN - 1].to_vec()];
+        assert_eq!(TornadoPublicInputs::from_instances(&short), None);
+        let two_columns = vec![instances[0].clone(), instances[0].clone()];
+        assert_eq!(TornadoPublicInputs::from_instances(&two_columns), None);
+    }
+
+    #[test]
+    fn test_amount_range_check() {
+        let (circuit, public_input) = circuit(Fp::from(u64::MAX));
+        let prover = MockProver::run(10, &circuit, vec![public_input]).unwrap();
+        assert!(prover.verify().is_ok());
+
+        // a commitment to an amount above 64 bits can't be withdrawn
+        let (circuit, public_input) = circuit(Fp::from(u64::MAX) + Fp::from(1));
+        let prover = MockProver::run(10, &circuit, vec![public_input]).unwrap();
+        assert!(prover.verify().is_err());
+    }
+
+    #[test]
+    fn test_from_witness_file() {
+        let dir = std::env::temp_dir().join("tornado-witness-file");
+        fs::create_dir_all(&dir).unwrap();
+        let (witness_path, public_path) = (dir.join("witness.json"), dir.join("public.json"));
+        fs::write(
+            &witness_path,
+            r#"{
+                "nullifier": "0x456",
+                "secret": "0xabc",
+                "pathElements": ["0x2", "0x5", "0x7", "0xe", "0x17"],
+                "leafIndex": 12
+            }"#,
+        )
+        .unwrap();
+        let root = 0x456 * 0xabc * 3 * 5 * 2 * 5 * 7 * 14 * 23u64;
+        let public = format!(
+            r#"{{
+                "root": "{}",
+                "nullifierHash": "{}",
+                "extDataHash": "0xe47",
+                "recipient": "0x1111111111111111111111111111111111111111",
+                "relayer": "0x2222222222222222222222222222222222222222",
+                "fee": "50",
+                "refund": "0",
+                "amount": "3",
+                "assetId": "0x5"
+            }}"#,
+            to_hex(&Fp::from(root)),
+            to_hex(&Fp::from(0x456 * 0x456)),
+        );
+        fs::write(&public_path, public).unwrap();
+
+        let (circuit, public_inputs) =
+            TornadoCircuit::<Fp, 5>::from_witness_file(&witness_path, &public_path).unwrap();
+        assert_eq!(public_inputs.recipient, address_to_field(&[0x11; 20]));
+        let prover = MockProver::run(10, &circuit, public_inputs.to_instances()).unwrap();
+        assert!(prover.verify().is_ok());
+
+        assert!(matches!(
+            TornadoCircuit::<Fp, 4>::from_witness_file(&witness_path, &public_path),
+            Err(witness::Error::WrongDepth {
+                expected: 4,
+                found: 5
+            })
+        ));
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn test_builder() {
+        let note = Note::<Fp>::new(Fp::from(0x456), Fp::from(0xabc), "eth", "0.1", 1).unwrap();
+        let mut tree = IncrementalMerkleTree::new(5);
+        for leaf in [3, 5, 7] {
+            tree.insert(Fp::from(leaf)).unwrap();
+        }
+        let index = tree.insert(note.commitment()).unwrap();
+        let merkle_proof: MerkleProof<Fp> = tree.proof(index).unwrap().into();
+
+        let circuit = TornadoCircuit::<Fp, 5>::builder(&note, merkle_proof.clone())
+            .ext_data_hash(Fp::from(0xe47))
+            .recipient(Fp::from(0x11))
+            .fee(Fp::from(50))
+            .build()
+            .unwrap();
+        let public_inputs = TornadoPublicInputs {
+            nullifier_hash: note.nullifier_hash(),
+            root: tree.root(),
+            ext_data_hash: Fp::from(0xe47),
+            recipient: Fp::from(0x11),
+            relayer: Fp::from(0),
+            fee: Fp::from(50),
+            refund: Fp::from(0),
+            amount: Fp::from(note.amount()),
+            asset_id: note.asset_id(),
+        };
+        let prover = MockProver::run(10, &circuit, public_inputs.to_instances()).unwrap();
+        assert!(prover.verify().is_ok());
+
+        assert_eq!(
+            TornadoCircuit::<Fp, 4>::builder(&note, merkle_proof.clone())
+                .build()
+                .unwrap_err(),
+            BuildError::WrongPathElements {
+                expected: 4,
+                found: 5
+            }
+        );
+        let mut short = merkle_proof.clone();
+        short.path_indices.pop();
+        assert_eq!(
+            TornadoCircuit::<Fp, 5>::builder(&note, short)
+                .build()
+                .unwrap_err(),
+            BuildError::WrongPathIndices {
+                expected: 5,
+                found: 4
+            }
+        );
+        let mut not_a_bit = merkle_proof;
+        not_a_bit.path_indices[2] = Fp::from(2);
+        assert_eq!(
+            TornadoCircuit::<Fp, 5>::builder(&note, not_a_bit)
+                .build()
+                .unwrap_err(),
+            BuildError::InvalidPathIndex { layer: 2 }
+        );
+    }
+}
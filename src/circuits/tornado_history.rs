@@ -0,0 +1,221 @@
+use crate::{
+    chips::{
+        merkle::MerkleChip,
+        tornado::{TornadoChip, TornadoConfig},
+    },
+    circuits::merkle::MerklePathWitness,
+};
+use halo2_proofs::{
+    circuit::{Layouter, SimpleFloorPlanner, Value},
+    halo2curves::ff::PrimeField,
+    plonk::{Circuit, Column, ConstraintSystem, Error, Instance},
+};
+
+/// Row of the first root of the history, after the nullifier hash and the public values
+pub const HISTORY_START: usize = 5;
+
+/// A withdrawal proving membership in one of `HISTORY` recent roots instead of the latest
+/// root, so that a proof stays valid while other deposits land.
+///
+/// It is a fixed-denomination, single-asset variant, not `TornadoCircuit` with a history:
+/// commitments are `H(nullifier, secret)`, without the amount and asset id of
+/// `note::commitment`, so there is no amount row to range check and no asset id row, and the
+/// layout below isn't that of `PublicInputs`. The contract verifying it serves one asset at one
+/// denomination: it pays that denomination (less the fee) on every withdrawal, and must not
+/// share its tree with pools of other amounts or assets, whose notes would withdraw from it.
+///
+/// Instance layout: row 0 = nullifier hash, row 1 = ext data hash, row 2 = relayer, row 3 =
+/// fee, row 4 = refund, rows `HISTORY_START..HISTORY_START + HISTORY` = the root history (see
+/// `tree::RootHistory::roots`). The proven root itself stays private. The recipient is bound
+/// through the ext data hash, the relayer and its fee and refund by their own rows, so whoever
+/// submits the proof can't change them.
+#[derive(Debug)]
+pub struct TornadoHistoryCircuit<F, const DEPTH: usize, const HISTORY: usize> {
+    pub nullifier: Value<F>,
+    pub secret: Value<F>,
+    pub path: MerklePathWitness<F, DEPTH>,
+    pub ext_data_hash: Value<F>,
+    /// See `ext_data::address_to_field`
+    pub relayer: Value<F>,
+    pub fee: Value<F>,
+    pub refund: Value<F>,
+}
+
+impl<F: PrimeField, const DEPTH: usize, const HISTORY: usize>
+    TornadoHistoryCircuit<F, DEPTH, HISTORY>
+{
+    pub fn new(
+        nullifier: Value<F>,
+        secret: Value<F>,
+        path: MerklePathWitness<F, DEPTH>,
+        ext_data_hash: Value<F>,
+        relayer: Value<F>,
+        fee: Value<F>,
+        refund: Value<F>,
+    ) -> Self {
+        Self {
+            nullifier,
+            secret,
+            path,
+            ext_data_hash,
+            relayer,
+            fee,
+            refund,
+        }
+    }
+}
+
+impl<F: PrimeField, const DEPTH: usize, const HISTORY: usize> Circuit<F>
+    for TornadoHistoryCircuit<F, DEPTH, HISTORY>
+{
+    type Config = (TornadoConfig, Column<Instance>);
+    type FloorPlanner = SimpleFloorPlanner;
+
+    fn without_witnesses(&self) -> Self {
+        Self {
+            nullifier: Value::unknown(),
+            secret: Value::unknown(),
+            path: MerklePathWitness::unknown(),
+            ext_data_hash: Value::unknown(),
+            relayer: Value::unknown(),
+            fee: Value::unknown(),
+            refund: Value::unknown(),
+        }
+    }
+
+    fn configure(meta: &mut ConstraintSystem<F>) -> Self::Config {
+        let advice = [
+            meta.advice_column(),
+            meta.advice_column(),
+            meta.advice_column(),
+        ];
+        let instance = meta.instance_column();
+        meta.enable_equality(instance);
+        (TornadoChip::configure(meta, advice), instance)
+    }
+
+    fn synthesize(
+        &self,
+        (config, instance): Self::Config,
+        mut layouter: impl Layouter<F>,
+    ) -> Result<(), Error> {
+        let tornado_chip = TornadoChip::construct(config.clone());
+
+        let nullifier_hash_cell = tornado_chip.compute_hash(
+            layouter.namespace(|| "get nullifier hash"),
+            self.nullifier,
+            self.nullifier,
+        )?;
+        layouter.constrain_instance(nullifier_hash_cell.cell(), instance, 0)?;
+
+        let commitment_hash_cell = tornado_chip.compute_hash(
+            layouter.namespace(|| "get commitment"),
+            self.nullifier,
+            self.secret,
+        )?;
+        let merkle_chip = MerkleChip::construct(config.merkle_config.clone());
+        let merkle_root_cell = merkle_chip.prove_tree_root(
+            layouter.namespace(|| "prove merkle tree"),
+            commitment_hash_cell,
+            self.path.path_elements,
+            self.path.leaf_index,
+        )?;
+        let history = layouter.assign_region(
+            || "load root history",
+            |mut region| {
+                (0..HISTORY)
+                    .map(|row| {
+                        region.assign_advice_from_instance(
+                            || "history entry",
+                            instance,
+                            HISTORY_START + row,
+                            config.advice[1],
+                            row,
+                        )
+                    })
+                    .collect::<Result<Vec<_>, _>>()
+            },
+        )?;
+        tornado_chip.prove_root_in_history(
+            layouter.namespace(|| "prove root in history"),
+            merkle_root_cell,
+            &history,
+        )?;
+
+        for (row, (name, value)) in [
+            ("ext data hash", self.ext_data_hash),
+            ("relayer", self.relayer),
+            ("fee", self.fee),
+            ("refund", self.refund),
+        ]
+        .into_iter()
+        .enumerate()
+        {
+            let cell = tornado_chip
+                .load_public_value(layouter.namespace(|| format!("load {name}")), value)?;
+            layouter.constrain_instance(cell.cell(), instance, 1 + row)?;
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{TornadoHistoryCircuit, HISTORY_START};
+    use crate::{
+        circuits::merkle::MerklePathWitness,
+        tree::{IncrementalMerkleTree, RootHistory},
+    };
+    use halo2_proofs::{circuit::Value, dev::MockProver, halo2curves::pasta::Fp};
+
+    #[test]
+    fn test_tornado_history_circuit() {
+        let nullifier = Fp::from(0x456);
+        let secret = Fp::from(0xabc);
+
+        let mut tree = IncrementalMerkleTree::new(5);
+        let mut history = RootHistory::<Fp, 4>::new(tree.root());
+        let index = tree.insert(nullifier * secret).unwrap();
+        history.push(tree.root());
+        // the proof is built against the root right after the deposit...
+        let circuit = TornadoHistoryCircuit::<Fp, 5, 4>::new(
+            Value::known(nullifier),
+            Value::known(secret),
+            MerklePathWitness::from_tree(&tree, index).unwrap(),
+            Value::known(Fp::from(0xe47)),
+            Value::known(Fp::from(0x22)),
+            Value::known(Fp::from(50)),
+            Value::known(Fp::from(0)),
+        );
+        let public_input = |history: &RootHistory<Fp, 4>| {
+            let mut public_input = [0xe47, 0x22, 50, 0].map(Fp::from).to_vec();
+            public_input.insert(0, nullifier * nullifier);
+            public_input.extend(history.roots());
+            public_input
+        };
+
+        // ...and stays valid while the root is in the history
+        for leaf in [3, 5] {
+            tree.insert(Fp::from(leaf)).unwrap();
+            history.push(tree.root());
+            let prover = MockProver::run(10, &circuit, vec![public_input(&history)]).unwrap();
+            assert!(prover.verify().is_ok());
+        }
+
+        // a relayer can't raise its fee, redirect the refund or take over the withdrawal
+        for row in 1..HISTORY_START {
+            let mut tampered = public_input(&history);
+            tampered[row] += Fp::from(1);
+            let prover = MockProver::run(10, &circuit, vec![tampered]).unwrap();
+            assert!(prover.verify().is_err());
+        }
+
+        for leaf in [7, 11] {
+            tree.insert(Fp::from(leaf)).unwrap();
+            history.push(tree.root());
+        }
+        let prover = MockProver::run(10, &circuit, vec![public_input(&history)]).unwrap();
+        assert!(prover.verify().is_err());
+    }
+}
@@ -0,0 +1,217 @@
+//! Selective disclosure of a note's withdrawal to the holder of its viewing key.
+//!
+//! `Note::viewing_key` is `H(secret, secret)`. The owner gives it to an auditor, along with a
+//! proof that a nullifier hash (seen on withdrawal) and a commitment (seen on deposit) belong
+//! to a note with that key. The auditor learns the link between the two and nothing else: the
+//! nullifier, the secret and the note's value stay private, and other users' deposits and
+//! withdrawals are untouched.
+use crate::{
+    chips::{
+        hash::HashChip,
+        tornado::{TornadoChip, TornadoConfig},
+    },
+    note::Note,
+};
+use halo2_proofs::{
+    circuit::{Layouter, SimpleFloorPlanner, Value},
+    halo2curves::ff::PrimeField,
+    plonk::{Circuit, Column, ConstraintSystem, Error, Instance},
+};
+
+/// The public inputs of `ViewingCircuit`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ViewingPublicInputs<F> {
+    pub nullifier_hash: F,
+    pub commitment: F,
+    pub viewing_key: F,
+}
+
+impl<F: PrimeField> ViewingPublicInputs<F> {
+    /// Row 0 = nullifier hash, row 1 = commitment, row 2 = viewing key
+    pub fn to_instances(&self) -> Vec<Vec<F>> {
+        vec![vec![self.nullifier_hash, self.commitment, self.viewing_key]]
+    }
+
+    /// Returns `None` unless `instances` is a single column of 3 values
+    pub fn from_instances(instances: &[Vec<F>]) -> Option<Self> {
+        let [column] = instances else {
+            return None;
+        };
+        let [nullifier_hash, commitment, viewing_key] = column.as_slice() else {
+            return None;
+        };
+        Some(Self {
+            nullifier_hash: *nullifier_hash,
+            commitment: *commitment,
+            viewing_key: *viewing_key,
+        })
+    }
+}
+
+impl<F: PrimeField<Repr = [u8; 32]>> ViewingPublicInputs<F> {
+    pub fn from_note(note: &Note<F>) -> Self {
+        Self {
+            nullifier_hash: note.nullifier_hash(),
+            commitment: note.commitment(),
+            viewing_key: note.viewing_key(),
+        }
+    }
+}
+
+/// Proves that the nullifier hash, the commitment and the viewing key are those of one note.
+/// The nullifier and the secret are assigned once and copied into each hash.
+///
+/// Instance layout: see `ViewingPublicInputs::to_instances`.
+#[derive(Debug, Clone, Copy)]
+pub struct ViewingCircuit<F> {
+    pub nullifier: Value<F>,
+    pub secret: Value<F>,
+    pub amount: Value<F>,
+    pub asset_id: Value<F>,
+}
+
+impl<F: PrimeField<Repr = [u8; 32]>> ViewingCircuit<F> {
+    pub fn from_note(note: &Note<F>) -> Self {
+        Self {
+            nullifier: Value::known(note.nullifier),
+            secret: Value::known(note.secret),
+            amount: Value::known(F::from(note.amount())),
+            asset_id: Value::known(note.asset_id()),
+        }
+    }
+}
+
+impl<F: PrimeField> Circuit<F> for ViewingCircuit<F> {
+    type Config = (TornadoConfig, Column<Instance>);
+    type FloorPlanner = SimpleFloorPlanner;
+
+    fn without_witnesses(&self) -> Self {
+        Self {
+            nullifier: Value::unknown(),
+            secret: Value::unknown(),
+            amount: Value::unknown(),
+            asset_id: Value::unknown(),
+        }
+    }
+
+    fn configure(meta: &mut ConstraintSystem<F>) -> Self::Config {
+        let advice = [
+            meta.advice_column(),
+            meta.advice_column(),
+            meta.advice_column(),
+        ];
+        let instance = meta.instance_column();
+        meta.enable_equality(instance);
+        (TornadoChip::configure(meta, advice), instance)
+    }
+
+    fn synthesize(
+        &self,
+        (config, instance): Self::Config,
+        mut layouter: impl Layouter<F>,
+    ) -> Result<(), Error> {
+        let [nullifier, secret, amount, asset_id] = layouter.assign_region(
+            || "load note",
+            |mut region| {
+                let advice = config.advice;
+                Ok([
+                    region.assign_advice(|| "nullifier", advice[0], 0, || self.nullifier)?,
+                    region.assign_advice(|| "secret", advice[1], 0, || self.secret)?,
+                    region.assign_advice(|| "amount", advice[0], 1, || self.amount)?,
+                    region.assign_advice(|| "asset id", advice[1], 1, || self.asset_id)?,
+                ])
+            },
+        )?;
+
+        let hash_chip = HashChip::construct(config.hash_config);
+        let nullifier_hash = hash_chip.hash(
+            layouter.namespace(|| "nullifier hash"),
+            nullifier.clone(),
+            nullifier.clone(),
+        )?;
+        layouter.constrain_instance(nullifier_hash.cell(), instance, 0)?;
+
+        let inner = hash_chip.hash(
+            layouter.namespace(|| "hash nullifier and secret"),
+            nullifier,
+            secret.clone(),
+        )?;
+        let inner = hash_chip.hash(layouter.namespace(|| "hash amount"), inner, amount)?;
+        let commitment = hash_chip.hash(layouter.namespace(|| "hash asset id"), inner, asset_id)?;
+        layouter.constrain_instance(commitment.cell(), instance, 1)?;
+
+        let viewing_key =
+            hash_chip.hash(layouter.namespace(|| "viewing key"), secret.clone(), secret)?;
+        layouter.constrain_instance(viewing_key.cell(), instance, 2)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{ViewingCircuit, ViewingPublicInputs};
+    use crate::note::Note;
+    use halo2_proofs::{circuit::Value, dev::MockProver, halo2curves::pasta::Fp};
+
+    fn verify(circuit: &ViewingCircuit<Fp>, public_inputs: ViewingPublicInputs<Fp>) -> bool {
+        MockProver::run(6, circuit, public_inputs.to_instances())
+            .unwrap()
+            .verify()
+            .is_ok()
+    }
+
+    #[test]
+    fn test_disclosure() {
+        let note = Note::new(Fp::from(0x456), Fp::from(0xabc), "eth", "0.1", 1).unwrap();
+        let circuit = ViewingCircuit::from_note(&note);
+        let public_inputs = ViewingPublicInputs::from_note(&note);
+        assert_eq!(
+            ViewingPublicInputs::from_instances(&public_inputs.to_instances()),
+            Some(public_inputs)
+        );
+        assert!(verify(&circuit, public_inputs));
+    }
+
+    #[test]
+    fn test_other_note() {
+        let note = Note::new(Fp::from(0x456), Fp::from(0xabc), "eth", "0.1", 1).unwrap();
+        let other = Note::new(Fp::from(0x789), Fp::from(0xdef), "eth", "0.1", 1).unwrap();
+        let circuit = ViewingCircuit::from_note(&note);
+        let public_inputs = ViewingPublicInputs::from_note(&note);
+
+        // another key can't claim the note's withdrawal
+        let mut other_key = public_inputs;
+        other_key.viewing_key = other.viewing_key();
+        assert!(!verify(&circuit, other_key));
+        // nor can the key claim another note's withdrawal or deposit
+        let mut other_nullifier_hash = public_inputs;
+        other_nullifier_hash.nullifier_hash = other.nullifier_hash();
+        assert!(!verify(&circuit, other_nullifier_hash));
+        let mut other_commitment = public_inputs;
+        other_commitment.commitment = other.commitment();
+        assert!(!verify(&circuit, other_commitment));
+    }
+
+    #[test]
+    fn test_mixed_notes() {
+        // another note's nullifier hash with this note's commitment and key: the nullifier
+        // hashed for the one must be the nullifier committed to in the other
+        let note = Note::new(Fp::from(0x456), Fp::from(0xabc), "eth", "0.1", 1).unwrap();
+        let other = Note::new(Fp::from(0x789), Fp::from(0xdef), "eth", "0.1", 1).unwrap();
+        let public_inputs = ViewingPublicInputs {
+            nullifier_hash: other.nullifier_hash(),
+            commitment: note.commitment(),
+            viewing_key: note.viewing_key(),
+        };
+        let mixed = ViewingCircuit {
+            nullifier: Value::known(other.nullifier),
+            ..ViewingCircuit::from_note(&note)
+        };
+        for circuit in [
+            mixed,
+            ViewingCircuit::from_note(&note),
+            ViewingCircuit::from_note(&other),
+        ] {
+            assert!(!verify(&circuit, public_inputs));
+        }
+    }
+}
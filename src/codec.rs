@@ -0,0 +1,146 @@
+//! Canonical external encodings of field elements: 32-byte big-endian, `0x`-prefixed hex
+//! (of those big-endian bytes) and decimal strings, as used by Solidity, snarkjs and circom.
+//!
+//! `PrimeField::to_repr` is little-endian for every field we use (bn256, pasta), so anything
+//! leaving the crate should go through here rather than through the raw repr.
+use halo2_proofs::halo2curves::ff::PrimeField;
+use std::fmt;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Error {
+    InvalidHex,
+    InvalidDecimal,
+    /// The value is not smaller than the field modulus
+    NonCanonical,
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::InvalidHex => write!(f, "invalid hex field element"),
+            Error::InvalidDecimal => write!(f, "invalid decimal field element"),
+            Error::NonCanonical => write!(f, "value is not a canonical field element"),
+        }
+    }
+}
+
+impl std::error::Error for Error {}
+
+pub fn to_bytes_be<F: PrimeField<Repr = [u8; 32]>>(f: &F) -> [u8; 32] {
+    let mut bytes = f.to_repr();
+    bytes.reverse();
+    bytes
+}
+
+pub fn from_bytes_be<F: PrimeField<Repr = [u8; 32]>>(bytes: &[u8; 32]) -> Result<F, Error> {
+    let mut repr = *bytes;
+    repr.reverse();
+    Option::from(F::from_repr(repr)).ok_or(Error::NonCanonical)
+}
+
+pub fn to_hex<F: PrimeField<Repr = [u8; 32]>>(f: &F) -> String {
+    format!("0x{}", hex::encode(to_bytes_be(f)))
+}
+
+/// Accepts an optional `0x` prefix and fewer than 64 digits
+pub fn from_hex<F: PrimeField<Repr = [u8; 32]>>(s: &str) -> Result<F, Error> {
+    let digits = s.strip_prefix("0x").unwrap_or(s);
+    if digits.is_empty() || digits.len() > 64 {
+        return Err(Error::InvalidHex);
+    }
+    let padded = format!("{digits:0>64}");
+    let mut bytes = [0u8; 32];
+    hex::decode_to_slice(padded, &mut bytes).map_err(|_| Error::InvalidHex)?;
+    from_bytes_be(&bytes)
+}
+
+pub fn to_decimal<F: PrimeField<Repr = [u8; 32]>>(f: &F) -> String {
+    le_bytes_to_decimal(&f.to_repr())
+}
+
+pub fn from_decimal<F: PrimeField<Repr = [u8; 32]>>(s: &str) -> Result<F, Error> {
+    let repr = decimal_to_le_bytes(s)?;
+    Option::from(F::from_repr(repr)).ok_or(Error::NonCanonical)
+}
+
+/// Decimal form of a little-endian 256-bit integer
+pub(crate) fn le_bytes_to_decimal(bytes: &[u8; 32]) -> String {
+    let mut n = *bytes;
+    let mut digits = vec![];
+    while n.iter().any(|b| *b != 0) {
+        let mut rem = 0u16;
+        for byte in n.iter_mut().rev() {
+            let cur = (rem << 8) | *byte as u16;
+            *byte = (cur / 10) as u8;
+            rem = cur % 10;
+        }
+        digits.push(b'0' + rem as u8);
+    }
+    if digits.is_empty() {
+        return "0".to_string();
+    }
+    digits.reverse();
+    String::from_utf8(digits).unwrap()
+}
+
+/// Little-endian 256-bit integer from its decimal form, without any modulus check
+pub(crate) fn decimal_to_le_bytes(s: &str) -> Result<[u8; 32], Error> {
+    if s.is_empty() {
+        return Err(Error::InvalidDecimal);
+    }
+    let mut n = [0u8; 32];
+    for c in s.chars() {
+        let mut carry = c.to_digit(10).ok_or(Error::InvalidDecimal)? as u16;
+        for byte in n.iter_mut() {
+            let cur = *byte as u16 * 10 + carry;
+            *byte = cur as u8;
+            carry = cur >> 8;
+        }
+        if carry != 0 {
+            return Err(Error::NonCanonical);
+        }
+    }
+    Ok(n)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{from_bytes_be, from_decimal, from_hex, to_bytes_be, to_decimal, to_hex, Error};
+    use halo2_proofs::halo2curves::{bn256::Fr, ff::PrimeField, pasta::Fp};
+
+    #[test]
+    fn test_big_endian() {
+        let f = Fr::from(0x0102);
+        let bytes = to_bytes_be(&f);
+        assert_eq!(bytes[30..], [1, 2]);
+        assert!(bytes[..30].iter().all(|b| *b == 0));
+        assert_eq!(from_bytes_be::<Fr>(&bytes).unwrap(), f);
+        assert_eq!(
+            to_hex(&f),
+            "0x0000000000000000000000000000000000000000000000000000000000000102"
+        );
+    }
+
+    #[test]
+    fn test_roundtrips() {
+        let f = -Fp::from(0x456);
+        assert_eq!(from_hex::<Fp>(&to_hex(&f)).unwrap(), f);
+        assert_eq!(from_decimal::<Fp>(&to_decimal(&f)).unwrap(), f);
+        assert_eq!(from_hex::<Fp>("0x456").unwrap(), Fp::from(0x456));
+        assert_eq!(from_decimal::<Fr>("1110").unwrap(), Fr::from(1110));
+        assert_eq!(to_decimal(&Fr::from(0)), "0");
+    }
+
+    #[test]
+    fn test_rejects_non_canonical() {
+        assert_eq!(from_hex::<Fr>(Fr::MODULUS), Err(Error::NonCanonical));
+        assert_eq!(
+            from_decimal::<Fr>(
+                "21888242871839275222246405745257275088548364400416034343698204186575808495617"
+            ),
+            Err(Error::NonCanonical)
+        );
+        assert_eq!(from_hex::<Fr>("0xzz"), Err(Error::InvalidHex));
+        assert_eq!(from_decimal::<Fr>("12a"), Err(Error::InvalidDecimal));
+    }
+}
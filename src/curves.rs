@@ -0,0 +1,94 @@
+//! The withdrawal circuit pinned to each curve it is deployed on. The chips, circuits, tree and
+//! note hashing are generic over the field; these aliases are the only place a deployment picks
+//! one. `bn256` is the KZG curve the prover and the EVM verifier use, `pasta` the cycle of the
+//! IPA setting, which the mock prover tests also run on.
+#[cfg(any(feature = "bn256", feature = "pasta"))]
+use crate::circuits::tornado::TornadoCircuit;
+#[cfg(feature = "bn256")]
+use halo2_proofs::halo2curves::bn256;
+#[cfg(feature = "pasta")]
+use halo2_proofs::halo2curves::pasta;
+
+/// The withdrawal circuit over the scalar field of bn256
+#[cfg(feature = "bn256")]
+pub type TornadoBn256<const DEPTH: usize> = TornadoCircuit<bn256::Fr, DEPTH>;
+
+/// The withdrawal circuit over the scalar field of Pallas
+#[cfg(feature = "pasta")]
+pub type TornadoPasta<const DEPTH: usize> = TornadoCircuit<pasta::Fp, DEPTH>;
+
+#[cfg(all(test, any(feature = "bn256", feature = "pasta")))]
+mod tests {
+    use crate::{
+        circuits::tornado::{TornadoCircuit, TornadoPublicInputs},
+        note::Note,
+        tree::{IncrementalMerkleTree, MerkleProof},
+    };
+    use halo2_proofs::{
+        dev::MockProver,
+        halo2curves::ff::{FromUniformBytes, PrimeField},
+    };
+
+    /// The same withdrawal, whatever the field
+    fn withdrawal<F: PrimeField<Repr = [u8; 32]> + FromUniformBytes<64>>(
+    ) -> (TornadoCircuit<F, 5>, TornadoPublicInputs<F>) {
+        let note = Note::new(F::from(0x456), F::from(0xabc), "eth", "0.1", 1).unwrap();
+        let mut tree = IncrementalMerkleTree::new(5);
+        for leaf in [3, 5, 7] {
+            tree.insert(F::from(leaf)).unwrap();
+        }
+        let index = tree.insert(note.commitment()).unwrap();
+        let merkle_proof: MerkleProof<F> = tree.proof(index).unwrap().into();
+
+        let circuit = TornadoCircuit::builder(&note, merkle_proof)
+            .fee(F::from(50))
+            .build()
+            .unwrap();
+        let public_inputs = TornadoPublicInputs {
+            nullifier_hash: note.nullifier_hash(),
+            root: tree.root(),
+            ext_data_hash: F::ZERO,
+            recipient: F::ZERO,
+            relayer: F::ZERO,
+            fee: F::from(50),
+            refund: F::ZERO,
+            amount: F::from(note.amount()),
+            asset_id: note.asset_id(),
+        };
+        (circuit, public_inputs)
+    }
+
+    fn verifies<F: PrimeField + FromUniformBytes<64> + Ord>(
+        circuit: &TornadoCircuit<F, 5>,
+        public_inputs: &TornadoPublicInputs<F>,
+    ) -> bool {
+        MockProver::run(10, circuit, public_inputs.to_instances())
+            .unwrap()
+            .verify()
+            .is_ok()
+    }
+
+    #[cfg(feature = "bn256")]
+    #[test]
+    fn test_bn256() {
+        use super::TornadoBn256;
+        use halo2_proofs::halo2curves::bn256::Fr;
+
+        let (circuit, mut public_inputs): (TornadoBn256<5>, _) = withdrawal();
+        assert!(verifies(&circuit, &public_inputs));
+        public_inputs.root += Fr::from(1);
+        assert!(!verifies(&circuit, &public_inputs));
+    }
+
+    #[cfg(feature = "pasta")]
+    #[test]
+    fn test_pasta() {
+        use super::TornadoPasta;
+        use halo2_proofs::halo2curves::pasta::Fp;
+
+        let (circuit, mut public_inputs): (TornadoPasta<5>, _) = withdrawal();
+        assert!(verifies(&circuit, &public_inputs));
+        public_inputs.root += Fp::from(1);
+        assert!(!verifies(&circuit, &public_inputs));
+    }
+}
@@ -0,0 +1,215 @@
+//! Malicious witnesses, each changing an honest withdrawal the way a prover would to withdraw a
+//! note it can't open or to spend one twice. The tests check that every one of them is
+//! rejected, so this is also the circuit's threat model: a new chip comes with the attacks on
+//! it.
+use crate::{
+    chips::merkle::{MerkleChip, MerkleConfig},
+    circuits::tornado::{TornadoCircuit, TornadoPublicInputs},
+    native::{hash_value, hash_values},
+};
+use halo2_proofs::{
+    circuit::{Layouter, SimpleFloorPlanner, Value},
+    halo2curves::ff::PrimeField,
+    plonk::{Circuit, Column, ConstraintSystem, Error, Instance},
+};
+
+/// Adds one to the sibling at `layer`, so the path leads to another root
+pub fn wrong_neighbor<F: PrimeField, const DEPTH: usize>(
+    mut circuit: TornadoCircuit<F, DEPTH>,
+    layer: usize,
+) -> TornadoCircuit<F, DEPTH> {
+    let element = &mut circuit.path.path_elements[layer];
+    *element = element.map(|element| element + F::ONE);
+    circuit
+}
+
+/// Replaces the top sibling with `F::ONE`, the identity of the product hash, so the path stops
+/// one layer below the root. The circuit always hashes `DEPTH` layers, and `MerklePathWitness`
+/// can't hold a shorter path.
+pub fn truncated_path<F: PrimeField, const DEPTH: usize>(
+    mut circuit: TornadoCircuit<F, DEPTH>,
+) -> TornadoCircuit<F, DEPTH> {
+    circuit.path.path_elements[DEPTH - 1] = Value::known(F::ONE);
+    circuit
+}
+
+/// The instance column with rows `a` and `b` swapped, e.g. the nullifier hash and the root
+pub fn swapped_instances<F: PrimeField>(
+    public_inputs: &TornadoPublicInputs<F>,
+    a: usize,
+    b: usize,
+) -> Vec<Vec<F>> {
+    let mut instances = public_inputs.to_instances();
+    instances[0].swap(a, b);
+    instances
+}
+
+/// Spends the note again under the nullifier hash of `fresh_nullifier`, to get past the
+/// contract's set of spent nullifier hashes
+pub fn nullifier_reuse<F: PrimeField>(
+    mut public_inputs: TornadoPublicInputs<F>,
+    fresh_nullifier: F,
+) -> TornadoPublicInputs<F> {
+    public_inputs.nullifier_hash = hash_value(fresh_nullifier);
+    public_inputs
+}
+
+/// One Merkle layer over a swap bit that isn't decomposed from a leaf index, so it can be any
+/// value. `TornadoCircuit` always decomposes its bits; a chip taking a bit from elsewhere must
+/// still reject `bit` outside `{0, 1}`, which would hash neither ordering of the two nodes.
+///
+/// Instance layout: row 0 = parent node.
+#[derive(Debug, Clone, Copy)]
+pub struct NonBooleanSwapCircuit<F> {
+    pub node: Value<F>,
+    pub neighbor: Value<F>,
+    pub bit: Value<F>,
+}
+
+impl<F: PrimeField> NonBooleanSwapCircuit<F> {
+    /// The circuit over `bit`, and the parent the prover gets for it: `H(2b - a, 2a - b)` for
+    /// a bit of 2
+    pub fn new(node: F, neighbor: F, bit: F) -> (Self, F) {
+        let left = node + bit * (neighbor - node);
+        let right = neighbor + bit * (node - neighbor);
+        let circuit = Self {
+            node: Value::known(node),
+            neighbor: Value::known(neighbor),
+            bit: Value::known(bit),
+        };
+        (circuit, hash_values(&[left, right]))
+    }
+}
+
+impl<F: PrimeField> Circuit<F> for NonBooleanSwapCircuit<F> {
+    type Config = (MerkleConfig, Column<Instance>);
+    type FloorPlanner = SimpleFloorPlanner;
+
+    fn without_witnesses(&self) -> Self {
+        Self {
+            node: Value::unknown(),
+            neighbor: Value::unknown(),
+            bit: Value::unknown(),
+        }
+    }
+
+    fn configure(meta: &mut ConstraintSystem<F>) -> Self::Config {
+        let advice = [
+            meta.advice_column(),
+            meta.advice_column(),
+            meta.advice_column(),
+        ];
+        let instance = meta.instance_column();
+        meta.enable_equality(instance);
+        (MerkleChip::configure(meta, advice), instance)
+    }
+
+    fn synthesize(
+        &self,
+        (config, instance): Self::Config,
+        mut layouter: impl Layouter<F>,
+    ) -> Result<(), Error> {
+        let (node, layer, bit) = layouter.assign_region(
+            || "load node and bit",
+            |mut region| {
+                let node = region.assign_advice(|| "node", config.advice[0], 0, || self.node)?;
+                let layer = region.assign_advice_from_constant(
+                    || "no layers",
+                    config.advice[1],
+                    0,
+                    F::ZERO,
+                )?;
+                let bit = region.assign_advice(|| "bit", config.advice[2], 0, || self.bit)?;
+                Ok((node, layer, bit))
+            },
+        )?;
+
+        let chip = MerkleChip::construct(config);
+        let (parent, _) = chip.merkle_prove_layer(
+            layouter.namespace(|| "merkle layer"),
+            &node,
+            &layer,
+            self.neighbor,
+            &bit,
+        )?;
+        layouter.constrain_instance(parent.cell(), instance, 0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        nullifier_reuse, swapped_instances, truncated_path, wrong_neighbor, NonBooleanSwapCircuit,
+    };
+    use crate::{
+        circuits::{
+            merkle::MerklePathWitness,
+            tornado::{PublicInputs, TornadoCircuit},
+        },
+        test_utils::{assert_fails_constraint, assert_proves, random_withdrawal},
+    };
+    use halo2_proofs::{dev::MockProver, halo2curves::pasta::Fp};
+    use rand_core::OsRng;
+
+    fn rejects(circuit: &TornadoCircuit<Fp, 5>, instances: Vec<Vec<Fp>>) -> bool {
+        MockProver::run(10, circuit, instances)
+            .unwrap()
+            .verify()
+            .is_err()
+    }
+
+    #[test]
+    fn test_honest_withdrawal() {
+        let (circuit, public_inputs) = random_withdrawal::<Fp, 5>(OsRng);
+        assert_proves(&circuit, public_inputs.to_instances());
+    }
+
+    #[test]
+    fn test_wrong_neighbor() {
+        for layer in 0..5 {
+            let (circuit, public_inputs) = random_withdrawal::<Fp, 5>(OsRng);
+            let circuit = wrong_neighbor(circuit, layer);
+            assert!(rejects(&circuit, public_inputs.to_instances()));
+        }
+    }
+
+    #[test]
+    fn test_truncated_path() {
+        let (circuit, public_inputs) = random_withdrawal::<Fp, 5>(OsRng);
+        let path_elements = circuit.path.path_elements[..4].to_vec();
+        assert!(MerklePathWitness::<Fp, 5>::new(path_elements, circuit.path.leaf_index).is_none());
+
+        let circuit = truncated_path(circuit);
+        assert!(rejects(&circuit, public_inputs.to_instances()));
+    }
+
+    #[test]
+    fn test_swapped_instances() {
+        let (circuit, public_inputs) = random_withdrawal::<Fp, 5>(OsRng);
+        for (a, b) in [
+            (PublicInputs::NULLIFIER_HASH, PublicInputs::ROOT),
+            (PublicInputs::ROOT, PublicInputs::ASSET_ID),
+            (PublicInputs::AMOUNT, PublicInputs::FEE),
+        ] {
+            assert!(rejects(&circuit, swapped_instances(&public_inputs, a, b)));
+        }
+    }
+
+    #[test]
+    fn test_nullifier_reuse() {
+        let (circuit, public_inputs) = random_withdrawal::<Fp, 5>(OsRng);
+        let public_inputs = nullifier_reuse(public_inputs, Fp::from(0x789));
+        assert!(rejects(&circuit, public_inputs.to_instances()));
+    }
+
+    #[test]
+    fn test_non_boolean_swap_bit() {
+        let (node, neighbor) = (Fp::from(3), Fp::from(5));
+        for bit in [0, 1] {
+            let (circuit, parent) = NonBooleanSwapCircuit::new(node, neighbor, Fp::from(bit));
+            assert_proves(&circuit, vec![vec![parent]]);
+        }
+        let (circuit, parent) = NonBooleanSwapCircuit::new(node, neighbor, Fp::from(2));
+        assert_fails_constraint(&circuit, vec![vec![parent]], "conditional swap");
+    }
+}
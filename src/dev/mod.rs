@@ -0,0 +1,360 @@
+//! Contributor tooling, not part of the proving API.
+//!
+//! `circuit_stats` measures the withdrawal circuit (rows, columns, gate degrees, minimum `k`),
+//! to catch regressions when a chip changes, and `bench` also times its keygen, proof and
+//! verification at that `k`. With the `dev-graph` feature, `render_layout` draws
+//! a circuit's regions over its columns and rows: how the chips share the advice columns, where
+//! the equality constraints are, and which rows are left empty. `attacks` builds the malicious
+//! witnesses the circuit must reject.
+pub mod attacks;
+
+use crate::{
+    circuits::tornado::{TornadoCircuit, TornadoPublicInputs},
+    error::TornadoError,
+    note::Note,
+    prover,
+    tree::IncrementalMerkleTree,
+};
+#[cfg(feature = "dev-graph")]
+use halo2_proofs::dev::CircuitLayout;
+use halo2_proofs::{
+    circuit::{FloorPlanner, Value},
+    halo2curves::{
+        bn256::{Bn256, Fr},
+        ff::Field,
+    },
+    plonk::{
+        Advice, Any, Assigned, Assignment, Challenge, Circuit, Column, ConstraintSystem, Error,
+        Fixed, Instance, Selector,
+    },
+    poly::kzg::commitment::ParamsKZG,
+};
+#[cfg(feature = "dev-graph")]
+use plotters::prelude::{BitMapBackend, IntoDrawingArea, WHITE};
+use rand_core::OsRng;
+use serde::Serialize;
+use std::time::Instant;
+#[cfg(feature = "dev-graph")]
+use std::{any, fmt, io, path::Path};
+
+/// Size of a circuit, as laid out by its floor planner
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct CircuitStats {
+    /// Rows used by regions, constants and lookup tables, blinding rows excluded
+    pub rows: usize,
+    pub advice_columns: usize,
+    pub fixed_columns: usize,
+    pub instance_columns: usize,
+    /// Selectors become fixed columns at keygen, possibly combined
+    pub selectors: usize,
+    /// Name and degree of each gate, in configuration order
+    pub gate_degrees: Vec<(String, usize)>,
+    /// Degree of the constraint system, lookups and permutation included
+    pub degree: usize,
+    /// Smallest `k` whose `2^k` rows hold `rows` and the blinding rows
+    pub min_k: u32,
+}
+
+/// An `Assignment` that only records the last row each column is assigned at, the way
+/// `halo2_proofs::dev::CircuitCost` measures a layout. Values are never computed.
+#[derive(Default)]
+struct RowCounter {
+    rows: usize,
+}
+
+impl RowCounter {
+    fn touch(&mut self, row: usize) {
+        self.rows = self.rows.max(row + 1);
+    }
+}
+
+impl<F: Field> Assignment<F> for RowCounter {
+    fn enter_region<NR, N>(&mut self, _: N)
+    where
+        NR: Into<String>,
+        N: FnOnce() -> NR,
+    {
+    }
+
+    fn exit_region(&mut self) {}
+
+    fn enable_selector<A, AR>(&mut self, _: A, _: &Selector, row: usize) -> Result<(), Error>
+    where
+        A: FnOnce() -> AR,
+        AR: Into<String>,
+    {
+        self.touch(row);
+        Ok(())
+    }
+
+    fn query_instance(&self, _: Column<Instance>, _: usize) -> Result<Value<F>, Error> {
+        Ok(Value::unknown())
+    }
+
+    fn assign_advice<V, VR, A, AR>(
+        &mut self,
+        _: A,
+        _: Column<Advice>,
+        row: usize,
+        _: V,
+    ) -> Result<(), Error>
+    where
+        V: FnOnce() -> Value<VR>,
+        VR: Into<Assigned<F>>,
+        A: FnOnce() -> AR,
+        AR: Into<String>,
+    {
+        self.touch(row);
+        Ok(())
+    }
+
+    fn assign_fixed<V, VR, A, AR>(
+        &mut self,
+        _: A,
+        _: Column<Fixed>,
+        row: usize,
+        _: V,
+    ) -> Result<(), Error>
+    where
+        V: FnOnce() -> Value<VR>,
+        VR: Into<Assigned<F>>,
+        A: FnOnce() -> AR,
+        AR: Into<String>,
+    {
+        self.touch(row);
+        Ok(())
+    }
+
+    fn copy(&mut self, _: Column<Any>, _: usize, _: Column<Any>, _: usize) -> Result<(), Error> {
+        Ok(())
+    }
+
+    /// Pads a lookup table up to the usable rows, which doesn't use new rows
+    fn fill_from_row(
+        &mut self,
+        _: Column<Fixed>,
+        _: usize,
+        _: Value<Assigned<F>>,
+    ) -> Result<(), Error> {
+        Ok(())
+    }
+
+    fn get_challenge(&self, _: Challenge) -> Value<F> {
+        Value::unknown()
+    }
+
+    fn push_namespace<NR, N>(&mut self, _: N)
+    where
+        NR: Into<String>,
+        N: FnOnce() -> NR,
+    {
+    }
+
+    fn pop_namespace(&mut self, _: Option<String>) {}
+}
+
+/// Measures `circuit` without computing any witness value
+pub fn stats<C: Circuit<Fr>>(circuit: &C) -> Result<CircuitStats, TornadoError> {
+    let mut cs = ConstraintSystem::default();
+    let config = C::configure(&mut cs);
+    let mut counter = RowCounter::default();
+    C::FloorPlanner::synthesize(&mut counter, circuit, config, cs.constants().clone())?;
+
+    let rows = counter.rows;
+    let needed = (rows + cs.blinding_factors() + 1).max(cs.minimum_rows());
+    Ok(CircuitStats {
+        rows,
+        advice_columns: cs.num_advice_columns(),
+        fixed_columns: cs.num_fixed_columns(),
+        instance_columns: cs.num_instance_columns(),
+        selectors: cs.num_selectors(),
+        gate_degrees: cs
+            .gates()
+            .iter()
+            .map(|gate| {
+                let degree = gate.polynomials().iter().map(|p| p.degree()).max();
+                (gate.name().to_string(), degree.unwrap_or(0))
+            })
+            .collect(),
+        degree: cs.degree(),
+        min_k: needed.next_power_of_two().trailing_zeros(),
+    })
+}
+
+/// `stats` of the withdrawal circuit for a tree of `DEPTH` levels
+pub fn circuit_stats<const DEPTH: usize>() -> Result<CircuitStats, TornadoError> {
+    stats(&TornadoCircuit::<Fr, DEPTH>::default())
+}
+
+/// Wall times of one keygen, proof and verification of the withdrawal circuit, see `bench`
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct BenchReport {
+    pub depth: usize,
+    #[serde(flatten)]
+    pub stats: CircuitStats,
+    pub keygen_ms: u128,
+    pub prove_ms: u128,
+    pub verify_ms: u128,
+    /// Size of the transcript
+    pub proof_bytes: usize,
+}
+
+/// Proves and verifies a withdrawal from a tree of `DEPTH` levels, with fresh KZG params of
+/// the circuit's minimum `k`. Generating the params isn't timed.
+pub fn bench<const DEPTH: usize>() -> Result<BenchReport, TornadoError> {
+    let stats = circuit_stats::<DEPTH>()?;
+    let note =
+        Note::<Fr>::new(Fr::from(0x456), Fr::from(0xabc), "eth", "0.1", 1).expect("a valid note");
+    let mut tree = IncrementalMerkleTree::new(DEPTH);
+    let index = tree.insert(note.commitment())?;
+    let merkle_proof = tree.proof(index).expect("the leaf exists").into();
+    let circuit = TornadoCircuit::<Fr, DEPTH>::builder(&note, merkle_proof).build()?;
+    let instances = TornadoPublicInputs {
+        nullifier_hash: note.nullifier_hash(),
+        root: tree.root(),
+        ext_data_hash: Fr::ZERO,
+        recipient: Fr::ZERO,
+        relayer: Fr::ZERO,
+        fee: Fr::ZERO,
+        refund: Fr::ZERO,
+        amount: Fr::from(note.amount()),
+        asset_id: note.asset_id(),
+    }
+    .to_instances();
+    let params = ParamsKZG::<Bn256>::setup(stats.min_k, OsRng);
+
+    let start = Instant::now();
+    let pk = prover::keygen(&params, &circuit)?;
+    let keygen_ms = start.elapsed().as_millis();
+    let start = Instant::now();
+    let proof = prover::prove(&params, &pk, circuit, &instances)?;
+    let prove_ms = start.elapsed().as_millis();
+    let start = Instant::now();
+    prover::verify(&params, pk.get_vk(), &proof, &instances)?;
+    let verify_ms = start.elapsed().as_millis();
+
+    Ok(BenchReport {
+        depth: DEPTH,
+        stats,
+        keygen_ms,
+        prove_ms,
+        verify_ms,
+        proof_bytes: proof.len(),
+    })
+}
+
+/// Size of the rendered image, in pixels
+#[cfg(feature = "dev-graph")]
+const WIDTH: u32 = 1024;
+#[cfg(feature = "dev-graph")]
+const HEIGHT: u32 = 3096;
+
+/// The drawing errors of `plotters` are generic over the backend, they all end up as failures
+/// to write the image
+#[cfg(feature = "dev-graph")]
+fn render_error(e: impl fmt::Display) -> TornadoError {
+    io::Error::other(e.to_string()).into()
+}
+
+/// Renders the layout of `C` with `2^k` rows to a PNG at `path`, labelled with the region
+/// names. The layout doesn't depend on the witnesses, so `C::default()` is laid out.
+#[cfg(feature = "dev-graph")]
+pub fn render_layout<C: Circuit<Fr> + Default>(
+    k: u32,
+    path: impl AsRef<Path>,
+) -> Result<(), TornadoError> {
+    let root = BitMapBackend::new(path.as_ref(), (WIDTH, HEIGHT)).into_drawing_area();
+    root.fill(&WHITE).map_err(render_error)?;
+    let root = root
+        .titled(any::type_name::<C>(), ("sans-serif", 20))
+        .map_err(render_error)?;
+    CircuitLayout::default()
+        .show_labels(true)
+        .mark_equality_cells(true)
+        .show_equality_constraints(true)
+        .render(k, &C::default(), &root)
+        .map_err(render_error)?;
+    root.present().map_err(render_error)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{bench, circuit_stats};
+    use crate::{
+        circuits::tornado::TornadoCircuit,
+        note::Note,
+        tree::{IncrementalMerkleTree, MerkleProof},
+    };
+    use halo2_proofs::{dev::MockProver, halo2curves::bn256::Fr};
+
+    #[test]
+    fn test_circuit_stats() {
+        let stats = circuit_stats::<5>().unwrap();
+        assert_eq!(stats.advice_columns, 3);
+        assert_eq!(stats.instance_columns, 1);
+        assert!(stats
+            .gate_degrees
+            .iter()
+            .all(|(_, degree)| *degree <= stats.degree));
+        // the chips share a single hash gate
+        let hash_gates = stats
+            .gate_degrees
+            .iter()
+            .filter(|(name, _)| name == "hash constraint")
+            .count();
+        assert_eq!(hash_gates, 1);
+        // a deeper tree only adds Merkle layers
+        let deeper = circuit_stats::<20>().unwrap();
+        assert!(deeper.rows > stats.rows);
+        assert_eq!(deeper.gate_degrees, stats.gate_degrees);
+
+        // `min_k` is the smallest k the mock prover runs with
+        let note = Note::<Fr>::new(Fr::from(0x456), Fr::from(0xabc), "eth", "0.1", 1).unwrap();
+        let mut tree = IncrementalMerkleTree::new(5);
+        let index = tree.insert(note.commitment()).unwrap();
+        let merkle_proof: MerkleProof<Fr> = tree.proof(index).unwrap().into();
+        let circuit = TornadoCircuit::<Fr, 5>::builder(&note, merkle_proof)
+            .build()
+            .unwrap();
+        let public_input = vec![
+            note.nullifier_hash(),
+            tree.root(),
+            Fr::from(0),
+            Fr::from(0),
+            Fr::from(0),
+            Fr::from(0),
+            Fr::from(0),
+            Fr::from(note.amount()),
+            note.asset_id(),
+        ];
+        let prover = MockProver::run(stats.min_k, &circuit, vec![public_input.clone()]).unwrap();
+        assert!(prover.verify().is_ok());
+        assert!(MockProver::run(stats.min_k - 1, &circuit, vec![public_input]).is_err());
+    }
+
+    #[test]
+    fn test_bench() {
+        let report = bench::<5>().unwrap();
+        assert_eq!(report.depth, 5);
+        assert_eq!(report.stats, circuit_stats::<5>().unwrap());
+        assert!(report.proof_bytes > 0);
+        let json = serde_json::to_value(&report).unwrap();
+        assert_eq!(json["min_k"], report.stats.min_k);
+        assert_eq!(json["proof_bytes"], report.proof_bytes);
+    }
+
+    #[cfg(feature = "dev-graph")]
+    #[test]
+    fn test_render_layout() {
+        use super::render_layout;
+        use std::fs;
+
+        let dir = std::env::temp_dir().join("tornado-dev-layout");
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("layout.png");
+
+        render_layout::<TornadoCircuit<Fr, 5>>(8, &path).unwrap();
+        let png = fs::read(&path).unwrap();
+        assert_eq!(&png[1..4], b"PNG");
+    }
+}
@@ -0,0 +1,272 @@
+//! The binary envelope around serialized proofs and notes, so a stored or transmitted blob says
+//! what it is before anything decodes it.
+//!
+//! Layout, big-endian:
+//!
+//! | bytes | field |
+//! |-------|-------|
+//! | 4     | `MAGIC` |
+//! | 1     | format version, `FORMAT_VERSION` |
+//! | 1     | kind: 1 = proof, 2 = note |
+//! | 1     | curve: 0 = none, 1 = bn256, 2 = pasta |
+//! | 32    | circuit id, zeros when unbound |
+//! | 4     | payload length |
+//! | n     | payload |
+//!
+//! Forward compatibility:
+//! - the magic and the version byte never move. A reader rejects any version it doesn't know
+//!   rather than guessing at a newer layout, and a version's header never changes: new header
+//!   fields take a new version, new payload fields the payload's own version (`PROOF_VERSION`);
+//! - kind and curve codes are never reused, unknown ones are rejected;
+//! - the length covers the payload exactly, trailing bytes are rejected;
+//! - the payload is the encoding each kind had before envelopes, and bytes that don't start
+//!   with the magic are read as such a bare payload, so blobs written by earlier releases still
+//!   decode. The magic can't start one: a legacy proof starts with its `u16` version, and a
+//!   legacy note with a big-endian field element, below `0x41` on both curves.
+use crate::proof::CurveId;
+use std::fmt;
+
+pub const MAGIC: [u8; 4] = *b"th2e";
+/// Bumped whenever the header layout changes
+pub const FORMAT_VERSION: u8 = 1;
+/// Size of the header before the payload
+pub const HEADER_LEN: usize = 4 + 1 + 1 + 1 + 32 + 4;
+
+/// Identifies the circuit (and its layout) a proof is for, see `Envelope::circuit_id`
+pub type CircuitId = [u8; 32];
+
+/// The circuit id of an envelope that isn't bound to a circuit, e.g. a note's
+pub const UNBOUND: CircuitId = [0; 32];
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Error {
+    /// Doesn't start with `MAGIC`
+    InvalidMagic,
+    /// Shorter than the header
+    Truncated,
+    UnsupportedVersion(u8),
+    UnknownKind(u8),
+    UnknownCurve(u8),
+    /// An envelope of another kind than the one being decoded
+    UnexpectedKind(Kind),
+    /// The payload isn't as long as the header says
+    LengthMismatch,
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::InvalidMagic => write!(f, "not an envelope"),
+            Error::Truncated => write!(f, "envelope shorter than its header"),
+            Error::UnsupportedVersion(v) => {
+                write!(
+                    f,
+                    "unsupported envelope version {v} (expected {FORMAT_VERSION})"
+                )
+            }
+            Error::UnknownKind(kind) => write!(f, "unknown envelope kind {kind}"),
+            Error::UnknownCurve(curve) => write!(f, "unknown envelope curve {curve}"),
+            Error::UnexpectedKind(kind) => write!(f, "unexpected envelope of a {kind:?}"),
+            Error::LengthMismatch => write!(f, "envelope payload length mismatch"),
+        }
+    }
+}
+
+impl std::error::Error for Error {}
+
+/// What the payload encodes
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Kind {
+    /// `bincode` of a `Proof`
+    Proof,
+    /// `Note::to_bytes` before envelopes
+    Note,
+}
+
+impl Kind {
+    fn code(self) -> u8 {
+        match self {
+            Kind::Proof => 1,
+            Kind::Note => 2,
+        }
+    }
+
+    fn from_code(code: u8) -> Result<Self, Error> {
+        match code {
+            1 => Ok(Kind::Proof),
+            2 => Ok(Kind::Note),
+            _ => Err(Error::UnknownKind(code)),
+        }
+    }
+}
+
+fn curve_code(curve: Option<CurveId>) -> u8 {
+    match curve {
+        None => 0,
+        Some(CurveId::Bn256) => 1,
+        Some(CurveId::Pasta) => 2,
+    }
+}
+
+fn curve_from_code(code: u8) -> Result<Option<CurveId>, Error> {
+    match code {
+        0 => Ok(None),
+        1 => Ok(Some(CurveId::Bn256)),
+        2 => Ok(Some(CurveId::Pasta)),
+        _ => Err(Error::UnknownCurve(code)),
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Envelope {
+    pub kind: Kind,
+    /// Field of the payload's values, if it has any
+    pub curve: Option<CurveId>,
+    /// `UNBOUND` unless the payload is only valid for one circuit
+    pub circuit_id: CircuitId,
+    pub payload: Vec<u8>,
+}
+
+impl Envelope {
+    /// Whether `bytes` are an envelope rather than a legacy bare payload. They may still fail to
+    /// decode.
+    pub fn is_envelope(bytes: &[u8]) -> bool {
+        bytes.starts_with(&MAGIC)
+    }
+
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = Vec::with_capacity(HEADER_LEN + self.payload.len());
+        bytes.extend(MAGIC);
+        bytes.push(FORMAT_VERSION);
+        bytes.push(self.kind.code());
+        bytes.push(curve_code(self.curve));
+        bytes.extend(self.circuit_id);
+        let len = u32::try_from(self.payload.len()).expect("payload shorter than 4 GiB");
+        bytes.extend(len.to_be_bytes());
+        bytes.extend(&self.payload);
+        bytes
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, Error> {
+        if !Self::is_envelope(bytes) {
+            return Err(Error::InvalidMagic);
+        }
+        if bytes.len() < HEADER_LEN {
+            return Err(Error::Truncated);
+        }
+        // the version first: a later version may lay out the rest differently
+        let version = bytes[4];
+        if version != FORMAT_VERSION {
+            return Err(Error::UnsupportedVersion(version));
+        }
+        let kind = Kind::from_code(bytes[5])?;
+        let curve = curve_from_code(bytes[6])?;
+        let (circuit_id, rest) = bytes[7..].split_at(32);
+        let (len, payload) = rest.split_at(4);
+        let len = u32::from_be_bytes(len.try_into().expect("4 bytes"));
+        if payload.len() != len as usize {
+            return Err(Error::LengthMismatch);
+        }
+        Ok(Self {
+            kind,
+            curve,
+            circuit_id: circuit_id.try_into().expect("32 bytes"),
+            payload: payload.to_vec(),
+        })
+    }
+
+    /// Decodes an envelope of `kind`, or returns `None` for bytes without the magic, which the
+    /// caller reads as a legacy bare payload
+    pub fn open(bytes: &[u8], kind: Kind) -> Result<Option<Self>, Error> {
+        if !Self::is_envelope(bytes) {
+            return Ok(None);
+        }
+        let envelope = Self::from_bytes(bytes)?;
+        if envelope.kind != kind {
+            return Err(Error::UnexpectedKind(envelope.kind));
+        }
+        Ok(Some(envelope))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Envelope, Error, Kind, FORMAT_VERSION, HEADER_LEN, MAGIC, UNBOUND};
+    use crate::proof::CurveId;
+
+    fn sample() -> Envelope {
+        Envelope {
+            kind: Kind::Proof,
+            curve: Some(CurveId::Bn256),
+            circuit_id: [7; 32],
+            payload: vec![1, 2, 3],
+        }
+    }
+
+    #[test]
+    fn test_layout() {
+        let bytes = sample().to_bytes();
+        assert_eq!(bytes.len(), HEADER_LEN + 3);
+        assert_eq!(&bytes[..4], &MAGIC);
+        assert_eq!(bytes[4..7], [FORMAT_VERSION, 1, 1]);
+        assert_eq!(bytes[7..39], [7; 32]);
+        assert_eq!(bytes[39..], [0, 0, 0, 3, 1, 2, 3]);
+        assert_eq!(Envelope::from_bytes(&bytes), Ok(sample()));
+
+        let note = Envelope {
+            kind: Kind::Note,
+            curve: None,
+            circuit_id: UNBOUND,
+            payload: vec![],
+        };
+        assert_eq!(Envelope::from_bytes(&note.to_bytes()), Ok(note));
+    }
+
+    #[test]
+    fn test_rejects_unknown_header() {
+        let bytes = sample().to_bytes();
+        let with = |i: usize, b: u8| {
+            let mut bytes = bytes.clone();
+            bytes[i] = b;
+            Envelope::from_bytes(&bytes)
+        };
+        assert_eq!(
+            with(4, FORMAT_VERSION + 1),
+            Err(Error::UnsupportedVersion(FORMAT_VERSION + 1))
+        );
+        assert_eq!(with(5, 3), Err(Error::UnknownKind(3)));
+        assert_eq!(with(6, 3), Err(Error::UnknownCurve(3)));
+        assert_eq!(
+            Envelope::open(&bytes, Kind::Note),
+            Err(Error::UnexpectedKind(Kind::Proof))
+        );
+        assert_eq!(Envelope::open(&[1, 0, 2], Kind::Proof), Ok(None));
+        assert_eq!(Envelope::from_bytes(&[1, 0, 2]), Err(Error::InvalidMagic));
+    }
+
+    #[test]
+    fn test_rejects_wrong_length() {
+        let bytes = sample().to_bytes();
+        for len in 0..bytes.len() {
+            assert!(Envelope::from_bytes(&bytes[..len]).is_err());
+        }
+        assert_eq!(
+            Envelope::from_bytes(&[bytes.as_slice(), &[0]].concat()),
+            Err(Error::LengthMismatch)
+        );
+    }
+
+    #[test]
+    fn test_bit_flips() {
+        // every single bit flip fails to decode, or decodes to an envelope that encodes back
+        let bytes = sample().to_bytes();
+        for i in 0..bytes.len() * 8 {
+            let mut flipped = bytes.clone();
+            flipped[i / 8] ^= 1 << (i % 8);
+            if let Ok(envelope) = Envelope::from_bytes(&flipped) {
+                assert_ne!(envelope, sample());
+                assert_eq!(envelope.to_bytes(), flipped);
+            }
+        }
+    }
+}
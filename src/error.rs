@@ -0,0 +1,74 @@
+//! The error returned by the crate's top-level APIs. Each module keeps its own `Error` for its
+//! own failures; `TornadoError` wraps them so callers of the prover can tell a mistake in their
+//! inputs (`is_user_error`) from a failure of the proving system or of the machine.
+use crate::{circuits::tornado::BuildError, tree, witness};
+use halo2_proofs::plonk;
+use std::io;
+
+#[derive(Debug, thiserror::Error)]
+pub enum TornadoError {
+    #[error("invalid circuit inputs: {0}")]
+    Build(#[from] BuildError),
+    #[error("invalid witness: {0}")]
+    Witness(#[from] witness::Error),
+    #[error("{0}")]
+    Tree(#[from] tree::Error),
+    /// The proof doesn't verify against the verifying key and public inputs
+    #[error("proof verification failed")]
+    VerificationFailed,
+    #[error("io error: {0}")]
+    Io(#[from] io::Error),
+    #[error("invalid json: {0}")]
+    Json(#[from] serde_json::Error),
+    /// Keygen or proving failed, e.g. the circuit doesn't fit in the params
+    #[error("proving system error: {0}")]
+    Plonk(#[from] plonk::Error),
+    /// A proving service answered with an error, see `proving_service::Error::status_code`
+    #[error("proving service error {status}: {message}")]
+    Remote { status: u16, message: String },
+    /// The proof's `async_prover::CancellationToken` was cancelled
+    #[error("proving was cancelled")]
+    Cancelled,
+}
+
+impl TornadoError {
+    /// Whether the caller can fix the error by changing its inputs. IO and proving system
+    /// failures are internal, retrying with the same inputs may succeed, and so may retrying a
+    /// cancelled proof.
+    pub fn is_user_error(&self) -> bool {
+        match self {
+            TornadoError::Io(_) | TornadoError::Plonk(_) | TornadoError::Cancelled => false,
+            TornadoError::Remote { status, .. } => (400..500).contains(status),
+            _ => true,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::TornadoError;
+    use crate::{circuits::tornado::BuildError, tree};
+    use halo2_proofs::plonk;
+
+    #[test]
+    fn test_is_user_error() {
+        let build: TornadoError = BuildError::InvalidPathIndex { layer: 2 }.into();
+        assert!(build.is_user_error());
+        assert_eq!(
+            build.to_string(),
+            format!(
+                "invalid circuit inputs: {}",
+                BuildError::InvalidPathIndex { layer: 2 }
+            )
+        );
+        assert!(TornadoError::from(tree::Error::TreeFull).is_user_error());
+        assert!(TornadoError::VerificationFailed.is_user_error());
+        assert!(!TornadoError::from(plonk::Error::Synthesis).is_user_error());
+        let remote = |status| TornadoError::Remote {
+            status,
+            message: String::new(),
+        };
+        assert!(remote(400).is_user_error());
+        assert!(!remote(503).is_user_error());
+    }
+}
@@ -0,0 +1,413 @@
+use crate::{
+    circuits::tornado::{PublicInputs, TornadoPublicInputs},
+    codec::to_bytes_be,
+    error::TornadoError,
+    prover::{self, TranscriptKind},
+    trace::trace_span,
+};
+use halo2_proofs::{
+    halo2curves::bn256::{Bn256, Fr, G1Affine},
+    plonk::{Circuit, ProvingKey, VerifyingKey},
+    poly::kzg::commitment::ParamsKZG,
+};
+use halo2_solidity_verifier::{encode_calldata, BatchOpenScheme, Evm, SolidityGenerator};
+use serde::Serialize;
+use sha3::{Digest, Keccak256};
+use std::{
+    fmt,
+    io::Write,
+    process::{Command, Stdio},
+};
+
+/// Number of public inputs of `TornadoCircuit`, in the order of `PublicInputs`
+pub const TORNADO_NUM_INSTANCES: usize = PublicInputs::LEN;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Error {
+    /// The verifier template failed to render
+    Render,
+    /// `solc` couldn't be run or rejected its input, with its message
+    Solc(String),
+    /// The proof doesn't verify, so the verifier would revert rather than measure it
+    InvalidProof,
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::Render => write!(f, "failed to render the verifier"),
+            Error::Solc(message) => write!(f, "solc failed: {message}"),
+            Error::InvalidProof => write!(f, "the proof doesn't verify"),
+        }
+    }
+}
+
+impl std::error::Error for Error {}
+
+impl From<fmt::Error> for Error {
+    fn from(_: fmt::Error) -> Self {
+        Error::Render
+    }
+}
+
+/// Renders `Halo2Verifier.sol`, exposing `verifyProof(bytes proof, uint256[] instances)`.
+/// The verifier reads a keccak256 transcript, so proofs must come from `prove` below.
+pub fn render_verifier(
+    params: &ParamsKZG<Bn256>,
+    vk: &VerifyingKey<G1Affine>,
+    num_instances: usize,
+) -> Result<String, fmt::Error> {
+    SolidityGenerator::new(params, vk, BatchOpenScheme::Bdfg21, num_instances).render()
+}
+
+/// The verifier of `render_verifier` as a Yul object, through solc's optimized IR pipeline
+/// (`solc --ir-optimized --optimize`). The template is mostly inline assembly already; going
+/// through Yul lets the optimizer inline and reorder across the whole contract, and
+/// `estimate_verify_gas` tells whether that pays off. Needs `solc` on the `PATH`.
+pub fn render_yul_verifier(
+    params: &ParamsKZG<Bn256>,
+    vk: &VerifyingKey<G1Affine>,
+    num_instances: usize,
+) -> Result<String, Error> {
+    solidity_to_yul(&render_verifier(params, vk, num_instances)?)
+}
+
+/// The optimized IR of the only contract in `solidity`, which solc prints after its header
+fn solidity_to_yul(solidity: &str) -> Result<String, Error> {
+    let output = solc(&["--ir-optimized", "--optimize"], solidity)?;
+    let (_, ir) = output
+        .split_once("Optimized IR:")
+        .ok_or_else(|| Error::Solc("no optimized IR in the output".to_string()))?;
+    Ok(ir.trim().to_string())
+}
+
+/// Creation bytecode of the only contract in `solidity`
+pub fn compile_solidity(solidity: &str) -> Result<Vec<u8>, Error> {
+    bytecode_after(&solc(&["--bin", "--optimize"], solidity)?, "Binary:")
+}
+
+/// Creation bytecode of the Yul object `yul`, e.g. from `render_yul_verifier`
+pub fn compile_yul(yul: &str) -> Result<Vec<u8>, Error> {
+    let output = solc(&["--strict-assembly", "--optimize", "--bin"], yul)?;
+    bytecode_after(&output, "Binary representation:")
+}
+
+/// Runs `solc` with `args` over `source`, passed on stdin, and returns what it printed
+fn solc(args: &[&str], source: &str) -> Result<String, Error> {
+    let io_error = |e: std::io::Error| Error::Solc(e.to_string());
+    let mut child = Command::new("solc")
+        .args(args)
+        .arg("-")
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .map_err(io_error)?;
+    // solc reads all of its input before writing anything, so this can't deadlock
+    child
+        .stdin
+        .take()
+        .expect("stdin is piped")
+        .write_all(source.as_bytes())
+        .map_err(io_error)?;
+    let output = child.wait_with_output().map_err(io_error)?;
+    if !output.status.success() {
+        return Err(Error::Solc(
+            String::from_utf8_lossy(&output.stderr).into_owned(),
+        ));
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+}
+
+/// Decodes the hex line following `label` in solc's output
+fn bytecode_after(output: &str, label: &str) -> Result<Vec<u8>, Error> {
+    let mut lines = output.lines().skip_while(|line| line.trim() != label);
+    let hex = lines
+        .nth(1)
+        .ok_or_else(|| Error::Solc(format!("no `{label}` in the output")))?;
+    hex::decode(hex.trim()).map_err(|e| Error::Solc(format!("invalid bytecode: {e}")))
+}
+
+/// A verifier's size and the gas of one `verifyProof` call to it
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub struct VerifierGas {
+    /// Size of the creation bytecode
+    pub bytecode_bytes: usize,
+    /// Gas used by the call transaction, as revm reports it
+    pub verify_gas: u64,
+}
+
+/// Gas of verifying one proof, with the Solidity verifier and with its Yul build. The pool's
+/// `withdraw` pays this on top of its own bookkeeping.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub struct GasReport {
+    pub solidity: VerifierGas,
+    pub yul: VerifierGas,
+    /// Size of the `verifyProof` calldata
+    pub calldata_bytes: usize,
+}
+
+/// Deploys the verifiers of `render_verifier` and `render_yul_verifier` in revm and calls
+/// `verifyProof` on each with `proof`, which must come from `prove` below. The proof is checked
+/// natively first: the verifiers revert on an invalid proof instead of measuring it. Needs
+/// `solc` on the `PATH`.
+pub fn estimate_verify_gas(
+    params: &ParamsKZG<Bn256>,
+    vk: &VerifyingKey<G1Affine>,
+    proof: &[u8],
+    instances: &[Fr],
+) -> Result<GasReport, Error> {
+    trace_span!(INFO, "estimate verify gas", num_instances = instances.len());
+    prover::verify_with_transcript(
+        params,
+        vk,
+        proof,
+        &[instances.to_vec()],
+        TranscriptKind::Keccak256,
+    )
+    .map_err(|_| Error::InvalidProof)?;
+
+    let solidity = render_verifier(params, vk, instances.len())?;
+    let yul = solidity_to_yul(&solidity)?;
+    let calldata = encode_verify_calldata(proof, instances);
+    Ok(GasReport {
+        solidity: measure(compile_solidity(&solidity)?, &calldata)?,
+        yul: measure(compile_yul(&yul)?, &calldata)?,
+        calldata_bytes: calldata.len(),
+    })
+}
+
+/// Deploys `bytecode` in a fresh EVM and calls it with `calldata`
+fn measure(bytecode: Vec<u8>, calldata: &[u8]) -> Result<VerifierGas, Error> {
+    let bytecode_bytes = bytecode.len();
+    let mut evm = Evm::default();
+    let verifier = evm.create(bytecode);
+    let (verify_gas, output) = evm.call(verifier, calldata.to_vec());
+    // `verifyProof` returns true, ABI-encoded
+    if output.last() != Some(&1) {
+        return Err(Error::InvalidProof);
+    }
+    Ok(VerifierGas {
+        bytecode_bytes,
+        verify_gas,
+    })
+}
+
+/// Typed entrypoint in front of `Halo2Verifier`, laying out the instances
+/// the same way `TornadoCircuit::synthesize` constrains them
+pub fn render_tornado_verifier() -> String {
+    r#"// SPDX-License-Identifier: MIT
+pragma solidity ^0.8.0;
+
+interface IHalo2Verifier {
+    function verifyProof(bytes calldata proof, uint256[] calldata instances) external returns (bool);
+}
+
+contract TornadoVerifier {
+    IHalo2Verifier public immutable verifier;
+
+    constructor(IHalo2Verifier _verifier) {
+        verifier = _verifier;
+    }
+
+    function withdraw(
+        bytes calldata proof,
+        bytes32 root,
+        bytes32 nullifierHash,
+        bytes32 extDataHash,
+        address recipient,
+        address relayer,
+        uint256 fee,
+        uint256 refund,
+        uint256 amount,
+        bytes32 assetId
+    ) external returns (bool) {
+        uint256[] memory instances = new uint256[](9);
+        instances[0] = uint256(nullifierHash);
+        instances[1] = uint256(root);
+        instances[2] = uint256(extDataHash);
+        instances[3] = uint256(uint160(recipient));
+        instances[4] = uint256(uint160(relayer));
+        instances[5] = fee;
+        instances[6] = refund;
+        instances[7] = amount;
+        instances[8] = uint256(assetId);
+        return verifier.verifyProof(proof, instances);
+    }
+}
+"#
+    .to_string()
+}
+
+/// Calldata for `TornadoVerifier.withdraw(bytes proof, bytes32 root, bytes32 nullifierHash,
+/// bytes32 extDataHash, address recipient, address relayer, uint256 fee, uint256 refund,
+/// uint256 amount, bytes32 assetId)`. The addresses are encoded as by
+/// `ext_data::address_to_field`, which is also their ABI encoding.
+pub fn encode_withdraw_calldata(proof: &[u8], public_inputs: &TornadoPublicInputs<Fr>) -> Vec<u8> {
+    let mut calldata = Keccak256::digest(
+        b"withdraw(bytes,bytes32,bytes32,bytes32,address,address,uint256,uint256,uint256,bytes32)",
+    )[..4]
+        .to_vec();
+    // head: offset of `proof`, then the static arguments
+    calldata.extend(abi_word(10 * 32));
+    for value in [
+        &public_inputs.root,
+        &public_inputs.nullifier_hash,
+        &public_inputs.ext_data_hash,
+        &public_inputs.recipient,
+        &public_inputs.relayer,
+        &public_inputs.fee,
+        &public_inputs.refund,
+        &public_inputs.amount,
+        &public_inputs.asset_id,
+    ] {
+        calldata.extend(to_bytes_be(value));
+    }
+    // tail: length-prefixed `proof`, right-padded to a multiple of 32 bytes
+    calldata.extend(abi_word(proof.len()));
+    calldata.extend(proof);
+    calldata.resize(calldata.len() + (32 - proof.len() % 32) % 32, 0);
+    calldata
+}
+
+/// Calldata for `Halo2Verifier.verifyProof(bytes proof, uint256[] instances)`
+pub fn encode_verify_calldata(proof: &[u8], instances: &[Fr]) -> Vec<u8> {
+    encode_calldata(None, proof, instances)
+}
+
+fn abi_word(n: usize) -> [u8; 32] {
+    let mut word = [0u8; 32];
+    word[24..].copy_from_slice(&(n as u64).to_be_bytes());
+    word
+}
+
+/// Same as `prover::prove`, with the keccak256 transcript the Solidity verifier expects (see
+/// `TranscriptKind::Keccak256`)
+pub fn prove<C: Circuit<Fr>>(
+    params: &ParamsKZG<Bn256>,
+    pk: &ProvingKey<G1Affine>,
+    circuit: C,
+    instances: &[Fr],
+) -> Result<Vec<u8>, TornadoError> {
+    prover::prove_with_transcript(
+        params,
+        pk,
+        circuit,
+        &[instances.to_vec()],
+        TranscriptKind::Keccak256,
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        encode_verify_calldata, encode_withdraw_calldata, estimate_verify_gas, prove,
+        render_tornado_verifier, render_verifier, render_yul_verifier, Error,
+        TORNADO_NUM_INSTANCES,
+    };
+    use crate::{
+        circuits::{
+            merkle::MerklePathWitness,
+            tornado::{TornadoCircuit, TornadoPublicInputs},
+        },
+        ext_data::address_to_field,
+        prover::keygen,
+        test_utils::random_withdrawal,
+    };
+    use halo2_proofs::{
+        circuit::Value,
+        halo2curves::bn256::{Bn256, Fr},
+        poly::kzg::commitment::ParamsKZG,
+    };
+    use rand_core::OsRng;
+
+    #[test]
+    fn test_render_verifier() {
+        let circuit = TornadoCircuit::<Fr, 4>::new(
+            Value::known(Fr::from(2)),
+            Value::known(Fr::from(3)),
+            MerklePathWitness::known([Fr::from(5); 4], Fr::from(0)),
+            Value::known(Fr::from(7)),
+            Value::known(Fr::from(0x11)),
+            Value::known(Fr::from(0x22)),
+            Value::known(Fr::from(50)),
+            Value::known(Fr::from(0)),
+            Value::known(Fr::from(100_000_000)),
+            Value::known(Fr::from(0xda1)),
+        );
+        let params = ParamsKZG::<Bn256>::setup(8, OsRng);
+        let pk = keygen(&params, &circuit).unwrap();
+
+        let verifier = render_verifier(&params, pk.get_vk(), TORNADO_NUM_INSTANCES).unwrap();
+        assert!(verifier.contains("function verifyProof("));
+
+        let wrapper = render_tornado_verifier();
+        assert!(wrapper.contains("function withdraw("));
+        assert!(wrapper.contains("instances[3] = uint256(uint160(recipient));"));
+    }
+
+    #[test]
+    fn test_encode_withdraw_calldata() {
+        let proof = vec![0xab; 40];
+        let public_inputs = TornadoPublicInputs {
+            nullifier_hash: Fr::from(0x1111),
+            root: Fr::from(0x2222),
+            ext_data_hash: Fr::from(0x3333),
+            recipient: address_to_field(&[0x44; 20]),
+            relayer: address_to_field(&[0x55; 20]),
+            fee: Fr::from(0x66),
+            refund: Fr::from(0x77),
+            amount: Fr::from(0x88),
+            asset_id: Fr::from(0x99),
+        };
+        let calldata = encode_withdraw_calldata(&proof, &public_inputs);
+
+        assert_eq!(calldata[..4], [0xfe, 0xda, 0x31, 0x2d]);
+        assert_eq!(calldata.len(), 4 + 32 * 11 + 64);
+        let words: Vec<&[u8]> = calldata[4..].chunks(32).collect();
+        assert_eq!(words[0][30..], [0x01, 0x40]);
+        assert_eq!(words[1][30..], [0x22, 0x22]);
+        assert_eq!(words[2][30..], [0x11, 0x11]);
+        assert_eq!(words[3][30..], [0x33, 0x33]);
+        assert!(words[4][..12].iter().all(|b| *b == 0));
+        assert_eq!(words[4][12..], [0x44; 20]);
+        assert_eq!(words[5][12..], [0x55; 20]);
+        assert_eq!(words[6][31], 0x66);
+        assert_eq!(words[7][31], 0x77);
+        assert_eq!(words[8][31], 0x88);
+        assert_eq!(words[9][31], 0x99);
+        assert_eq!(words[10][31], 40);
+        assert_eq!(words[11], [0xab; 32]);
+        assert_eq!(words[12][..8], [0xab; 8]);
+        assert!(words[12][8..].iter().all(|b| *b == 0));
+    }
+
+    #[test]
+    #[ignore = "needs solc"]
+    fn test_estimate_verify_gas() {
+        let (circuit, public_inputs) = random_withdrawal::<Fr, 4>(OsRng);
+        let params = ParamsKZG::<Bn256>::setup(8, OsRng);
+        let pk = keygen(&params, &circuit).unwrap();
+        let instances = public_inputs.to_instances().remove(0);
+        let proof = prove(&params, &pk, circuit, &instances).unwrap();
+
+        let yul = render_yul_verifier(&params, pk.get_vk(), TORNADO_NUM_INSTANCES).unwrap();
+        assert!(yul.contains("object \""));
+        let report = estimate_verify_gas(&params, pk.get_vk(), &proof, &instances).unwrap();
+        assert_eq!(
+            report.calldata_bytes,
+            encode_verify_calldata(&proof, &instances).len()
+        );
+        // both pay at least the pairing check's precompile
+        assert!(report.solidity.verify_gas > 113_000);
+        assert!(report.yul.verify_gas > 113_000);
+
+        // the verifiers would revert, the proof is rejected before deploying them
+        let mut tampered = instances;
+        tampered[0] += Fr::from(1);
+        assert_eq!(
+            estimate_verify_gas(&params, pk.get_vk(), &proof, &tampered),
+            Err(Error::InvalidProof)
+        );
+    }
+}
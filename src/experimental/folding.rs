@@ -0,0 +1,249 @@
+//! Experimental Nova-style folding of Merkle insertions.
+//!
+//! Each leaf insertion (`old_root -> new_root` by writing `leaf` into an empty slot) is expressed
+//! as an R1CS instance. Instances are folded into one *relaxed* instance
+//! `Az ∘ Bz = u·Cz + E`, so that N insertions only need a single final check instead of N proofs.
+//!
+//! What is here is the folding arithmetic, done in the clear. Missing before this is a sound
+//! scheme: commitments to `W` and `E` (the verifier currently sees the witnesses), a Fiat-Shamir
+//! challenge bound to those commitments, and the halo2 decider circuit proving the final relaxed
+//! instance.
+use crate::tree::ZERO_VALUE;
+use halo2_proofs::halo2curves::ff::PrimeField;
+
+type LinearCombination<F> = Vec<(usize, F)>;
+
+/// Sparse R1CS over `z = [u, public inputs.., witness..]`
+#[derive(Debug, Clone)]
+pub struct R1cs<F> {
+    pub num_vars: usize,
+    pub a: Vec<LinearCombination<F>>,
+    pub b: Vec<LinearCombination<F>>,
+    pub c: Vec<LinearCombination<F>>,
+}
+
+/// Relaxed R1CS instance and witness. `z[0]` is the scalar `u`, which is 1 for a fresh instance.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RelaxedInstance<F> {
+    pub z: Vec<F>,
+    pub e: Vec<F>,
+}
+
+fn eval<F: PrimeField>(lc: &LinearCombination<F>, z: &[F]) -> F {
+    lc.iter().map(|(i, coeff)| z[*i] * coeff).sum()
+}
+
+impl<F: PrimeField> R1cs<F> {
+    fn products(&self, z: &[F]) -> (Vec<F>, Vec<F>, Vec<F>) {
+        let az = self.a.iter().map(|lc| eval(lc, z)).collect();
+        let bz = self.b.iter().map(|lc| eval(lc, z)).collect();
+        let cz = self.c.iter().map(|lc| eval(lc, z)).collect();
+        (az, bz, cz)
+    }
+
+    pub fn is_satisfied(&self, instance: &RelaxedInstance<F>) -> bool {
+        if instance.z.len() != self.num_vars || instance.e.len() != self.a.len() {
+            return false;
+        }
+        let u = instance.z[0];
+        let (az, bz, cz) = self.products(&instance.z);
+        (0..self.a.len()).all(|i| az[i] * bz[i] == u * cz[i] + instance.e[i])
+    }
+
+    /// `z = z1 + r·z2`, `E = E1 + r·T + r²·E2` with the cross term
+    /// `T = Az1 ∘ Bz2 + Az2 ∘ Bz1 - u1·Cz2 - u2·Cz1`
+    pub fn fold(
+        &self,
+        first: &RelaxedInstance<F>,
+        second: &RelaxedInstance<F>,
+        r: F,
+    ) -> RelaxedInstance<F> {
+        let (az1, bz1, cz1) = self.products(&first.z);
+        let (az2, bz2, cz2) = self.products(&second.z);
+        let (u1, u2) = (first.z[0], second.z[0]);
+
+        let z = first
+            .z
+            .iter()
+            .zip(second.z.iter())
+            .map(|(z1, z2)| *z1 + r * z2)
+            .collect();
+        let e = (0..self.a.len())
+            .map(|i| {
+                let t = az1[i] * bz2[i] + az2[i] * bz1[i] - u1 * cz2[i] - u2 * cz1[i];
+                first.e[i] + r * t + r.square() * second.e[i]
+            })
+            .collect();
+        RelaxedInstance { z, e }
+    }
+}
+
+const U: usize = 0;
+const OLD_ROOT: usize = 1;
+const NEW_ROOT: usize = 2;
+const LEAF: usize = 3;
+const VARS_PER_LEVEL: usize = 6;
+
+// per level: swap bit, sibling, left input of the old hash, left input of the new hash,
+// old parent, new parent (the last level's parents are the roots)
+fn level_vars(depth: usize, level: usize) -> [usize; 6] {
+    let base = 4 + level * VARS_PER_LEVEL;
+    let (old_parent, new_parent) = if level + 1 == depth {
+        (OLD_ROOT, NEW_ROOT)
+    } else {
+        (base + 4, base + 5)
+    };
+    [base, base + 1, base + 2, base + 3, old_parent, new_parent]
+}
+
+/// R1CS of inserting `leaf` in an empty slot of a `depth` tree hashed with the product hash.
+/// Public inputs are `[old_root, new_root, leaf]`.
+pub fn insertion_r1cs<F: PrimeField>(depth: usize) -> R1cs<F> {
+    let mut r1cs = R1cs {
+        num_vars: 4 + depth * VARS_PER_LEVEL,
+        a: vec![],
+        b: vec![],
+        c: vec![],
+    };
+    let mut constrain = |a, b, c| {
+        r1cs.a.push(a);
+        r1cs.b.push(b);
+        r1cs.c.push(c);
+    };
+
+    let one = F::ONE;
+    // the old path starts from an empty leaf
+    let mut old_node: LinearCombination<F> = vec![(U, F::from(ZERO_VALUE))];
+    let mut new_node: LinearCombination<F> = vec![(LEAF, one)];
+    for level in 0..depth {
+        let [bit, sibling, old_left, new_left, old_parent, new_parent] = level_vars(depth, level);
+
+        constrain(vec![(bit, one)], vec![(bit, one)], vec![(bit, one)]);
+
+        for (node, left, parent) in [
+            (&old_node, old_left, old_parent),
+            (&new_node, new_left, new_parent),
+        ] {
+            // bit * (sibling - node) = left - node
+            let mut sibling_minus_node = vec![(sibling, one)];
+            sibling_minus_node.extend(node.iter().map(|(i, c)| (*i, -*c)));
+            let mut left_minus_node = vec![(left, one)];
+            left_minus_node.extend(node.iter().map(|(i, c)| (*i, -*c)));
+            constrain(vec![(bit, one)], sibling_minus_node, left_minus_node);
+
+            // left * right = parent, with right = node + sibling - left
+            let mut right = node.clone();
+            right.extend([(sibling, one), (left, -one)]);
+            constrain(vec![(left, one)], right, vec![(parent, one)]);
+        }
+
+        old_node = vec![(old_parent, one)];
+        new_node = vec![(new_parent, one)];
+    }
+    r1cs
+}
+
+/// Fresh (`u = 1`, `E = 0`) instance for an insertion, from the leaf's Merkle path
+pub fn insertion_instance<F: PrimeField>(
+    old_root: F,
+    new_root: F,
+    leaf: F,
+    path_elements: &[F],
+    path_indices: &[F],
+) -> RelaxedInstance<F> {
+    let depth = path_elements.len();
+    let mut z = vec![F::ZERO; 4 + depth * VARS_PER_LEVEL];
+    z[U] = F::ONE;
+    z[OLD_ROOT] = old_root;
+    z[NEW_ROOT] = new_root;
+    z[LEAF] = leaf;
+
+    let mut old_node = F::from(ZERO_VALUE);
+    let mut new_node = leaf;
+    for level in 0..depth {
+        let [bit, sibling, old_left, new_left, old_parent, new_parent] = level_vars(depth, level);
+        let swap = path_indices[level] != F::ZERO;
+        z[bit] = path_indices[level];
+        z[sibling] = path_elements[level];
+
+        let (left, right) = if swap {
+            (path_elements[level], old_node)
+        } else {
+            (old_node, path_elements[level])
+        };
+        z[old_left] = left;
+        old_node = left * right;
+
+        let (left, right) = if swap {
+            (path_elements[level], new_node)
+        } else {
+            (new_node, path_elements[level])
+        };
+        z[new_left] = left;
+        new_node = left * right;
+
+        // the roots are public inputs: only fill in intermediate parents
+        if level + 1 < depth {
+            z[old_parent] = old_node;
+            z[new_parent] = new_node;
+        }
+    }
+
+    RelaxedInstance {
+        z,
+        e: vec![F::ZERO; depth * 5],
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{insertion_instance, insertion_r1cs, RelaxedInstance};
+    use crate::tree::IncrementalMerkleTree;
+    use halo2_proofs::halo2curves::pasta::Fp;
+
+    fn insertions(depth: usize, leaves: &[u64]) -> Vec<RelaxedInstance<Fp>> {
+        let mut tree = IncrementalMerkleTree::new(depth);
+        leaves
+            .iter()
+            .map(|leaf| {
+                let old_root = tree.root();
+                let index = tree.insert(Fp::from(*leaf)).unwrap();
+                let (path_elements, path_indices) = tree.proof(index).unwrap();
+                insertion_instance(
+                    old_root,
+                    tree.root(),
+                    Fp::from(*leaf),
+                    &path_elements,
+                    &path_indices,
+                )
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_fold_insertions() {
+        let r1cs = insertion_r1cs::<Fp>(3);
+        let instances = insertions(3, &[3, 5, 7, 11, 13]);
+        assert!(instances.iter().all(|i| r1cs.is_satisfied(i)));
+
+        let folded = instances[1..]
+            .iter()
+            .enumerate()
+            .fold(instances[0].clone(), |acc, (i, instance)| {
+                r1cs.fold(&acc, instance, Fp::from(0x1000 + i as u64))
+            });
+        assert!(r1cs.is_satisfied(&folded));
+    }
+
+    #[test]
+    fn test_fold_rejects_bad_insertion() {
+        let r1cs = insertion_r1cs::<Fp>(3);
+        let mut instances = insertions(3, &[3, 5]);
+        // claim a different new root for the second insertion
+        instances[1].z[2] += Fp::from(1);
+        assert!(!r1cs.is_satisfied(&instances[1]));
+
+        let folded = r1cs.fold(&instances[0], &instances[1], Fp::from(0x1234));
+        assert!(!r1cs.is_satisfied(&folded));
+    }
+}
@@ -0,0 +1,5 @@
+//! Subsystems that are still being designed, behind the `experimental` feature.
+//!
+//! Nothing in here follows semver: APIs can change or disappear in any release. Once a module
+//! stabilizes it moves to the crate root.
+pub mod folding;
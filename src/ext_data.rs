@@ -0,0 +1,82 @@
+use crate::codec::from_bytes_be;
+use halo2_proofs::halo2curves::ff::{FromUniformBytes, PrimeField};
+use sha3::{Digest, Keccak256};
+
+/// `keccak256(recipient || calldata) mod p`, the `extDataHash` public input of a withdrawal.
+/// Binding it in the proof means the recipient contract and the callback it receives
+/// (e.g. an auto-swap) can't be changed by whoever submits the transaction.
+/// A plain withdrawal to an EOA uses empty `calldata`.
+pub fn ext_data_hash<F: FromUniformBytes<64>>(recipient: &[u8; 20], calldata: &[u8]) -> F {
+    keccak_to_field(&[recipient, calldata])
+}
+
+/// `uint256(keccak256(bytes(currency))) % p`, the `assetId` of the pool holding `currency`
+/// (e.g. `"eth"` or `"dai"`). It is folded into note commitments and public on withdrawal, so
+/// one tree can back a pool per ERC-20, the contract mapping each asset id to its token.
+pub fn asset_id<F: FromUniformBytes<64>>(currency: &str) -> F {
+    keccak_to_field(&[currency.as_bytes()])
+}
+
+fn keccak_to_field<F: FromUniformBytes<64>>(data: &[&[u8]]) -> F {
+    let mut hasher = Keccak256::new();
+    for part in data {
+        hasher.update(part);
+    }
+    let digest = hasher.finalize();
+
+    // `from_uniform_bytes` reads a little-endian 512-bit integer and reduces it,
+    // which for a 256-bit value is exactly `uint256(digest) % p`
+    let mut wide = [0u8; 64];
+    wide[..32].copy_from_slice(&digest);
+    wide[..32].reverse();
+    F::from_uniform_bytes(&wide)
+}
+
+/// `uint256(uint160(address))`, how the recipient and relayer public inputs are encoded
+pub fn address_to_field<F: PrimeField<Repr = [u8; 32]>>(address: &[u8; 20]) -> F {
+    let mut bytes = [0u8; 32];
+    bytes[12..].copy_from_slice(address);
+    from_bytes_be(&bytes).expect("a 160-bit value is below the modulus")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{address_to_field, asset_id, ext_data_hash};
+    use crate::codec::from_hex;
+    use halo2_proofs::halo2curves::{bn256::Fr, ff::Field};
+
+    #[test]
+    fn test_ext_data_hash() {
+        let recipient = [0x11; 20];
+        // keccak256 is 0xe2c0...22c0, above the modulus
+        let expected =
+            from_hex::<Fr>("0x212f3a3933fb5ea564e14d4bc4c12ab431aad549d6f723ddfa6b90d4c39b22bc")
+                .unwrap();
+        assert_eq!(ext_data_hash::<Fr>(&recipient, &[]), expected);
+        assert_ne!(
+            ext_data_hash::<Fr>(&recipient, b"swap(address,uint256)"),
+            expected
+        );
+    }
+
+    #[test]
+    fn test_asset_id() {
+        // keccak256("eth") is 0x4f5b...d7f0, above the modulus
+        assert_eq!(
+            asset_id::<Fr>("eth"),
+            from_hex("0x1ef732b4a8cac04229636bb28759bb9f5266f7342d6cd1670993bedc79d3d7ef").unwrap()
+        );
+        assert_ne!(asset_id::<Fr>("dai"), asset_id::<Fr>("eth"));
+    }
+
+    #[test]
+    fn test_address_to_field() {
+        let mut address = [0; 20];
+        address[19] = 0x2a;
+        address[0] = 1;
+        assert_eq!(
+            address_to_field::<Fr>(&address),
+            Fr::from(0x2a) + Fr::from(2).pow([152])
+        );
+    }
+}
@@ -0,0 +1,482 @@
+//! C ABI for embedding the prover in mobile wallets. The header is `include/tornado_halo2.h`,
+//! regenerated with `cbindgen --config cbindgen.toml --output include/tornado_halo2.h`.
+//!
+//! Ownership rules:
+//! - Every pointer passed in is borrowed for the duration of the call only. Strings are
+//!   NUL-terminated UTF-8.
+//! - Every `TornadoBuffer` filled in by a call is owned by the caller, who must release it
+//!   with `tornado_buffer_free` exactly once. Nothing is written to it unless the call
+//!   returns `Ok`.
+//! - No call keeps state between calls, so they can be made from any thread. The only state is
+//!   a `TornadoProver`, created by `tornado_prover_new` and released with `tornado_prover_free`
+//!   exactly once. It isn't modified after creation, so it can be shared between threads.
+use crate::{
+    circuits::tornado::{TornadoCircuit, TornadoPublicInputs},
+    codec::to_bytes_be,
+    note::Note,
+    params,
+    proof::{CurveId, Proof},
+    prover,
+    witness::{PublicFile, WitnessFile},
+};
+use halo2_proofs::{
+    halo2curves::bn256::{Bn256, Fr, G1Affine},
+    plonk::{keygen_vk, ProvingKey, VerifyingKey},
+    poly::kzg::commitment::ParamsKZG,
+};
+use rand_core::OsRng;
+use std::{
+    ffi::{c_char, CStr},
+    panic::{self, AssertUnwindSafe},
+    ptr, slice,
+};
+
+/// Depth of the pool's tree
+pub const TREE_DEPTH: usize = 20;
+
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TornadoStatus {
+    Ok = 0,
+    /// A pointer is null or a string isn't UTF-8
+    InvalidArgument = 1,
+    /// A note, JSON file or proof couldn't be parsed
+    InvalidInput = 2,
+    /// The params file is missing, doesn't match its `.sha256` sidecar or has the wrong size
+    InvalidParams = 3,
+    ProvingFailed = 4,
+    VerificationFailed = 5,
+    /// A bug: the call panicked
+    Panic = 6,
+    /// The proof is valid for other public inputs than the expected ones
+    WrongPublicInputs = 7,
+}
+
+/// Bytes allocated by this library, see the ownership rules above
+#[repr(C)]
+#[derive(Debug)]
+pub struct TornadoBuffer {
+    pub data: *mut u8,
+    pub len: usize,
+}
+
+impl TornadoBuffer {
+    fn from_vec(bytes: Vec<u8>) -> Self {
+        let len = bytes.len();
+        let data = Box::into_raw(bytes.into_boxed_slice()) as *mut u8;
+        Self { data, len }
+    }
+}
+
+/// Runs `f`, turning a panic into `TornadoStatus::Panic` so it doesn't unwind into C
+fn guard(f: impl FnOnce() -> Result<(), TornadoStatus>) -> TornadoStatus {
+    match panic::catch_unwind(AssertUnwindSafe(f)) {
+        Ok(Ok(())) => TornadoStatus::Ok,
+        Ok(Err(status)) => status,
+        Err(_) => TornadoStatus::Panic,
+    }
+}
+
+unsafe fn read_str<'a>(s: *const c_char) -> Result<&'a str, TornadoStatus> {
+    if s.is_null() {
+        return Err(TornadoStatus::InvalidArgument);
+    }
+    CStr::from_ptr(s)
+        .to_str()
+        .map_err(|_| TornadoStatus::InvalidArgument)
+}
+
+unsafe fn write_out<T>(out: *mut T, value: T) -> Result<(), TornadoStatus> {
+    if out.is_null() {
+        return Err(TornadoStatus::InvalidArgument);
+    }
+    ptr::write(out, value);
+    Ok(())
+}
+
+fn load_params(path: &str) -> Result<ParamsKZG<Bn256>, TornadoStatus> {
+    params::load(path, tornado_k()).map_err(|_| TornadoStatus::InvalidParams)
+}
+
+/// The verifying key only depends on the circuit shape, so it is rebuilt from an empty circuit
+fn verifying_key(params: &ParamsKZG<Bn256>) -> Result<VerifyingKey<G1Affine>, TornadoStatus> {
+    keygen_vk(params, &TornadoCircuit::<Fr, TREE_DEPTH>::default())
+        .map_err(|_| TornadoStatus::InvalidParams)
+}
+
+/// `k` of the withdrawal circuit for `TREE_DEPTH`, i.e. the smallest one it fits in. The params
+/// file must be generated for this `k`.
+#[no_mangle]
+pub extern "C" fn tornado_k() -> u32 {
+    TornadoCircuit::<Fr, TREE_DEPTH>::min_k()
+}
+
+/// Generates a random note for the `currency` pool, e.g. `eth`. On success, `note_out` holds
+/// the note string (UTF-8, not NUL-terminated) and `commitment_out` the 32-byte big-endian
+/// commitment to deposit.
+///
+/// # Safety
+///
+/// `currency` and `denomination` must be valid NUL-terminated strings, `note_out` must point
+/// to a writable `TornadoBuffer` and `commitment_out` to 32 writable bytes.
+#[no_mangle]
+pub unsafe extern "C" fn tornado_note_new(
+    currency: *const c_char,
+    denomination: *const c_char,
+    chain_id: u64,
+    note_out: *mut TornadoBuffer,
+    commitment_out: *mut [u8; 32],
+) -> TornadoStatus {
+    guard(|| {
+        let currency = read_str(currency)?;
+        let denomination = read_str(denomination)?;
+        if note_out.is_null() || commitment_out.is_null() {
+            return Err(TornadoStatus::InvalidArgument);
+        }
+        let note = Note::<Fr>::random(currency, denomination, chain_id, OsRng)
+            .ok_or(TornadoStatus::InvalidInput)?;
+        write_out(commitment_out, to_bytes_be(&note.commitment()))?;
+        write_out(
+            note_out,
+            TornadoBuffer::from_vec(note.to_string().into_bytes()),
+        )
+    })
+}
+
+/// The proving key of the withdrawal circuit, built once by `tornado_prover_new`. Building it
+/// costs about as much as a proof at `TREE_DEPTH`, so wallets proving more than once should
+/// keep a prover rather than call `tornado_prove`.
+pub struct TornadoProver {
+    params: ParamsKZG<Bn256>,
+    pk: ProvingKey<G1Affine>,
+}
+
+impl TornadoProver {
+    fn load(params_path: &str) -> Result<Self, TornadoStatus> {
+        let params = load_params(params_path)?;
+        let pk = prover::keygen(&params, &TornadoCircuit::<Fr, TREE_DEPTH>::default())
+            .map_err(|_| TornadoStatus::InvalidParams)?;
+        Ok(Self { params, pk })
+    }
+
+    fn prove(&self, witness_json: &str, public_json: &str) -> Result<Vec<u8>, TornadoStatus> {
+        let witness: WitnessFile =
+            serde_json::from_str(witness_json).map_err(|_| TornadoStatus::InvalidInput)?;
+        let public: PublicFile =
+            serde_json::from_str(public_json).map_err(|_| TornadoStatus::InvalidInput)?;
+        let (circuit, public_inputs) =
+            TornadoCircuit::<Fr, TREE_DEPTH>::from_witness(&witness, &public)
+                .map_err(|_| TornadoStatus::InvalidInput)?;
+        let instances = public_inputs.to_instances();
+        let transcript = prover::prove(&self.params, &self.pk, circuit, &instances)
+            .map_err(|_| TornadoStatus::ProvingFailed)?;
+        Proof::new(CurveId::Bn256, tornado_k(), &instances, transcript)
+            .with_circuit_id(prover::tornado_circuit_id::<TREE_DEPTH>(self.pk.get_vk()))
+            .to_bytes()
+            .map_err(|_| TornadoStatus::ProvingFailed)
+    }
+}
+
+/// Loads the params and builds the proving key. On success, `prover_out` holds a prover to
+/// pass to `tornado_prover_prove` and release with `tornado_prover_free`.
+///
+/// # Safety
+///
+/// `params_path` must be a valid NUL-terminated string and `prover_out` must point to a
+/// writable pointer.
+#[no_mangle]
+pub unsafe extern "C" fn tornado_prover_new(
+    params_path: *const c_char,
+    prover_out: *mut *mut TornadoProver,
+) -> TornadoStatus {
+    guard(|| {
+        let params_path = read_str(params_path)?;
+        if prover_out.is_null() {
+            return Err(TornadoStatus::InvalidArgument);
+        }
+        let prover = TornadoProver::load(params_path)?;
+        write_out(prover_out, Box::into_raw(Box::new(prover)))
+    })
+}
+
+/// Proves a withdrawal from the JSON witness and public inputs described in `crate::witness`.
+/// On success, `proof_out` holds the proof in the `Proof` binary format.
+///
+/// # Safety
+///
+/// `prover` must come from `tornado_prover_new` and not have been freed, `witness_json` and
+/// `public_json` must be valid NUL-terminated strings and `proof_out` must point to a writable
+/// `TornadoBuffer`.
+#[no_mangle]
+pub unsafe extern "C" fn tornado_prover_prove(
+    prover: *const TornadoProver,
+    witness_json: *const c_char,
+    public_json: *const c_char,
+    proof_out: *mut TornadoBuffer,
+) -> TornadoStatus {
+    guard(|| {
+        let prover = prover.as_ref().ok_or(TornadoStatus::InvalidArgument)?;
+        let witness_json = read_str(witness_json)?;
+        let public_json = read_str(public_json)?;
+        if proof_out.is_null() {
+            return Err(TornadoStatus::InvalidArgument);
+        }
+        let proof = prover.prove(witness_json, public_json)?;
+        write_out(proof_out, TornadoBuffer::from_vec(proof))
+    })
+}
+
+/// Releases a prover. Freeing a null prover is a no-op.
+///
+/// # Safety
+///
+/// `prover` must come from `tornado_prover_new` and must not be used or freed again afterwards.
+#[no_mangle]
+pub unsafe extern "C" fn tornado_prover_free(prover: *mut TornadoProver) {
+    if !prover.is_null() {
+        drop(Box::from_raw(prover));
+    }
+}
+
+/// `tornado_prover_prove` with a prover built for this call only. Building the proving key
+/// roughly doubles the cost of the proof, see `TornadoProver`.
+///
+/// # Safety
+///
+/// `params_path`, `witness_json` and `public_json` must be valid NUL-terminated strings and
+/// `proof_out` must point to a writable `TornadoBuffer`.
+#[no_mangle]
+pub unsafe extern "C" fn tornado_prove(
+    params_path: *const c_char,
+    witness_json: *const c_char,
+    public_json: *const c_char,
+    proof_out: *mut TornadoBuffer,
+) -> TornadoStatus {
+    guard(|| {
+        let params_path = read_str(params_path)?;
+        let witness_json = read_str(witness_json)?;
+        let public_json = read_str(public_json)?;
+        if proof_out.is_null() {
+            return Err(TornadoStatus::InvalidArgument);
+        }
+        let proof = TornadoProver::load(params_path)?.prove(witness_json, public_json)?;
+        write_out(proof_out, TornadoBuffer::from_vec(proof))
+    })
+}
+
+/// Verifies that a proof written by `tornado_prove` is valid for the public inputs of
+/// `public_json`, in the format described in `crate::witness`. Returns `Ok` if it is,
+/// `WrongPublicInputs` if the proof is for other public inputs, e.g. another note or
+/// recipient, and `VerificationFailed` if it isn't valid.
+///
+/// # Safety
+///
+/// `params_path` and `public_json` must be valid NUL-terminated strings and `proof` must point
+/// to `proof_len` readable bytes.
+#[no_mangle]
+pub unsafe extern "C" fn tornado_verify(
+    params_path: *const c_char,
+    public_json: *const c_char,
+    proof: *const u8,
+    proof_len: usize,
+) -> TornadoStatus {
+    guard(|| {
+        let params_path = read_str(params_path)?;
+        let public: PublicFile = serde_json::from_str(read_str(public_json)?)
+            .map_err(|_| TornadoStatus::InvalidInput)?;
+        if proof.is_null() {
+            return Err(TornadoStatus::InvalidArgument);
+        }
+        let expected = public
+            .public_inputs::<Fr>()
+            .map_err(|_| TornadoStatus::InvalidInput)?;
+        let proof = Proof::from_bytes(slice::from_raw_parts(proof, proof_len))
+            .map_err(|_| TornadoStatus::InvalidInput)?;
+        if proof.curve != CurveId::Bn256 || proof.k != tornado_k() {
+            return Err(TornadoStatus::InvalidInput);
+        }
+        let public_inputs = proof
+            .public_inputs::<Fr>()
+            .ok()
+            .and_then(|instances| TornadoPublicInputs::from_instances(&instances))
+            .ok_or(TornadoStatus::InvalidInput)?;
+        if public_inputs != expected {
+            return Err(TornadoStatus::WrongPublicInputs);
+        }
+
+        let params = load_params(params_path)?;
+        let vk = verifying_key(&params)?;
+        proof
+            .require_circuit_id(&prover::tornado_circuit_id::<TREE_DEPTH>(&vk))
+            .map_err(|_| TornadoStatus::InvalidInput)?;
+        prover::verify(&params, &vk, &proof.transcript, &expected.to_instances())
+            .map_err(|_| TornadoStatus::VerificationFailed)
+    })
+}
+
+/// Releases a buffer filled in by this library. Freeing a buffer with a null `data` is a no-op.
+///
+/// # Safety
+///
+/// `buffer` must come from this library and must not be used or freed again afterwards.
+#[no_mangle]
+pub unsafe extern "C" fn tornado_buffer_free(buffer: TornadoBuffer) {
+    if !buffer.data.is_null() {
+        drop(Box::from_raw(ptr::slice_from_raw_parts_mut(
+            buffer.data,
+            buffer.len,
+        )));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        tornado_buffer_free, tornado_k, tornado_note_new, tornado_prover_free, tornado_prover_new,
+        tornado_prover_prove, tornado_verify, TornadoBuffer, TornadoStatus,
+    };
+    use crate::{
+        circuits::tornado::TornadoPublicInputs,
+        codec::to_bytes_be,
+        note::Note,
+        proof::{CurveId, Proof},
+        witness::PublicFile,
+    };
+    use halo2_proofs::halo2curves::bn256::Fr;
+    use std::{ffi::CString, ptr, slice};
+
+    const PUBLIC: &str = r#"{
+        "root": "0x1",
+        "nullifierHash": "0x2",
+        "extDataHash": "0x3",
+        "recipient": "0x1111111111111111111111111111111111111111",
+        "relayer": "0x0000000000000000000000000000000000000000",
+        "fee": "50",
+        "refund": "0",
+        "amount": "100000000",
+        "assetId": "0x4"
+    }"#;
+
+    #[test]
+    fn test_note_new() {
+        let mut note = TornadoBuffer {
+            data: ptr::null_mut(),
+            len: 0,
+        };
+        let mut commitment = [0u8; 32];
+        let currency = CString::new("dai").unwrap();
+        let denomination = CString::new("100").unwrap();
+        let status = unsafe {
+            tornado_note_new(
+                currency.as_ptr(),
+                denomination.as_ptr(),
+                1,
+                &mut note,
+                &mut commitment,
+            )
+        };
+        assert_eq!(status, TornadoStatus::Ok);
+
+        let note_str = unsafe { slice::from_raw_parts(note.data, note.len) };
+        let parsed: Note<Fr> = std::str::from_utf8(note_str).unwrap().parse().unwrap();
+        assert_eq!(parsed.currency, "dai");
+        assert_eq!(to_bytes_be(&parsed.commitment()), commitment);
+        unsafe { tornado_buffer_free(note) };
+    }
+
+    #[test]
+    fn test_invalid_arguments() {
+        let mut note = TornadoBuffer {
+            data: ptr::null_mut(),
+            len: 0,
+        };
+        let mut commitment = [0u8; 32];
+        let currency = CString::new("eth").unwrap();
+        let status = unsafe {
+            tornado_note_new(
+                currency.as_ptr(),
+                ptr::null(),
+                1,
+                &mut note,
+                &mut commitment,
+            )
+        };
+        assert_eq!(status, TornadoStatus::InvalidArgument);
+        let denomination = CString::new("0.x").unwrap();
+        let status = unsafe {
+            tornado_note_new(
+                currency.as_ptr(),
+                denomination.as_ptr(),
+                1,
+                &mut note,
+                &mut commitment,
+            )
+        };
+        assert_eq!(status, TornadoStatus::InvalidInput);
+        assert!(note.data.is_null());
+
+        let garbage = [0xffu8; 16];
+        let params_path = CString::new("params.bin").unwrap();
+        let public = CString::new(PUBLIC).unwrap();
+        let status = unsafe {
+            tornado_verify(
+                params_path.as_ptr(),
+                public.as_ptr(),
+                garbage.as_ptr(),
+                garbage.len(),
+            )
+        };
+        assert_eq!(status, TornadoStatus::InvalidInput);
+
+        let mut prover = ptr::null_mut();
+        let missing = CString::new("missing-params.bin").unwrap();
+        let status = unsafe { tornado_prover_new(missing.as_ptr(), &mut prover) };
+        assert_eq!(status, TornadoStatus::InvalidParams);
+        assert!(prover.is_null());
+        let status =
+            unsafe { tornado_prover_prove(prover, public.as_ptr(), public.as_ptr(), &mut note) };
+        assert_eq!(status, TornadoStatus::InvalidArgument);
+        unsafe { tornado_prover_free(prover) };
+    }
+
+    #[test]
+    fn test_verify_public_inputs() {
+        let expected = serde_json::from_str::<PublicFile>(PUBLIC)
+            .unwrap()
+            .public_inputs::<Fr>()
+            .unwrap();
+        let verify = |public_inputs: TornadoPublicInputs<Fr>| {
+            let proof = Proof::new(
+                CurveId::Bn256,
+                tornado_k(),
+                &public_inputs.to_instances(),
+                vec![],
+            )
+            .to_bytes()
+            .unwrap();
+            let params_path = CString::new("missing-params.bin").unwrap();
+            let public = CString::new(PUBLIC).unwrap();
+            unsafe {
+                tornado_verify(
+                    params_path.as_ptr(),
+                    public.as_ptr(),
+                    proof.as_ptr(),
+                    proof.len(),
+                )
+            }
+        };
+        // a valid proof of another withdrawal isn't one of the expected withdrawal
+        let other_recipient = TornadoPublicInputs {
+            recipient: Fr::from(0x22),
+            ..expected
+        };
+        assert_eq!(verify(other_recipient), TornadoStatus::WrongPublicInputs);
+        let other_note = TornadoPublicInputs {
+            nullifier_hash: Fr::from(3),
+            ..expected
+        };
+        assert_eq!(verify(other_note), TornadoStatus::WrongPublicInputs);
+        // matching public inputs go on to the params
+        assert_eq!(verify(expected), TornadoStatus::InvalidParams);
+    }
+}
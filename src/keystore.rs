@@ -0,0 +1,221 @@
+//! Proving keys generated once per process, for relayers and proving services that prove for
+//! many requests. [`KeyStore::get`] runs keygen the first time a key is asked for and returns
+//! the same key afterwards; requests for a key being generated wait for it rather than running
+//! keygen again.
+//!
+//! Keys are identified by `k`, the params' `params::fingerprint` and the circuit type, which
+//! fixes the tree depth and the floor planner (proving is KZG over bn256 only), so loading other
+//! params for the same `k` gets keys of its own. With a scratch directory, keys are also written
+//! to disk as `keygen_with_options` does, named after their verifying key's fingerprint: a
+//! changed circuit or params reads another file, and `invalidate` drops a fingerprint's key from
+//! memory and disk, e.g. once params are no longer used.
+use crate::{
+    error::TornadoError,
+    params,
+    prover::{self, ProverOptions},
+};
+use halo2_proofs::{
+    halo2curves::bn256::{Bn256, Fr, G1Affine},
+    plonk::{Circuit, ProvingKey},
+    poly::{commitment::Params, kzg::commitment::ParamsKZG},
+};
+use std::{
+    any::TypeId,
+    collections::HashMap,
+    fs, io,
+    path::PathBuf,
+    sync::{Arc, Mutex, PoisonError},
+};
+
+/// A generated key and the fingerprint of its verifying key
+#[derive(Clone)]
+struct Entry {
+    pk: Arc<ProvingKey<G1Affine>>,
+    fingerprint: String,
+}
+
+/// Empty until the key's first keygen succeeds
+type Slot = Arc<Mutex<Option<Entry>>>;
+
+#[derive(Default)]
+pub struct KeyStore {
+    scratch_dir: Option<PathBuf>,
+    slots: Mutex<HashMap<(u32, [u8; 32], TypeId), Slot>>,
+}
+
+impl KeyStore {
+    /// Keeps keys in memory only
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Also keeps keys in `scratch_dir`, so a restarted process reads them back instead of
+    /// running keygen
+    pub fn with_scratch_dir(scratch_dir: impl Into<PathBuf>) -> Self {
+        Self {
+            scratch_dir: Some(scratch_dir.into()),
+            slots: Mutex::default(),
+        }
+    }
+
+    /// The proving key of circuits of type `C` over `params`. Only the first call for the params
+    /// and type runs keygen (or reads the key from disk); a failed keygen is retried by the next.
+    pub fn get<C: Circuit<Fr> + 'static>(
+        &self,
+        params: &ParamsKZG<Bn256>,
+        circuit: &C,
+    ) -> Result<Arc<ProvingKey<G1Affine>>, TornadoError> {
+        let slot = self
+            .slots
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .entry((params.k(), params::fingerprint(params), TypeId::of::<C>()))
+            .or_default()
+            .clone();
+        // held during keygen, so that concurrent requests for this key wait for it
+        let mut entry = slot.lock().unwrap_or_else(PoisonError::into_inner);
+        if let Some(entry) = entry.as_ref() {
+            return Ok(entry.pk.clone());
+        }
+        let options = ProverOptions {
+            scratch_dir: self.scratch_dir.clone(),
+            ..ProverOptions::default()
+        };
+        let pk = Arc::new(prover::keygen_with_options(params, circuit, &options)?);
+        *entry = Some(Entry {
+            pk: pk.clone(),
+            fingerprint: prover::vk_fingerprint(pk.get_vk()),
+        });
+        Ok(pk)
+    }
+
+    /// Drops the key whose verifying key has `fingerprint` (see `prover::vk_fingerprint`) from
+    /// memory and from the scratch directory. Returns whether there was one.
+    pub fn invalidate(&self, fingerprint: &str) -> io::Result<bool> {
+        let mut found = false;
+        for slot in self.slots() {
+            let mut entry = slot.lock().unwrap_or_else(PoisonError::into_inner);
+            if entry.as_ref().is_some_and(|e| e.fingerprint == fingerprint) {
+                *entry = None;
+                found = true;
+            }
+        }
+        if let Some(scratch_dir) = &self.scratch_dir {
+            match fs::remove_file(prover::pk_path(scratch_dir, fingerprint)) {
+                Ok(()) => found = true,
+                Err(e) if e.kind() == io::ErrorKind::NotFound => {}
+                Err(e) => return Err(e),
+            }
+        }
+        Ok(found)
+    }
+
+    /// Number of keys in memory
+    pub fn len(&self) -> usize {
+        self.slots()
+            .iter()
+            .filter(|slot| {
+                slot.lock()
+                    .unwrap_or_else(PoisonError::into_inner)
+                    .is_some()
+            })
+            .count()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Copied out, so that waiting on a slot during its keygen doesn't block the others
+    fn slots(&self) -> Vec<Slot> {
+        self.slots
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .values()
+            .cloned()
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::KeyStore;
+    use crate::{circuits::tornado::TornadoCircuit, prover::vk_fingerprint};
+    use halo2_proofs::{
+        halo2curves::bn256::{Bn256, Fr},
+        poly::kzg::commitment::ParamsKZG,
+    };
+    use rand_core::OsRng;
+    use std::{fs, sync::Arc, thread};
+
+    #[test]
+    fn test_memoized() {
+        let params = ParamsKZG::<Bn256>::setup(8, OsRng);
+        let store = KeyStore::new();
+        assert!(store.is_empty());
+
+        // concurrent requests share a single keygen
+        let keys: Vec<_> = thread::scope(|scope| {
+            let handles: Vec<_> = (0..3)
+                .map(|_| scope.spawn(|| store.get(&params, &TornadoCircuit::<Fr, 5>::default())))
+                .collect();
+            handles
+                .into_iter()
+                .map(|h| h.join().unwrap().unwrap())
+                .collect()
+        });
+        assert!(keys.iter().all(|pk| Arc::ptr_eq(pk, &keys[0])));
+        assert_eq!(store.len(), 1);
+
+        // another k is another key
+        let larger_params = ParamsKZG::<Bn256>::setup(9, OsRng);
+        let larger = store
+            .get(&larger_params, &TornadoCircuit::<Fr, 5>::default())
+            .unwrap();
+        let fingerprint = vk_fingerprint(larger.get_vk());
+        assert_ne!(fingerprint, vk_fingerprint(keys[0].get_vk()));
+        assert_eq!(store.len(), 2);
+
+        assert!(store.invalidate(&fingerprint).unwrap());
+        assert!(!store.invalidate(&fingerprint).unwrap());
+        assert_eq!(store.len(), 1);
+        let again = store
+            .get(&larger_params, &TornadoCircuit::<Fr, 5>::default())
+            .unwrap();
+        assert!(!Arc::ptr_eq(&again, &larger));
+
+        // and so are other params for the same k
+        let other_params = ParamsKZG::<Bn256>::setup(8, OsRng);
+        let other = store
+            .get(&other_params, &TornadoCircuit::<Fr, 5>::default())
+            .unwrap();
+        assert_ne!(
+            vk_fingerprint(other.get_vk()),
+            vk_fingerprint(keys[0].get_vk())
+        );
+        assert_eq!(store.len(), 3);
+    }
+
+    #[test]
+    fn test_scratch_dir() {
+        let dir = std::env::temp_dir().join("tornado-halo2-keystore-test");
+        let _ = fs::remove_dir_all(&dir);
+        let params = ParamsKZG::<Bn256>::setup(8, OsRng);
+        let circuit = TornadoCircuit::<Fr, 5>::default();
+
+        let pk = KeyStore::with_scratch_dir(&dir)
+            .get(&params, &circuit)
+            .unwrap();
+        assert_eq!(fs::read_dir(&dir).unwrap().count(), 1);
+        // a restarted process reads the key back
+        let store = KeyStore::with_scratch_dir(&dir);
+        let read_back = store.get(&params, &circuit).unwrap();
+        let fingerprint = vk_fingerprint(pk.get_vk());
+        assert_eq!(vk_fingerprint(read_back.get_vk()), fingerprint);
+
+        assert!(store.invalidate(&fingerprint).unwrap());
+        assert!(store.is_empty());
+        assert_eq!(fs::read_dir(&dir).unwrap().count(), 0);
+        fs::remove_dir_all(dir).unwrap();
+    }
+}
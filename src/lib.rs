@@ -1,2 +1,43 @@
+//! Tornado Cash withdrawals proven with Halo2. Start from [`prelude`], which re-exports the
+//! withdrawal circuit, notes, the prover and the Merkle tree.
+pub mod async_prover;
+#[cfg(feature = "auction")]
+pub mod auction;
+pub mod bench_circuits;
+pub mod checkpoint;
 pub mod chips;
 pub mod circuits;
+pub mod codec;
+pub mod curves;
+pub mod dev;
+pub mod envelope;
+pub mod error;
+#[cfg(feature = "evm")]
+pub mod evm;
+#[cfg(feature = "experimental")]
+pub mod experimental;
+pub mod ext_data;
+#[cfg(feature = "ffi")]
+pub mod ffi;
+pub mod keystore;
+pub mod native;
+pub mod note;
+pub mod nullifier;
+pub mod offline;
+#[cfg(feature = "onchain")]
+pub mod onchain;
+mod oneshot;
+pub mod params;
+pub mod prelude;
+pub mod proof;
+pub mod prover;
+pub mod proving_service;
+pub mod recovery;
+pub mod relayer;
+#[cfg(any(test, feature = "test-utils"))]
+pub mod test_utils;
+mod trace;
+pub mod tree;
+pub mod tune;
+pub mod witness;
+pub mod witness_calculator;
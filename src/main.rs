@@ -1,129 +1,442 @@
+use clap::{Args, Parser, Subcommand, ValueEnum};
 use halo2_proofs::{
-    arithmetic::Field,
-    circuit::{Layouter, SimpleFloorPlanner, Value},
-    dev::MockProver,
-    halo2curves::{ff::PrimeField, pasta::Fp},
-    plonk::{Circuit, ConstraintSystem, Error},
+    halo2curves::{bn256::Fr, ff::PrimeField},
+    plonk::keygen_vk,
 };
-use tornado_halo2::chips::{
-    merkle::MerkleChip,
-    tornado::{TornadoChip, TornadoConfig},
+use rand_core::OsRng;
+use std::{error::Error, fs, path::PathBuf, process};
+use tornado_halo2::{
+    codec::{from_hex, to_hex},
+    dev::{self, BenchReport, CircuitStats},
+    offline, params,
+    prelude::{
+        address_to_field, ext_data_hash, CurveId, IncrementalMerkleTree, Note, Proof, PublicInputs,
+        TornadoCircuit, TornadoPublicInputs,
+    },
+    prover::{self, ProverOptions},
+    tune,
 };
 
-#[derive(Debug, Default)]
-pub struct TornadoCircuit<F> {
-    nullifier: Value<F>,
-    secret: Value<F>,
-    path_elements: Vec<Value<F>>,
-    path_indices: Vec<Value<F>>,
+/// Depth of the pool's tree. It sets the number of Merkle layers, so keys and circuit ids are
+/// only valid for this depth.
+const TREE_DEPTH: usize = 20;
+
+/// Depths `dev bench` and `dev cost` measure, the depth being a parameter of the circuit type
+const DEV_DEPTHS: [usize; 6] = [5, 10, 16, 20, 24, 32];
+
+type Result<T> = std::result::Result<T, Box<dyn Error>>;
+
+#[derive(Parser)]
+#[command(
+    name = "tornado",
+    about = "Tornado Cash notes and withdrawal proofs with Halo2"
+)]
+struct Cli {
+    #[command(subcommand)]
+    command: Command,
 }
 
-impl<F: PrimeField> Circuit<F> for TornadoCircuit<F> {
-    type Config = TornadoConfig;
-    type FloorPlanner = SimpleFloorPlanner;
+#[derive(Subcommand)]
+enum Command {
+    /// Deposit notes
+    #[command(subcommand)]
+    Note(NoteCommand),
+    /// Prove the withdrawal of a note
+    Prove(ProveArgs),
+    /// Prove a withdrawal from JSON witness and public input files, see `witness`
+    ProveWitness {
+        #[arg(long)]
+        witness: PathBuf,
+        #[arg(long)]
+        public: PathBuf,
+        /// KZG params, generated if missing
+        #[arg(long)]
+        params: PathBuf,
+        #[arg(long)]
+        out: PathBuf,
+        /// Prove in low-memory mode, keeping proving keys in this directory between runs
+        #[arg(long)]
+        scratch_dir: Option<PathBuf>,
+    },
+    /// Verify a proof written by `prove`, against the public inputs it embeds
+    Verify {
+        #[arg(long)]
+        params: PathBuf,
+        #[arg(long)]
+        proof: PathBuf,
+    },
+    /// Print the root of a tree file
+    Root {
+        /// JSON array of deposit commitments, as `0x` hex, in insertion order
+        #[arg(long)]
+        tree: PathBuf,
+    },
+    /// Verify a proof from files alone, see `offline`
+    VerifyOffline {
+        params: PathBuf,
+        vk: PathBuf,
+        proof: PathBuf,
+        instances: PathBuf,
+    },
+    /// Search layout presets and write the best one as JSON
+    Tune {
+        max_k: u32,
+        out: PathBuf,
+        /// Benchmark the low-memory prover, keeping proving keys in this directory
+        #[arg(long)]
+        scratch_dir: Option<PathBuf>,
+    },
+    /// Contributor tooling
+    #[command(subcommand)]
+    Dev(DevCommand),
+}
 
-    fn without_witnesses(&self) -> Self {
-        Self::default()
-    }
+#[derive(Args)]
+struct ProveArgs {
+    /// File holding the note string
+    #[arg(long)]
+    note: PathBuf,
+    /// JSON array of the pool's deposit commitments, as `0x` hex, in insertion order
+    #[arg(long)]
+    tree: PathBuf,
+    /// KZG params, generated if missing
+    #[arg(long)]
+    params: PathBuf,
+    #[arg(long)]
+    out: PathBuf,
+    /// Prove in low-memory mode, keeping proving keys in this directory between runs
+    #[arg(long)]
+    scratch_dir: Option<PathBuf>,
+    /// Address receiving the funds, as `0x` hex
+    #[arg(long, value_parser = parse_address)]
+    recipient: [u8; 20],
+    /// Address of the relayer submitting the withdrawal, if any
+    #[arg(long, value_parser = parse_address, default_value = "0x0000000000000000000000000000000000000000")]
+    relayer: [u8; 20],
+    /// Relayer fee, in wei
+    #[arg(long, default_value_t = 0)]
+    fee: u128,
+    /// ETH sent to the recipient along with a token withdrawal, in wei
+    #[arg(long, default_value_t = 0)]
+    refund: u128,
+}
+
+#[derive(Subcommand)]
+enum NoteCommand {
+    /// Generate a random note
+    New {
+        /// Token symbol of the pool, e.g. `dai`
+        #[arg(long, default_value = "eth")]
+        currency: String,
+        /// Pool amount in units of the currency
+        #[arg(long, default_value = "0.1")]
+        denomination: String,
+        #[arg(long, default_value_t = 1)]
+        chain_id: u64,
+        /// Write the note there instead of printing it
+        #[arg(long)]
+        out: Option<PathBuf>,
+    },
+}
+
+#[derive(Subcommand)]
+enum DevCommand {
+    /// Render the layout of the withdrawal circuit to a PNG
+    #[cfg(feature = "dev-graph")]
+    Layout {
+        /// Defaults to the smallest `k` the circuit fits in
+        #[arg(long)]
+        k: Option<u32>,
+        #[arg(long)]
+        out: PathBuf,
+    },
+    /// Print the size of the withdrawal circuit, and time its keygen, proof and verification
+    Bench {
+        #[arg(long, default_value_t = TREE_DEPTH)]
+        depth: usize,
+        #[arg(long, value_enum, default_value_t = Backend::Kzg)]
+        backend: Backend,
+        /// Print JSON instead of a table
+        #[arg(long)]
+        json: bool,
+    },
+    /// Print the size of the withdrawal circuit, without proving
+    Cost {
+        #[arg(long, default_value_t = TREE_DEPTH)]
+        depth: usize,
+        /// Print JSON instead of a table
+        #[arg(long)]
+        json: bool,
+    },
+}
 
-    fn configure(meta: &mut ConstraintSystem<F>) -> Self::Config {
-        let advice = [
-            meta.advice_column(),
-            meta.advice_column(),
-            meta.advice_column(),
-        ];
-        let instance = meta.instance_column();
-        TornadoChip::configure(meta, advice, instance)
+/// Proving backends `dev bench` can time
+#[derive(Clone, Copy, ValueEnum)]
+enum Backend {
+    /// KZG over bn256 with SHPLONK, the prover's only backend. With the `icicle` feature, its
+    /// MSMs run on the GPU.
+    Kzg,
+}
+
+fn main() {
+    let result = match Cli::parse().command {
+        Command::Note(NoteCommand::New {
+            currency,
+            denomination,
+            chain_id,
+            out,
+        }) => new_note(&currency, &denomination, chain_id, out),
+        Command::Prove(args) => prove(args),
+        Command::ProveWitness {
+            witness,
+            public,
+            params,
+            out,
+            scratch_dir,
+        } => prove_witness(witness, public, params, out, prover_options(scratch_dir)),
+        Command::Verify { params, proof } => verify(params, proof),
+        Command::Root { tree } => read_tree(tree).map(|tree| println!("{}", to_hex(&tree.root()))),
+        Command::VerifyOffline {
+            params,
+            vk,
+            proof,
+            instances,
+        } => offline::verify_files::<TornadoCircuit<Fr, TREE_DEPTH>>(
+            params,
+            vk,
+            proof,
+            instances,
+            PublicInputs::LEN,
+            TREE_DEPTH,
+        )
+        .map(|()| println!("proof is valid"))
+        .map_err(Into::into),
+        Command::Tune {
+            max_k,
+            out,
+            scratch_dir,
+        } => tune_layout(max_k, out, prover_options(scratch_dir)),
+        #[cfg(feature = "dev-graph")]
+        Command::Dev(DevCommand::Layout { k, out }) => {
+            let k = k.unwrap_or_else(TornadoCircuit::<Fr, TREE_DEPTH>::min_k);
+            dev::render_layout::<TornadoCircuit<Fr, TREE_DEPTH>>(k, out).map_err(Into::into)
+        }
+        Command::Dev(DevCommand::Bench {
+            depth,
+            backend: Backend::Kzg,
+            json,
+        }) => bench(depth).and_then(|report| print_report(&report, json)),
+        Command::Dev(DevCommand::Cost { depth, json }) => {
+            circuit_stats(depth).and_then(|stats| print_stats(depth, &stats, json))
+        }
+    };
+    if let Err(e) = result {
+        eprintln!("{e}");
+        process::exit(1);
     }
+}
 
-    fn synthesize(
-        &self,
-        config: Self::Config,
-        mut layouter: impl Layouter<F>,
-    ) -> Result<(), Error> {
-        let tornado_chip = TornadoChip::construct(config.clone());
-
-        // step 1: nullifier hash
-        let nullifier_hash_cell = tornado_chip.compute_hash(
-            layouter.namespace(|| "get nullifier hash"),
-            self.nullifier,
-            self.nullifier,
-        )?;
-        println!("nullifier_hash_cell {nullifier_hash_cell:?}");
-        layouter.constrain_instance(nullifier_hash_cell.cell(), config.clone().instance, 0)?;
-
-        // step 2: compute commitment
-        let commitment_hash_cell = tornado_chip.compute_hash(
-            layouter.namespace(|| "get nullifier hash"),
-            self.nullifier,
-            self.secret,
-        )?;
-        println!("commitment_hash_cell {commitment_hash_cell:?}");
-        let merkle_chip = MerkleChip::construct(config.clone().merkle_config);
-        let merkle_root_cell = merkle_chip.prove_tree_root(
-            layouter.namespace(|| "prove merkle tree"),
-            commitment_hash_cell,
-            self.path_elements.clone(),
-            self.path_indices.clone(),
-        )?;
-        println!("merkle_root_cell: {merkle_root_cell:?}");
-        layouter.constrain_instance(merkle_root_cell.cell(), config.clone().instance, 1)?;
-
-        Ok(())
+fn parse_address(s: &str) -> std::result::Result<[u8; 20], String> {
+    let mut address = [0u8; 20];
+    s.strip_prefix("0x")
+        .and_then(|digits| hex::decode_to_slice(digits, &mut address).ok())
+        .ok_or_else(|| format!("{s} is not a 0x-prefixed, 40-digit address"))?;
+    Ok(address)
+}
+
+fn new_note(currency: &str, denomination: &str, chain_id: u64, out: Option<PathBuf>) -> Result<()> {
+    let note = Note::<Fr>::random(currency, denomination, chain_id, OsRng)
+        .ok_or_else(|| format!("invalid currency {currency} or denomination {denomination}"))?;
+    match out {
+        Some(path) => fs::write(path, note.to_string())?,
+        None => println!("{note}"),
     }
+    Ok(())
 }
 
-fn main() {
-    let nullifier = Fp::from(0x456);
-    let secret = Fp::from(0xabc);
-    let path_elements: Vec<Fp> = vec![2, 5, 7, 14, 23].iter().map(|e| Fp::from(*e)).collect();
-    let path_indices: Vec<Fp> = vec![0, 0, 1, 1, 0].iter().map(|e| Fp::from(*e)).collect();
-
-    let circuit = TornadoCircuit {
-        nullifier: Value::known(nullifier),
-        secret: Value::known(secret),
-        path_elements: path_elements.iter().map(|e| Value::known(*e)).collect(),
-        path_indices: path_indices.iter().map(|e| Value::known(*e)).collect(),
+fn read_tree(path: PathBuf) -> Result<IncrementalMerkleTree<Fr>> {
+    let leaves: Vec<String> = serde_json::from_slice(&fs::read(path)?)?;
+    let leaves = leaves
+        .iter()
+        .map(|leaf| from_hex(leaf))
+        .collect::<std::result::Result<Vec<Fr>, _>>()?;
+    let mut tree = IncrementalMerkleTree::new(TREE_DEPTH);
+    tree.insert_batch(&leaves)?;
+    Ok(tree)
+}
+
+/// `ProverOptions::low_memory` with a scratch directory, the defaults otherwise
+fn prover_options(scratch_dir: Option<PathBuf>) -> ProverOptions {
+    scratch_dir.map_or_else(ProverOptions::default, ProverOptions::low_memory)
+}
+
+fn prove(args: ProveArgs) -> Result<()> {
+    let note: Note<Fr> = fs::read_to_string(args.note)?.trim().parse()?;
+    let tree = read_tree(args.tree)?;
+    let index = tree
+        .leaves()
+        .iter()
+        .position(|leaf| *leaf == note.commitment())
+        .ok_or("the note's commitment is not in the tree")?;
+    let merkle_proof = tree.proof(index).expect("the leaf exists").into();
+
+    let public_inputs = TornadoPublicInputs {
+        nullifier_hash: note.nullifier_hash(),
+        root: tree.root(),
+        ext_data_hash: ext_data_hash(&args.recipient, &[]),
+        recipient: address_to_field(&args.recipient),
+        relayer: address_to_field(&args.relayer),
+        fee: Fr::from_u128(args.fee),
+        refund: Fr::from_u128(args.refund),
+        amount: Fr::from(note.amount()),
+        asset_id: note.asset_id(),
     };
+    let circuit = TornadoCircuit::<Fr, TREE_DEPTH>::builder(&note, merkle_proof)
+        .ext_data_hash(public_inputs.ext_data_hash)
+        .recipient(public_inputs.recipient)
+        .relayer(public_inputs.relayer)
+        .fee(public_inputs.fee)
+        .refund(public_inputs.refund)
+        .build()?;
 
-    let commitment = hash_values(vec![nullifier, secret]);
-    println!("commitment {:?}", commitment);
+    let options = prover_options(args.scratch_dir);
+    prove_circuit(circuit, public_inputs, args.params, args.out, options)
+}
 
-    let root = compute_root(commitment, path_elements, path_indices);
-    println!("root {:?}", root);
+fn prove_witness(
+    witness: PathBuf,
+    public: PathBuf,
+    params: PathBuf,
+    out: PathBuf,
+    options: ProverOptions,
+) -> Result<()> {
+    let (circuit, public_inputs) =
+        TornadoCircuit::<Fr, TREE_DEPTH>::from_witness_file(witness, public)?;
+    prove_circuit(circuit, public_inputs, params, out, options)
+}
+
+fn prove_circuit(
+    circuit: TornadoCircuit<Fr, TREE_DEPTH>,
+    public_inputs: TornadoPublicInputs<Fr>,
+    params: PathBuf,
+    out: PathBuf,
+    options: ProverOptions,
+) -> Result<()> {
+    let instances = public_inputs.to_instances();
+    let k = TornadoCircuit::<Fr, TREE_DEPTH>::min_k();
+    let params = params::load_or_generate(params, k)?;
+    let pk = prover::keygen_with_options(&params, &circuit, &options)?;
+    let transcript = prover::prove_with_options(&params, &pk, circuit, &instances, &options)?;
+    let proof = Proof::new(CurveId::Bn256, k, &instances, transcript)
+        .with_circuit_id(prover::tornado_circuit_id::<TREE_DEPTH>(pk.get_vk()));
+    fs::write(out, proof.to_bytes()?)?;
+    Ok(())
+}
 
-    let nullifier_hash = hash_value(nullifier);
-    println!("nullifier_hash {:?}", nullifier_hash);
+fn verify(params: PathBuf, proof: PathBuf) -> Result<()> {
+    let proof = Proof::from_bytes(&fs::read(proof)?)?;
+    if proof.curve != CurveId::Bn256 {
+        return Err("proof is not over bn256".into());
+    }
+    let public_inputs = TornadoPublicInputs::from_instances(&proof.public_inputs::<Fr>()?)
+        .ok_or("proof doesn't have the withdrawal public inputs")?;
+    let params = params::load(params, proof.k)?;
+    let vk = keygen_vk(&params, &TornadoCircuit::<Fr, TREE_DEPTH>::default())?;
+    proof.check_circuit_id(&prover::tornado_circuit_id::<TREE_DEPTH>(&vk))?;
+    prover::verify(
+        &params,
+        &vk,
+        &proof.transcript,
+        &public_inputs.to_instances(),
+    )
+    .map_err(|_| "proof verification failed")?;
+    println!("proof is valid");
+    Ok(())
+}
 
-    let public_input = vec![nullifier_hash, root];
-    let prover = MockProver::run(10, &circuit, vec![public_input]).unwrap();
+fn tune_layout(max_k: u32, out: PathBuf, options: ProverOptions) -> Result<()> {
+    let presets = tune::tune::<TREE_DEPTH, { TREE_DEPTH / 2 }>(max_k, Some(&options))?;
+    for preset in presets.iter() {
+        println!("{preset:?}");
+    }
+    let best = presets
+        .first()
+        .ok_or_else(|| format!("no layout fits in k = {max_k}"))?;
+    fs::write(out, serde_json::to_string_pretty(best)?)?;
+    Ok(())
+}
 
-    println!("MAIN prover: {:?}", prover.verify());
-    // assert!(prover.verify().is_ok());
+fn unsupported_depth(depth: usize) -> Box<dyn Error> {
+    format!("unsupported depth {depth}, expected one of {DEV_DEPTHS:?}").into()
 }
 
-fn hash_value(value: Fp) -> Fp {
-    hash_values(vec![value, value])
+fn circuit_stats(depth: usize) -> Result<CircuitStats> {
+    Ok(match depth {
+        5 => dev::circuit_stats::<5>()?,
+        10 => dev::circuit_stats::<10>()?,
+        16 => dev::circuit_stats::<16>()?,
+        20 => dev::circuit_stats::<20>()?,
+        24 => dev::circuit_stats::<24>()?,
+        32 => dev::circuit_stats::<32>()?,
+        _ => return Err(unsupported_depth(depth)),
+    })
 }
-fn hash_values(values: Vec<Fp>) -> Fp {
-    values.iter().product()
+
+fn bench(depth: usize) -> Result<BenchReport> {
+    Ok(match depth {
+        5 => dev::bench::<5>()?,
+        10 => dev::bench::<10>()?,
+        16 => dev::bench::<16>()?,
+        20 => dev::bench::<20>()?,
+        24 => dev::bench::<24>()?,
+        32 => dev::bench::<32>()?,
+        _ => return Err(unsupported_depth(depth)),
+    })
 }
 
-fn compute_root(leaf: Fp, path_elements: Vec<Fp>, path_indices: Vec<Fp>) -> Fp {
-    assert!(path_elements.len() == path_indices.len());
+/// Rows of the `dev` tables
+fn stats_rows(depth: usize, stats: &CircuitStats) -> Vec<(&'static str, String)> {
+    vec![
+        ("depth", depth.to_string()),
+        ("rows", stats.rows.to_string()),
+        ("advice columns", stats.advice_columns.to_string()),
+        ("fixed columns", stats.fixed_columns.to_string()),
+        ("instance columns", stats.instance_columns.to_string()),
+        ("selectors", stats.selectors.to_string()),
+        ("degree", stats.degree.to_string()),
+        ("min k", stats.min_k.to_string()),
+    ]
+}
 
-    let mut node = leaf;
-    for i in 0..path_elements.len() {
-        let mut left = node;
-        let mut right = path_elements[i];
+fn print_table(rows: &[(&str, String)]) {
+    let width = rows.iter().map(|(name, _)| name.len()).max().unwrap_or(0);
+    for (name, value) in rows {
+        println!("{name:<width$}  {value}");
+    }
+}
 
-        (left, right) = if path_indices[i] == Fp::ZERO {
-            (left, right)
-        } else {
-            (right, left)
-        };
+fn print_stats(depth: usize, stats: &CircuitStats, json: bool) -> Result<()> {
+    if json {
+        println!("{}", serde_json::to_string_pretty(stats)?);
+    } else {
+        print_table(&stats_rows(depth, stats));
+    }
+    Ok(())
+}
 
-        node = hash_values(vec![left, right]);
+fn print_report(report: &BenchReport, json: bool) -> Result<()> {
+    if json {
+        println!("{}", serde_json::to_string_pretty(report)?);
+        return Ok(());
     }
-    node
+    let mut rows = stats_rows(report.depth, &report.stats);
+    rows.extend([
+        ("backend", "kzg".to_string()),
+        ("keygen", format!("{} ms", report.keygen_ms)),
+        ("prove", format!("{} ms", report.prove_ms)),
+        ("verify", format!("{} ms", report.verify_ms)),
+        ("proof size", format!("{} bytes", report.proof_bytes)),
+    ]);
+    print_table(&rows);
+    Ok(())
 }
@@ -0,0 +1,150 @@
+//! The hashes the circuits constrain, computed outside of them: commitments, nullifier hashes
+//! and roots that a withdrawal circuit accepts. They all go through `tree::ProductHasher`, the
+//! native counterpart of `HashChip`, and the tests below run them against the circuits.
+use crate::tree::{Hasher, ProductHasher};
+use halo2_proofs::halo2curves::ff::PrimeField;
+
+/// `H(value, value)`, e.g. the nullifier hash of a nullifier
+pub fn hash_value<F: PrimeField>(value: F) -> F {
+    ProductHasher::hash(value, value)
+}
+
+/// `H(...H(H(v_0, v_1), v_2)..., v_{N-1})`, or zero if `values` is empty. A note commitment is
+/// `hash_values(&[nullifier, secret, amount, asset_id])`.
+pub fn hash_values<F: PrimeField>(values: &[F]) -> F {
+    values
+        .iter()
+        .copied()
+        .reduce(<ProductHasher as Hasher<F>>::hash)
+        .unwrap_or(F::ZERO)
+}
+
+/// Root of the tree holding `leaf` at the given path, hashed in the order of `MerkleChip`: the
+/// node is on the left when its path index is `0` and on the right when it is `1`. Returns
+/// `None` if the lengths differ or an index is not a bit, as the circuit would reject the path.
+pub fn compute_root<F: PrimeField>(leaf: F, path_elements: &[F], path_indices: &[F]) -> Option<F> {
+    if path_elements.len() != path_indices.len() {
+        return None;
+    }
+
+    let mut node = leaf;
+    for (element, index) in path_elements.iter().zip(path_indices) {
+        let (left, right) = match *index {
+            index if index == F::ZERO => (node, *element),
+            index if index == F::ONE => (*element, node),
+            _ => return None,
+        };
+        node = ProductHasher::hash(left, right);
+    }
+    Some(node)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{compute_root, hash_value, hash_values};
+    use crate::{
+        circuits::{
+            batch_deposit::commitments_hash,
+            hash::HashCircuit,
+            tornado::{TornadoCircuit, TornadoPublicInputs},
+        },
+        ext_data::asset_id,
+        note::{self, Note},
+        tree::{IncrementalMerkleTree, MerkleProof},
+    };
+    use halo2_proofs::{circuit::Value, dev::MockProver, halo2curves::pasta::Fp};
+
+    #[test]
+    fn test_hash_gate() {
+        let (a, b) = (Fp::from(11), Fp::from(7));
+        let circuit = HashCircuit {
+            a: Value::known(a),
+            b: Value::known(b),
+        };
+        let prover = MockProver::run(4, &circuit, vec![vec![hash_values(&[a, b])]]).unwrap();
+        assert!(prover.verify().is_ok());
+
+        let circuit = HashCircuit {
+            a: Value::known(a),
+            b: Value::known(a),
+        };
+        let prover = MockProver::run(4, &circuit, vec![vec![hash_value(a)]]).unwrap();
+        assert!(prover.verify().is_ok());
+    }
+
+    #[test]
+    fn test_hash_values() {
+        let (nullifier, secret, amount) = (Fp::from(0x456), Fp::from(0xabc), Fp::from(100));
+        let asset_id = asset_id::<Fp>("eth");
+        assert_eq!(
+            hash_values(&[nullifier, secret, amount, asset_id]),
+            note::commitment(nullifier, secret, amount, asset_id)
+        );
+        let commitments = [11, 13, 17].map(Fp::from);
+        assert_eq!(hash_values(&commitments), commitments_hash(&commitments));
+        assert_eq!(hash_values::<Fp>(&[]), Fp::from(0));
+    }
+
+    #[test]
+    fn test_compute_root() {
+        let mut tree = IncrementalMerkleTree::new(4);
+        for leaf in [3, 5, 7, 11, 13] {
+            tree.insert(Fp::from(leaf)).unwrap();
+        }
+        for (index, leaf) in tree.leaves().iter().enumerate() {
+            let (path_elements, path_indices) = tree.proof(index).unwrap();
+            assert_eq!(
+                compute_root(*leaf, &path_elements, &path_indices),
+                Some(tree.root())
+            );
+        }
+
+        let (path_elements, mut path_indices) = tree.proof(1).unwrap();
+        assert_eq!(
+            compute_root(Fp::from(5), &path_elements[1..], &path_indices),
+            None
+        );
+        path_indices[0] = Fp::from(2);
+        assert_eq!(
+            compute_root(Fp::from(5), &path_elements, &path_indices),
+            None
+        );
+    }
+
+    #[test]
+    fn test_circuit_accepts_native_values() {
+        let note = Note::new(Fp::from(0x456), Fp::from(0xabc), "eth", "0.1", 1).unwrap();
+        let amount = Fp::from(note.amount());
+        let commitment = hash_values(&[note.nullifier, note.secret, amount, note.asset_id()]);
+
+        let mut tree = IncrementalMerkleTree::new(5);
+        for leaf in [3, 5, 7] {
+            tree.insert(Fp::from(leaf)).unwrap();
+        }
+        let index = tree.insert(commitment).unwrap();
+        let merkle_proof: MerkleProof<Fp> = tree.proof(index).unwrap().into();
+        let root = compute_root(
+            commitment,
+            &merkle_proof.path_elements,
+            &merkle_proof.path_indices,
+        )
+        .unwrap();
+
+        let circuit = TornadoCircuit::<Fp, 5>::builder(&note, merkle_proof)
+            .build()
+            .unwrap();
+        let public_inputs = TornadoPublicInputs {
+            nullifier_hash: hash_value(note.nullifier),
+            root,
+            ext_data_hash: Fp::from(0),
+            recipient: Fp::from(0),
+            relayer: Fp::from(0),
+            fee: Fp::from(0),
+            refund: Fp::from(0),
+            amount,
+            asset_id: note.asset_id(),
+        };
+        let prover = MockProver::run(10, &circuit, public_inputs.to_instances()).unwrap();
+        assert!(prover.verify().is_ok());
+    }
+}
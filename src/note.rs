@@ -0,0 +1,718 @@
+//! Deposit notes, the secret a depositor keeps between deposit and withdrawal.
+//!
+//! A note serializes as `tornado-<currency>-<denomination>-<chain id>-0x<hex>` like Tornado's,
+//! the hex being the 32-byte big-endian nullifier followed by the secret (Tornado packs two
+//! 31-byte values instead, which can't hold every field element).
+//!
+//! The commitment binds the note's amount and asset, `H(H(H(nullifier, secret), amount),
+//! asset_id)`, so a single tree can hold any denomination of any ERC-20: see `commitment`.
+//!
+//! Notes can also be derived from a wallet seed, so that a single backup phrase recovers every
+//! deposit: see `derive` and `scan`. For random notes, `encrypt` produces a blob that can be
+//! published on-chain with the deposit, like Tornado's note backups, and recovered later with
+//! the account's x25519 key.
+use crate::{
+    codec::{self, from_bytes_be, to_bytes_be},
+    envelope::{self, Envelope, Kind, UNBOUND},
+    ext_data::asset_id,
+    proof::CurveId,
+    tree::{Hasher, IncrementalMerkleTree, ProductHasher},
+};
+use chacha20poly1305::{aead::Aead, ChaCha20Poly1305, KeyInit, Nonce};
+use halo2_proofs::halo2curves::ff::{FromUniformBytes, PrimeField};
+use hkdf::Hkdf;
+use rand_core::{CryptoRng, RngCore};
+use sha2::{Sha256, Sha512};
+use std::{collections::HashMap, fmt, str::FromStr};
+use x25519_dalek::{EphemeralSecret, PublicKey, StaticSecret};
+
+const PREFIX: &str = "tornado";
+const DERIVATION_SALT: &[u8] = b"tornado-halo2 note derivation";
+const BACKUP_INFO: &[u8] = b"tornado-halo2 note backup";
+/// First byte of an encrypted note, to allow changing the scheme later
+const BACKUP_VERSION: u8 = 2;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Error {
+    /// Not `tornado-<currency>-<denomination>-<chain id>-0x<hex>`
+    InvalidFormat,
+    InvalidCurrency(String),
+    InvalidDenomination(String),
+    InvalidChainId(String),
+    InvalidSecret(codec::Error),
+    /// Not a note in the binary encoding, or a note of another curve
+    InvalidEncoding,
+    InvalidEnvelope(envelope::Error),
+    /// Wrong key, or a corrupted or tampered backup
+    DecryptionFailed,
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::InvalidFormat => write!(f, "invalid note format"),
+            Error::InvalidCurrency(currency) => write!(f, "invalid currency {currency}"),
+            Error::InvalidDenomination(denomination) => {
+                write!(f, "invalid denomination {denomination}")
+            }
+            Error::InvalidChainId(chain_id) => write!(f, "invalid chain id {chain_id}"),
+            Error::InvalidSecret(e) => write!(f, "invalid note secret: {e}"),
+            Error::InvalidEncoding => write!(f, "invalid binary note"),
+            Error::InvalidEnvelope(e) => write!(f, "invalid note envelope: {e}"),
+            Error::DecryptionFailed => write!(f, "note backup decryption failed"),
+        }
+    }
+}
+
+impl std::error::Error for Error {}
+
+impl From<codec::Error> for Error {
+    fn from(e: codec::Error) -> Self {
+        Error::InvalidSecret(e)
+    }
+}
+
+impl From<envelope::Error> for Error {
+    fn from(e: envelope::Error) -> Self {
+        Error::InvalidEnvelope(e)
+    }
+}
+
+/// Note amounts are committed in units of 10^-9 of the asset (gwei for ETH), so that any
+/// denomination up to `u64::MAX` units (about 18 billion ETH) passes the circuit's 64-bit range
+/// check
+pub const AMOUNT_DECIMALS: usize = 9;
+
+/// A decimal amount of the asset such as `0.1` or `100`, in units of 10^-9. Returns `None` if
+/// it has more than `AMOUNT_DECIMALS` decimals or doesn't fit in a `u64`.
+pub fn denomination_to_amount(s: &str) -> Option<u64> {
+    let (integer, fraction) = s.split_once('.').unwrap_or((s, "0"));
+    let is_digits = |part: &str| !part.is_empty() && part.bytes().all(|b| b.is_ascii_digit());
+    if !is_digits(integer) || !is_digits(fraction) || fraction.len() > AMOUNT_DECIMALS {
+        return None;
+    }
+    let integer: u64 = integer.parse().ok()?;
+    let fraction: u64 = format!("{fraction:0<AMOUNT_DECIMALS$}").parse().ok()?;
+    integer
+        .checked_mul(10u64.pow(AMOUNT_DECIMALS as u32))?
+        .checked_add(fraction)
+}
+
+fn is_denomination(s: &str) -> bool {
+    denomination_to_amount(s).is_some()
+}
+
+/// A lowercase token symbol such as `eth` or `usdc`
+fn is_currency(s: &str) -> bool {
+    !s.is_empty()
+        && s.len() <= u8::MAX as usize
+        && s.bytes()
+            .all(|b| b.is_ascii_lowercase() || b.is_ascii_digit())
+}
+
+/// `H(H(H(nullifier, secret), amount), asset_id)`, the leaf of a note worth `amount` units of
+/// the asset `asset_id` (see `ext_data::asset_id`)
+pub fn commitment<F: PrimeField>(nullifier: F, secret: F, amount: F, asset_id: F) -> F {
+    ProductHasher::hash(
+        ProductHasher::hash(ProductHasher::hash(nullifier, secret), amount),
+        asset_id,
+    )
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Note<F> {
+    pub nullifier: F,
+    pub secret: F,
+    /// Token symbol of the pool, e.g. `eth`
+    pub currency: String,
+    /// Pool amount in units of the asset, e.g. `0.1`
+    pub denomination: String,
+    pub chain_id: u64,
+}
+
+impl<F: PrimeField<Repr = [u8; 32]>> Note<F> {
+    /// Returns `None` if `currency` isn't a lowercase token symbol or `denomination` isn't a
+    /// decimal amount, see `denomination_to_amount`
+    pub fn new(
+        nullifier: F,
+        secret: F,
+        currency: &str,
+        denomination: &str,
+        chain_id: u64,
+    ) -> Option<Self> {
+        (is_currency(currency) && is_denomination(denomination)).then(|| Self {
+            nullifier,
+            secret,
+            currency: currency.to_string(),
+            denomination: denomination.to_string(),
+            chain_id,
+        })
+    }
+
+    /// A fresh note with a random nullifier and secret
+    pub fn random(
+        currency: &str,
+        denomination: &str,
+        chain_id: u64,
+        mut rng: impl RngCore,
+    ) -> Option<Self> {
+        Self::new(
+            F::random(&mut rng),
+            F::random(&mut rng),
+            currency,
+            denomination,
+            chain_id,
+        )
+    }
+
+    /// The denomination in units of 10^-9 of the asset
+    pub fn amount(&self) -> u64 {
+        denomination_to_amount(&self.denomination).expect("checked when the note was built")
+    }
+
+    /// Revealed on withdrawal to prevent double spends
+    pub fn nullifier_hash(&self) -> F {
+        ProductHasher::hash(self.nullifier, self.nullifier)
+    }
+
+    /// `H(secret, secret)`, given to an auditor to check which withdrawals spent this note,
+    /// see `circuits::viewing`. It doesn't open the note, so it can't withdraw it.
+    pub fn viewing_key(&self) -> F {
+        ProductHasher::hash(self.secret, self.secret)
+    }
+
+    /// Binary encoding: an `Envelope` of the note's curve around its `payload`
+    pub fn to_bytes(&self) -> Vec<u8> {
+        Envelope {
+            kind: Kind::Note,
+            curve: CurveId::of::<F>(),
+            circuit_id: UNBOUND,
+            payload: self.payload(),
+        }
+        .to_bytes()
+    }
+
+    /// Also reads the bare payload written before envelopes
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, Error> {
+        match Envelope::open(bytes, Kind::Note)? {
+            Some(envelope) if envelope.curve != CurveId::of::<F>() => Err(Error::InvalidEncoding),
+            Some(envelope) => Self::from_payload(&envelope.payload),
+            None => Self::from_payload(bytes),
+        }
+    }
+
+    /// Compact binary encoding: the big-endian nullifier and secret, the big-endian chain id,
+    /// then the length-prefixed denomination and currency
+    fn payload(&self) -> Vec<u8> {
+        let mut bytes =
+            Vec::with_capacity(32 + 32 + 8 + 1 + self.denomination.len() + 1 + self.currency.len());
+        bytes.extend(to_bytes_be(&self.nullifier));
+        bytes.extend(to_bytes_be(&self.secret));
+        bytes.extend(self.chain_id.to_be_bytes());
+        for s in [&self.denomination, &self.currency] {
+            bytes.push(s.len() as u8);
+            bytes.extend(s.as_bytes());
+        }
+        bytes
+    }
+
+    fn from_payload(bytes: &[u8]) -> Result<Self, Error> {
+        if bytes.len() < 32 + 32 + 8 {
+            return Err(Error::InvalidEncoding);
+        }
+        let (nullifier, rest) = bytes.split_at(32);
+        let (secret, rest) = rest.split_at(32);
+        let (chain_id, rest) = rest.split_at(8);
+        let (denomination, rest) = split_str(rest)?;
+        let (currency, rest) = split_str(rest)?;
+        if !rest.is_empty() {
+            return Err(Error::InvalidEncoding);
+        }
+        if !is_currency(currency) {
+            return Err(Error::InvalidCurrency(currency.to_string()));
+        }
+
+        Self::new(
+            from_bytes_be(nullifier.try_into().expect("32 bytes"))?,
+            from_bytes_be(secret.try_into().expect("32 bytes"))?,
+            currency,
+            denomination,
+            u64::from_be_bytes(chain_id.try_into().expect("8 bytes")),
+        )
+        .ok_or_else(|| Error::InvalidDenomination(denomination.to_string()))
+    }
+}
+
+impl<F: PrimeField<Repr = [u8; 32]> + FromUniformBytes<64>> Note<F> {
+    /// The `assetId` of the note's pool, see `ext_data::asset_id`
+    pub fn asset_id(&self) -> F {
+        asset_id(&self.currency)
+    }
+
+    /// The leaf inserted in the tree on deposit
+    pub fn commitment(&self) -> F {
+        commitment(
+            self.nullifier,
+            self.secret,
+            F::from(self.amount()),
+            self.asset_id(),
+        )
+    }
+}
+
+/// Splits a `u8` length-prefixed UTF-8 string off the front of `bytes`
+fn split_str(bytes: &[u8]) -> Result<(&str, &[u8]), Error> {
+    let (&len, rest) = bytes.split_first().ok_or(Error::InvalidEncoding)?;
+    if rest.len() < len as usize {
+        return Err(Error::InvalidEncoding);
+    }
+    let (s, rest) = rest.split_at(len as usize);
+    let s = std::str::from_utf8(s).map_err(|_| Error::InvalidEncoding)?;
+    Ok((s, rest))
+}
+
+impl<F: PrimeField<Repr = [u8; 32]>> fmt::Display for Note<F> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{PREFIX}-{}-{}-{}-0x{}{}",
+            self.currency,
+            self.denomination,
+            self.chain_id,
+            hex::encode(to_bytes_be(&self.nullifier)),
+            hex::encode(to_bytes_be(&self.secret)),
+        )
+    }
+}
+
+impl<F: PrimeField<Repr = [u8; 32]>> FromStr for Note<F> {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Error> {
+        let [prefix, currency, denomination, chain_id, secrets]: [&str; 5] = s
+            .split('-')
+            .collect::<Vec<_>>()
+            .try_into()
+            .map_err(|_| Error::InvalidFormat)?;
+        if prefix != PREFIX {
+            return Err(Error::InvalidFormat);
+        }
+        if !is_currency(currency) {
+            return Err(Error::InvalidCurrency(currency.to_string()));
+        }
+        if !is_denomination(denomination) {
+            return Err(Error::InvalidDenomination(denomination.to_string()));
+        }
+        // `u64::from_str` accepts a leading `+`, which would give two strings for one note
+        if !chain_id.bytes().all(|b| b.is_ascii_digit()) {
+            return Err(Error::InvalidChainId(chain_id.to_string()));
+        }
+        let chain_id = chain_id
+            .parse()
+            .map_err(|_| Error::InvalidChainId(chain_id.to_string()))?;
+
+        let digits = secrets.strip_prefix("0x").ok_or(Error::InvalidFormat)?;
+        let mut bytes = [0u8; 64];
+        hex::decode_to_slice(digits, &mut bytes).map_err(|_| codec::Error::InvalidHex)?;
+        let (nullifier, secret) = bytes.split_at(32);
+        Ok(Self {
+            nullifier: from_bytes_be(nullifier.try_into().expect("32 bytes"))?,
+            secret: from_bytes_be(secret.try_into().expect("32 bytes"))?,
+            currency: currency.to_string(),
+            denomination: denomination.to_string(),
+            chain_id,
+        })
+    }
+}
+
+/// The 64-byte seed of a BIP-39 mnemonic: PBKDF2-HMAC-SHA512 over the phrase, 2048 rounds,
+/// salted with `"mnemonic" || passphrase`. The phrase isn't checked against the wordlist.
+pub fn mnemonic_to_seed(phrase: &str, passphrase: &str) -> [u8; 64] {
+    let mut seed = [0u8; 64];
+    pbkdf2::pbkdf2_hmac::<Sha512>(
+        phrase.as_bytes(),
+        format!("mnemonic{passphrase}").as_bytes(),
+        2048,
+        &mut seed,
+    );
+    seed
+}
+
+/// Field element for `label` from the HKDF-SHA256 key of the note. 64 bytes are reduced so the
+/// result is uniform.
+fn derive_element<F: FromUniformBytes<64>>(hkdf: &Hkdf<Sha256>, info: &str, label: &str) -> F {
+    let mut wide = [0u8; 64];
+    hkdf.expand_multi_info(&[info.as_bytes(), b"/", label.as_bytes()], &mut wide)
+        .expect("64 bytes is a valid HKDF-SHA256 output length");
+    F::from_uniform_bytes(&wide)
+}
+
+/// The `index`-th note of `seed` for the pool of `denomination` `currency` on `chain_id`. The
+/// pool is part of the derivation, so the same index gives unrelated notes in different pools.
+/// Returns `None` if `currency` or `denomination` is invalid, see `Note::new`.
+pub fn derive<F>(
+    seed: &[u8],
+    index: u32,
+    currency: &str,
+    denomination: &str,
+    chain_id: u64,
+) -> Option<Note<F>>
+where
+    F: PrimeField<Repr = [u8; 32]> + FromUniformBytes<64>,
+{
+    let hkdf = Hkdf::<Sha256>::new(Some(DERIVATION_SALT), seed);
+    let info = format!("{currency}-{denomination}-{chain_id}-{index}");
+    Note::new(
+        derive_element(&hkdf, &info, "nullifier"),
+        derive_element(&hkdf, &info, "secret"),
+        currency,
+        denomination,
+        chain_id,
+    )
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Scanned<F> {
+    /// Derivation index of the note
+    pub index: u32,
+    /// Position of its commitment in the tree
+    pub leaf_index: usize,
+    pub note: Note<F>,
+}
+
+/// Derives notes `0..count` and returns those whose commitment is a leaf of `tree`, i.e. the
+/// deposits made from `seed` in that pool
+pub fn scan<F, H>(
+    seed: &[u8],
+    count: u32,
+    currency: &str,
+    denomination: &str,
+    chain_id: u64,
+    tree: &IncrementalMerkleTree<F, H>,
+) -> Vec<Scanned<F>>
+where
+    F: PrimeField<Repr = [u8; 32]> + FromUniformBytes<64>,
+    H: Hasher<F>,
+{
+    let leaves: HashMap<[u8; 32], usize> = tree
+        .leaves()
+        .iter()
+        .enumerate()
+        .map(|(i, leaf)| (leaf.to_repr(), i))
+        .collect();
+
+    (0..count)
+        .filter_map(|index| {
+            let note = derive::<F>(seed, index, currency, denomination, chain_id)?;
+            leaves
+                .get(&note.commitment().to_repr())
+                .map(|leaf_index| Scanned {
+                    index,
+                    leaf_index: *leaf_index,
+                    note,
+                })
+        })
+        .collect()
+}
+
+/// The x25519 public key of an account's secret key, which notes are encrypted to
+pub fn public_key(secret_key: &[u8; 32]) -> [u8; 32] {
+    PublicKey::from(&StaticSecret::from(*secret_key)).to_bytes()
+}
+
+/// ChaCha20-Poly1305 key for a backup. Each backup has a fresh ephemeral key, so the key is
+/// never reused and the nonce can be fixed.
+fn backup_cipher(
+    shared_secret: &[u8; 32],
+    ephemeral: &[u8; 32],
+    recipient: &[u8; 32],
+) -> ChaCha20Poly1305 {
+    let mut key = [0u8; 32];
+    Hkdf::<Sha256>::new(Some(BACKUP_INFO), shared_secret)
+        .expand_multi_info(&[ephemeral, recipient], &mut key)
+        .expect("32 bytes is a valid HKDF-SHA256 output length");
+    ChaCha20Poly1305::new(&key.into())
+}
+
+/// Encrypts `note` to `public_key`: version byte, ephemeral x25519 public key, then the
+/// encrypted note payload, without an envelope, and its tag
+pub fn encrypt<F: PrimeField<Repr = [u8; 32]>>(
+    note: &Note<F>,
+    public_key: &[u8; 32],
+    rng: impl RngCore + CryptoRng,
+) -> Vec<u8> {
+    let ephemeral = EphemeralSecret::random_from_rng(rng);
+    let ephemeral_public = PublicKey::from(&ephemeral).to_bytes();
+    let shared_secret = ephemeral.diffie_hellman(&PublicKey::from(*public_key));
+
+    let ciphertext = backup_cipher(shared_secret.as_bytes(), &ephemeral_public, public_key)
+        .encrypt(&Nonce::default(), note.payload().as_slice())
+        .expect("a note is far below the ChaCha20-Poly1305 message limit");
+
+    let mut blob = vec![BACKUP_VERSION];
+    blob.extend(ephemeral_public);
+    blob.extend(ciphertext);
+    blob
+}
+
+/// Decrypts a blob from `encrypt` with the account's secret key
+pub fn decrypt<F: PrimeField<Repr = [u8; 32]>>(
+    blob: &[u8],
+    secret_key: &[u8; 32],
+) -> Result<Note<F>, Error> {
+    let (&version, rest) = blob.split_first().ok_or(Error::InvalidEncoding)?;
+    if version != BACKUP_VERSION || rest.len() < 32 {
+        return Err(Error::InvalidEncoding);
+    }
+    let (ephemeral_public, ciphertext) = rest.split_at(32);
+    let ephemeral_public: [u8; 32] = ephemeral_public.try_into().expect("32 bytes");
+
+    let secret_key = StaticSecret::from(*secret_key);
+    let shared_secret = secret_key.diffie_hellman(&PublicKey::from(ephemeral_public));
+    let recipient = PublicKey::from(&secret_key).to_bytes();
+    let plaintext = backup_cipher(shared_secret.as_bytes(), &ephemeral_public, &recipient)
+        .decrypt(&Nonce::default(), ciphertext)
+        .map_err(|_| Error::DecryptionFailed)?;
+    Note::from_payload(&plaintext)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        decrypt, denomination_to_amount, derive, encrypt, mnemonic_to_seed, public_key, scan,
+        Error, Note,
+    };
+    use crate::{
+        codec,
+        envelope::{self, Envelope, Kind},
+        ext_data::asset_id,
+        proof::CurveId,
+        tree::IncrementalMerkleTree,
+    };
+    use halo2_proofs::halo2curves::{bn256::Fr, pasta::Fp};
+    use rand_core::OsRng;
+
+    #[test]
+    fn test_note_roundtrip() {
+        let note = Note::<Fr>::random("eth", "0.1", 1, OsRng).unwrap();
+        let s = note.to_string();
+        assert!(s.starts_with("tornado-eth-0.1-1-0x"));
+        assert_eq!(s.len(), "tornado-eth-0.1-1-0x".len() + 128);
+        assert_eq!(s.parse::<Note<Fr>>().unwrap(), note);
+
+        let note = Note::new(Fr::from(0x456), Fr::from(0xabc), "eth", "100", 5).unwrap();
+        assert_eq!(note.amount(), 100_000_000_000);
+        assert_eq!(
+            note.commitment(),
+            Fr::from(0x456 * 0xabc) * Fr::from(100_000_000_000) * asset_id::<Fr>("eth")
+        );
+        assert_eq!(note.nullifier_hash(), Fr::from(0x456 * 0x456));
+        assert_eq!(
+            note.to_string(),
+            format!("tornado-eth-100-5-0x{:0>64}{:0>64}", "456", "abc")
+        );
+        assert!(Note::new(Fr::from(1), Fr::from(1), "eth", "1.", 5).is_none());
+        assert!(Note::new(Fr::from(1), Fr::from(1), "", "1", 5).is_none());
+
+        // same secrets and amount in another pool of the tree
+        let dai = Note::new(Fr::from(0x456), Fr::from(0xabc), "dai", "100", 5).unwrap();
+        assert_ne!(dai.commitment(), note.commitment());
+        assert_eq!(dai.nullifier_hash(), note.nullifier_hash());
+        assert_eq!(dai.to_string().parse::<Note<Fr>>().unwrap(), dai);
+    }
+
+    #[test]
+    fn test_denomination_to_amount() {
+        assert_eq!(denomination_to_amount("0.1"), Some(100_000_000));
+        assert_eq!(denomination_to_amount("100"), Some(100_000_000_000));
+        assert_eq!(denomination_to_amount("0.000000001"), Some(1));
+        assert_eq!(denomination_to_amount("0.0000000001"), None);
+        assert_eq!(
+            denomination_to_amount("18446744073.709551615"),
+            Some(u64::MAX)
+        );
+        assert_eq!(denomination_to_amount("18446744073.709551616"), None);
+        assert_eq!(denomination_to_amount("+1"), None);
+    }
+
+    #[test]
+    fn test_parse_invalid_notes() {
+        let secrets = format!("0x{:0>64}{:0>64}", "456", "abc");
+        let parse = |s: &str| s.parse::<Note<Fr>>();
+        assert!(parse(&format!("tornado-eth-0.1-1-{secrets}")).is_ok());
+        assert!(parse(&format!("tornado-usdc-100-1-{secrets}")).is_ok());
+
+        for (note, error) in [
+            (format!("tornado-eth-0.1-{secrets}"), Error::InvalidFormat),
+            (
+                format!("tornado-eth-0.1-1-1-{secrets}"),
+                Error::InvalidFormat,
+            ),
+            (
+                format!("tornadoo-eth-0.1-1-{secrets}"),
+                Error::InvalidFormat,
+            ),
+            (
+                format!("tornado-DAI-100-1-{secrets}"),
+                Error::InvalidCurrency("DAI".to_string()),
+            ),
+            (
+                format!("tornado-eth-.1-1-{secrets}"),
+                Error::InvalidDenomination(".1".to_string()),
+            ),
+            (
+                format!("tornado-eth-0.1-+1-{secrets}"),
+                Error::InvalidChainId("+1".to_string()),
+            ),
+            (
+                format!("tornado-eth-0.1-1-{}", &secrets[2..]),
+                Error::InvalidFormat,
+            ),
+            (
+                format!("tornado-eth-0.1-1-{}", &secrets[..secrets.len() - 2]),
+                Error::InvalidSecret(codec::Error::InvalidHex),
+            ),
+            (
+                format!("tornado-eth-0.1-1-0x{}", "ff".repeat(64)),
+                Error::InvalidSecret(codec::Error::NonCanonical),
+            ),
+        ] {
+            assert_eq!(parse(&note), Err(error));
+        }
+    }
+
+    #[test]
+    fn test_mnemonic_to_seed() {
+        // BIP-39 test vector, with the "TREZOR" passphrase
+        let phrase = "abandon abandon abandon abandon abandon abandon abandon abandon abandon \
+                      abandon abandon about";
+        assert_eq!(
+            hex::encode(mnemonic_to_seed(phrase, "TREZOR")),
+            "c55257c360c07c72029aebc1b53c05ed0362ada38ead3e3e9efa3708e53495531f09a6987599d18264c1e1c92f2cf141630c7a3c4ab7c81b2f001698e7463b04"
+        );
+    }
+
+    #[test]
+    fn test_derive_and_scan() {
+        let seed = mnemonic_to_seed("legal winner thank year wave sausage worth useful", "");
+        let note = derive::<Fr>(&seed, 3, "eth", "0.1", 1).unwrap();
+        assert_eq!(derive::<Fr>(&seed, 3, "eth", "0.1", 1).unwrap(), note);
+        assert_ne!(derive::<Fr>(&seed, 4, "eth", "0.1", 1).unwrap(), note);
+        assert_ne!(derive::<Fr>(&seed, 3, "eth", "1", 1).unwrap(), note);
+        assert_ne!(derive::<Fr>(&seed, 3, "eth", "0.1", 5).unwrap(), note);
+        assert_ne!(derive::<Fr>(&[0; 64], 3, "eth", "0.1", 1).unwrap(), note);
+        assert_ne!(derive::<Fr>(&seed, 3, "dai", "0.1", 1).unwrap(), note);
+        assert!(derive::<Fr>(&seed, 3, "eth", "-1", 1).is_none());
+
+        // deposits 1 and 4 from this seed, among other people's
+        let mut tree = IncrementalMerkleTree::new(5);
+        for leaf in [
+            Fr::from(3),
+            derive::<Fr>(&seed, 1, "eth", "0.1", 1)
+                .unwrap()
+                .commitment(),
+            Fr::from(5),
+            derive::<Fr>(&seed, 4, "eth", "0.1", 1)
+                .unwrap()
+                .commitment(),
+            // same index in another pool
+            derive::<Fr>(&seed, 2, "eth", "1", 1).unwrap().commitment(),
+        ] {
+            tree.insert(leaf).unwrap();
+        }
+
+        let found = scan(&seed, 10, "eth", "0.1", 1, &tree);
+        assert_eq!(
+            found
+                .iter()
+                .map(|scanned| (scanned.index, scanned.leaf_index))
+                .collect::<Vec<_>>(),
+            vec![(1, 1), (4, 3)]
+        );
+        assert_eq!(found[1].note, derive(&seed, 4, "eth", "0.1", 1).unwrap());
+        assert!(scan(&seed, 1, "eth", "0.1", 1, &tree).is_empty());
+    }
+
+    #[test]
+    fn test_binary_encoding() {
+        let note = Note::new(Fr::from(0x456), Fr::from(0xabc), "eth", "0.1", 5).unwrap();
+        let bytes = note.payload();
+        assert_eq!(bytes.len(), 32 + 32 + 8 + 1 + 3 + 1 + 3);
+        assert_eq!(Note::<Fr>::from_bytes(&bytes), Ok(note.clone()));
+
+        assert_eq!(
+            Note::<Fr>::from_bytes(&bytes[..bytes.len() - 1]),
+            Err(Error::InvalidEncoding)
+        );
+        assert_eq!(
+            Note::<Fr>::from_bytes(&[bytes.as_slice(), &[0]].concat()),
+            Err(Error::InvalidEncoding)
+        );
+        let mut bad_denomination = bytes.clone();
+        bad_denomination[32 + 32 + 8 + 3] = b'x';
+        assert_eq!(
+            Note::<Fr>::from_bytes(&bad_denomination),
+            Err(Error::InvalidDenomination("0.x".to_string()))
+        );
+        let mut bad_currency = bytes.clone();
+        *bad_currency.last_mut().unwrap() = b'H';
+        assert_eq!(
+            Note::<Fr>::from_bytes(&bad_currency),
+            Err(Error::InvalidCurrency("etH".to_string()))
+        );
+    }
+
+    #[test]
+    fn test_envelope() {
+        let note = Note::new(Fr::from(0x456), Fr::from(0xabc), "eth", "0.1", 5).unwrap();
+        let bytes = note.to_bytes();
+        let envelope = Envelope::from_bytes(&bytes).unwrap();
+        assert_eq!(envelope.kind, Kind::Note);
+        assert_eq!(envelope.curve, Some(CurveId::Bn256));
+        assert_eq!(envelope.payload, note.payload());
+        assert_eq!(Note::<Fr>::from_bytes(&bytes), Ok(note));
+
+        assert_eq!(
+            Note::<Fr>::from_bytes(&bytes[..bytes.len() - 1]),
+            Err(Error::InvalidEnvelope(envelope::Error::LengthMismatch))
+        );
+        assert_eq!(Note::<Fp>::from_bytes(&bytes), Err(Error::InvalidEncoding));
+        let proof = Envelope {
+            kind: Kind::Proof,
+            ..envelope
+        };
+        assert_eq!(
+            Note::<Fr>::from_bytes(&proof.to_bytes()),
+            Err(Error::InvalidEnvelope(envelope::Error::UnexpectedKind(
+                Kind::Proof
+            )))
+        );
+    }
+
+    #[test]
+    fn test_encrypted_backup() {
+        let secret_key = [7u8; 32];
+        let note = Note::<Fr>::random("eth", "1", 1, OsRng).unwrap();
+        let blob = encrypt(&note, &public_key(&secret_key), OsRng);
+        assert_eq!(blob.len(), 1 + 32 + note.payload().len() + 16);
+        assert_eq!(decrypt::<Fr>(&blob, &secret_key), Ok(note.clone()));
+        // a fresh ephemeral key each time
+        assert_ne!(encrypt(&note, &public_key(&secret_key), OsRng), blob);
+
+        assert_eq!(
+            decrypt::<Fr>(&blob, &[8u8; 32]),
+            Err(Error::DecryptionFailed)
+        );
+        let mut tampered = blob.clone();
+        *tampered.last_mut().unwrap() ^= 1;
+        assert_eq!(
+            decrypt::<Fr>(&tampered, &secret_key),
+            Err(Error::DecryptionFailed)
+        );
+        assert_eq!(
+            decrypt::<Fr>(&blob[..40], &secret_key),
+            Err(Error::DecryptionFailed)
+        );
+        assert_eq!(decrypt::<Fr>(&[], &secret_key), Err(Error::InvalidEncoding));
+    }
+}
@@ -0,0 +1,528 @@
+//! Spent nullifier hashes. A withdrawal whose nullifier hash is already spent reverts on-chain,
+//! so anything submitting or accepting withdrawals (relayers, wallets, indexers) keeps a set of
+//! them to reject double spends before paying gas.
+//!
+//! `NullifierSet` is implemented in memory (`MemoryNullifierSet`), in an append-only file that
+//! survives restarts (`FileNullifierSet`) and, behind the `redis` feature, in a Redis set shared
+//! by several processes (`RedisNullifierSet`).
+//!
+//! `IndexedNullifierTree` also commits to the set in a Merkle tree, so that
+//! `NullifierExclusionCircuit` can prove a nullifier hash is not spent.
+use crate::{
+    codec::{from_bytes_be, to_bytes_be},
+    proof::Proof,
+    tree::{self, Hasher, IncrementalMerkleTree, ProductHasher},
+};
+use halo2_proofs::halo2curves::{bn256::Fr, ff::PrimeField};
+use std::{
+    collections::{BTreeMap, HashSet},
+    convert::Infallible,
+    fmt,
+    fs::{File, OpenOptions},
+    io::{self, Read, Write},
+    path::Path,
+};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Error<E> {
+    /// The proof has no public input to read the nullifier hash from
+    MissingNullifierHash,
+    InvalidFieldElement,
+    /// The nullifier hash is already in the set
+    AlreadySpent,
+    /// The set itself failed, e.g. it couldn't be reached
+    Backend(E),
+}
+
+impl<E: fmt::Display> fmt::Display for Error<E> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::MissingNullifierHash => write!(f, "proof has no nullifier hash"),
+            Error::InvalidFieldElement => write!(f, "proof has a non-canonical public input"),
+            Error::AlreadySpent => write!(f, "note has already been spent"),
+            Error::Backend(e) => write!(f, "nullifier set error: {e}"),
+        }
+    }
+}
+
+impl<E: fmt::Debug + fmt::Display> std::error::Error for Error<E> {}
+
+/// Spent nullifier hashes, shared by everything submitting withdrawals
+pub trait NullifierSet {
+    type Error: std::error::Error;
+
+    fn contains(&mut self, nullifier_hash: &Fr) -> Result<bool, Self::Error>;
+    /// Atomic set-if-absent: returns `false` when the nullifier hash was already there
+    fn insert(&mut self, nullifier_hash: Fr) -> Result<bool, Self::Error>;
+    /// Every nullifier hash in the set, e.g. to check them against the pool's `isSpent`
+    fn members(&mut self) -> Result<Vec<Fr>, Self::Error>;
+
+    /// Returns the nullifier hash of a withdrawal proof, its first public input, unless it is
+    /// already spent. Nothing is recorded: `insert` it once the withdrawal goes through.
+    fn check_proof(&mut self, proof: &Proof) -> Result<Fr, Error<Self::Error>> {
+        let instances = proof
+            .public_inputs::<Fr>()
+            .map_err(|_| Error::InvalidFieldElement)?;
+        let nullifier_hash = *instances
+            .first()
+            .and_then(|column| column.first())
+            .ok_or(Error::MissingNullifierHash)?;
+        if self.contains(&nullifier_hash).map_err(Error::Backend)? {
+            return Err(Error::AlreadySpent);
+        }
+        Ok(nullifier_hash)
+    }
+}
+
+/// In-process `NullifierSet`, forgotten when the process exits
+#[derive(Debug, Clone, Default)]
+pub struct MemoryNullifierSet(HashSet<[u8; 32]>);
+
+impl MemoryNullifierSet {
+    pub fn len(&self) -> usize {
+        self.0.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+}
+
+impl NullifierSet for MemoryNullifierSet {
+    type Error = Infallible;
+
+    fn contains(&mut self, nullifier_hash: &Fr) -> Result<bool, Infallible> {
+        Ok(self.0.contains(&to_bytes_be(nullifier_hash)))
+    }
+
+    fn insert(&mut self, nullifier_hash: Fr) -> Result<bool, Infallible> {
+        Ok(self.0.insert(to_bytes_be(&nullifier_hash)))
+    }
+
+    fn members(&mut self) -> Result<Vec<Fr>, Infallible> {
+        Ok(self
+            .0
+            .iter()
+            .map(|bytes| from_bytes_be(bytes).expect("only field elements are inserted"))
+            .collect())
+    }
+}
+
+/// `NullifierSet` persisted to an append-only file of 32-byte big-endian nullifier hashes, for a
+/// single process that must remember spends across restarts. The set is kept in memory too, and
+/// every insert is synced to disk before it returns.
+#[derive(Debug)]
+pub struct FileNullifierSet {
+    file: File,
+    set: MemoryNullifierSet,
+}
+
+impl FileNullifierSet {
+    /// Opens the set at `path`, creating it if missing. A record cut short by a crash during an
+    /// insert is dropped, as that insert never returned.
+    pub fn open(path: impl AsRef<Path>) -> io::Result<Self> {
+        let mut file = OpenOptions::new()
+            .read(true)
+            .append(true)
+            .create(true)
+            .open(path)?;
+        let mut bytes = vec![];
+        file.read_to_end(&mut bytes)?;
+        let complete = bytes.len() - bytes.len() % 32;
+        file.set_len(complete as u64)?;
+
+        let mut set = HashSet::new();
+        for record in bytes[..complete].chunks(32) {
+            let record: [u8; 32] = record.try_into().expect("32 bytes");
+            from_bytes_be::<Fr>(&record).map_err(|_| {
+                io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    "nullifier set record is not a field element",
+                )
+            })?;
+            set.insert(record);
+        }
+        Ok(Self {
+            file,
+            set: MemoryNullifierSet(set),
+        })
+    }
+
+    pub fn len(&self) -> usize {
+        self.set.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.set.is_empty()
+    }
+}
+
+impl NullifierSet for FileNullifierSet {
+    type Error = io::Error;
+
+    fn contains(&mut self, nullifier_hash: &Fr) -> io::Result<bool> {
+        Ok(self.set.0.contains(&to_bytes_be(nullifier_hash)))
+    }
+
+    fn insert(&mut self, nullifier_hash: Fr) -> io::Result<bool> {
+        let record = to_bytes_be(&nullifier_hash);
+        if self.set.0.contains(&record) {
+            return Ok(false);
+        }
+        // on disk first, so the set never remembers a spend it would forget on restart
+        self.file.write_all(&record)?;
+        self.file.sync_data()?;
+        Ok(self.set.0.insert(record))
+    }
+
+    fn members(&mut self) -> io::Result<Vec<Fr>> {
+        self.set.members().map_err(|e| match e {})
+    }
+}
+
+/// `NullifierSet` in a Redis set, shared by every relayer instance behind a load balancer.
+/// `SADD` is atomic, so two instances racing on the same note can't both submit it.
+#[cfg(feature = "redis")]
+pub struct RedisNullifierSet {
+    connection: redis::Connection,
+    key: String,
+}
+
+#[cfg(feature = "redis")]
+impl RedisNullifierSet {
+    /// Uses the Redis set `key` at `url`, e.g. `redis://127.0.0.1/`
+    pub fn open(url: &str, key: &str) -> redis::RedisResult<Self> {
+        Ok(Self {
+            connection: redis::Client::open(url)?.get_connection()?,
+            key: key.to_string(),
+        })
+    }
+}
+
+#[cfg(feature = "redis")]
+impl NullifierSet for RedisNullifierSet {
+    type Error = redis::RedisError;
+
+    fn contains(&mut self, nullifier_hash: &Fr) -> redis::RedisResult<bool> {
+        redis::cmd("SISMEMBER")
+            .arg(&self.key)
+            .arg(crate::codec::to_hex(nullifier_hash))
+            .query(&mut self.connection)
+    }
+
+    fn insert(&mut self, nullifier_hash: Fr) -> redis::RedisResult<bool> {
+        let added: u32 = redis::cmd("SADD")
+            .arg(&self.key)
+            .arg(crate::codec::to_hex(&nullifier_hash))
+            .query(&mut self.connection)?;
+        Ok(added == 1)
+    }
+
+    fn members(&mut self) -> redis::RedisResult<Vec<Fr>> {
+        let members: Vec<String> = redis::cmd("SMEMBERS")
+            .arg(&self.key)
+            .query(&mut self.connection)?;
+        members
+            .iter()
+            .map(|member| {
+                crate::codec::from_hex(member).map_err(|_| {
+                    redis::RedisError::from((
+                        redis::ErrorKind::TypeError,
+                        "nullifier set member is not a field element",
+                    ))
+                })
+            })
+            .collect()
+    }
+}
+
+/// A leaf of `IndexedNullifierTree`: a nullifier hash in the set and the next larger one
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct IndexedLeaf<F> {
+    pub value: F,
+    pub next: F,
+}
+
+impl<F: PrimeField> IndexedLeaf<F> {
+    /// `H(value, next)`, the leaf of the underlying Merkle tree
+    pub fn hash(&self) -> F {
+        ProductHasher::hash(self.value, self.next)
+    }
+}
+
+/// Proof that a nullifier hash is not in an `IndexedNullifierTree`: a leaf of the tree whose
+/// range `(value, next)` holds it. This is the witness of `NullifierExclusionCircuit`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ExclusionProof<F> {
+    pub low_leaf: IndexedLeaf<F>,
+    pub leaf_index: usize,
+    pub path_elements: Vec<F>,
+}
+
+/// Sorted nullifier set committed to by a Merkle tree, as a linked list: each leaf points to
+/// the next larger value, so a value is absent iff some leaf `(value, next)` brackets it.
+///
+/// The tree starts with the sentinel leaf `(1, p - 1)`, which every inserted value splits, so
+/// `0`, `1` and `p - 1` are reserved: they count as spent and can't be proven absent. The low
+/// bound isn't `0`, whose product hash would zero the root.
+#[derive(Debug, Clone)]
+pub struct IndexedNullifierTree<F> {
+    tree: IncrementalMerkleTree<F>,
+    leaves: Vec<IndexedLeaf<F>>,
+    // leaf index of each value, keyed by its big-endian bytes so values sort as integers
+    indices: BTreeMap<[u8; 32], usize>,
+}
+
+fn sort_key<F: PrimeField<Repr = [u8; 32]>>(value: &F) -> [u8; 32] {
+    let mut key = value.to_repr();
+    key.reverse();
+    key
+}
+
+impl<F: PrimeField<Repr = [u8; 32]>> IndexedNullifierTree<F> {
+    pub fn new(depth: usize) -> Self {
+        let sentinel = IndexedLeaf {
+            value: F::ONE,
+            next: -F::ONE,
+        };
+        let mut tree = IncrementalMerkleTree::new(depth);
+        tree.insert(sentinel.hash())
+            .expect("a tree has room for one leaf");
+        Self {
+            tree,
+            leaves: vec![sentinel],
+            indices: BTreeMap::from([(sort_key(&sentinel.value), 0)]),
+        }
+    }
+
+    pub fn root(&self) -> F {
+        self.tree.root()
+    }
+
+    /// Leaves in insertion order, the sentinel first
+    pub fn leaves(&self) -> &[IndexedLeaf<F>] {
+        &self.leaves
+    }
+
+    /// Whether `value` is in the set or reserved
+    pub fn contains(&self, value: &F) -> bool {
+        *value == F::ZERO || *value == -F::ONE || self.indices.contains_key(&sort_key(value))
+    }
+
+    /// Index of the leaf whose range holds `value`, unless it is in the set or reserved
+    fn low_leaf_index(&self, value: &F) -> Option<usize> {
+        if self.contains(value) {
+            return None;
+        }
+        let (_, index) = self.indices.range(..sort_key(value)).next_back()?;
+        Some(*index)
+    }
+
+    /// Returns `None` if `value` is in the set or reserved
+    pub fn exclusion_proof(&self, value: &F) -> Option<ExclusionProof<F>> {
+        let leaf_index = self.low_leaf_index(value)?;
+        let (path_elements, _) = self.tree.proof(leaf_index).expect("the leaf exists");
+        Some(ExclusionProof {
+            low_leaf: self.leaves[leaf_index],
+            leaf_index,
+            path_elements,
+        })
+    }
+
+    /// Returns `false` if `value` is already in the set or reserved. Inserting takes two leaf
+    /// writes: the low leaf now points to `value`, and `value` is appended pointing to the low
+    /// leaf's former next.
+    pub fn insert(&mut self, value: F) -> Result<bool, tree::Error> {
+        let Some(low_index) = self.low_leaf_index(&value) else {
+            return Ok(false);
+        };
+        let low_leaf = &mut self.leaves[low_index];
+        let leaf = IndexedLeaf {
+            value,
+            next: low_leaf.next,
+        };
+        let index = self.tree.insert(leaf.hash())?;
+        low_leaf.next = value;
+        self.tree.update(low_index, low_leaf.hash());
+        self.leaves.push(leaf);
+        self.indices.insert(sort_key(&value), index);
+        Ok(true)
+    }
+}
+
+impl NullifierSet for IndexedNullifierTree<Fr> {
+    type Error = tree::Error;
+
+    fn contains(&mut self, nullifier_hash: &Fr) -> Result<bool, tree::Error> {
+        Ok(IndexedNullifierTree::contains(self, nullifier_hash))
+    }
+
+    fn insert(&mut self, nullifier_hash: Fr) -> Result<bool, tree::Error> {
+        IndexedNullifierTree::insert(self, nullifier_hash)
+    }
+
+    fn members(&mut self) -> Result<Vec<Fr>, tree::Error> {
+        Ok(self.leaves[1..].iter().map(|leaf| leaf.value).collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        Error, FileNullifierSet, IndexedLeaf, IndexedNullifierTree, MemoryNullifierSet,
+        NullifierSet,
+    };
+    use crate::{
+        proof::{CurveId, Proof},
+        tree::{self, IncrementalMerkleTree},
+    };
+    use halo2_proofs::halo2curves::{bn256::Fr, pasta::Fp};
+    use std::fs;
+
+    fn proof(public_inputs: Vec<Fr>) -> Proof {
+        Proof::new(CurveId::Bn256, 8, &[public_inputs], vec![])
+    }
+
+    #[test]
+    fn test_memory_set() {
+        let mut set = MemoryNullifierSet::default();
+        assert_eq!(set.insert(Fr::from(1)), Ok(true));
+        assert_eq!(set.insert(Fr::from(1)), Ok(false));
+        assert_eq!(set.contains(&Fr::from(1)), Ok(true));
+        assert_eq!(set.contains(&Fr::from(2)), Ok(false));
+        assert_eq!(set.members(), Ok(vec![Fr::from(1)]));
+    }
+
+    #[test]
+    fn test_check_proof() {
+        let mut set = MemoryNullifierSet::default();
+        let withdrawal = proof(vec![Fr::from(7), Fr::from(0xaaa)]);
+        assert_eq!(set.check_proof(&withdrawal), Ok(Fr::from(7)));
+        // checking doesn't spend it
+        assert_eq!(set.check_proof(&withdrawal), Ok(Fr::from(7)));
+
+        set.insert(Fr::from(7)).unwrap();
+        assert_eq!(set.check_proof(&withdrawal), Err(Error::AlreadySpent));
+        // the root is not a nullifier hash
+        set.insert(Fr::from(0xaaa)).unwrap();
+        assert_eq!(
+            set.check_proof(&proof(vec![Fr::from(8), Fr::from(0xaaa)])),
+            Ok(Fr::from(8))
+        );
+        assert_eq!(
+            set.check_proof(&proof(vec![])),
+            Err(Error::MissingNullifierHash)
+        );
+
+        let mut non_canonical = proof(vec![Fr::from(8)]);
+        non_canonical.public_inputs[0][0] = [0xff; 32];
+        assert_eq!(
+            set.check_proof(&non_canonical),
+            Err(Error::InvalidFieldElement)
+        );
+    }
+
+    #[test]
+    fn test_file_set() {
+        let dir = std::env::temp_dir().join("tornado-nullifier-set");
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("nullifiers.bin");
+
+        let mut set = FileNullifierSet::open(&path).unwrap();
+        assert!(set.is_empty());
+        assert!(set.insert(Fr::from(1)).unwrap());
+        assert!(set.insert(Fr::from(2)).unwrap());
+        assert!(!set.insert(Fr::from(1)).unwrap());
+        drop(set);
+        assert_eq!(fs::metadata(&path).unwrap().len(), 64);
+
+        // spends survive a restart
+        let mut set = FileNullifierSet::open(&path).unwrap();
+        assert_eq!(set.len(), 2);
+        assert!(set.contains(&Fr::from(2)).unwrap());
+        assert!(matches!(
+            set.check_proof(&proof(vec![Fr::from(1)])),
+            Err(Error::AlreadySpent)
+        ));
+        drop(set);
+
+        // a crash in the middle of an insert leaves a partial record
+        let mut bytes = fs::read(&path).unwrap();
+        bytes.extend([0; 7]);
+        fs::write(&path, &bytes).unwrap();
+        let mut set = FileNullifierSet::open(&path).unwrap();
+        assert_eq!(set.len(), 2);
+        assert!(set.insert(Fr::from(3)).unwrap());
+        drop(set);
+        assert_eq!(FileNullifierSet::open(&path).unwrap().len(), 3);
+
+        fs::write(&path, [0xff; 32]).unwrap();
+        assert!(FileNullifierSet::open(&path).is_err());
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn test_indexed_tree() {
+        let mut set = IndexedNullifierTree::<Fp>::new(3);
+        for value in [7, 3, 11, 5] {
+            assert!(set.insert(Fp::from(value)).unwrap());
+        }
+        assert!(!set.insert(Fp::from(5)).unwrap());
+        for reserved in [Fp::from(0), Fp::from(1), -Fp::from(1)] {
+            assert!(set.contains(&reserved));
+            assert!(set.exclusion_proof(&reserved).is_none());
+            assert!(!set.insert(reserved).unwrap());
+        }
+
+        // a linked list sorted as integers, in insertion order
+        let leaf = |value: u64, next: Fp| IndexedLeaf {
+            value: Fp::from(value),
+            next,
+        };
+        assert_eq!(
+            set.leaves(),
+            [
+                leaf(1, Fp::from(3)),
+                leaf(7, Fp::from(11)),
+                leaf(3, Fp::from(5)),
+                leaf(11, -Fp::from(1)),
+                leaf(5, Fp::from(7)),
+            ]
+        );
+        let mut rebuilt = IncrementalMerkleTree::new(3);
+        for leaf in set.leaves() {
+            rebuilt.insert(leaf.hash()).unwrap();
+        }
+        assert_eq!(set.root(), rebuilt.root());
+
+        // large values compare as integers, not as their little-endian bytes
+        let large = Fp::from(1 << 8);
+        let proof = set.exclusion_proof(&large).unwrap();
+        assert_eq!(proof.low_leaf, leaf(11, -Fp::from(1)));
+        assert_eq!(proof.leaf_index, 3);
+        let (path_elements, _) = rebuilt.proof(3).unwrap();
+        assert_eq!(proof.path_elements, path_elements);
+        assert!(set.exclusion_proof(&Fp::from(7)).is_none());
+
+        for value in [12, 13, 14] {
+            set.insert(Fp::from(value)).unwrap();
+        }
+        assert_eq!(set.insert(Fp::from(15)), Err(tree::Error::TreeFull));
+        // a failed insert leaves the set untouched
+        assert!(!set.contains(&Fp::from(15)));
+        assert_eq!(set.leaves()[7].next, -Fp::from(1));
+    }
+
+    #[test]
+    fn test_indexed_tree_as_nullifier_set() {
+        let mut set = IndexedNullifierTree::<Fr>::new(4);
+        assert_eq!(set.check_proof(&proof(vec![Fr::from(7)])), Ok(Fr::from(7)));
+        assert_eq!(NullifierSet::insert(&mut set, Fr::from(7)), Ok(true));
+        assert_eq!(
+            set.check_proof(&proof(vec![Fr::from(7)])),
+            Err(Error::AlreadySpent)
+        );
+        assert_eq!(set.members(), Ok(vec![Fr::from(7)]));
+    }
+}
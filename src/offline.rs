@@ -0,0 +1,272 @@
+//! Verification of a withdrawal proof from files alone, for auditors on air-gapped machines.
+//!
+//! Inputs are the KZG params (with their `.sha256` sidecar), the verifying key written by
+//! `prover::write_vk`, a proof in the `Proof` binary format and a JSON instance file: an array
+//! of `0x`-prefixed, 64-digit big-endian hex strings, as found in the withdrawal calldata.
+//! No RPC, tree or proving key is needed. Every input is validated strictly, and the instances
+//! embedded in the proof must match the instance file.
+use crate::{
+    codec::from_hex,
+    params,
+    proof::{self, CurveId, Proof},
+    prover,
+};
+use halo2_proofs::{halo2curves::bn256::Fr, plonk::Circuit};
+use std::{
+    fmt, fs, io,
+    path::{Path, PathBuf},
+};
+
+/// Largest vk, proof or instance file accepted. Params files are checked by hash instead.
+pub const MAX_INPUT_SIZE: u64 = 1024 * 1024;
+
+#[derive(Debug)]
+pub enum Error {
+    Io(io::Error),
+    Params(params::Error),
+    TooLarge {
+        path: PathBuf,
+    },
+    InvalidVk(io::Error),
+    InvalidProof(proof::Error),
+    UnsupportedCurve,
+    /// The proof is bound to another circuit than the verifying key's
+    WrongCircuit,
+    InvalidInstances(String),
+    /// The instance file doesn't match the public inputs embedded in the proof
+    InstanceMismatch,
+    VerificationFailed,
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::Io(e) => write!(f, "io error: {e}"),
+            Error::Params(e) => write!(f, "{e}"),
+            Error::TooLarge { path } => write!(f, "{} is too large", path.display()),
+            Error::InvalidVk(e) => write!(f, "invalid verifying key: {e}"),
+            Error::InvalidProof(e) => write!(f, "invalid proof file: {e}"),
+            Error::UnsupportedCurve => write!(f, "proof is not over bn256"),
+            Error::WrongCircuit => write!(f, "proof was made for another circuit"),
+            Error::InvalidInstances(reason) => write!(f, "invalid instance file: {reason}"),
+            Error::InstanceMismatch => {
+                write!(f, "instance file doesn't match the proof's public inputs")
+            }
+            Error::VerificationFailed => write!(f, "proof verification failed"),
+        }
+    }
+}
+
+impl std::error::Error for Error {}
+
+impl From<io::Error> for Error {
+    fn from(e: io::Error) -> Self {
+        Error::Io(e)
+    }
+}
+
+impl From<params::Error> for Error {
+    fn from(e: params::Error) -> Self {
+        Error::Params(e)
+    }
+}
+
+fn read_bounded(path: &Path) -> Result<Vec<u8>, Error> {
+    if fs::metadata(path)?.len() > MAX_INPUT_SIZE {
+        return Err(Error::TooLarge {
+            path: path.to_path_buf(),
+        });
+    }
+    Ok(fs::read(path)?)
+}
+
+fn invalid_instances(reason: &str) -> Error {
+    Error::InvalidInstances(reason.to_string())
+}
+
+/// Parses exactly `count` instances. Only the canonical `0x` + 64 hex digits form is accepted.
+pub fn parse_instances(json: &[u8], count: usize) -> Result<Vec<Fr>, Error> {
+    let values: Vec<String> =
+        serde_json::from_slice(json).map_err(|e| Error::InvalidInstances(e.to_string()))?;
+    if values.len() != count {
+        return Err(Error::InvalidInstances(format!(
+            "expected {count} values, found {}",
+            values.len()
+        )));
+    }
+    values
+        .iter()
+        .map(|value| {
+            let digits = value
+                .strip_prefix("0x")
+                .ok_or_else(|| invalid_instances("missing 0x prefix"))?;
+            if digits.len() != 64 {
+                return Err(invalid_instances("values must have 64 hex digits"));
+            }
+            from_hex(value).map_err(|e| Error::InvalidInstances(e.to_string()))
+        })
+        .collect()
+}
+
+/// Verifies a proof for circuit `C` with `num_instances` public inputs in a single column, over
+/// a tree of `depth` (see `prover::circuit_id`)
+pub fn verify_files<C: Circuit<Fr>>(
+    params_path: impl AsRef<Path>,
+    vk_path: impl AsRef<Path>,
+    proof_path: impl AsRef<Path>,
+    instances_path: impl AsRef<Path>,
+    num_instances: usize,
+    depth: usize,
+) -> Result<(), Error> {
+    let proof =
+        Proof::from_bytes(&read_bounded(proof_path.as_ref())?).map_err(Error::InvalidProof)?;
+    if proof.curve != CurveId::Bn256 {
+        return Err(Error::UnsupportedCurve);
+    }
+    let instances = parse_instances(&read_bounded(instances_path.as_ref())?, num_instances)?;
+    let embedded = proof.public_inputs::<Fr>().map_err(Error::InvalidProof)?;
+    if embedded != [instances.clone()] {
+        return Err(Error::InstanceMismatch);
+    }
+
+    let vk_bytes = read_bounded(vk_path.as_ref())?;
+    let vk = prover::read_vk::<C>(&mut vk_bytes.as_slice()).map_err(Error::InvalidVk)?;
+    proof
+        .check_circuit_id(&prover::circuit_id(&vk, &[num_instances], depth))
+        .map_err(|_| Error::WrongCircuit)?;
+    let params = params::load(params_path, proof.k)?;
+
+    prover::verify(&params, &vk, &proof.transcript, &[instances])
+        .map_err(|_| Error::VerificationFailed)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{parse_instances, verify_files, Error};
+    use crate::{
+        circuits::{
+            merkle::MerklePathWitness,
+            tornado::{PublicInputs, TornadoCircuit},
+        },
+        codec::to_hex,
+        params::load_or_generate,
+        proof::{CurveId, Proof},
+        prover::{keygen, prove, tornado_circuit_id, write_vk},
+    };
+    use halo2_proofs::{circuit::Value, halo2curves::bn256::Fr};
+    use std::fs;
+
+    #[test]
+    fn test_parse_instances() {
+        let one = to_hex(&Fr::from(1));
+        let json = format!(r#"["{one}", "{one}"]"#);
+        assert_eq!(
+            parse_instances(json.as_bytes(), 2).unwrap(),
+            vec![Fr::from(1); 2]
+        );
+
+        for bad in [
+            format!(r#"["{one}"]"#),
+            format!(r#"["{one}", "0x1"]"#),
+            format!(r#"["{one}", "{}"]"#, &one[2..]),
+            format!(r#"["{one}", 1]"#),
+            format!(r#"{{"a": "{one}"}}"#),
+            format!(
+                r#"["{one}", "0x30644e72e131a029b85045b68181585d2833e84879b9709143e1f593f0000001"]"#
+            ),
+        ] {
+            assert!(matches!(
+                parse_instances(bad.as_bytes(), 2),
+                Err(Error::InvalidInstances(_))
+            ));
+        }
+    }
+
+    #[test]
+    fn test_verify_files() {
+        const K: u32 = 8;
+        let dir = std::env::temp_dir().join("tornado-halo2-offline-test");
+        fs::create_dir_all(&dir).unwrap();
+
+        let elements = [2, 5, 7, 14, 23];
+        let circuit = TornadoCircuit::<Fr, 5>::new(
+            Value::known(Fr::from(0x456)),
+            Value::known(Fr::from(0xabc)),
+            // path indices 0, 0, 1, 1, 0
+            MerklePathWitness::known(elements.map(Fr::from), Fr::from(0b01100)),
+            Value::known(Fr::from(0xe47)),
+            Value::known(Fr::from(0x11)),
+            Value::known(Fr::from(0x22)),
+            Value::known(Fr::from(50)),
+            Value::known(Fr::from(0)),
+            Value::known(Fr::from(3)),
+            Value::known(Fr::from(5)),
+        );
+        let root = 0x456 * 0xabc * 3 * 5 * elements.iter().product::<u64>();
+        let instances = vec![
+            Fr::from(0x456 * 0x456),
+            Fr::from(root),
+            Fr::from(0xe47),
+            Fr::from(0x11),
+            Fr::from(0x22),
+            Fr::from(50),
+            Fr::from(0),
+            Fr::from(3),
+            Fr::from(5),
+        ];
+
+        let params = load_or_generate(dir.join(format!("kzg_bn256_{K}.params")), K).unwrap();
+        let pk = keygen(&params, &circuit).unwrap();
+        let transcript = prove(&params, &pk, circuit, &[instances.clone()]).unwrap();
+
+        let mut vk = vec![];
+        write_vk(pk.get_vk(), &mut vk).unwrap();
+        fs::write(dir.join("vk.bin"), vk).unwrap();
+        let proof = Proof::new(CurveId::Bn256, K, &[instances.clone()], transcript)
+            .with_circuit_id(tornado_circuit_id::<5>(pk.get_vk()));
+        fs::write(dir.join("proof.bin"), proof.to_bytes().unwrap()).unwrap();
+        let write_instances = |instances: &[Fr]| {
+            let hex: Vec<String> = instances.iter().map(to_hex).collect();
+            fs::write(
+                dir.join("instances.json"),
+                serde_json::to_vec(&hex).unwrap(),
+            )
+            .unwrap();
+        };
+
+        let verify = || {
+            verify_files::<TornadoCircuit<Fr, 5>>(
+                dir.join(format!("kzg_bn256_{K}.params")),
+                dir.join("vk.bin"),
+                dir.join("proof.bin"),
+                dir.join("instances.json"),
+                PublicInputs::LEN,
+                5,
+            )
+        };
+
+        write_instances(&instances);
+        assert!(verify().is_ok());
+
+        let mut wrong = instances.clone();
+        wrong[1] += Fr::from(1);
+        write_instances(&wrong);
+        assert!(matches!(verify(), Err(Error::InstanceMismatch)));
+
+        // consistent instances, but not the ones the proof was made for
+        let tampered = Proof {
+            public_inputs: Proof::new(CurveId::Bn256, K, &[wrong], vec![]).public_inputs,
+            ..proof
+        };
+        fs::write(dir.join("proof.bin"), tampered.to_bytes().unwrap()).unwrap();
+        assert!(matches!(verify(), Err(Error::VerificationFailed)));
+
+        // a proof of another circuit version
+        let other_circuit = Proof {
+            circuit_id: [1; 32],
+            ..tampered
+        };
+        fs::write(dir.join("proof.bin"), other_circuit.to_bytes().unwrap()).unwrap();
+        assert!(matches!(verify(), Err(Error::WrongCircuit)));
+    }
+}
@@ -0,0 +1,264 @@
+use crate::{
+    codec::{from_bytes_be, to_bytes_be},
+    tree::{self, IncrementalMerkleTree, ProductHasher, TreeCheckpoint},
+};
+use ethers::{
+    contract::{abigen, ContractError},
+    providers::Middleware,
+    types::{Address, Bytes, TransactionReceipt, U256},
+};
+use halo2_proofs::halo2curves::bn256::Fr;
+use std::{fmt, sync::Arc};
+
+abigen!(
+    TornadoPool,
+    r#"[
+        function deposit(bytes32 commitment) external payable
+        function withdraw(bytes proof, bytes32 root, bytes32 nullifierHash, address recipient, address relayer, uint256 fee, uint256 refund) external payable
+        function isSpent(bytes32 nullifierHash) external view returns (bool)
+        function isKnownRoot(bytes32 root) external view returns (bool)
+        function getLastRoot() external view returns (bytes32)
+        function denomination() external view returns (uint256)
+        event Deposit(bytes32 indexed commitment, uint32 leafIndex, uint256 timestamp)
+        event Withdrawal(address to, bytes32 nullifierHash, address indexed relayer, uint256 fee)
+    ]"#
+);
+
+/// Blocks per `eth_getLogs` request, below the usual RPC provider limits
+const LOG_CHUNK_SIZE: u64 = 10_000;
+
+pub enum Error<M: Middleware> {
+    Contract(ContractError<M>),
+    Tree(tree::Error),
+    /// Deposit events don't form a contiguous sequence of leaf indices
+    MissingLeaf(u32),
+    /// The rebuilt root is not known to the contract
+    UnknownRoot,
+    /// The transaction was dropped from the mempool
+    MissingReceipt,
+    /// The contract returned a bytes32 that is not a field element
+    NonCanonicalValue,
+}
+
+impl<M: Middleware> fmt::Debug for Error<M> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::Contract(e) => write!(f, "Contract({e:?})"),
+            Error::Tree(e) => write!(f, "Tree({e:?})"),
+            Error::MissingLeaf(i) => write!(f, "MissingLeaf({i})"),
+            Error::UnknownRoot => write!(f, "UnknownRoot"),
+            Error::MissingReceipt => write!(f, "MissingReceipt"),
+            Error::NonCanonicalValue => write!(f, "NonCanonicalValue"),
+        }
+    }
+}
+
+impl<M: Middleware> fmt::Display for Error<M> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::Contract(e) => write!(f, "pool contract error: {e}"),
+            Error::Tree(e) => write!(f, "{e}"),
+            Error::MissingLeaf(i) => write!(f, "no deposit event for leaf {i}"),
+            Error::UnknownRoot => write!(f, "rebuilt tree root is unknown to the pool"),
+            Error::MissingReceipt => write!(f, "transaction dropped without a receipt"),
+            Error::NonCanonicalValue => write!(f, "pool returned a non-canonical field element"),
+        }
+    }
+}
+
+impl<M: Middleware> std::error::Error for Error<M> {}
+
+impl<M: Middleware> From<ContractError<M>> for Error<M> {
+    fn from(e: ContractError<M>) -> Self {
+        Error::Contract(e)
+    }
+}
+
+impl<M: Middleware> From<tree::Error> for Error<M> {
+    fn from(e: tree::Error) -> Self {
+        Error::Tree(e)
+    }
+}
+
+/// Field-typed wrapper around the `TornadoPool` binding
+pub struct Pool<M> {
+    pub contract: TornadoPool<M>,
+}
+
+impl<M: Middleware + 'static> Pool<M> {
+    pub fn new(address: Address, client: Arc<M>) -> Self {
+        Self {
+            contract: TornadoPool::new(address, client),
+        }
+    }
+
+    pub async fn denomination(&self) -> Result<U256, Error<M>> {
+        Ok(self.contract.denomination().call().await?)
+    }
+
+    /// Sends `denomination` wei along with the commitment
+    pub async fn deposit(&self, commitment: Fr) -> Result<TransactionReceipt, Error<M>> {
+        let value = self.denomination().await?;
+        let call = self.contract.deposit(to_bytes_be(&commitment)).value(value);
+        let pending = call.send().await?;
+        pending
+            .await
+            .map_err(|e| Error::Contract(ContractError::ProviderError { e }))?
+            .ok_or(Error::MissingReceipt)
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    pub async fn withdraw(
+        &self,
+        proof: &[u8],
+        root: Fr,
+        nullifier_hash: Fr,
+        recipient: Address,
+        relayer: Address,
+        fee: U256,
+        refund: U256,
+    ) -> Result<TransactionReceipt, Error<M>> {
+        let call = self
+            .contract
+            .withdraw(
+                Bytes::from(proof.to_vec()),
+                to_bytes_be(&root),
+                to_bytes_be(&nullifier_hash),
+                recipient,
+                relayer,
+                fee,
+                refund,
+            )
+            .value(refund);
+        let pending = call.send().await?;
+        pending
+            .await
+            .map_err(|e| Error::Contract(ContractError::ProviderError { e }))?
+            .ok_or(Error::MissingReceipt)
+    }
+
+    pub async fn is_spent(&self, nullifier_hash: Fr) -> Result<bool, Error<M>> {
+        Ok(self
+            .contract
+            .is_spent(to_bytes_be(&nullifier_hash))
+            .call()
+            .await?)
+    }
+
+    pub async fn is_known_root(&self, root: Fr) -> Result<bool, Error<M>> {
+        Ok(self
+            .contract
+            .is_known_root(to_bytes_be(&root))
+            .call()
+            .await?)
+    }
+
+    /// Consistency check for a relayer's nullifier set (`nullifier::NullifierSet::members`):
+    /// returns the nullifier hashes it recorded as spent that the pool doesn't, i.e.
+    /// withdrawals that reverted or were dropped. Meant to run periodically.
+    pub async fn unconfirmed_spends(&self, nullifier_hashes: &[Fr]) -> Result<Vec<Fr>, Error<M>> {
+        let mut unconfirmed = vec![];
+        for nullifier_hash in nullifier_hashes {
+            if !self.is_spent(*nullifier_hash).await? {
+                unconfirmed.push(*nullifier_hash);
+            }
+        }
+        Ok(unconfirmed)
+    }
+
+    pub async fn last_root(&self) -> Result<Fr, Error<M>> {
+        let root = self.contract.get_last_root().call().await?;
+        from_bytes_be(&root).map_err(|_| Error::NonCanonicalValue)
+    }
+}
+
+/// Rebuilds the pool's tree from its `Deposit` events and checks the resulting root against
+/// `isKnownRoot`. `from_block` must be the pool deployment block (or earlier), since every
+/// leaf is needed.
+pub async fn sync_tree<M: Middleware + 'static>(
+    provider: Arc<M>,
+    pool_address: Address,
+    from_block: u64,
+    depth: usize,
+) -> Result<IncrementalMerkleTree<Fr>, Error<M>> {
+    let latest = provider
+        .get_block_number()
+        .await
+        .map_err(ContractError::from_middleware_error)?
+        .as_u64();
+    let pool = Pool::new(pool_address, provider);
+
+    let mut tree = IncrementalMerkleTree::new(depth);
+    for commitment in deposits(&pool, from_block, latest, 0).await? {
+        tree.insert(commitment)?;
+    }
+
+    if !pool.is_known_root(tree.root()).await? {
+        return Err(Error::UnknownRoot);
+    }
+    Ok(tree)
+}
+
+/// Commitments of the `Deposit` events from `from_block` to `to_block`, checked to be the
+/// leaves `first_leaf..` in order
+async fn deposits<M: Middleware + 'static>(
+    pool: &Pool<M>,
+    from_block: u64,
+    to_block: u64,
+    first_leaf: usize,
+) -> Result<Vec<Fr>, Error<M>> {
+    let mut deposits = vec![];
+    let mut start = from_block;
+    while start <= to_block {
+        let end = (start + LOG_CHUNK_SIZE - 1).min(to_block);
+        let events = pool
+            .contract
+            .deposit_filter()
+            .from_block(start)
+            .to_block(end)
+            .query()
+            .await?;
+        deposits.extend(events);
+        start = end + 1;
+    }
+    deposits.sort_by_key(|deposit| deposit.leaf_index);
+
+    deposits
+        .iter()
+        .enumerate()
+        .map(|(i, deposit)| {
+            let expected = first_leaf + i;
+            if deposit.leaf_index as usize != expected {
+                return Err(Error::MissingLeaf(expected as u32));
+            }
+            from_bytes_be(&deposit.commitment).map_err(|_| Error::NonCanonicalValue)
+        })
+        .collect()
+}
+
+/// Continues a scan from `checkpoint`, reading only the `Deposit` events after its block, and
+/// returns the checkpoint at the latest block. Only the frontier is kept, which is enough to
+/// follow the pool's roots (e.g. for a relayer) but not to prove old deposits: a wallet needs
+/// every leaf, see `sync_tree`.
+pub async fn sync_checkpoint<M: Middleware + 'static>(
+    provider: Arc<M>,
+    pool_address: Address,
+    checkpoint: &TreeCheckpoint,
+) -> Result<TreeCheckpoint, Error<M>> {
+    let mut frontier = checkpoint.frontier::<Fr, ProductHasher>()?;
+    let latest = provider
+        .get_block_number()
+        .await
+        .map_err(ContractError::from_middleware_error)?
+        .as_u64();
+    let pool = Pool::new(pool_address, provider);
+
+    for commitment in deposits(&pool, checkpoint.block + 1, latest, frontier.len()).await? {
+        frontier.insert(commitment)?;
+    }
+
+    if !pool.is_known_root(frontier.root()).await? {
+        return Err(Error::UnknownRoot);
+    }
+    Ok(TreeCheckpoint::new(latest, &frontier))
+}
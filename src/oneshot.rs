@@ -0,0 +1,88 @@
+//! A single value sent from a worker thread to a future, or to a thread blocking on it. The
+//! crate doesn't depend on an async runtime, so the futures it returns wait on this.
+use std::{
+    future::Future,
+    pin::Pin,
+    sync::{
+        mpsc::{self, TryRecvError},
+        Arc, Mutex, PoisonError,
+    },
+    task::{Context, Poll, Waker},
+};
+
+pub(crate) fn channel<T>() -> (Sender<T>, Receiver<T>) {
+    let (sender, receiver) = mpsc::channel();
+    let waker = Arc::new(Mutex::new(None));
+    let sender = Sender {
+        sender: Some(sender),
+        waker: waker.clone(),
+    };
+    (sender, Receiver { receiver, waker })
+}
+
+/// Dropping it wakes the receiver, which then resolves to the value, or to `None` if the
+/// sender was dropped without sending one (e.g. its thread panicked)
+pub(crate) struct Sender<T> {
+    sender: Option<mpsc::Sender<T>>,
+    waker: Arc<Mutex<Option<Waker>>>,
+}
+
+impl<T> Sender<T> {
+    pub(crate) fn send(self, value: T) {
+        if let Some(sender) = &self.sender {
+            // the receiver may have been dropped
+            let _ = sender.send(value);
+        }
+    }
+}
+
+impl<T> Drop for Sender<T> {
+    fn drop(&mut self) {
+        // disconnect before waking, so the receiver can't find the channel still open
+        drop(self.sender.take());
+        let waker = self
+            .waker
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .take();
+        if let Some(waker) = waker {
+            waker.wake();
+        }
+    }
+}
+
+pub(crate) struct Receiver<T> {
+    receiver: mpsc::Receiver<T>,
+    waker: Arc<Mutex<Option<Waker>>>,
+}
+
+impl<T> Receiver<T> {
+    /// Blocks until the value is sent
+    pub(crate) fn wait(self) -> Option<T> {
+        self.receiver.recv().ok()
+    }
+
+    fn try_take(&self) -> Option<Option<T>> {
+        match self.receiver.try_recv() {
+            Ok(value) => Some(Some(value)),
+            Err(TryRecvError::Disconnected) => Some(None),
+            Err(TryRecvError::Empty) => None,
+        }
+    }
+}
+
+impl<T> Future for Receiver<T> {
+    type Output = Option<T>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        if let Some(value) = self.try_take() {
+            return Poll::Ready(value);
+        }
+        *self.waker.lock().unwrap_or_else(PoisonError::into_inner) = Some(cx.waker().clone());
+        // the value may have been sent before the waker was stored
+        match self.try_take() {
+            Some(value) => Poll::Ready(value),
+            None => Poll::Pending,
+        }
+    }
+}
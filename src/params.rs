@@ -0,0 +1,348 @@
+use halo2_proofs::{
+    arithmetic::g_to_lagrange,
+    halo2curves::{
+        bn256::{Bn256, Fq, G1Affine, G2Affine, G1},
+        ff::PrimeField,
+        serde::SerdeObject,
+    },
+    poly::{commitment::Params, kzg::commitment::ParamsKZG},
+    SerdeFormat,
+};
+use rand_core::OsRng;
+use sha2::{Digest, Sha256};
+use std::{
+    collections::HashMap,
+    fmt, fs, io,
+    path::{Path, PathBuf},
+};
+
+#[derive(Debug)]
+pub enum Error {
+    Io(io::Error),
+    /// The params file doesn't match the hash recorded in its sidecar
+    IntegrityMismatch {
+        path: PathBuf,
+    },
+    WrongK {
+        expected: u32,
+        found: u32,
+    },
+    InvalidPtau(String),
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::Io(e) => write!(f, "params io error: {e}"),
+            Error::IntegrityMismatch { path } => {
+                write!(f, "{} doesn't match its recorded hash", path.display())
+            }
+            Error::WrongK { expected, found } => {
+                write!(
+                    f,
+                    "params were generated for k = {found}, expected k = {expected}"
+                )
+            }
+            Error::InvalidPtau(reason) => write!(f, "invalid ptau file: {reason}"),
+        }
+    }
+}
+
+impl std::error::Error for Error {}
+
+impl From<io::Error> for Error {
+    fn from(e: io::Error) -> Self {
+        Error::Io(e)
+    }
+}
+
+/// `<path>.sha256`, holding the hex sha256 of the params file
+fn sidecar_path(path: &Path) -> PathBuf {
+    let mut sidecar = path.as_os_str().to_owned();
+    sidecar.push(".sha256");
+    PathBuf::from(sidecar)
+}
+
+/// Loads KZG params from `path`, checking them against the sidecar hash.
+/// If `path` doesn't exist, generates fresh params for `k` and writes both files.
+///
+/// Locally generated params come from an insecure setup: only use them for tests and development.
+pub fn load_or_generate(path: impl AsRef<Path>, k: u32) -> Result<ParamsKZG<Bn256>, Error> {
+    let path = path.as_ref();
+    if path.exists() {
+        return load(path, k);
+    }
+
+    let params = ParamsKZG::<Bn256>::setup(k, OsRng);
+    let mut bytes = vec![];
+    params.write(&mut bytes)?;
+
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    fs::write(path, &bytes)?;
+    fs::write(sidecar_path(path), hex::encode(Sha256::digest(&bytes)))?;
+    Ok(params)
+}
+
+/// Loads KZG params from `path`, checking them against the sidecar hash
+pub fn load(path: impl AsRef<Path>, k: u32) -> Result<ParamsKZG<Bn256>, Error> {
+    let path = path.as_ref();
+    let bytes = fs::read(path)?;
+    let expected = fs::read_to_string(sidecar_path(path))?;
+    if hex::encode(Sha256::digest(&bytes)) != expected.trim() {
+        return Err(Error::IntegrityMismatch {
+            path: path.to_path_buf(),
+        });
+    }
+
+    let params = ParamsKZG::<Bn256>::read(&mut bytes.as_slice())?;
+    if params.k() != k {
+        return Err(Error::WrongK {
+            expected: k,
+            found: params.k(),
+        });
+    }
+    Ok(params)
+}
+
+const PTAU_HEADER_SECTION: u32 = 1;
+const PTAU_TAU_G1_SECTION: u32 = 2;
+const PTAU_TAU_G2_SECTION: u32 = 3;
+
+fn invalid_ptau(reason: &str) -> Error {
+    Error::InvalidPtau(reason.to_string())
+}
+
+fn read_u32(bytes: &[u8], offset: usize) -> Result<u32, Error> {
+    bytes
+        .get(offset..offset + 4)
+        .map(|b| u32::from_le_bytes(b.try_into().unwrap()))
+        .ok_or_else(|| invalid_ptau("unexpected end of file"))
+}
+
+fn read_u64(bytes: &[u8], offset: usize) -> Result<u64, Error> {
+    bytes
+        .get(offset..offset + 8)
+        .map(|b| u64::from_le_bytes(b.try_into().unwrap()))
+        .ok_or_else(|| invalid_ptau("unexpected end of file"))
+}
+
+/// Converts a Perpetual Powers of Tau (snarkjs `.ptau`) file into KZG params of size `2^k`.
+///
+/// A ptau file is `"ptau" | version: u32 | n_sections: u32` followed by sections
+/// `type: u32 | size: u64 | data`. We need the header (1), the powers of tau in G1 (2)
+/// and the first two powers in G2 (3). Points are stored uncompressed, with coordinates
+/// in little-endian Montgomery form, which is exactly halo2curves' raw encoding.
+pub fn from_ptau(path: impl AsRef<Path>, k: u32) -> Result<ParamsKZG<Bn256>, Error> {
+    let bytes = fs::read(path)?;
+    if bytes.get(0..4) != Some(b"ptau".as_slice()) {
+        return Err(invalid_ptau("bad magic"));
+    }
+
+    let n_sections = read_u32(&bytes, 8)?;
+    let mut sections = HashMap::new();
+    let mut offset = 12;
+    for _ in 0..n_sections {
+        let section_type = read_u32(&bytes, offset)?;
+        let size = read_u64(&bytes, offset + 4)?;
+        let start = offset + 12;
+        // the size is read from the file, so the end of the section may not even be a `usize`
+        let end = usize::try_from(size)
+            .ok()
+            .and_then(|size| start.checked_add(size))
+            .ok_or_else(|| invalid_ptau("section exceeds file size"))?;
+        let data = bytes
+            .get(start..end)
+            .ok_or_else(|| invalid_ptau("section exceeds file size"))?;
+        sections.insert(section_type, data);
+        offset = end;
+    }
+    let section = |section_type: u32| {
+        sections
+            .get(&section_type)
+            .copied()
+            .ok_or_else(|| invalid_ptau(&format!("missing section {section_type}")))
+    };
+
+    let header = section(PTAU_HEADER_SECTION)?;
+    let n8 = read_u32(header, 0)? as usize;
+    let mut modulus = hex::decode(Fq::MODULUS.trim_start_matches("0x")).unwrap();
+    modulus.reverse();
+    if n8 != 32 || header.get(4..4 + n8) != Some(modulus.as_slice()) {
+        return Err(invalid_ptau("not a bn254 ceremony"));
+    }
+    let power = read_u32(header, 4 + n8)?;
+    if k > power {
+        return Err(invalid_ptau(&format!(
+            "ceremony only supports k <= {power}, got {k}"
+        )));
+    }
+
+    // `power` is read from the file too, so it doesn't keep `1 << k` from overflowing
+    let n = 1usize
+        .checked_shl(k)
+        .ok_or_else(|| invalid_ptau(&format!("k = {k} is too large")))?;
+    let g = section(PTAU_TAU_G1_SECTION)?
+        .chunks_exact(64)
+        .take(n)
+        .map(|point| G1Affine::from_raw_bytes(point).ok_or_else(|| invalid_ptau("bad G1 point")))
+        .collect::<Result<Vec<_>, _>>()?;
+    let g2s = section(PTAU_TAU_G2_SECTION)?
+        .chunks_exact(128)
+        .take(2)
+        .map(|point| G2Affine::from_raw_bytes(point).ok_or_else(|| invalid_ptau("bad G2 point")))
+        .collect::<Result<Vec<_>, _>>()?;
+    if g.len() != n || g2s.len() != 2 {
+        return Err(invalid_ptau("not enough powers of tau"));
+    }
+    let g_lagrange = g_to_lagrange::<G1Affine>(g.iter().map(|p| G1::from(*p)).collect(), k);
+
+    // ParamsKZG has no public constructor from parts, so we go through its raw serialization:
+    // k | g | g_lagrange | g2 | s_g2
+    let mut raw = k.to_le_bytes().to_vec();
+    for point in g.iter().chain(g_lagrange.iter()) {
+        raw.extend(point.to_raw_bytes());
+    }
+    for point in g2s.iter() {
+        raw.extend(point.to_raw_bytes());
+    }
+    Ok(ParamsKZG::<Bn256>::read_custom(
+        &mut raw.as_slice(),
+        SerdeFormat::RawBytes,
+    )?)
+}
+
+/// sha256 of `s_g2`, i.e. of the setup's secret. Params of the same `k` share it only if they
+/// come from the same setup, so it tells e.g. test params from the ceremony's.
+pub fn fingerprint(params: &ParamsKZG<Bn256>) -> [u8; 32] {
+    Sha256::digest(params.s_g2().to_raw_bytes()).into()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{from_ptau, load_or_generate, sidecar_path, Error};
+    use halo2_proofs::{
+        halo2curves::{
+            bn256::{Bn256, Fq, Fr, G1Affine, G2Affine},
+            ff::{Field, PrimeField},
+            group::prime::PrimeCurveAffine,
+            serde::SerdeObject,
+        },
+        poly::{commitment::Params, kzg::commitment::ParamsKZG},
+    };
+
+    #[test]
+    fn test_params_cache() {
+        let dir = std::env::temp_dir().join("tornado-params-cache");
+        let _ = std::fs::remove_dir_all(&dir);
+        let path = dir.join("kzg_bn256_4.params");
+
+        let generated = load_or_generate(&path, 4).unwrap();
+        assert!(sidecar_path(&path).exists());
+
+        let loaded = load_or_generate(&path, 4).unwrap();
+        let (mut a, mut b) = (vec![], vec![]);
+        generated.write(&mut a).unwrap();
+        loaded.write(&mut b).unwrap();
+        assert_eq!(a, b);
+
+        assert!(matches!(
+            load_or_generate(&path, 5),
+            Err(Error::WrongK {
+                expected: 5,
+                found: 4
+            })
+        ));
+
+        let mut bytes = std::fs::read(&path).unwrap();
+        let last = bytes.len() - 1;
+        bytes[last] ^= 1;
+        std::fs::write(&path, bytes).unwrap();
+        assert!(matches!(
+            load_or_generate(&path, 4),
+            Err(Error::IntegrityMismatch { .. })
+        ));
+    }
+
+    fn write_section(file: &mut Vec<u8>, section_type: u32, data: &[u8]) {
+        file.extend(section_type.to_le_bytes());
+        file.extend((data.len() as u64).to_le_bytes());
+        file.extend(data);
+    }
+
+    #[test]
+    fn test_from_ptau() {
+        let power = 4;
+        let k = 3;
+        let tau = Fr::from(0xdeadbeef);
+
+        let mut header = 32u32.to_le_bytes().to_vec();
+        let mut modulus = hex::decode(Fq::MODULUS.trim_start_matches("0x")).unwrap();
+        modulus.reverse();
+        header.extend(modulus);
+        header.extend(power.to_le_bytes());
+        header.extend(power.to_le_bytes());
+
+        let mut tau_g1 = vec![];
+        let mut tau_g2 = vec![];
+        let mut s = Fr::ONE;
+        for i in 0..(1u64 << power) * 2 - 1 {
+            let g1: G1Affine = (G1Affine::generator() * s).into();
+            tau_g1.extend(g1.to_raw_bytes());
+            if i < 1 << power {
+                let g2: G2Affine = (G2Affine::generator() * s).into();
+                tau_g2.extend(g2.to_raw_bytes());
+            }
+            s *= tau;
+        }
+
+        let mut file = b"ptau".to_vec();
+        file.extend(1u32.to_le_bytes());
+        file.extend(3u32.to_le_bytes());
+        write_section(&mut file, 1, &header);
+        write_section(&mut file, 2, &tau_g1);
+        write_section(&mut file, 3, &tau_g2);
+
+        let path = std::env::temp_dir().join("tornado-test.ptau");
+        std::fs::write(&path, file).unwrap();
+
+        let params = from_ptau(&path, k).unwrap();
+        let expected = ParamsKZG::<Bn256>::unsafe_setup_with_s(k, tau);
+        let (mut a, mut b) = (vec![], vec![]);
+        params.write(&mut a).unwrap();
+        expected.write(&mut b).unwrap();
+        assert_eq!(a, b);
+
+        assert!(matches!(
+            from_ptau(&path, power + 1),
+            Err(Error::InvalidPtau(_))
+        ));
+
+        // a header claiming more powers than `1 << k` can count
+        let mut huge = header.clone();
+        let power_offset = huge.len() - 8;
+        huge[power_offset..power_offset + 4].copy_from_slice(&u32::MAX.to_le_bytes());
+        let mut file = b"ptau".to_vec();
+        file.extend(1u32.to_le_bytes());
+        file.extend(3u32.to_le_bytes());
+        write_section(&mut file, 1, &huge);
+        write_section(&mut file, 2, &tau_g1);
+        write_section(&mut file, 3, &tau_g2);
+        std::fs::write(&path, file).unwrap();
+        assert!(matches!(
+            from_ptau(&path, usize::BITS),
+            Err(Error::InvalidPtau(_))
+        ));
+
+        // a section size overflowing the offset of the next one
+        let mut file = b"ptau".to_vec();
+        file.extend(1u32.to_le_bytes());
+        file.extend(2u32.to_le_bytes());
+        write_section(&mut file, 1, &header);
+        file.extend(2u32.to_le_bytes());
+        file.extend(u64::MAX.to_le_bytes());
+        std::fs::write(&path, file).unwrap();
+        assert!(matches!(from_ptau(&path, k), Err(Error::InvalidPtau(_))));
+    }
+}
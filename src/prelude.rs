@@ -0,0 +1,34 @@
+//! What an application proving and verifying withdrawals needs, in one import:
+//!
+//! ```ignore
+//! use tornado_halo2::prelude::*;
+//! ```
+//!
+//! The chips and the other circuits stay in their modules: they are building blocks for new
+//! circuits, not part of the withdrawal flow.
+pub use crate::{
+    async_prover::{prove_async, CancellationToken, ProofTask, Stage},
+    circuits::merkle::MerklePathWitness,
+    circuits::tornado::{
+        BuildError, PublicInputs, TornadoCircuit, TornadoCircuitBuilder, TornadoPublicInputs,
+    },
+    error::TornadoError,
+    ext_data::{address_to_field, asset_id, ext_data_hash},
+    native::{compute_root, hash_value, hash_values},
+    note::Note,
+    proof::{CurveId, Proof},
+    prover::{
+        keygen, keygen_with_options, prove, prove_many, prove_pipelined, prove_with_options,
+        prove_with_transcript, read_vk, tornado_circuit_id, verify, verify_batch,
+        verify_with_transcript, vk_fingerprint, write_vk, ProverOptions, TranscriptKind,
+    },
+    tree::{
+        Frontier, Hasher, IncrementalMerkleTree, MerkleProof, ProductHasher, RootHistory,
+        Sha256Hasher, TreeCheckpoint, TreeDelta, ROOT_HISTORY_SIZE,
+    },
+};
+
+#[cfg(feature = "bn256")]
+pub use crate::curves::TornadoBn256;
+#[cfg(feature = "pasta")]
+pub use crate::curves::TornadoPasta;
@@ -0,0 +1,397 @@
+use crate::{
+    codec::{decimal_to_le_bytes, le_bytes_to_decimal},
+    envelope::{self, CircuitId, Envelope, Kind, UNBOUND},
+};
+use halo2_proofs::halo2curves::{bn256, ff::PrimeField, pasta};
+use serde::{Deserialize, Serialize};
+use std::fmt;
+
+/// Bumped whenever the serialized layout of `Proof` changes
+pub const PROOF_VERSION: u16 = 1;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum CurveId {
+    Bn256,
+    Pasta,
+}
+
+#[derive(Debug)]
+pub enum Error {
+    Encoding(bincode::Error),
+    Hex(hex::FromHexError),
+    Json(serde_json::Error),
+    Envelope(envelope::Error),
+    UnsupportedVersion(u16),
+    UnsupportedProtocol(String),
+    InvalidFieldElement,
+    /// The envelope's curve isn't the proof's
+    CurveMismatch,
+    /// The proof was made for another circuit, see `Proof::check_circuit_id`
+    CircuitMismatch,
+    /// The proof isn't bound to a circuit, see `Proof::require_circuit_id`
+    UnboundCircuit,
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::Encoding(e) => write!(f, "proof encoding error: {e}"),
+            Error::Hex(e) => write!(f, "invalid proof hex: {e}"),
+            Error::Json(e) => write!(f, "invalid proof json: {e}"),
+            Error::Envelope(e) => write!(f, "invalid proof envelope: {e}"),
+            Error::UnsupportedVersion(v) => {
+                write!(
+                    f,
+                    "unsupported proof version {v} (expected {PROOF_VERSION})"
+                )
+            }
+            Error::UnsupportedProtocol(p) => write!(f, "unsupported proof protocol {p}"),
+            Error::InvalidFieldElement => {
+                write!(f, "public input is not a canonical field element")
+            }
+            Error::CurveMismatch => write!(f, "proof envelope is for another curve"),
+            Error::CircuitMismatch => write!(f, "proof was made for another circuit"),
+            Error::UnboundCircuit => write!(f, "proof is not bound to a circuit"),
+        }
+    }
+}
+
+impl std::error::Error for Error {}
+
+impl From<bincode::Error> for Error {
+    fn from(e: bincode::Error) -> Self {
+        Error::Encoding(e)
+    }
+}
+
+impl From<hex::FromHexError> for Error {
+    fn from(e: hex::FromHexError) -> Self {
+        Error::Hex(e)
+    }
+}
+
+impl From<serde_json::Error> for Error {
+    fn from(e: serde_json::Error) -> Self {
+        Error::Json(e)
+    }
+}
+
+impl From<envelope::Error> for Error {
+    fn from(e: envelope::Error) -> Self {
+        Error::Envelope(e)
+    }
+}
+
+impl CurveId {
+    /// The curve whose scalar field is `F`. Both fields of the pasta cycle are `Pasta`.
+    pub fn of<F: PrimeField>() -> Option<Self> {
+        if F::MODULUS == bn256::Fr::MODULUS {
+            Some(CurveId::Bn256)
+        } else if [pasta::Fp::MODULUS, pasta::Fq::MODULUS].contains(&F::MODULUS) {
+            Some(CurveId::Pasta)
+        } else {
+            None
+        }
+    }
+
+    /// Curve name as used by snarkjs/circom
+    pub fn snarkjs_name(&self) -> &'static str {
+        match self {
+            CurveId::Bn256 => "bn128",
+            CurveId::Pasta => "pasta",
+        }
+    }
+
+    pub fn from_snarkjs_name(name: &str) -> Option<Self> {
+        match name {
+            "bn128" => Some(CurveId::Bn256),
+            "pasta" => Some(CurveId::Pasta),
+            _ => None,
+        }
+    }
+}
+
+/// `proof.json` layout. snarkjs only knows Groth16/Plonk/Fflonk proof objects, so for halo2
+/// we keep its envelope (`protocol`, `curve`) and carry the transcript as a single hex string.
+#[derive(Debug, Serialize, Deserialize)]
+struct SnarkJsProof {
+    protocol: String,
+    curve: String,
+    version: u16,
+    k: u32,
+    proof: String,
+}
+
+/// A proof as it is persisted or sent over the wire: the raw transcript bytes
+/// plus everything a verifier needs to know about how they were produced.
+/// Public inputs are stored per instance column, as field element representations.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Proof {
+    pub version: u16,
+    pub curve: CurveId,
+    pub k: u32,
+    pub public_inputs: Vec<Vec<[u8; 32]>>,
+    pub transcript: Vec<u8>,
+    /// Carried by the envelope rather than the payload, so legacy proofs still decode.
+    /// `UNBOUND` unless set.
+    #[serde(skip)]
+    pub circuit_id: CircuitId,
+}
+
+impl Proof {
+    pub fn new<F: PrimeField<Repr = [u8; 32]>>(
+        curve: CurveId,
+        k: u32,
+        public_inputs: &[Vec<F>],
+        transcript: Vec<u8>,
+    ) -> Self {
+        Self {
+            version: PROOF_VERSION,
+            curve,
+            k,
+            public_inputs: public_inputs
+                .iter()
+                .map(|column| column.iter().map(|f| f.to_repr()).collect())
+                .collect(),
+            transcript,
+            circuit_id: UNBOUND,
+        }
+    }
+
+    /// Binds the proof to the circuit it was made for, see `prover::circuit_id`
+    pub fn with_circuit_id(self, circuit_id: CircuitId) -> Self {
+        Self { circuit_id, ..self }
+    }
+
+    /// Fails with `CircuitMismatch` if the proof is bound to a circuit other than `expected`.
+    /// Unbound proofs, e.g. written before circuit ids, pass: their transcript is the only check.
+    pub fn check_circuit_id(&self, expected: &CircuitId) -> Result<(), Error> {
+        if self.circuit_id != UNBOUND && self.circuit_id != *expected {
+            return Err(Error::CircuitMismatch);
+        }
+        Ok(())
+    }
+
+    /// Like `check_circuit_id`, but also fails with `UnboundCircuit` on unbound proofs, so that
+    /// a client can't skip the check by stripping the id. Verifiers taking proofs from untrusted
+    /// parties use this one, `check_circuit_id` being for proofs decoded from legacy files.
+    pub fn require_circuit_id(&self, expected: &CircuitId) -> Result<(), Error> {
+        if self.circuit_id == UNBOUND {
+            return Err(Error::UnboundCircuit);
+        }
+        self.check_circuit_id(expected)
+    }
+
+    pub fn public_inputs<F: PrimeField<Repr = [u8; 32]>>(&self) -> Result<Vec<Vec<F>>, Error> {
+        self.public_inputs
+            .iter()
+            .map(|column| {
+                column
+                    .iter()
+                    .map(|repr| Option::from(F::from_repr(*repr)).ok_or(Error::InvalidFieldElement))
+                    .collect()
+            })
+            .collect()
+    }
+
+    /// The `bincode` encoding of the proof in an `Envelope`
+    pub fn to_bytes(&self) -> Result<Vec<u8>, Error> {
+        let envelope = Envelope {
+            kind: Kind::Proof,
+            curve: Some(self.curve),
+            circuit_id: self.circuit_id,
+            payload: bincode::serialize(self)?,
+        };
+        Ok(envelope.to_bytes())
+    }
+
+    /// Also reads the bare `bincode` encoding written before envelopes, as an unbound proof
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, Error> {
+        let envelope = Envelope::open(bytes, Kind::Proof)?;
+        let payload = envelope.as_ref().map_or(bytes, |e| e.payload.as_slice());
+        let mut proof: Self = bincode::deserialize(payload)?;
+        if proof.version != PROOF_VERSION {
+            return Err(Error::UnsupportedVersion(proof.version));
+        }
+        if let Some(envelope) = envelope {
+            if envelope.curve != Some(proof.curve) {
+                return Err(Error::CurveMismatch);
+            }
+            proof.circuit_id = envelope.circuit_id;
+        }
+        Ok(proof)
+    }
+
+    pub fn to_hex(&self) -> Result<String, Error> {
+        Ok(hex::encode(self.to_bytes()?))
+    }
+
+    pub fn from_hex(s: &str) -> Result<Self, Error> {
+        let bytes = hex::decode(s.trim_start_matches("0x"))?;
+        Self::from_bytes(&bytes)
+    }
+
+    /// Returns `(proof.json, public.json)`.
+    /// Like snarkjs, `public.json` is a flat array of decimal strings, so instance columns are
+    /// concatenated. All our circuits have a single instance column.
+    pub fn to_snarkjs_json(&self) -> Result<(String, String), Error> {
+        let proof = SnarkJsProof {
+            protocol: "halo2".to_string(),
+            curve: self.curve.snarkjs_name().to_string(),
+            version: self.version,
+            k: self.k,
+            proof: format!("0x{}", hex::encode(&self.transcript)),
+        };
+        let public: Vec<String> = self
+            .public_inputs
+            .iter()
+            .flatten()
+            .map(le_bytes_to_decimal)
+            .collect();
+        Ok((
+            serde_json::to_string_pretty(&proof)?,
+            serde_json::to_string_pretty(&public)?,
+        ))
+    }
+
+    pub fn from_snarkjs_json(proof_json: &str, public_json: &str) -> Result<Self, Error> {
+        let proof: SnarkJsProof = serde_json::from_str(proof_json)?;
+        if proof.protocol != "halo2" {
+            return Err(Error::UnsupportedProtocol(proof.protocol));
+        }
+        if proof.version != PROOF_VERSION {
+            return Err(Error::UnsupportedVersion(proof.version));
+        }
+        let curve = CurveId::from_snarkjs_name(&proof.curve)
+            .ok_or_else(|| Error::UnsupportedProtocol(proof.curve.clone()))?;
+
+        let public: Vec<String> = serde_json::from_str(public_json)?;
+        let public_inputs = public
+            .iter()
+            .map(|s| decimal_to_le_bytes(s).map_err(|_| Error::InvalidFieldElement))
+            .collect::<Result<Vec<_>, _>>()?;
+
+        Ok(Self {
+            version: proof.version,
+            curve,
+            k: proof.k,
+            public_inputs: vec![public_inputs],
+            transcript: hex::decode(proof.proof.trim_start_matches("0x"))?,
+            circuit_id: UNBOUND,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{CurveId, Error, Proof, PROOF_VERSION};
+    use crate::envelope::{self, Envelope, Kind};
+    use halo2_proofs::halo2curves::{bn256::Fr, pasta::Fp};
+
+    fn sample() -> Proof {
+        let public_inputs = vec![vec![Fp::from(0x456 * 0x456), Fp::from(1234)]];
+        Proof::new(CurveId::Pasta, 10, &public_inputs, vec![1, 2, 3, 4])
+    }
+
+    #[test]
+    fn test_proof_bytes_roundtrip() {
+        let proof = sample();
+        let decoded = Proof::from_bytes(&proof.to_bytes().unwrap()).unwrap();
+        assert_eq!(proof, decoded);
+        assert_eq!(
+            decoded.public_inputs::<Fp>().unwrap(),
+            vec![vec![Fp::from(0x456 * 0x456), Fp::from(1234)]]
+        );
+    }
+
+    #[test]
+    fn test_proof_envelope() {
+        let proof = sample().with_circuit_id([9; 32]);
+        let bytes = proof.to_bytes().unwrap();
+        let envelope = Envelope::from_bytes(&bytes).unwrap();
+        assert_eq!(envelope.kind, Kind::Proof);
+        assert_eq!(envelope.curve, Some(CurveId::Pasta));
+        assert_eq!(envelope.circuit_id, [9; 32]);
+        assert_eq!(Proof::from_bytes(&bytes).unwrap(), proof);
+
+        // bare bincode, as written before envelopes
+        let legacy = bincode::serialize(&proof).unwrap();
+        assert_eq!(Proof::from_bytes(&legacy).unwrap(), sample());
+
+        let other_curve = Envelope {
+            curve: Some(CurveId::Bn256),
+            ..envelope.clone()
+        };
+        assert!(matches!(
+            Proof::from_bytes(&other_curve.to_bytes()),
+            Err(Error::CurveMismatch)
+        ));
+        let note = Envelope {
+            kind: Kind::Note,
+            ..envelope
+        };
+        assert!(matches!(
+            Proof::from_bytes(&note.to_bytes()),
+            Err(Error::Envelope(envelope::Error::UnexpectedKind(Kind::Note)))
+        ));
+    }
+
+    #[test]
+    fn test_check_circuit_id() {
+        let proof = sample().with_circuit_id([9; 32]);
+        let decoded = Proof::from_bytes(&proof.to_bytes().unwrap()).unwrap();
+        assert!(decoded.check_circuit_id(&[9; 32]).is_ok());
+        assert!(matches!(
+            decoded.check_circuit_id(&[8; 32]),
+            Err(Error::CircuitMismatch)
+        ));
+        assert!(sample().check_circuit_id(&[8; 32]).is_ok());
+
+        assert!(decoded.require_circuit_id(&[9; 32]).is_ok());
+        assert!(matches!(
+            decoded.require_circuit_id(&[8; 32]),
+            Err(Error::CircuitMismatch)
+        ));
+        assert!(matches!(
+            sample().require_circuit_id(&[8; 32]),
+            Err(Error::UnboundCircuit)
+        ));
+    }
+
+    #[test]
+    fn test_curve_of() {
+        assert_eq!(CurveId::of::<Fr>(), Some(CurveId::Bn256));
+        assert_eq!(CurveId::of::<Fp>(), Some(CurveId::Pasta));
+    }
+
+    #[test]
+    fn test_proof_hex_roundtrip() {
+        let proof = sample();
+        let hex = proof.to_hex().unwrap();
+        assert_eq!(Proof::from_hex(&hex).unwrap(), proof);
+        assert_eq!(Proof::from_hex(&format!("0x{hex}")).unwrap(), proof);
+    }
+
+    #[test]
+    fn test_proof_snarkjs_roundtrip() {
+        let proof = sample();
+        let (proof_json, public_json) = proof.to_snarkjs_json().unwrap();
+
+        let public: Vec<String> = serde_json::from_str(&public_json).unwrap();
+        assert_eq!(public, vec!["1232100", "1234"]);
+
+        let decoded = Proof::from_snarkjs_json(&proof_json, &public_json).unwrap();
+        assert_eq!(decoded, proof);
+    }
+
+    #[test]
+    fn test_proof_rejects_unknown_version() {
+        let mut proof = sample();
+        proof.version = PROOF_VERSION + 1;
+        let bytes = proof.to_bytes().unwrap();
+        assert!(matches!(
+            Proof::from_bytes(&bytes),
+            Err(Error::UnsupportedVersion(_))
+        ));
+    }
+}
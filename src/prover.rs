@@ -0,0 +1,621 @@
+use crate::{
+    circuits::tornado::PublicInputs,
+    envelope::CircuitId,
+    error::TornadoError,
+    trace::{trace_event, trace_span},
+};
+use halo2_proofs::{
+    halo2curves::bn256::{Bn256, Fr, G1Affine},
+    plonk::{create_proof, keygen_pk, keygen_vk, verify_proof, Circuit, ProvingKey, VerifyingKey},
+    poly::{
+        kzg::{
+            commitment::{KZGCommitmentScheme, ParamsKZG},
+            multiopen::{ProverSHPLONK, VerifierSHPLONK},
+            strategy::{AccumulatorStrategy, SingleStrategy},
+        },
+        VerificationStrategy,
+    },
+    transcript::{
+        Blake2bRead, Blake2bWrite, Challenge255, TranscriptRead, TranscriptReadBuffer,
+        TranscriptWrite, TranscriptWriterBuffer,
+    },
+    SerdeFormat,
+};
+#[cfg(feature = "evm")]
+use halo2_solidity_verifier::Keccak256Transcript;
+use rand_core::OsRng;
+use sha2::{Digest, Sha256};
+use std::{
+    fs, io,
+    path::{Path, PathBuf},
+    sync::{mpsc, Arc, Mutex, PoisonError},
+    thread,
+};
+
+/// Hash of the Fiat-Shamir transcript. A proof only verifies with the transcript it was made
+/// with, and `prove`, `verify` and the rest of this module use `Blake2b`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum TranscriptKind {
+    /// halo2's own transcript, the cheapest to verify off-chain
+    #[default]
+    Blake2b,
+    /// keccak256, which the Solidity verifier of `evm::render_verifier` computes natively
+    #[cfg(feature = "evm")]
+    Keccak256,
+}
+
+pub fn keygen<C: Circuit<Fr>>(
+    params: &ParamsKZG<Bn256>,
+    circuit: &C,
+) -> Result<ProvingKey<G1Affine>, TornadoError> {
+    trace_span!(INFO, "keygen", k = params.k());
+    let vk = keygen_vk(params, circuit)?;
+    Ok(keygen_pk(params, vk, circuit)?)
+}
+
+/// Returns the raw transcript bytes. With the `icicle` feature, halo2 runs the large MSMs on
+/// the GPU and nothing else changes.
+pub fn prove<C: Circuit<Fr>>(
+    params: &ParamsKZG<Bn256>,
+    pk: &ProvingKey<G1Affine>,
+    circuit: C,
+    instances: &[Vec<Fr>],
+) -> Result<Vec<u8>, TornadoError> {
+    prove_with_transcript(params, pk, circuit, instances, TranscriptKind::Blake2b)
+}
+
+/// Same as `prove`, with the transcript hashed by `transcript`
+pub fn prove_with_transcript<C: Circuit<Fr>>(
+    params: &ParamsKZG<Bn256>,
+    pk: &ProvingKey<G1Affine>,
+    circuit: C,
+    instances: &[Vec<Fr>],
+    transcript: TranscriptKind,
+) -> Result<Vec<u8>, TornadoError> {
+    trace_span!(INFO, "prove", k = params.k(), transcript = ?transcript);
+    let instances: Vec<&[Fr]> = instances.iter().map(|column| column.as_slice()).collect();
+    let proof = match transcript {
+        TranscriptKind::Blake2b => create(
+            params,
+            pk,
+            circuit,
+            &instances,
+            Blake2bWrite::<_, G1Affine, Challenge255<_>>::init(vec![]),
+        )?,
+        #[cfg(feature = "evm")]
+        TranscriptKind::Keccak256 => create(
+            params,
+            pk,
+            circuit,
+            &instances,
+            Keccak256Transcript::new(vec![]),
+        )?,
+    };
+    trace_event!(bytes = proof.len(), "proof created");
+    Ok(proof)
+}
+
+/// How `keygen_with_options` and `prove_with_options` trade speed for peak memory. The
+/// defaults are those of `keygen` and `prove`.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ProverOptions {
+    /// Threads the proof runs on, `None` for rayon's global pool (one per core). halo2 splits
+    /// its FFTs and MSMs into a chunk per thread, each with its own scratch buffers, so fewer
+    /// threads lower the peak memory and slow the proof down.
+    pub threads: Option<usize>,
+    /// Directory holding proving keys between runs. Reading a key back needs far less memory
+    /// than generating it, so only the first run pays for keygen.
+    pub scratch_dir: Option<PathBuf>,
+    pub transcript: TranscriptKind,
+}
+
+impl ProverOptions {
+    /// For devices with a few GB of memory, e.g. proving a depth-32 withdrawal on a phone
+    pub fn low_memory(scratch_dir: impl Into<PathBuf>) -> Self {
+        Self {
+            threads: Some(2),
+            scratch_dir: Some(scratch_dir.into()),
+            transcript: TranscriptKind::default(),
+        }
+    }
+}
+
+/// Same as `keygen`, reading the proving key from `options.scratch_dir` if an earlier run
+/// wrote it there. Keys are named after the fingerprint of their verifying key, so a changed
+/// circuit or params never reads a stale key.
+pub fn keygen_with_options<C: Circuit<Fr>>(
+    params: &ParamsKZG<Bn256>,
+    circuit: &C,
+    options: &ProverOptions,
+) -> Result<ProvingKey<G1Affine>, TornadoError> {
+    let Some(scratch_dir) = &options.scratch_dir else {
+        return keygen(params, circuit);
+    };
+    trace_span!(INFO, "keygen", k = params.k());
+    let vk = keygen_vk(params, circuit)?;
+    let path = pk_path(scratch_dir, &vk_fingerprint(&vk));
+    if path.exists() {
+        trace_event!(path = %path.display(), "proving key read");
+        let mut reader = io::BufReader::new(fs::File::open(path)?);
+        return Ok(ProvingKey::read::<_, C>(
+            &mut reader,
+            SerdeFormat::RawBytes,
+        )?);
+    }
+
+    let pk = keygen_pk(params, vk, circuit)?;
+    fs::create_dir_all(scratch_dir)?;
+    // renamed once complete, so an interrupted write isn't read back as a key
+    let partial = path.with_extension("partial");
+    let mut writer = io::BufWriter::new(fs::File::create(&partial)?);
+    pk.write(&mut writer, SerdeFormat::RawBytes)?;
+    io::Write::flush(&mut writer)?;
+    fs::rename(partial, path)?;
+    Ok(pk)
+}
+
+/// Where `keygen_with_options` keeps the proving key of the verifying key with `fingerprint`
+pub(crate) fn pk_path(scratch_dir: &Path, fingerprint: &str) -> PathBuf {
+    scratch_dir.join(format!("pk-{fingerprint}.bin"))
+}
+
+/// Same as `prove_with_transcript(.., options.transcript)`, on `options.threads` threads
+pub fn prove_with_options<C: Circuit<Fr> + Send>(
+    params: &ParamsKZG<Bn256>,
+    pk: &ProvingKey<G1Affine>,
+    circuit: C,
+    instances: &[Vec<Fr>],
+    options: &ProverOptions,
+) -> Result<Vec<u8>, TornadoError> {
+    let Some(threads) = options.threads else {
+        return prove_with_transcript(params, pk, circuit, instances, options.transcript);
+    };
+    let pool = rayon::ThreadPoolBuilder::new()
+        .num_threads(threads)
+        .build()
+        .map_err(io::Error::other)?;
+    // halo2's parallel FFTs and MSMs run on the pool they are called from
+    pool.install(|| prove_with_transcript(params, pk, circuit, instances, options.transcript))
+}
+
+fn create<C, T>(
+    params: &ParamsKZG<Bn256>,
+    pk: &ProvingKey<G1Affine>,
+    circuit: C,
+    instances: &[&[Fr]],
+    mut transcript: T,
+) -> Result<Vec<u8>, TornadoError>
+where
+    C: Circuit<Fr>,
+    T: TranscriptWriterBuffer<Vec<u8>, G1Affine, Challenge255<G1Affine>>,
+{
+    write_proof(params, pk, circuit, instances, &mut transcript)?;
+    Ok(transcript.finalize())
+}
+
+/// Writes the proof to `transcript`, which may wrap one of `TranscriptKind`
+pub(crate) fn write_proof<C, T>(
+    params: &ParamsKZG<Bn256>,
+    pk: &ProvingKey<G1Affine>,
+    circuit: C,
+    instances: &[&[Fr]],
+    transcript: &mut T,
+) -> Result<(), TornadoError>
+where
+    C: Circuit<Fr>,
+    T: TranscriptWrite<G1Affine, Challenge255<G1Affine>>,
+{
+    create_proof::<KZGCommitmentScheme<Bn256>, ProverSHPLONK<'_, Bn256>, _, _, _, _>(
+        params,
+        pk,
+        &[circuit],
+        &[instances],
+        OsRng,
+        transcript,
+    )?;
+    Ok(())
+}
+
+/// Proves many jobs one after the other, while a producer thread runs `build_witness` for job
+/// N+1 during job N's proof. At most one built circuit waits in between, to bound memory.
+/// Results are returned in job order.
+///
+/// Only `build_witness` overlaps with proving: halo2 synthesizes the circuit and assigns its
+/// witness inside `create_proof`, so that part of each proof still runs in turn. It pays off
+/// when building a job is slow by itself, e.g. syncing or reading a tree to take a path from;
+/// to prove faster, prove several jobs at once with `prove_many`.
+pub fn prove_pipelined<J, C, W>(
+    params: &ParamsKZG<Bn256>,
+    pk: &ProvingKey<G1Affine>,
+    jobs: Vec<J>,
+    build_witness: W,
+) -> Vec<Result<Vec<u8>, TornadoError>>
+where
+    J: Send,
+    C: Circuit<Fr> + Send,
+    W: Fn(J) -> Result<(C, Vec<Vec<Fr>>), TornadoError> + Send,
+{
+    let (sender, receiver) = mpsc::sync_channel(1);
+    thread::scope(|scope| {
+        scope.spawn(move || {
+            for job in jobs {
+                if sender.send(build_witness(job)).is_err() {
+                    break;
+                }
+            }
+        });
+
+        receiver
+            .into_iter()
+            .map(|built| {
+                let (circuit, instances) = built?;
+                prove(params, pk, circuit, &instances)
+            })
+            .collect()
+    })
+}
+
+/// Proves independent jobs on `concurrency` threads sharing `params` and `pk`, e.g. the
+/// withdrawals of a batch payout. Each proof also runs its FFTs and MSMs on rayon's global
+/// pool, so a few threads are usually enough to keep every core busy.
+///
+/// Returns the results as they complete, with the index of their job: iterating the receiver
+/// ends once every job is done. A job whose proof panics has no result. A `concurrency` of 0
+/// proves on a single thread.
+pub fn prove_many<C: Circuit<Fr> + Send + 'static>(
+    params: Arc<ParamsKZG<Bn256>>,
+    pk: Arc<ProvingKey<G1Affine>>,
+    jobs: Vec<(C, Vec<Vec<Fr>>)>,
+    concurrency: usize,
+) -> mpsc::Receiver<(usize, Result<Vec<u8>, TornadoError>)> {
+    let workers = concurrency.max(1).min(jobs.len());
+    let queue = Arc::new(Mutex::new(jobs.into_iter().enumerate()));
+    let (sender, receiver) = mpsc::channel();
+    for _ in 0..workers {
+        let (params, pk, queue, sender) =
+            (params.clone(), pk.clone(), queue.clone(), sender.clone());
+        thread::spawn(move || loop {
+            let job = queue.lock().unwrap_or_else(PoisonError::into_inner).next();
+            let Some((index, (circuit, instances))) = job else {
+                break;
+            };
+            if sender
+                .send((index, prove(&params, &pk, circuit, &instances)))
+                .is_err()
+            {
+                // the receiver was dropped, nobody waits for the remaining proofs
+                break;
+            }
+        });
+    }
+    receiver
+}
+
+/// Fails with `TornadoError::VerificationFailed` if the proof is invalid for `instances`
+pub fn verify(
+    params: &ParamsKZG<Bn256>,
+    vk: &VerifyingKey<G1Affine>,
+    proof: &[u8],
+    instances: &[Vec<Fr>],
+) -> Result<(), TornadoError> {
+    verify_with_transcript(params, vk, proof, instances, TranscriptKind::Blake2b)
+}
+
+/// Same as `verify`, for a proof made with `prove_with_transcript(.., transcript)`
+pub fn verify_with_transcript(
+    params: &ParamsKZG<Bn256>,
+    vk: &VerifyingKey<G1Affine>,
+    proof: &[u8],
+    instances: &[Vec<Fr>],
+    transcript: TranscriptKind,
+) -> Result<(), TornadoError> {
+    trace_span!(
+        INFO,
+        "verify",
+        k = params.k(),
+        bytes = proof.len(),
+        transcript = ?transcript
+    );
+    let instances: Vec<&[Fr]> = instances.iter().map(|column| column.as_slice()).collect();
+    match transcript {
+        TranscriptKind::Blake2b => check(
+            params,
+            vk,
+            &instances,
+            Blake2bRead::<_, G1Affine, Challenge255<_>>::init(proof),
+        ),
+        #[cfg(feature = "evm")]
+        TranscriptKind::Keccak256 => check(params, vk, &instances, Keccak256Transcript::new(proof)),
+    }
+}
+
+fn check<T: TranscriptRead<G1Affine, Challenge255<G1Affine>>>(
+    params: &ParamsKZG<Bn256>,
+    vk: &VerifyingKey<G1Affine>,
+    instances: &[&[Fr]],
+    mut transcript: T,
+) -> Result<(), TornadoError> {
+    verify_proof::<KZGCommitmentScheme<Bn256>, VerifierSHPLONK<'_, Bn256>, _, _, _>(
+        params,
+        vk,
+        SingleStrategy::new(params),
+        &[instances],
+        &mut transcript,
+    )
+    .map_err(|_| TornadoError::VerificationFailed)
+}
+
+/// Verifies many proofs for the same circuit at once. Each proof's opening is folded into a
+/// single accumulator, so the batch costs one final MSM and pairing check instead of one per
+/// proof. Fails if any proof is invalid, without telling which: fall back to `verify` for that.
+pub fn verify_batch(
+    params: &ParamsKZG<Bn256>,
+    vk: &VerifyingKey<G1Affine>,
+    proofs: &[(Vec<u8>, Vec<Vec<Fr>>)],
+) -> Result<(), TornadoError> {
+    trace_span!(INFO, "verify batch", k = params.k(), proofs = proofs.len());
+    let mut strategy = AccumulatorStrategy::new(params);
+    for (proof, instances) in proofs {
+        let instances: Vec<&[Fr]> = instances.iter().map(|column| column.as_slice()).collect();
+        let mut transcript = Blake2bRead::<_, G1Affine, Challenge255<_>>::init(proof.as_slice());
+        strategy = verify_proof::<KZGCommitmentScheme<Bn256>, VerifierSHPLONK<'_, Bn256>, _, _, _>(
+            params,
+            vk,
+            strategy,
+            &[&instances],
+            &mut transcript,
+        )
+        .map_err(|_| TornadoError::VerificationFailed)?;
+    }
+    if strategy.finalize() {
+        Ok(())
+    } else {
+        Err(TornadoError::VerificationFailed)
+    }
+}
+
+pub fn write_vk(vk: &VerifyingKey<G1Affine>, writer: &mut impl io::Write) -> io::Result<()> {
+    vk.write(writer, SerdeFormat::RawBytes)
+}
+
+/// The circuit type is only needed to rebuild the constraint system (`C::configure`),
+/// no witness or proving key is involved.
+pub fn read_vk<C: Circuit<Fr>>(reader: &mut impl io::Read) -> io::Result<VerifyingKey<G1Affine>> {
+    VerifyingKey::read::<_, C>(reader, SerdeFormat::RawBytes)
+}
+
+/// Hex sha256 of the serialized verifying key
+pub fn vk_fingerprint(vk: &VerifyingKey<G1Affine>) -> String {
+    hex::encode(Sha256::digest(vk.to_bytes(SerdeFormat::RawBytes)))
+}
+
+/// Identifies a version of a circuit: sha256 of the serialized verifying key, the length of
+/// each instance column and the tree depth. A proof carrying it (`Proof::with_circuit_id`) is
+/// rejected by `Proof::check_circuit_id` before its transcript is read if it was made for
+/// another circuit, e.g. after the circuit changed or with other params.
+pub fn circuit_id(
+    vk: &VerifyingKey<G1Affine>,
+    instance_lengths: &[usize],
+    depth: usize,
+) -> CircuitId {
+    let mut hasher = Sha256::new();
+    hasher.update(b"tornado-halo2 circuit id");
+    hasher.update(vk.to_bytes(SerdeFormat::RawBytes));
+    hasher.update((instance_lengths.len() as u32).to_be_bytes());
+    for len in instance_lengths {
+        hasher.update((*len as u32).to_be_bytes());
+    }
+    hasher.update((depth as u32).to_be_bytes());
+    hasher.finalize().into()
+}
+
+/// `circuit_id` of `TornadoCircuit<Fr, DEPTH>` with the verifying key `vk`
+pub fn tornado_circuit_id<const DEPTH: usize>(vk: &VerifyingKey<G1Affine>) -> CircuitId {
+    circuit_id(vk, &[PublicInputs::LEN], DEPTH)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        circuit_id, keygen, keygen_with_options, prove, prove_many, prove_pipelined,
+        prove_with_options, read_vk, tornado_circuit_id, verify, verify_batch, vk_fingerprint,
+        write_vk, ProverOptions,
+    };
+    use crate::circuits::{merkle::MerklePathWitness, tornado::TornadoCircuit};
+    use halo2_proofs::{
+        circuit::Value,
+        halo2curves::bn256::{Bn256, Fr},
+        poly::kzg::commitment::ParamsKZG,
+    };
+    use rand_core::OsRng;
+    use std::{fs, sync::Arc};
+
+    fn circuit(nullifier: u64, secret: u64) -> (TornadoCircuit<Fr, 5>, Vec<Vec<Fr>>) {
+        let elements = [2, 5, 7, 14, 23];
+        // an amount and asset id of 1 keep the commitment `nullifier * secret`
+        let root = nullifier * secret * elements.iter().product::<u64>();
+        let circuit = TornadoCircuit::new(
+            Value::known(Fr::from(nullifier)),
+            Value::known(Fr::from(secret)),
+            // path indices 0, 0, 1, 1, 0
+            MerklePathWitness::known(elements.map(Fr::from), Fr::from(0b01100)),
+            Value::known(Fr::from(0xe47)),
+            Value::known(Fr::from(0x11)),
+            Value::known(Fr::from(0x22)),
+            Value::known(Fr::from(50)),
+            Value::known(Fr::from(0)),
+            Value::known(Fr::from(1)),
+            Value::known(Fr::from(1)),
+        );
+        (
+            circuit,
+            vec![vec![
+                Fr::from(nullifier * nullifier),
+                Fr::from(root),
+                Fr::from(0xe47),
+                Fr::from(0x11),
+                Fr::from(0x22),
+                Fr::from(50),
+                Fr::from(0),
+                Fr::from(1),
+                Fr::from(1),
+            ]],
+        )
+    }
+
+    #[test]
+    fn test_prove_pipelined() {
+        let params = ParamsKZG::<Bn256>::setup(8, OsRng);
+        let pk = keygen(&params, &circuit(1, 1).0).unwrap();
+
+        let jobs = vec![(0x456, 0xabc), (0x123, 0x789), (7, 11)];
+        let proofs = prove_pipelined(&params, &pk, jobs.clone(), |(nullifier, secret)| {
+            Ok(circuit(nullifier, secret))
+        });
+        assert_eq!(proofs.len(), jobs.len());
+        for (proof, (nullifier, secret)) in proofs.into_iter().zip(jobs) {
+            let (_, instances) = circuit(nullifier, secret);
+            assert!(verify(&params, pk.get_vk(), &proof.unwrap(), &instances).is_ok());
+        }
+    }
+
+    #[test]
+    fn test_prove_many() {
+        let params = Arc::new(ParamsKZG::<Bn256>::setup(8, OsRng));
+        let pk = Arc::new(keygen(&params, &circuit(1, 1).0).unwrap());
+
+        let jobs = [(0x456, 0xabc), (0x123, 0x789), (7, 11), (13, 17)];
+        let batch = jobs.iter().map(|&(n, s)| circuit(n, s)).collect();
+        let mut proofs: Vec<_> = prove_many(params.clone(), pk.clone(), batch, 2)
+            .into_iter()
+            .collect();
+        assert_eq!(proofs.len(), jobs.len());
+        proofs.sort_by_key(|(index, _)| *index);
+        for (i, (index, proof)) in proofs.into_iter().enumerate() {
+            assert_eq!(index, i);
+            let (_, instances) = circuit(jobs[i].0, jobs[i].1);
+            assert!(verify(&params, pk.get_vk(), &proof.unwrap(), &instances).is_ok());
+        }
+
+        // no thread, more threads than jobs, and no job
+        assert_eq!(
+            prove_many(params.clone(), pk.clone(), vec![circuit(7, 11)], 0)
+                .iter()
+                .count(),
+            1
+        );
+        assert_eq!(
+            prove_many(params.clone(), pk.clone(), vec![circuit(7, 11)], 4)
+                .iter()
+                .count(),
+            1
+        );
+        assert_eq!(
+            prove_many::<TornadoCircuit<Fr, 5>>(params, pk, vec![], 4)
+                .iter()
+                .count(),
+            0
+        );
+    }
+
+    #[test]
+    fn test_prove_verify_with_exported_vk() {
+        let (circuit, instances) = circuit(0x456, 0xabc);
+
+        let params = ParamsKZG::<Bn256>::setup(8, OsRng);
+        let pk = keygen(&params, &circuit).unwrap();
+        let proof = prove(&params, &pk, circuit, &instances).unwrap();
+
+        let mut vk_bytes = vec![];
+        write_vk(pk.get_vk(), &mut vk_bytes).unwrap();
+        let vk = read_vk::<TornadoCircuit<Fr, 5>>(&mut vk_bytes.as_slice()).unwrap();
+        assert_eq!(vk_fingerprint(&vk), vk_fingerprint(pk.get_vk()));
+        assert_eq!(
+            tornado_circuit_id::<5>(&vk),
+            tornado_circuit_id::<5>(pk.get_vk())
+        );
+
+        assert!(verify(&params, &vk, &proof, &instances).is_ok());
+        let mut wrong_instances = instances.clone();
+        wrong_instances[0][1] += Fr::from(1);
+        assert!(verify(&params, &vk, &proof, &wrong_instances).is_err());
+    }
+
+    #[test]
+    fn test_prove_with_options() {
+        let dir = std::env::temp_dir().join("tornado-prover-scratch");
+        let _ = fs::remove_dir_all(&dir);
+        let options = ProverOptions::low_memory(&dir);
+        let (circuit, instances) = circuit(0x456, 0xabc);
+        let params = ParamsKZG::<Bn256>::setup(8, OsRng);
+
+        let pk = keygen_with_options(&params, &circuit, &options).unwrap();
+        assert_eq!(fs::read_dir(&dir).unwrap().count(), 1);
+        // read back from the scratch directory
+        let cached = keygen_with_options(&params, &circuit, &options).unwrap();
+        assert_eq!(vk_fingerprint(cached.get_vk()), vk_fingerprint(pk.get_vk()));
+
+        let proof = prove_with_options(&params, &cached, circuit, &instances, &options).unwrap();
+        assert!(verify(&params, pk.get_vk(), &proof, &instances).is_ok());
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[cfg(feature = "evm")]
+    #[test]
+    fn test_keccak256_transcript() {
+        use super::{prove_with_transcript, verify_with_transcript, TranscriptKind};
+
+        let (withdrawal, instances) = circuit(0x456, 0xabc);
+        let params = ParamsKZG::<Bn256>::setup(8, OsRng);
+        let pk = keygen(&params, &withdrawal).unwrap();
+        let keccak = TranscriptKind::Keccak256;
+        let proof = prove_with_transcript(&params, &pk, withdrawal, &instances, keccak).unwrap();
+        assert!(verify_with_transcript(&params, pk.get_vk(), &proof, &instances, keccak).is_ok());
+
+        // a proof only verifies with the transcript it was made with
+        assert!(verify(&params, pk.get_vk(), &proof, &instances).is_err());
+        let blake2b = prove(&params, &pk, circuit(0x456, 0xabc).0, &instances).unwrap();
+        assert!(
+            verify_with_transcript(&params, pk.get_vk(), &blake2b, &instances, keccak).is_err()
+        );
+    }
+
+    #[test]
+    fn test_verify_batch() {
+        let params = ParamsKZG::<Bn256>::setup(8, OsRng);
+        let pk = keygen(&params, &circuit(1, 1).0).unwrap();
+
+        let mut batch: Vec<_> = [(0x456, 0xabc), (0x123, 0x789), (7, 11)]
+            .into_iter()
+            .map(|(nullifier, secret)| {
+                let (circuit, instances) = circuit(nullifier, secret);
+                let proof = prove(&params, &pk, circuit, &instances).unwrap();
+                (proof, instances)
+            })
+            .collect();
+        assert!(verify_batch(&params, pk.get_vk(), &[]).is_ok());
+        assert!(verify_batch(&params, pk.get_vk(), &batch).is_ok());
+
+        // a single bad proof fails the whole batch
+        batch[1].1[0][1] += Fr::from(1);
+        assert!(verify_batch(&params, pk.get_vk(), &batch).is_err());
+    }
+
+    #[test]
+    fn test_circuit_id() {
+        let params = ParamsKZG::<Bn256>::setup(8, OsRng);
+        let pk = keygen(&params, &circuit(1, 1).0).unwrap();
+        let vk = pk.get_vk();
+        let id = tornado_circuit_id::<5>(vk);
+        assert_eq!(id, circuit_id(vk, &[9], 5));
+        assert_eq!(id, tornado_circuit_id::<5>(vk));
+
+        assert_ne!(id, circuit_id(vk, &[9], 6));
+        assert_ne!(id, circuit_id(vk, &[10], 5));
+        assert_ne!(id, circuit_id(vk, &[9, 0], 5));
+        // the same circuit with other params
+        let other_params = ParamsKZG::<Bn256>::setup(8, OsRng);
+        let other_vk = keygen(&other_params, &circuit(1, 1).0).unwrap();
+        assert_ne!(id, tornado_circuit_id::<5>(other_vk.get_vk()));
+    }
+}
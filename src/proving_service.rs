@@ -0,0 +1,461 @@
+//! Proving on a server, for clients that can't prove a deep tree themselves (browsers, phones).
+//! Like `relayer`, this is independent of the HTTP server in front of it.
+//!
+//! [`ProvingService`] takes the JSON body of a [`ProveRequest`], a withdrawal witness and its
+//! public inputs in the format of `witness`, and queues it for a fixed pool of worker threads:
+//! at most `workers` proofs run at once, and once `queue_size` requests wait, new ones are
+//! turned away with `Error::QueueFull` instead of piling up in memory. [`RemoteProver`] is its
+//! client, and implements [`WithdrawalProver`] like [`LocalProver`], so a wallet can prove on
+//! the device or on a service with the same code.
+use crate::{
+    circuits::tornado::TornadoCircuit,
+    error::TornadoError,
+    oneshot,
+    proof::{CurveId, Proof},
+    prover,
+    witness::{PublicFile, WitnessFile},
+};
+use halo2_proofs::{
+    halo2curves::bn256::{Bn256, Fr, G1Affine},
+    plonk::ProvingKey,
+    poly::{commitment::Params, kzg::commitment::ParamsKZG},
+};
+use serde::{Deserialize, Serialize};
+use std::{
+    fmt,
+    future::Future,
+    io,
+    panic::{self, AssertUnwindSafe},
+    pin::Pin,
+    sync::{
+        mpsc::{self, SyncSender, TrySendError},
+        Arc, Mutex, PoisonError,
+    },
+    task::{Context, Poll},
+    thread::{self, JoinHandle},
+};
+
+/// Largest request body accepted, well above the witness of a depth-32 tree
+pub const MAX_REQUEST_SIZE: usize = 64 * 1024;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Error {
+    RequestTooLarge,
+    InvalidJson,
+    /// The witness or public inputs don't make a withdrawal, e.g. a malformed field element or
+    /// a path of the wrong depth
+    InvalidWitness(String),
+    /// Every worker is busy and the queue is full
+    QueueFull,
+    /// The proving system failed, retrying may succeed
+    ProvingFailed(String),
+}
+
+impl Error {
+    /// HTTP status the server should answer with
+    pub fn status_code(&self) -> u16 {
+        match self {
+            Error::RequestTooLarge => 413,
+            Error::QueueFull => 503,
+            Error::ProvingFailed(_) => 500,
+            _ => 400,
+        }
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::RequestTooLarge => write!(f, "request body is too large"),
+            Error::InvalidJson => write!(f, "request body is not a valid prove request"),
+            Error::InvalidWitness(e) => write!(f, "invalid witness: {e}"),
+            Error::QueueFull => write!(f, "the proving queue is full, retry later"),
+            Error::ProvingFailed(e) => write!(f, "proving failed: {e}"),
+        }
+    }
+}
+
+impl std::error::Error for Error {}
+
+/// JSON body of a prove request
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ProveRequest {
+    pub witness: WitnessFile,
+    pub public: PublicFile,
+}
+
+/// JSON body of a successful response
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ProveResponse {
+    /// `Proof::to_hex`
+    pub proof: String,
+}
+
+/// JSON body of an error response
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ErrorResponse {
+    pub error: String,
+}
+
+/// Proves withdrawals from their witness files
+pub trait WithdrawalProver {
+    fn prove(
+        &self,
+        witness: &WitnessFile,
+        public: &PublicFile,
+    ) -> impl Future<Output = Result<Proof, TornadoError>> + Send;
+}
+
+/// Proves `TornadoCircuit<Fr, DEPTH>` on this machine
+pub struct LocalProver<const DEPTH: usize> {
+    pub params: ParamsKZG<Bn256>,
+    pub pk: ProvingKey<G1Affine>,
+}
+
+impl<const DEPTH: usize> LocalProver<DEPTH> {
+    /// Runs the keygen of the circuit over `params`
+    pub fn new(params: ParamsKZG<Bn256>) -> Result<Self, TornadoError> {
+        let pk = prover::keygen(&params, &TornadoCircuit::<Fr, DEPTH>::default())?;
+        Ok(Self { params, pk })
+    }
+
+    /// Proves on the calling thread
+    pub fn prove_blocking(
+        &self,
+        witness: &WitnessFile,
+        public: &PublicFile,
+    ) -> Result<Proof, TornadoError> {
+        let (circuit, public_inputs) = TornadoCircuit::<Fr, DEPTH>::from_witness(witness, public)?;
+        let instances = public_inputs.to_instances();
+        let transcript = prover::prove(&self.params, &self.pk, circuit, &instances)?;
+        Ok(
+            Proof::new(CurveId::Bn256, self.params.k(), &instances, transcript)
+                .with_circuit_id(prover::tornado_circuit_id::<DEPTH>(self.pk.get_vk())),
+        )
+    }
+}
+
+impl<const DEPTH: usize> WithdrawalProver for LocalProver<DEPTH> {
+    /// Proves when first polled, which blocks the executor's thread for the whole proof: an
+    /// async application should run it on its blocking pool
+    fn prove(
+        &self,
+        witness: &WitnessFile,
+        public: &PublicFile,
+    ) -> impl Future<Output = Result<Proof, TornadoError>> + Send {
+        async move { self.prove_blocking(witness, public) }
+    }
+}
+
+struct Job {
+    request: ProveRequest,
+    reply: oneshot::Sender<Result<ProveResponse, Error>>,
+}
+
+/// A queued request: await it, or `wait` for it on a blocking server
+pub struct Ticket(oneshot::Receiver<Result<ProveResponse, Error>>);
+
+/// The worker panicked before replying, or the service stopped
+fn worker_stopped() -> Error {
+    Error::ProvingFailed("the worker stopped".to_string())
+}
+
+impl Ticket {
+    pub fn wait(self) -> Result<ProveResponse, Error> {
+        self.0.wait().unwrap_or_else(|| Err(worker_stopped()))
+    }
+}
+
+impl Future for Ticket {
+    type Output = Result<ProveResponse, Error>;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        Pin::new(&mut self.0)
+            .poll(cx)
+            .map(|reply| reply.unwrap_or_else(|| Err(worker_stopped())))
+    }
+}
+
+/// A pool of worker threads proving queued requests. Dropping it finishes the queued requests
+/// and stops the workers.
+pub struct ProvingService {
+    queue: Option<SyncSender<Job>>,
+    workers: Vec<JoinHandle<()>>,
+}
+
+impl ProvingService {
+    /// Starts `workers` threads sharing `prover`, with room for `queue_size` waiting requests.
+    /// A service of 0 workers starts one.
+    pub fn start<const DEPTH: usize>(
+        prover: LocalProver<DEPTH>,
+        workers: usize,
+        queue_size: usize,
+    ) -> Self {
+        let workers = workers.max(1);
+        let prover = Arc::new(prover);
+        let (queue, receiver) = mpsc::sync_channel::<Job>(queue_size);
+        let receiver = Arc::new(Mutex::new(receiver));
+        let workers = (0..workers)
+            .map(|_| {
+                let prover = prover.clone();
+                let receiver = receiver.clone();
+                thread::spawn(move || loop {
+                    let job = receiver
+                        .lock()
+                        .unwrap_or_else(PoisonError::into_inner)
+                        .recv();
+                    // the service was dropped and the queue is empty
+                    let Ok(Job { request, reply }) = job else {
+                        break;
+                    };
+                    // a panicking proof fails its own request, the worker going on with the next
+                    let proved = panic::catch_unwind(AssertUnwindSafe(|| {
+                        prover.prove_blocking(&request.witness, &request.public)
+                    }));
+                    let result = match proved {
+                        Ok(proved) => proved.map_err(|e| match e.is_user_error() {
+                            true => Error::InvalidWitness(e.to_string()),
+                            false => Error::ProvingFailed(e.to_string()),
+                        }),
+                        Err(_) => Err(Error::ProvingFailed("the prover panicked".to_string())),
+                    }
+                    .and_then(|proof| {
+                        let proof = proof
+                            .to_hex()
+                            .map_err(|e| Error::ProvingFailed(e.to_string()))?;
+                        Ok(ProveResponse { proof })
+                    });
+                    reply.send(result);
+                })
+            })
+            .collect();
+        Self {
+            queue: Some(queue),
+            workers,
+        }
+    }
+
+    /// Queues the JSON body of a `ProveRequest`. The witness itself is only checked by the
+    /// worker, the ticket then resolving to `Error::InvalidWitness`.
+    pub fn submit(&self, body: &[u8]) -> Result<Ticket, Error> {
+        if body.len() > MAX_REQUEST_SIZE {
+            return Err(Error::RequestTooLarge);
+        }
+        let request: ProveRequest = serde_json::from_slice(body).map_err(|_| Error::InvalidJson)?;
+        let (reply, receiver) = oneshot::channel();
+        let queue = self
+            .queue
+            .as_ref()
+            .expect("the queue lives as long as the service");
+        match queue.try_send(Job { request, reply }) {
+            Ok(()) => Ok(Ticket(receiver)),
+            Err(TrySendError::Full(_)) => Err(Error::QueueFull),
+            Err(TrySendError::Disconnected(_)) => Err(worker_stopped()),
+        }
+    }
+
+    /// Handles a request end to end, returning the HTTP status and the JSON body of the
+    /// response: a `ProveResponse` or an `ErrorResponse`
+    pub async fn handle(&self, body: &[u8]) -> (u16, Vec<u8>) {
+        let result = match self.submit(body) {
+            Ok(ticket) => ticket.await,
+            Err(e) => Err(e),
+        };
+        let response = match &result {
+            Ok(response) => serde_json::to_vec(response),
+            Err(e) => serde_json::to_vec(&ErrorResponse {
+                error: e.to_string(),
+            }),
+        };
+        let status = result.map_or_else(|e| e.status_code(), |_| 200);
+        (status, response.expect("responses serialize to json"))
+    }
+}
+
+impl Drop for ProvingService {
+    fn drop(&mut self) {
+        drop(self.queue.take());
+        for worker in self.workers.drain(..) {
+            // a panicked worker already failed its ticket
+            let _ = worker.join();
+        }
+    }
+}
+
+/// Carries a request body to a proving service, e.g. as an HTTP POST
+pub trait Transport {
+    /// Returns the HTTP status and the body of the response
+    fn post(&self, body: Vec<u8>) -> impl Future<Output = io::Result<(u16, Vec<u8>)>> + Send;
+}
+
+/// Proves on a `ProvingService` reached through `transport`
+pub struct RemoteProver<T> {
+    pub transport: T,
+}
+
+impl<T: Transport + Sync> WithdrawalProver for RemoteProver<T> {
+    fn prove(
+        &self,
+        witness: &WitnessFile,
+        public: &PublicFile,
+    ) -> impl Future<Output = Result<Proof, TornadoError>> + Send {
+        async move {
+            let body = serde_json::to_vec(&ProveRequest {
+                witness: witness.clone(),
+                public: public.clone(),
+            })?;
+            let (status, body) = self.transport.post(body).await?;
+            if status != 200 {
+                let message = match serde_json::from_slice::<ErrorResponse>(&body) {
+                    Ok(response) => response.error,
+                    Err(_) => String::from_utf8_lossy(&body).into_owned(),
+                };
+                return Err(TornadoError::Remote { status, message });
+            }
+            let response: ProveResponse = serde_json::from_slice(&body)?;
+            Proof::from_hex(&response.proof).map_err(|e| TornadoError::Remote {
+                status,
+                message: format!("invalid proof: {e}"),
+            })
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        Error, LocalProver, ProveRequest, ProvingService, RemoteProver, Transport, WithdrawalProver,
+    };
+    use crate::{
+        codec::to_hex,
+        error::TornadoError,
+        note::Note,
+        prover,
+        test_utils::block_on,
+        tree::IncrementalMerkleTree,
+        witness::{PublicFile, WitnessFile},
+    };
+    use halo2_proofs::{
+        halo2curves::bn256::{Bn256, Fr},
+        poly::kzg::commitment::ParamsKZG,
+    };
+    use rand_core::OsRng;
+    use std::{future::Future, io};
+
+    /// Calls the service in the same process
+    struct Loopback<'a>(&'a ProvingService);
+
+    impl Transport for Loopback<'_> {
+        fn post(&self, body: Vec<u8>) -> impl Future<Output = io::Result<(u16, Vec<u8>)>> + Send {
+            async move { Ok(self.0.handle(&body).await) }
+        }
+    }
+
+    fn withdrawal() -> (WitnessFile, PublicFile) {
+        let note = Note::new(Fr::from(0x456), Fr::from(0xabc), "eth", "0.1", 1).unwrap();
+        let mut tree = IncrementalMerkleTree::new(5);
+        for leaf in [3, 5, 7] {
+            tree.insert(Fr::from(leaf)).unwrap();
+        }
+        let index = tree.insert(note.commitment()).unwrap();
+        let (path_elements, _) = tree.proof(index).unwrap();
+
+        let witness = WitnessFile {
+            nullifier: to_hex(&note.nullifier),
+            secret: to_hex(&note.secret),
+            path_elements: path_elements.iter().map(to_hex).collect(),
+            leaf_index: index as u64,
+        };
+        let zero_address = format!("0x{}", "00".repeat(20));
+        let public = PublicFile {
+            root: to_hex(&tree.root()),
+            nullifier_hash: to_hex(&note.nullifier_hash()),
+            ext_data_hash: "0x0".to_string(),
+            recipient: zero_address.clone(),
+            relayer: zero_address,
+            fee: "0".to_string(),
+            refund: "0".to_string(),
+            amount: note.amount().to_string(),
+            asset_id: to_hex(&note.asset_id()),
+        };
+        (witness, public)
+    }
+
+    fn local_prover() -> LocalProver<5> {
+        LocalProver::new(ParamsKZG::<Bn256>::setup(8, OsRng)).unwrap()
+    }
+
+    #[test]
+    fn test_remote_prover() {
+        let local = local_prover();
+        let (params, vk) = (local.params.clone(), local.pk.get_vk().clone());
+        let service = ProvingService::start(local, 2, 4);
+        let remote = RemoteProver {
+            transport: Loopback(&service),
+        };
+
+        let (witness, public) = withdrawal();
+        let proof = block_on(remote.prove(&witness, &public)).unwrap();
+        let instances = proof.public_inputs::<Fr>().unwrap();
+        assert_eq!(
+            instances,
+            public.public_inputs::<Fr>().unwrap().to_instances()
+        );
+        assert!(prover::verify(&params, &vk, &proof.transcript, &instances).is_ok());
+
+        let mut wrong_depth = witness.clone();
+        wrong_depth.path_elements.pop();
+        match block_on(remote.prove(&wrong_depth, &public)) {
+            Err(e @ TornadoError::Remote { status: 400, .. }) => assert!(e.is_user_error()),
+            other => panic!("expected an invalid witness, got {other:?}"),
+        }
+    }
+
+    #[test]
+    fn test_local_prover() {
+        let local = local_prover();
+        let (witness, public) = withdrawal();
+        let proof = block_on(local.prove(&witness, &public)).unwrap();
+        let instances = proof.public_inputs::<Fr>().unwrap();
+        assert!(prover::verify(
+            &local.params,
+            local.pk.get_vk(),
+            &proof.transcript,
+            &instances
+        )
+        .is_ok());
+    }
+
+    #[test]
+    fn test_submit() {
+        let service = ProvingService::start(local_prover(), 1, 1);
+        assert_eq!(service.submit(b"{").err(), Some(Error::InvalidJson));
+        let too_large = vec![b' '; super::MAX_REQUEST_SIZE + 1];
+        assert_eq!(
+            service.submit(&too_large).err(),
+            Some(Error::RequestTooLarge)
+        );
+
+        let (witness, public) = withdrawal();
+        let body = serde_json::to_vec(&ProveRequest { witness, public }).unwrap();
+        // the worker takes at most one request before the others are submitted, and proving
+        // it takes far longer than submitting them
+        let results: Vec<_> = (0..3).map(|_| service.submit(&body)).collect();
+        assert!(results[0].is_ok());
+        assert!(results
+            .iter()
+            .any(|result| result.as_ref().err() == Some(&Error::QueueFull)));
+        for ticket in results.into_iter().flatten() {
+            assert!(ticket.wait().is_ok());
+        }
+    }
+
+    #[test]
+    fn test_no_worker() {
+        let service = ProvingService::start(local_prover(), 0, 1);
+        let (witness, public) = withdrawal();
+        let body = serde_json::to_vec(&ProveRequest { witness, public }).unwrap();
+        assert!(service.submit(&body).unwrap().wait().is_ok());
+    }
+}
@@ -0,0 +1,115 @@
+//! Recovery of partially lost notes.
+//!
+//! With one half of a note (nullifier or secret) and the list of deposit commitments, the other
+//! half can be found by trying candidates against every commitment. This is only feasible when
+//! the missing half comes from a small space: HD-derived values (try the derivation indices),
+//! or a low-entropy value. A lost random 31-byte secret cannot be recovered.
+use halo2_proofs::halo2curves::ff::PrimeField;
+use std::collections::HashMap;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Known<F> {
+    Nullifier(F),
+    Secret(F),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Recovered<F> {
+    pub nullifier: F,
+    pub secret: F,
+    /// Position of the commitment in the deposit list, i.e. its leaf index
+    pub leaf_index: usize,
+}
+
+/// Tries every candidate for the missing half, `commit(nullifier, secret)` being the commitment
+/// hash. Each candidate is hashed once and looked up among all commitments.
+pub fn recover<F, I, H>(
+    known: Known<F>,
+    commitments: &[F],
+    candidates: I,
+    commit: H,
+) -> Option<Recovered<F>>
+where
+    F: PrimeField,
+    I: IntoIterator<Item = F>,
+    H: Fn(F, F) -> F,
+{
+    let leaves: HashMap<Vec<u8>, usize> = commitments
+        .iter()
+        .enumerate()
+        .map(|(i, c)| (c.to_repr().as_ref().to_vec(), i))
+        .collect();
+
+    candidates.into_iter().find_map(|candidate| {
+        let (nullifier, secret) = match known {
+            Known::Nullifier(nullifier) => (nullifier, candidate),
+            Known::Secret(secret) => (candidate, secret),
+        };
+        let commitment = commit(nullifier, secret);
+        leaves
+            .get(commitment.to_repr().as_ref())
+            .map(|leaf_index| Recovered {
+                nullifier,
+                secret,
+                leaf_index: *leaf_index,
+            })
+    })
+}
+
+/// `start, start + 1, ..., start + count - 1`, for values known to be small
+pub fn counter_candidates<F: PrimeField>(start: u64, count: u64) -> impl Iterator<Item = F> {
+    (start..start.saturating_add(count)).map(F::from)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{counter_candidates, recover, Known, Recovered};
+    use halo2_proofs::halo2curves::pasta::Fp;
+
+    fn commit(nullifier: Fp, secret: Fp) -> Fp {
+        nullifier * secret
+    }
+
+    #[test]
+    fn test_recover_secret() {
+        let commitments: Vec<Fp> = [(3, 5), (0x456, 0xabc), (11, 13)]
+            .iter()
+            .map(|(n, s)| commit(Fp::from(*n), Fp::from(*s)))
+            .collect();
+
+        let recovered = recover(
+            Known::Nullifier(Fp::from(0x456)),
+            &commitments,
+            counter_candidates(0, 0x1000),
+            commit,
+        );
+        assert_eq!(
+            recovered,
+            Some(Recovered {
+                nullifier: Fp::from(0x456),
+                secret: Fp::from(0xabc),
+                leaf_index: 1,
+            })
+        );
+
+        let recovered = recover(
+            Known::Secret(Fp::from(13)),
+            &commitments,
+            counter_candidates(0, 100),
+            commit,
+        );
+        assert_eq!(recovered.map(|r| r.leaf_index), Some(2));
+    }
+
+    #[test]
+    fn test_recover_out_of_range() {
+        let commitments = vec![commit(Fp::from(0x456), Fp::from(0xabc))];
+        let recovered = recover(
+            Known::Nullifier(Fp::from(0x456)),
+            &commitments,
+            counter_candidates(0, 0x100),
+            commit,
+        );
+        assert!(recovered.is_none());
+    }
+}
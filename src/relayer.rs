@@ -0,0 +1,702 @@
+//! Request handling for relayers, independent of the HTTP server in front of it.
+//!
+//! A proof commits to the root the user saw when building it. The pool accepts any root in its
+//! on-chain history, but a relayer may want to be stricter (reject proofs built on an old view
+//! of the tree) or to know in advance whether a root is still acceptable before paying gas.
+//! [`Relayer::handle_withdraw`] runs every check a relayer does before submitting a withdrawal,
+//! cheapest first, and only records the nullifier once the proof verifies.
+use crate::{
+    checkpoint::RootOracle,
+    circuits::tornado::{PublicInputs, TornadoPublicInputs},
+    codec::to_bytes_be,
+    envelope::CircuitId,
+    ext_data::address_to_field,
+    nullifier::NullifierSet,
+    proof::{CurveId, Proof},
+    prover,
+};
+use halo2_proofs::{
+    halo2curves::bn256::{Bn256, Fr, G1Affine},
+    plonk::VerifyingKey,
+    poly::kzg::commitment::ParamsKZG,
+};
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::VecDeque,
+    fmt,
+    time::{Duration, Instant},
+};
+
+/// Roots the relayer remembers, to tell stale roots from unknown ones. It is the relayer's own
+/// window, independent of the roots the pool accepts (`tree::ROOT_HISTORY_SIZE`).
+pub const REMEMBERED_ROOTS: usize = 100;
+
+/// Largest request body accepted, well above a hex-encoded withdrawal proof
+pub const MAX_REQUEST_SIZE: usize = 64 * 1024;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Error {
+    RequestTooLarge,
+    InvalidJson,
+    /// The proof isn't a hex-encoded `Proof` of the current version
+    InvalidProof,
+    UnsupportedCurve,
+    /// The proof is bound to another circuit than the verifier's, e.g. an older version,
+    /// or to none
+    WrongCircuit,
+    /// Not a single column of the `TornadoCircuit` public inputs
+    WrongInstances,
+    InvalidFieldElement,
+    /// The proof pays another relayer
+    WrongRelayer,
+    /// The proof's fee is below the relayer's minimum
+    FeeTooLow,
+    /// The root was seen, but is outside the tolerance window
+    StaleRoot,
+    UnknownRoot,
+    DuplicateNullifier,
+    VerificationFailed,
+    /// The nullifier set couldn't be reached
+    NullifierSetUnavailable,
+}
+
+impl Error {
+    /// HTTP status the server should answer with
+    pub fn status_code(&self) -> u16 {
+        match self {
+            Error::RequestTooLarge => 413,
+            Error::DuplicateNullifier => 409,
+            Error::NullifierSetUnavailable => 503,
+            _ => 400,
+        }
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::RequestTooLarge => write!(f, "request body is too large"),
+            Error::InvalidJson => write!(f, "request body is not a valid withdrawal request"),
+            Error::InvalidProof => write!(f, "proof is not a valid encoded proof"),
+            Error::UnsupportedCurve => write!(f, "proof is not over bn256"),
+            Error::WrongCircuit => write!(f, "proof was made for another circuit"),
+            Error::WrongInstances => write!(f, "proof doesn't have the withdrawal public inputs"),
+            Error::InvalidFieldElement => write!(f, "public input is not a field element"),
+            Error::WrongRelayer => write!(f, "proof pays another relayer"),
+            Error::FeeTooLow => write!(f, "fee is below the relayer's minimum"),
+            Error::StaleRoot => write!(f, "root is outside the relayer's tolerance window"),
+            Error::UnknownRoot => write!(f, "root was never seen by the relayer"),
+            Error::DuplicateNullifier => write!(f, "note has already been spent"),
+            Error::VerificationFailed => write!(f, "proof verification failed"),
+            Error::NullifierSetUnavailable => write!(f, "nullifier set is unavailable"),
+        }
+    }
+}
+
+impl std::error::Error for Error {}
+
+/// How far behind the latest root a proof may be
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Tolerance {
+    /// The latest `n` roots, `Roots(1)` only accepting the current one
+    Roots(usize),
+    /// Roots that were current at some point during the last `duration`
+    Duration(Duration),
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Metrics {
+    pub accepted: u64,
+    pub stale_rejections: u64,
+    pub unknown_rejections: u64,
+}
+
+/// Recent roots, newest last, with the time each one was replaced
+#[derive(Debug, Clone)]
+pub struct RootWindow<F> {
+    tolerance: Tolerance,
+    roots: VecDeque<(F, Option<Instant>)>,
+    metrics: Metrics,
+}
+
+impl<F: PartialEq> RootWindow<F> {
+    pub fn new(tolerance: Tolerance) -> Self {
+        Self {
+            tolerance,
+            roots: VecDeque::new(),
+            metrics: Metrics::default(),
+        }
+    }
+
+    pub fn metrics(&self) -> Metrics {
+        self.metrics
+    }
+
+    /// Records `root` as the current root, e.g. after a `Deposit` event
+    pub fn push(&mut self, root: F, now: Instant) {
+        if let Some((_, replaced_at)) = self.roots.back_mut() {
+            *replaced_at = Some(now);
+        }
+        self.roots.push_back((root, None));
+        if self.roots.len() > REMEMBERED_ROOTS {
+            self.roots.pop_front();
+        }
+    }
+
+    fn position(&self, root: &F) -> Option<usize> {
+        // the same root can't appear twice in an append-only tree, but prefer the newest anyway
+        self.roots.iter().rposition(|(r, _)| r == root)
+    }
+
+    fn within_tolerance(&self, position: usize, now: Instant) -> bool {
+        match self.tolerance {
+            Tolerance::Roots(n) => self.roots.len() - position <= n,
+            Tolerance::Duration(duration) => match self.roots[position].1 {
+                None => true,
+                Some(replaced_at) => now.saturating_duration_since(replaced_at) <= duration,
+            },
+        }
+    }
+
+    /// Checks `root` against the window and updates the metrics
+    pub fn check(&mut self, root: &F, now: Instant) -> Result<(), Error> {
+        let result = match self.position(root) {
+            None => Err(Error::UnknownRoot),
+            Some(position) if self.within_tolerance(position, now) => Ok(()),
+            Some(_) => Err(Error::StaleRoot),
+        };
+        match result {
+            Ok(()) => self.metrics.accepted += 1,
+            Err(Error::StaleRoot) => self.metrics.stale_rejections += 1,
+            Err(_) => self.metrics.unknown_rejections += 1,
+        }
+        result
+    }
+}
+
+impl<F: PartialEq> RootOracle<F> for RootWindow<F> {
+    fn is_known_root(&self, root: &F) -> bool {
+        self.position(root)
+            .is_some_and(|position| self.within_tolerance(position, Instant::now()))
+    }
+}
+
+pub trait ProofVerifier {
+    fn verify(&self, transcript: &[u8], instances: &[Vec<Fr>]) -> bool;
+
+    /// The `prover::circuit_id` of the circuit verified, so that proofs bound to another one are
+    /// rejected without reading their transcript. `None` leaves it to `verify`.
+    fn circuit_id(&self) -> Option<CircuitId> {
+        None
+    }
+}
+
+/// Verifies withdrawal proofs with the pool's verifying key
+pub struct KzgVerifier {
+    pub params: ParamsKZG<Bn256>,
+    pub vk: VerifyingKey<G1Affine>,
+    /// `prover::tornado_circuit_id` of `vk`
+    pub circuit_id: CircuitId,
+}
+
+impl KzgVerifier {
+    /// The verifier of `TornadoCircuit<Fr, DEPTH>` proofs
+    pub fn new<const DEPTH: usize>(params: ParamsKZG<Bn256>, vk: VerifyingKey<G1Affine>) -> Self {
+        let circuit_id = prover::tornado_circuit_id::<DEPTH>(&vk);
+        Self {
+            params,
+            vk,
+            circuit_id,
+        }
+    }
+}
+
+impl ProofVerifier for KzgVerifier {
+    fn verify(&self, transcript: &[u8], instances: &[Vec<Fr>]) -> bool {
+        prover::verify(&self.params, &self.vk, transcript, instances).is_ok()
+    }
+
+    fn circuit_id(&self) -> Option<CircuitId> {
+        Some(self.circuit_id)
+    }
+}
+
+/// JSON body of a withdrawal request
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct WithdrawRequest {
+    /// `Proof::to_hex`
+    pub proof: String,
+}
+
+pub struct Relayer<V, N> {
+    pub verifier: V,
+    /// Where the relayer is paid, the `relayer` public input it accepts
+    pub address: [u8; 20],
+    /// Lowest `fee` public input accepted, in wei
+    pub min_fee: u64,
+    pub roots: RootWindow<Fr>,
+    pub nullifiers: N,
+}
+
+impl<V: ProofVerifier, N: NullifierSet> Relayer<V, N> {
+    pub fn new(
+        verifier: V,
+        address: [u8; 20],
+        min_fee: u64,
+        tolerance: Tolerance,
+        nullifiers: N,
+    ) -> Self {
+        Self {
+            verifier,
+            address,
+            min_fee,
+            roots: RootWindow::new(tolerance),
+            nullifiers,
+        }
+    }
+
+    /// Validates a withdrawal request and marks its nullifier as spent, returning the nullifier
+    /// hash. Nothing is recorded unless every check passes.
+    pub fn handle_withdraw(&mut self, body: &[u8], now: Instant) -> Result<Fr, Error> {
+        if body.len() > MAX_REQUEST_SIZE {
+            return Err(Error::RequestTooLarge);
+        }
+        let request: WithdrawRequest =
+            serde_json::from_slice(body).map_err(|_| Error::InvalidJson)?;
+        let proof = Proof::from_hex(&request.proof).map_err(|_| Error::InvalidProof)?;
+        if proof.curve != CurveId::Bn256 {
+            return Err(Error::UnsupportedCurve);
+        }
+        if let Some(circuit_id) = self.verifier.circuit_id() {
+            proof
+                .require_circuit_id(&circuit_id)
+                .map_err(|_| Error::WrongCircuit)?;
+        }
+        if proof.public_inputs.len() != 1 || proof.public_inputs[0].len() != PublicInputs::LEN {
+            return Err(Error::WrongInstances);
+        }
+        let instances = proof
+            .public_inputs::<Fr>()
+            .map_err(|_| Error::InvalidFieldElement)?;
+        let public_inputs =
+            TornadoPublicInputs::from_instances(&instances).ok_or(Error::WrongInstances)?;
+        let nullifier_hash = public_inputs.nullifier_hash;
+
+        if public_inputs.relayer != address_to_field(&self.address) {
+            return Err(Error::WrongRelayer);
+        }
+        // big-endian representations compare as the integers they encode
+        if to_bytes_be(&public_inputs.fee) < to_bytes_be(&Fr::from(self.min_fee)) {
+            return Err(Error::FeeTooLow);
+        }
+        self.roots.check(&public_inputs.root, now)?;
+        let spent = self
+            .nullifiers
+            .contains(&nullifier_hash)
+            .map_err(|_| Error::NullifierSetUnavailable)?;
+        if spent {
+            return Err(Error::DuplicateNullifier);
+        }
+        if !self.verifier.verify(&proof.transcript, &instances) {
+            return Err(Error::VerificationFailed);
+        }
+        // another instance sharing the set may have won the race since `contains`
+        let inserted = self
+            .nullifiers
+            .insert(nullifier_hash)
+            .map_err(|_| Error::NullifierSetUnavailable)?;
+        if !inserted {
+            return Err(Error::DuplicateNullifier);
+        }
+        Ok(nullifier_hash)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        Error, Metrics, ProofVerifier, Relayer, RootWindow, Tolerance, WithdrawRequest,
+        MAX_REQUEST_SIZE, REMEMBERED_ROOTS,
+    };
+    use crate::{
+        circuits::tornado::PublicInputs,
+        envelope::UNBOUND,
+        nullifier::{MemoryNullifierSet, NullifierSet},
+        proof::{CurveId, Proof},
+    };
+    use halo2_proofs::halo2curves::bn256::Fr;
+    use std::time::{Duration, Instant};
+
+    const VALID_TRANSCRIPT: &[u8] = b"valid transcript";
+    const CIRCUIT_ID: [u8; 32] = [1; 32];
+    /// Encoded as the `relayer` public input 0x22
+    const ADDRESS: [u8; 20] = {
+        let mut address = [0; 20];
+        address[19] = 0x22;
+        address
+    };
+    const MIN_FEE: u64 = 40;
+
+    /// Accepts exactly one transcript, whatever the instances
+    struct StubVerifier;
+
+    impl ProofVerifier for StubVerifier {
+        fn verify(&self, transcript: &[u8], _instances: &[Vec<Fr>]) -> bool {
+            transcript == VALID_TRANSCRIPT
+        }
+
+        fn circuit_id(&self) -> Option<[u8; 32]> {
+            Some(CIRCUIT_ID)
+        }
+    }
+
+    fn relayer(now: Instant) -> Relayer<StubVerifier, MemoryNullifierSet> {
+        let mut relayer = Relayer::new(
+            StubVerifier,
+            ADDRESS,
+            MIN_FEE,
+            Tolerance::Roots(5),
+            MemoryNullifierSet::default(),
+        );
+        relayer.roots.push(Fr::from(0xaaa), now);
+        relayer
+    }
+
+    fn request(proof: &Proof) -> Vec<u8> {
+        let request = WithdrawRequest {
+            proof: proof.to_hex().unwrap(),
+        };
+        serde_json::to_vec(&request).unwrap()
+    }
+
+    fn body(public_inputs: Vec<Fr>, transcript: &[u8]) -> Vec<u8> {
+        request(
+            &Proof::new(CurveId::Bn256, 8, &[public_inputs], transcript.to_vec())
+                .with_circuit_id(CIRCUIT_ID),
+        )
+    }
+
+    /// Paying `ADDRESS` a fee of 50. Recipient, refund, amount and asset id are only checked by
+    /// the proof.
+    fn public_inputs(nullifier_hash: u64, root: u64, ext_data_hash: u64) -> Vec<Fr> {
+        [
+            nullifier_hash,
+            root,
+            ext_data_hash,
+            0x11,
+            0x22,
+            50,
+            0,
+            100_000_000,
+            0xda1,
+        ]
+        .map(Fr::from)
+        .to_vec()
+    }
+
+    fn withdrawal(nullifier_hash: u64) -> Vec<u8> {
+        body(
+            public_inputs(nullifier_hash, 0xaaa, 0xe47),
+            VALID_TRANSCRIPT,
+        )
+    }
+
+    /// xorshift64, so that failures are reproducible
+    struct Rng(u64);
+
+    impl Rng {
+        fn next(&mut self) -> u64 {
+            self.0 ^= self.0 << 13;
+            self.0 ^= self.0 >> 7;
+            self.0 ^= self.0 << 17;
+            self.0
+        }
+
+        fn below(&mut self, n: usize) -> usize {
+            (self.next() % n as u64) as usize
+        }
+    }
+
+    #[test]
+    fn test_handle_withdraw() {
+        let now = Instant::now();
+        let mut relayer = relayer(now);
+        assert_eq!(
+            relayer.handle_withdraw(&withdrawal(1), now),
+            Ok(Fr::from(1))
+        );
+        assert_eq!(
+            relayer.handle_withdraw(&withdrawal(1), now),
+            Err(Error::DuplicateNullifier)
+        );
+        assert_eq!(
+            relayer.handle_withdraw(&body(public_inputs(2, 0xaaa, 0), b"forged"), now),
+            Err(Error::VerificationFailed)
+        );
+        assert_eq!(
+            relayer.handle_withdraw(&body(public_inputs(2, 0xbbb, 0), VALID_TRANSCRIPT), now),
+            Err(Error::UnknownRoot)
+        );
+        // a failed verification doesn't burn the nullifier
+        assert_eq!(
+            relayer.handle_withdraw(&withdrawal(2), now),
+            Ok(Fr::from(2))
+        );
+        assert_eq!(relayer.nullifiers.len(), 2);
+        let members = relayer.nullifiers.members().unwrap();
+        assert!(members.contains(&Fr::from(1)) && members.contains(&Fr::from(2)));
+    }
+
+    #[test]
+    fn test_relayer_and_fee() {
+        let now = Instant::now();
+        let mut relayer = relayer(now);
+        let paying = |nullifier_hash, address: u64, fee: u64| {
+            let mut public_inputs = public_inputs(nullifier_hash, 0xaaa, 0xe47);
+            public_inputs[PublicInputs::RELAYER] = Fr::from(address);
+            public_inputs[PublicInputs::FEE] = Fr::from(fee);
+            body(public_inputs, VALID_TRANSCRIPT)
+        };
+        assert_eq!(
+            relayer.handle_withdraw(&paying(1, 0x33, 50), now),
+            Err(Error::WrongRelayer)
+        );
+        assert_eq!(
+            relayer.handle_withdraw(&paying(1, 0x22, MIN_FEE - 1), now),
+            Err(Error::FeeTooLow)
+        );
+        assert_eq!(relayer.nullifiers.len(), 0);
+        assert_eq!(
+            relayer.handle_withdraw(&paying(1, 0x22, MIN_FEE), now),
+            Ok(Fr::from(1))
+        );
+        // a fee above 64 bits is still compared as an integer
+        let mut public_inputs = public_inputs(2, 0xaaa, 0xe47);
+        public_inputs[PublicInputs::FEE] = Fr::from(1 << 32) * Fr::from(1 << 32);
+        assert_eq!(
+            relayer.handle_withdraw(&body(public_inputs, VALID_TRANSCRIPT), now),
+            Ok(Fr::from(2))
+        );
+    }
+
+    #[test]
+    fn test_circuit_id() {
+        let now = Instant::now();
+        let mut relayer = relayer(now);
+        let bound = |nullifier_hash, circuit_id| {
+            let proof = Proof::new(
+                CurveId::Bn256,
+                8,
+                &[public_inputs(nullifier_hash, 0xaaa, 0xe47)],
+                VALID_TRANSCRIPT.to_vec(),
+            )
+            .with_circuit_id(circuit_id);
+            request(&proof)
+        };
+        assert_eq!(
+            relayer.handle_withdraw(&bound(1, [2; 32]), now),
+            Err(Error::WrongCircuit)
+        );
+        assert_eq!(
+            relayer.handle_withdraw(&bound(1, CIRCUIT_ID), now),
+            Ok(Fr::from(1))
+        );
+        // stripping the id doesn't skip the check
+        assert_eq!(
+            relayer.handle_withdraw(&bound(2, UNBOUND), now),
+            Err(Error::WrongCircuit)
+        );
+        assert_eq!(relayer.nullifiers.len(), 1);
+    }
+
+    #[test]
+    fn test_fuzz_malformed_requests() {
+        let now = Instant::now();
+        let mut relayer = relayer(now);
+        relayer.handle_withdraw(&withdrawal(1), now).unwrap();
+
+        let mut cases: Vec<(Vec<u8>, Error)> = [
+            ("", Error::InvalidJson),
+            ("{", Error::InvalidJson),
+            ("null", Error::InvalidJson),
+            ("[]", Error::InvalidJson),
+            ("{}", Error::InvalidJson),
+            (r#"{"proof": 1}"#, Error::InvalidJson),
+            (r#"{"proof": "", "extra": 1}"#, Error::InvalidJson),
+            (r#"{"proof": "zz"}"#, Error::InvalidProof),
+            (r#"{"proof": "0x"}"#, Error::InvalidProof),
+        ]
+        .iter()
+        .map(|(s, e)| (s.as_bytes().to_vec(), *e))
+        .collect();
+        // undersized and oversized instance columns, and a body too large to be read
+        for len in [
+            0,
+            2,
+            PublicInputs::LEN - 1,
+            PublicInputs::LEN + 1,
+            2 * PublicInputs::LEN,
+        ] {
+            let instances = vec![Fr::from(3); len];
+            cases.push((body(instances, VALID_TRANSCRIPT), Error::WrongInstances));
+        }
+        let two_columns = Proof::new(
+            CurveId::Bn256,
+            8,
+            &[public_inputs(3, 0xaaa, 0), public_inputs(3, 0xaaa, 0)],
+            VALID_TRANSCRIPT.to_vec(),
+        )
+        .with_circuit_id(CIRCUIT_ID);
+        cases.push((request(&two_columns), Error::WrongInstances));
+        let too_large = body(vec![Fr::from(3); 10_000], VALID_TRANSCRIPT);
+        assert!(too_large.len() > MAX_REQUEST_SIZE);
+        cases.push((too_large, Error::RequestTooLarge));
+        cases.push((vec![b' '; MAX_REQUEST_SIZE + 1], Error::RequestTooLarge));
+        // well-formed proofs failing one check each
+        let pasta = Proof::new(
+            CurveId::Pasta,
+            8,
+            &[public_inputs(3, 0xaaa, 0)],
+            VALID_TRANSCRIPT.to_vec(),
+        );
+        cases.push((request(&pasta), Error::UnsupportedCurve));
+        let unbound = Proof::new(
+            CurveId::Bn256,
+            8,
+            &[public_inputs(3, 0xaaa, 0)],
+            VALID_TRANSCRIPT.to_vec(),
+        );
+        cases.push((request(&unbound), Error::WrongCircuit));
+        let mut above_modulus = Proof::new(
+            CurveId::Bn256,
+            8,
+            &[public_inputs(3, 0xaaa, 0)],
+            VALID_TRANSCRIPT.to_vec(),
+        )
+        .with_circuit_id(CIRCUIT_ID);
+        above_modulus.public_inputs[0][2] = [0xff; 32];
+        cases.push((request(&above_modulus), Error::InvalidFieldElement));
+        let mut other_relayer = public_inputs(3, 0xaaa, 0);
+        other_relayer[PublicInputs::RELAYER] = Fr::from(0x33);
+        cases.push((body(other_relayer, VALID_TRANSCRIPT), Error::WrongRelayer));
+        let mut no_fee = public_inputs(3, 0xaaa, 0);
+        no_fee[PublicInputs::FEE] = Fr::from(0);
+        cases.push((body(no_fee, VALID_TRANSCRIPT), Error::FeeTooLow));
+        cases.push((
+            body(public_inputs(3, 0xbbb, 0), VALID_TRANSCRIPT),
+            Error::UnknownRoot,
+        ));
+        cases.push((
+            body(public_inputs(3, 0xaaa, 0), b"forged"),
+            Error::VerificationFailed,
+        ));
+        // duplicates of the spent nullifier
+        cases.extend(std::iter::repeat((withdrawal(1), Error::DuplicateNullifier)).take(10));
+        // no prefix of a JSON object is one
+        let valid = withdrawal(2);
+        for len in 0..valid.len() {
+            cases.push((valid[..len].to_vec(), Error::InvalidJson));
+        }
+
+        for (body, expected) in &cases {
+            assert_eq!(
+                relayer.handle_withdraw(body, now),
+                Err(*expected),
+                "{}",
+                String::from_utf8_lossy(&body[..body.len().min(100)])
+            );
+        }
+        assert_eq!(relayer.nullifiers.len(), 1);
+
+        let mut rng = Rng(0x746f726e61646f);
+        let mut accepted = vec![Fr::from(1)];
+        for _ in 0..2000 {
+            let mut mutated = valid.clone();
+            for _ in 0..1 + rng.below(4) {
+                let i = rng.below(mutated.len());
+                match rng.below(3) {
+                    0 => mutated[i] = rng.next() as u8,
+                    1 => mutated.insert(i, rng.next() as u8),
+                    _ => {
+                        mutated.remove(i);
+                    }
+                }
+            }
+            let spent_before = relayer.nullifiers.len();
+            match relayer.handle_withdraw(&mutated, now) {
+                Ok(nullifier_hash) => {
+                    assert!(!accepted.contains(&nullifier_hash));
+                    accepted.push(nullifier_hash);
+                }
+                // a few bytes more can't make the body too large, and the set is in memory
+                Err(e) => {
+                    assert!(
+                        !matches!(e, Error::RequestTooLarge | Error::NullifierSetUnavailable),
+                        "{e:?}"
+                    );
+                    assert_eq!(relayer.nullifiers.len(), spent_before);
+                }
+            }
+        }
+        assert_eq!(relayer.nullifiers.len(), accepted.len());
+        // the original valid request still goes through if no mutation happened to spend it
+        let expected = if accepted.contains(&Fr::from(2)) {
+            Err(Error::DuplicateNullifier)
+        } else {
+            Ok(Fr::from(2))
+        };
+        assert_eq!(relayer.handle_withdraw(&valid, now), expected);
+    }
+
+    #[test]
+    fn test_roots_tolerance() {
+        let now = Instant::now();
+        let mut window = RootWindow::new(Tolerance::Roots(2));
+        for root in 1..=4u64 {
+            window.push(root, now);
+        }
+
+        assert_eq!(window.check(&4, now), Ok(()));
+        assert_eq!(window.check(&3, now), Ok(()));
+        assert_eq!(window.check(&2, now), Err(Error::StaleRoot));
+        assert_eq!(window.check(&7, now), Err(Error::UnknownRoot));
+        assert_eq!(
+            window.metrics(),
+            Metrics {
+                accepted: 2,
+                stale_rejections: 1,
+                unknown_rejections: 1,
+            }
+        );
+    }
+
+    #[test]
+    fn test_duration_tolerance() {
+        let start = Instant::now();
+        let minute = Duration::from_secs(60);
+        let mut window = RootWindow::new(Tolerance::Duration(5 * minute));
+        window.push(1u64, start);
+        window.push(2, start + minute);
+        window.push(3, start + 10 * minute);
+
+        let now = start + 12 * minute;
+        assert_eq!(window.check(&3, now), Ok(()));
+        // replaced 2 minutes ago
+        assert_eq!(window.check(&2, now), Ok(()));
+        assert_eq!(window.check(&1, now), Err(Error::StaleRoot));
+        // the current root never goes stale
+        assert_eq!(window.check(&3, now + 60 * minute), Ok(()));
+    }
+
+    #[test]
+    fn test_history_is_bounded() {
+        let now = Instant::now();
+        let mut window = RootWindow::new(Tolerance::Roots(usize::MAX));
+        for root in 0..=REMEMBERED_ROOTS as u64 {
+            window.push(root, now);
+        }
+        assert_eq!(window.check(&0, now), Err(Error::UnknownRoot));
+        assert_eq!(window.check(&1, now), Ok(()));
+    }
+}
@@ -0,0 +1,410 @@
+//! `MockProver` helpers for testing circuits built from these chips, with the `test-utils`
+//! feature. The assertions pick the smallest `k` the circuit fits in, the fixtures build random
+//! trees and withdrawals, and `TEST_VECTORS` are fixed depth-20 withdrawals to check another
+//! implementation of the commitment, the tree or the witness against. `block_on` runs the crate's
+//! futures without an async runtime.
+pub mod proptest_strategies;
+
+use crate::{
+    circuits::{
+        merkle::MerklePathWitness,
+        tornado::{TornadoCircuit, TornadoPublicInputs},
+    },
+    codec::from_hex,
+    dev,
+    note::Note,
+    prover,
+    tree::{IncrementalMerkleTree, MerkleProof},
+};
+use halo2_proofs::{
+    circuit::Value,
+    dev::{metadata::Constraint, MockProver, VerifyFailure},
+    halo2curves::{
+        bn256::{Bn256, Fr},
+        ff::{FromUniformBytes, PrimeField},
+    },
+    plonk::{Circuit, Error},
+    poly::kzg::commitment::ParamsKZG,
+};
+use rand_core::{OsRng, RngCore};
+use std::{
+    future::Future,
+    pin::pin,
+    sync::Arc,
+    task::{Context, Poll, Wake, Waker},
+    thread::{self, Thread},
+};
+
+/// Largest `k` the assertions try before giving up
+const MAX_K: u32 = 18;
+
+/// Runs `circuit` with the smallest `k` it fits in
+fn mock_prove<F, C>(circuit: &C, publics: Vec<Vec<F>>) -> MockProver<F>
+where
+    F: FromUniformBytes<64> + Ord,
+    C: Circuit<F>,
+{
+    for k in 4..=MAX_K {
+        match MockProver::run(k, circuit, publics.clone()) {
+            Ok(prover) => return prover,
+            Err(Error::NotEnoughRowsAvailable { .. } | Error::InstanceTooLarge) => continue,
+            Err(err) => panic!("synthesis failed: {err:?}"),
+        }
+    }
+    panic!("the circuit doesn't fit in 2^{MAX_K} rows")
+}
+
+/// Panics, listing the failures, unless `circuit` is satisfied with `publics`
+pub fn assert_proves<F, C>(circuit: &C, publics: Vec<Vec<F>>)
+where
+    F: FromUniformBytes<64> + Ord,
+    C: Circuit<F>,
+{
+    mock_prove(circuit, publics).assert_satisfied();
+}
+
+/// Panics unless some constraint of the gate named `gate_name` fails. Other failures, e.g. of
+/// copy constraints, are allowed alongside it.
+pub fn assert_fails_constraint<F, C>(circuit: &C, publics: Vec<Vec<F>>, gate_name: &str)
+where
+    F: FromUniformBytes<64> + Ord,
+    C: Circuit<F>,
+{
+    let failures = mock_prove(circuit, publics)
+        .verify()
+        .expect_err("the circuit is satisfied");
+    assert!(
+        failures.iter().any(|failure| matches!(
+            failure,
+            VerifyFailure::ConstraintNotSatisfied { constraint, .. }
+                if in_gate(constraint, gate_name)
+        )),
+        "no constraint of gate `{gate_name}` fails: {failures:#?}"
+    );
+}
+
+/// Panics unless a proof of `circuit` verifies with keys generated from its
+/// `without_witnesses`, as real provers generate them. `MockProver` never runs keygen, so a
+/// witness-free circuit laying out other regions than the real one (e.g. a path of the wrong
+/// length) passes the other assertions and only fails here.
+pub fn assert_keygen_round_trip<C: Circuit<Fr>>(circuit: C, publics: &[Vec<Fr>]) {
+    let blank = circuit.without_witnesses();
+    let shape = dev::stats(&blank).expect("the witness-free circuit lays out");
+    assert_eq!(
+        dev::stats(&circuit).expect("the circuit lays out"),
+        shape,
+        "the witness changes the circuit's shape"
+    );
+    let params = ParamsKZG::<Bn256>::setup(shape.min_k, OsRng);
+    let pk = prover::keygen(&params, &blank).expect("keygen succeeds");
+    let proof = prover::prove(&params, &pk, circuit, publics).expect("proving succeeds");
+    prover::verify(&params, pk.get_vk(), &proof, publics).expect("the proof verifies");
+}
+
+/// `Constraint` only exposes its gate through `Display`, which ends with `in gate i ('name')`
+fn in_gate(constraint: &Constraint, gate_name: &str) -> bool {
+    constraint
+        .to_string()
+        .ends_with(&format!(" ('{gate_name}')"))
+}
+
+/// Tree of `depth` holding `len` random leaves
+pub fn random_tree<F: PrimeField>(
+    depth: usize,
+    len: usize,
+    mut rng: impl RngCore,
+) -> IncrementalMerkleTree<F> {
+    let mut tree = IncrementalMerkleTree::new(depth);
+    let leaves: Vec<F> = (0..len).map(|_| F::random(&mut rng)).collect();
+    tree.insert_batch(&leaves).expect("len fits in the tree");
+    tree
+}
+
+/// Withdrawal of a random note, inserted among up to 8 random leaves on each side
+pub fn random_withdrawal<F, const DEPTH: usize>(
+    mut rng: impl RngCore,
+) -> (TornadoCircuit<F, DEPTH>, TornadoPublicInputs<F>)
+where
+    F: PrimeField<Repr = [u8; 32]> + FromUniformBytes<64>,
+{
+    let capacity = 1 << DEPTH;
+    let before = rng.next_u32() as usize % capacity.min(8);
+    let after = rng.next_u32() as usize % (capacity - before).min(8);
+    let note = Note::random("eth", "0.1", 1, &mut rng).expect("eth 0.1 is a valid note");
+
+    let mut tree = random_tree(DEPTH, before, &mut rng);
+    let index = tree.insert(note.commitment()).expect("the tree has room");
+    let leaves: Vec<F> = (0..after).map(|_| F::random(&mut rng)).collect();
+    tree.insert_batch(&leaves).expect("the tree has room");
+    let merkle_proof: MerkleProof<F> = tree.proof(index).expect("the note is a leaf").into();
+
+    let circuit = TornadoCircuit::builder(&note, merkle_proof)
+        .build()
+        .expect("the path has DEPTH layers");
+    let public_inputs = TornadoPublicInputs {
+        nullifier_hash: note.nullifier_hash(),
+        root: tree.root(),
+        ext_data_hash: F::ZERO,
+        recipient: F::ZERO,
+        relayer: F::ZERO,
+        fee: F::ZERO,
+        refund: F::ZERO,
+        amount: F::from(note.amount()),
+        asset_id: note.asset_id(),
+    };
+    (circuit, public_inputs)
+}
+
+/// Wakes the thread parked in `block_on`
+struct Unpark(Thread);
+
+impl Wake for Unpark {
+    fn wake(self: Arc<Self>) {
+        self.0.unpark();
+    }
+}
+
+/// Polls `future` on the current thread, parking it until the future is woken
+pub fn block_on<T>(future: impl Future<Output = T>) -> T {
+    let waker = Waker::from(Arc::new(Unpark(thread::current())));
+    let mut cx = Context::from_waker(&waker);
+    let mut future = pin!(future);
+    loop {
+        match future.as_mut().poll(&mut cx) {
+            Poll::Ready(output) => return output,
+            Poll::Pending => thread::park(),
+        }
+    }
+}
+
+/// Depth of the trees of `TEST_VECTORS`
+pub const VECTOR_DEPTH: usize = 20;
+
+/// A withdrawal over the bn256 scalar field, with no external data, fee or refund. The
+/// expected values are big-endian hex, see `codec::to_hex`.
+#[derive(Debug, Clone, Copy)]
+pub struct TestVector {
+    pub nullifier: u64,
+    pub secret: u64,
+    pub amount: u64,
+    pub asset_id: u64,
+    /// The other leaves of the tree in insertion order, the note commitment is inserted at
+    /// `leaf_index`
+    pub leaves: &'static [u64],
+    pub leaf_index: usize,
+    pub commitment: &'static str,
+    pub nullifier_hash: &'static str,
+    pub root: &'static str,
+}
+
+pub const TEST_VECTORS: [TestVector; 3] = [
+    // the first deposit of an empty tree
+    TestVector {
+        nullifier: 0x456,
+        secret: 0xabc,
+        amount: 100_000_000,
+        asset_id: 1,
+        leaves: &[],
+        leaf_index: 0,
+        commitment: "0x0000000000000000000000000000000000000000000000000001156bdd962800",
+        nullifier_hash: "0x000000000000000000000000000000000000000000000000000000000012cce4",
+        root: "0x1597d6bca74bdd8c31c69c9c54e97aca382d576ce537adec1772d2037933ac19",
+    },
+    // a right child, with filled siblings on both sides
+    TestVector {
+        nullifier: 0x123456789,
+        secret: 0xdeadbeef,
+        amount: 1_000_000_000,
+        asset_id: 2,
+        leaves: &[3, 5, 7, 11, 13],
+        leaf_index: 3,
+        commitment: "0x000000000000000000000000000000000000000075fabb1e88318d67f1108c00",
+        nullifier_hash: "0x0000000000000000000000000000000000000000000000014b66dc326fb98751",
+        root: "0x1f74c0027b71ca9162cf4a44f65cc7d9c48327ff9010e55a2b13b79ea1caeb7f",
+    },
+    // the largest amount, and a commitment that wraps around the modulus
+    TestVector {
+        nullifier: 0xffff_ffff_ffff_fffe,
+        secret: 0x1234,
+        amount: u64::MAX,
+        asset_id: 3,
+        leaves: &[17, 19, 23],
+        leaf_index: 3,
+        commitment: "0x0000000000000000000000000000369bffffffffffff5c2c0000000000006d38",
+        nullifier_hash: "0x00000000000000000000000000000000fffffffffffffffc0000000000000004",
+        root: "0x0273b9be6a04984d7dce9c5170be8ba1bf88cfa96f8066b5c62a17a899fb7abc",
+    },
+];
+
+impl TestVector {
+    fn expected(hex: &str) -> Fr {
+        from_hex(hex).expect("test vectors are valid hex")
+    }
+
+    /// The tree after the deposit, built from the leaves and the expected commitment
+    pub fn tree(&self) -> IncrementalMerkleTree<Fr> {
+        let mut tree = IncrementalMerkleTree::new(VECTOR_DEPTH);
+        let (before, after) = self.leaves.split_at(self.leaf_index);
+        for leaf in before {
+            tree.insert(Fr::from(*leaf)).expect("the tree has room");
+        }
+        tree.insert(Self::expected(self.commitment))
+            .expect("the tree has room");
+        for leaf in after {
+            tree.insert(Fr::from(*leaf)).expect("the tree has room");
+        }
+        tree
+    }
+
+    /// The circuit withdrawing the note, and the public inputs taken from the expected values
+    pub fn withdrawal(&self) -> (TornadoCircuit<Fr, VECTOR_DEPTH>, TornadoPublicInputs<Fr>) {
+        let path = MerklePathWitness::from_tree(&self.tree(), self.leaf_index)
+            .expect("the note is a leaf of a VECTOR_DEPTH tree");
+        let circuit = TornadoCircuit::new(
+            Value::known(Fr::from(self.nullifier)),
+            Value::known(Fr::from(self.secret)),
+            path,
+            Value::known(Fr::ZERO),
+            Value::known(Fr::ZERO),
+            Value::known(Fr::ZERO),
+            Value::known(Fr::ZERO),
+            Value::known(Fr::ZERO),
+            Value::known(Fr::from(self.amount)),
+            Value::known(Fr::from(self.asset_id)),
+        );
+        let public_inputs = TornadoPublicInputs {
+            nullifier_hash: Self::expected(self.nullifier_hash),
+            root: Self::expected(self.root),
+            ext_data_hash: Fr::ZERO,
+            recipient: Fr::ZERO,
+            relayer: Fr::ZERO,
+            fee: Fr::ZERO,
+            refund: Fr::ZERO,
+            amount: Fr::from(self.amount),
+            asset_id: Fr::from(self.asset_id),
+        };
+        (circuit, public_inputs)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        assert_fails_constraint, assert_proves, random_tree, random_withdrawal, TEST_VECTORS,
+    };
+    use crate::{
+        chips::hash::{HashChip, HashConfig},
+        codec::to_hex,
+        native::{compute_root, hash_value, hash_values},
+    };
+    use halo2_proofs::{
+        circuit::{Layouter, SimpleFloorPlanner, Value},
+        halo2curves::{bn256::Fr, pasta::Fp},
+        plonk::{Circuit, ConstraintSystem, Error},
+    };
+    use rand_core::OsRng;
+
+    /// Hashes 3 and 5 into a wrong digest
+    #[derive(Default)]
+    struct WrongDigestCircuit;
+
+    impl Circuit<Fp> for WrongDigestCircuit {
+        type Config = HashConfig;
+        type FloorPlanner = SimpleFloorPlanner;
+
+        fn without_witnesses(&self) -> Self {
+            Self
+        }
+
+        fn configure(meta: &mut ConstraintSystem<Fp>) -> Self::Config {
+            let advice = [
+                meta.advice_column(),
+                meta.advice_column(),
+                meta.advice_column(),
+            ];
+            HashChip::configure(meta, advice)
+        }
+
+        fn synthesize(
+            &self,
+            config: Self::Config,
+            mut layouter: impl Layouter<Fp>,
+        ) -> Result<(), Error> {
+            let (left, right) = layouter.assign_region(
+                || "load inputs",
+                |mut region| {
+                    let left = region.assign_advice(
+                        || "left",
+                        config.advice[0],
+                        0,
+                        || Value::known(Fp::from(3)),
+                    )?;
+                    let right = region.assign_advice(
+                        || "right",
+                        config.advice[1],
+                        0,
+                        || Value::known(Fp::from(5)),
+                    )?;
+                    Ok((left, right))
+                },
+            )?;
+            HashChip::construct(config).hash_precomputed(
+                layouter.namespace(|| "hash"),
+                left,
+                right,
+                Value::known(Fp::from(16)),
+            )?;
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_assert_fails_constraint() {
+        assert_fails_constraint(&WrongDigestCircuit, vec![], "hash constraint");
+    }
+
+    #[test]
+    #[should_panic(expected = "no constraint of gate `bit decomposition` fails")]
+    fn test_assert_fails_other_constraint() {
+        assert_fails_constraint(&WrongDigestCircuit, vec![], "bit decomposition");
+    }
+
+    #[test]
+    fn test_random_fixtures() {
+        let tree = random_tree::<Fp>(4, 11, OsRng);
+        assert_eq!(tree.len(), 11);
+
+        let (circuit, public_inputs) = random_withdrawal::<Fp, 5>(OsRng);
+        assert_proves(&circuit, public_inputs.to_instances());
+        let (circuit, public_inputs) = random_withdrawal::<Fp, 2>(OsRng);
+        assert_proves(&circuit, public_inputs.to_instances());
+    }
+
+    #[test]
+    fn test_vectors() {
+        for vector in TEST_VECTORS {
+            let commitment = hash_values(&[
+                Fr::from(vector.nullifier),
+                Fr::from(vector.secret),
+                Fr::from(vector.amount),
+                Fr::from(vector.asset_id),
+            ]);
+            assert_eq!(to_hex(&commitment), vector.commitment);
+            assert_eq!(
+                to_hex(&hash_value(Fr::from(vector.nullifier))),
+                vector.nullifier_hash
+            );
+
+            let tree = vector.tree();
+            assert_eq!(to_hex(&tree.root()), vector.root);
+            let (path_elements, path_indices) = tree.proof(vector.leaf_index).unwrap();
+            assert_eq!(
+                compute_root(commitment, &path_elements, &path_indices),
+                Some(tree.root())
+            );
+
+            let (circuit, public_inputs) = vector.withdrawal();
+            assert_proves(&circuit, public_inputs.to_instances());
+        }
+    }
+}
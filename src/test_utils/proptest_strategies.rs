@@ -0,0 +1,146 @@
+//! `proptest` strategies for the Merkle chip: trees of random leaves and depths, arbitrary
+//! paths, and mutations of a leaf's path. The chip's tests check that a circuit accepts a path
+//! exactly when `native::compute_root` takes the leaf to the root.
+use crate::tree::IncrementalMerkleTree;
+use halo2_proofs::halo2curves::ff::PrimeField;
+use proptest::{collection::vec, prelude::*};
+use std::ops::RangeInclusive;
+
+/// Most leaves a `TreeCase` holds, whatever its depth
+const MAX_LEAVES: usize = 16;
+
+/// Any field element: 256 random bits, reduced
+pub fn field<F: PrimeField>() -> impl Strategy<Value = F> {
+    any::<[u64; 4]>().prop_map(|limbs| {
+        let shift = F::from(u64::MAX) + F::ONE;
+        limbs
+            .iter()
+            .fold(F::ZERO, |acc, limb| acc * shift + F::from(*limb))
+    })
+}
+
+/// The path indices of `leaf_index`, i.e. its `depth` lowest bits, least significant first
+pub fn path_indices<F: PrimeField>(leaf_index: usize, depth: usize) -> Vec<F> {
+    (0..depth)
+        .map(|i| F::from((leaf_index >> i) as u64 & 1))
+        .collect()
+}
+
+/// A tree of `depth` layers holding `leaves`, and the index of one of them
+#[derive(Debug, Clone)]
+pub struct TreeCase<F> {
+    pub depth: usize,
+    pub leaves: Vec<F>,
+    pub index: usize,
+}
+
+impl<F: PrimeField> TreeCase<F> {
+    pub fn tree(&self) -> IncrementalMerkleTree<F> {
+        let mut tree = IncrementalMerkleTree::new(self.depth);
+        tree.insert_batch(&self.leaves)
+            .expect("the leaves fit in the tree");
+        tree
+    }
+
+    pub fn leaf(&self) -> F {
+        self.leaves[self.index]
+    }
+}
+
+/// Trees with a depth in `depths` and 1 to 16 random leaves
+pub fn tree_case<F: PrimeField>(
+    depths: RangeInclusive<usize>,
+) -> impl Strategy<Value = TreeCase<F>> {
+    depths
+        .prop_flat_map(|depth| {
+            let len = 1..=(1 << depth).min(MAX_LEAVES);
+            (Just(depth), vec(field::<F>(), len))
+        })
+        .prop_flat_map(|(depth, leaves)| {
+            let index = 0..leaves.len();
+            (Just(depth), Just(leaves), index)
+        })
+        .prop_map(|(depth, leaves, index)| TreeCase {
+            depth,
+            leaves,
+            index,
+        })
+}
+
+/// A leaf and any path of random siblings, not necessarily from a tree. `bits` are the path
+/// indices, least significant first.
+#[derive(Debug, Clone)]
+pub struct PathCase<F> {
+    pub leaf: F,
+    pub path_elements: Vec<F>,
+    pub bits: Vec<bool>,
+}
+
+impl<F> PathCase<F> {
+    /// The leaf index whose bits are `bits`
+    pub fn leaf_index(&self) -> usize {
+        self.bits
+            .iter()
+            .rev()
+            .fold(0, |index, bit| (index << 1) | *bit as usize)
+    }
+}
+
+/// Paths with a depth in `depths`
+pub fn path_case<F: PrimeField>(
+    depths: RangeInclusive<usize>,
+) -> impl Strategy<Value = PathCase<F>> {
+    depths
+        .prop_flat_map(|depth| {
+            (
+                field::<F>(),
+                vec(field::<F>(), depth),
+                vec(any::<bool>(), depth),
+            )
+        })
+        .prop_map(|(leaf, path_elements, bits)| PathCase {
+            leaf,
+            path_elements,
+            bits,
+        })
+}
+
+/// A change to the path of a leaf
+#[derive(Debug, Clone, Copy)]
+pub enum PathMutation<F> {
+    /// Adds a non-zero `delta` to the sibling at `layer`
+    Element { layer: usize, delta: F },
+    /// Flips bit `layer` of the leaf index. At `layer == depth` the index no longer fits in
+    /// the path.
+    Bit { layer: usize },
+}
+
+impl<F: PrimeField> PathMutation<F> {
+    pub fn apply(&self, path_elements: &mut [F], leaf_index: &mut usize) {
+        match *self {
+            PathMutation::Element { layer, delta } => path_elements[layer] += delta,
+            PathMutation::Bit { layer } => *leaf_index ^= 1 << layer,
+        }
+    }
+}
+
+/// Mutations of a path of `depth` layers
+pub fn path_mutation<F: PrimeField>(depth: usize) -> impl Strategy<Value = PathMutation<F>> {
+    let delta = field::<F>().prop_filter("a zero delta changes nothing", |delta| {
+        !bool::from(delta.is_zero())
+    });
+    prop_oneof![
+        (0..depth, delta).prop_map(|(layer, delta)| PathMutation::Element { layer, delta }),
+        (0..=depth).prop_map(|layer| PathMutation::Bit { layer }),
+    ]
+}
+
+/// Trees with a depth in `depths`, and a mutation of the path of their leaf
+pub fn mutated_tree_case<F: PrimeField>(
+    depths: RangeInclusive<usize>,
+) -> impl Strategy<Value = (TreeCase<F>, PathMutation<F>)> {
+    tree_case(depths).prop_flat_map(|case| {
+        let depth = case.depth;
+        (Just(case), path_mutation(depth))
+    })
+}
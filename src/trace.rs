@@ -0,0 +1,48 @@
+//! `tracing` instrumentation behind the `trace-circuit` feature. Without it the macros expand to
+//! nothing, so the rest of the crate doesn't need `cfg`s. Nothing traced carries a witness
+//! value: only names, cell positions, sizes and timings.
+
+/// Enters a span at `$level` until the end of the enclosing block, and logs how long it took
+/// when it closes
+macro_rules! trace_span {
+    ($level:ident, $name:literal $(, $($fields:tt)+)?) => {
+        #[cfg(feature = "trace-circuit")]
+        let _span = tracing::span!(tracing::Level::$level, $name $(, $($fields)+)?).entered();
+        #[cfg(feature = "trace-circuit")]
+        let _timer = $crate::trace::Timer::start($name);
+    };
+}
+
+/// Debug event inside the current span
+macro_rules! trace_event {
+    ($($arg:tt)+) => {
+        #[cfg(feature = "trace-circuit")]
+        tracing::debug!($($arg)+);
+    };
+}
+
+pub(crate) use {trace_event, trace_span};
+
+/// Logs the time since it was started when dropped, i.e. at the end of its span
+#[cfg(feature = "trace-circuit")]
+pub(crate) struct Timer {
+    name: &'static str,
+    start: std::time::Instant,
+}
+
+#[cfg(feature = "trace-circuit")]
+impl Timer {
+    pub(crate) fn start(name: &'static str) -> Self {
+        Self {
+            name,
+            start: std::time::Instant::now(),
+        }
+    }
+}
+
+#[cfg(feature = "trace-circuit")]
+impl Drop for Timer {
+    fn drop(&mut self) {
+        tracing::debug!(elapsed = ?self.start.elapsed(), "{} done", self.name);
+    }
+}
@@ -0,0 +1,815 @@
+use crate::codec::to_bytes_be;
+use halo2_proofs::halo2curves::ff::{FromUniformBytes, PrimeField};
+use rayon::prelude::*;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::{fmt, marker::PhantomData};
+
+/// Value of an empty leaf: "tornado" as a big-endian integer
+pub const ZERO_VALUE: u64 = 0x746f726e61646f;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Error {
+    TreeFull,
+    /// A checkpoint's frontier has the wrong number of nodes for its leaf count, or a
+    /// non-canonical field element
+    InvalidFrontier,
+    /// A checkpoint's frontier, or a tree after a delta, doesn't reproduce the expected root
+    RootMismatch,
+    /// A delta has a non-canonical field element
+    InvalidDelta,
+    /// A delta doesn't start from the tree's leaf count and root
+    DeltaMismatch,
+    /// The tree doesn't have the depth of the circuit it is proven in
+    WrongDepth {
+        expected: usize,
+        found: usize,
+    },
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::TreeFull => write!(f, "merkle tree is full"),
+            Error::InvalidFrontier => write!(f, "invalid tree checkpoint frontier"),
+            Error::RootMismatch => write!(f, "tree doesn't match the expected root"),
+            Error::InvalidDelta => write!(f, "invalid tree delta"),
+            Error::DeltaMismatch => write!(f, "tree delta doesn't start from this tree"),
+            Error::WrongDepth { expected, found } => {
+                write!(f, "tree has depth {found}, expected {expected}")
+            }
+        }
+    }
+}
+
+impl std::error::Error for Error {}
+
+/// Native counterpart of an in-circuit two-to-one hash
+pub trait Hasher<F> {
+    fn hash(left: F, right: F) -> F;
+}
+
+/// The `HashChip` gate: `left * right`
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ProductHasher;
+
+impl<F: PrimeField> Hasher<F> for ProductHasher {
+    fn hash(left: F, right: F) -> F {
+        left * right
+    }
+}
+
+/// `sha256(left || right)` over the 32-byte big-endian encodings, reduced modulo p: the
+/// `uint256(sha256(abi.encodePacked(left, right))) % FIELD_SIZE` of mixers committing with
+/// SHA-256. `chips::sha256` hashes it in-circuit.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Sha256Hasher;
+
+impl<F: PrimeField<Repr = [u8; 32]> + FromUniformBytes<64>> Hasher<F> for Sha256Hasher {
+    fn hash(left: F, right: F) -> F {
+        let digest = Sha256::new()
+            .chain_update(to_bytes_be(&left))
+            .chain_update(to_bytes_be(&right))
+            .finalize();
+        // zero-extended to 64 little-endian bytes, the digest is reduced exactly
+        let mut wide = [0u8; 64];
+        wide[..32].copy_from_slice(&digest);
+        wide[..32].reverse();
+        F::from_uniform_bytes(&wide)
+    }
+}
+
+/// `zeros[i]` is the root of an empty subtree of height `i`, for `i` in `0..=depth`. These pad
+/// the path of any leaf whose right-hand siblings are still empty.
+pub fn zeros<F: PrimeField, H: Hasher<F>>(depth: usize) -> Vec<F> {
+    let mut zeros = vec![F::from(ZERO_VALUE)];
+    for i in 0..depth {
+        zeros.push(H::hash(zeros[i], zeros[i]));
+    }
+    zeros
+}
+
+/// Path from a leaf to the root: its siblings from the leaf up, and the path indices, `1` when
+/// the node is the right child. `IncrementalMerkleTree::proof` returns it as a tuple.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MerkleProof<F> {
+    pub path_elements: Vec<F>,
+    pub path_indices: Vec<F>,
+}
+
+impl<F> From<(Vec<F>, Vec<F>)> for MerkleProof<F> {
+    fn from((path_elements, path_indices): (Vec<F>, Vec<F>)) -> Self {
+        Self {
+            path_elements,
+            path_indices,
+        }
+    }
+}
+
+/// Append-only Merkle tree of fixed depth, as in Tornado's `MerkleTreeWithHistory`:
+/// leaves are inserted left to right and empty leaves are `ZERO_VALUE`.
+#[derive(Debug, Clone)]
+pub struct IncrementalMerkleTree<F, H = ProductHasher> {
+    depth: usize,
+    // layers[0] are the leaves, layers[depth] the root. The tree is sparse: only nodes with a
+    // non-empty leaf below them are stored, so layers[i] holds ceil(leaves / 2^i) nodes and
+    // everything to their right is read from `zeros`.
+    layers: Vec<Vec<F>>,
+    zeros: Vec<F>,
+    _marker: PhantomData<H>,
+}
+
+impl<F: PrimeField> IncrementalMerkleTree<F> {
+    /// Empty tree hashed like `MerkleChip`
+    pub fn new(depth: usize) -> Self {
+        Self::with_hasher(depth)
+    }
+}
+
+impl<F: PrimeField, H: Hasher<F>> IncrementalMerkleTree<F, H> {
+    pub fn with_hasher(depth: usize) -> Self {
+        Self {
+            depth,
+            layers: vec![vec![]; depth + 1],
+            zeros: zeros::<F, H>(depth),
+            _marker: PhantomData,
+        }
+    }
+
+    pub fn depth(&self) -> usize {
+        self.depth
+    }
+
+    pub fn root(&self) -> F {
+        self.layers[self.depth]
+            .first()
+            .copied()
+            .unwrap_or(self.zeros[self.depth])
+    }
+
+    /// Empty-subtree hashes, see [`zeros`]
+    pub fn zeros(&self) -> &[F] {
+        &self.zeros
+    }
+
+    pub fn leaves(&self) -> &[F] {
+        &self.layers[0]
+    }
+
+    pub fn len(&self) -> usize {
+        self.layers[0].len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.layers[0].is_empty()
+    }
+
+    /// Returns the index of the inserted leaf
+    pub fn insert(&mut self, leaf: F) -> Result<usize, Error> {
+        self.insert_batch(&[leaf])
+    }
+
+    /// Appends `leaves` and returns the index of the first one. Only nodes on the right of the
+    /// first new leaf are recomputed, and the nodes of each layer are hashed in parallel.
+    pub fn insert_batch(&mut self, leaves: &[F]) -> Result<usize, Error> {
+        let start = self.len();
+        if start + leaves.len() > 1 << self.depth {
+            return Err(Error::TreeFull);
+        }
+        self.layers[0].extend_from_slice(leaves);
+
+        let mut first_dirty = start;
+        for level in 0..self.depth {
+            let (lower, upper) = self.layers.split_at_mut(level + 1);
+            let children = &lower[level];
+            let parents = &mut upper[0];
+            let zero = self.zeros[level];
+
+            let first_parent = first_dirty / 2;
+            let parents_count = (children.len() + 1) / 2;
+            let recomputed: Vec<F> = (first_parent..parents_count)
+                .into_par_iter()
+                .map(|i| {
+                    H::hash(
+                        children[2 * i],
+                        children.get(2 * i + 1).copied().unwrap_or(zero),
+                    )
+                })
+                .collect();
+            parents.truncate(first_parent);
+            parents.extend(recomputed);
+            first_dirty = first_parent;
+        }
+        Ok(start)
+    }
+
+    /// Replaces the leaf at `index` and recomputes its path to the root. Returns the previous
+    /// leaf, or `None` if there is no leaf at `index` yet.
+    pub fn update(&mut self, index: usize, leaf: F) -> Option<F> {
+        let previous = std::mem::replace(self.layers[0].get_mut(index)?, leaf);
+        let mut position = index;
+        for level in 0..self.depth {
+            let children = &self.layers[level];
+            let left = children[position & !1];
+            let right = children
+                .get(position | 1)
+                .copied()
+                .unwrap_or(self.zeros[level]);
+            position /= 2;
+            self.layers[level + 1][position] = H::hash(left, right);
+        }
+        Some(previous)
+    }
+
+    /// Returns `(path_elements, path_indices)` for the leaf at `index`: siblings from the leaf
+    /// up, and `1` when the current node is the right child (i.e. the pair must be swapped
+    /// before hashing). The path indices are the bits of `index`, which is what
+    /// `MerkleChip::prove_tree_root` takes.
+    pub fn proof(&self, index: usize) -> Option<(Vec<F>, Vec<F>)> {
+        if index >= self.len() {
+            return None;
+        }
+
+        let (path_elements, path_indices) = (0..self.depth)
+            .map(|level| {
+                let position = index >> level;
+                let sibling = self.layers[level]
+                    .get(position ^ 1)
+                    .copied()
+                    .unwrap_or(self.zeros[level]);
+                (sibling, F::from((position % 2) as u64))
+            })
+            .unzip();
+        Some((path_elements, path_indices))
+    }
+
+    /// The right edge of the tree, to keep appending to it without its leaves
+    pub fn frontier(&self) -> Frontier<F, H> {
+        self.frontier_at(self.len())
+    }
+
+    /// The right edge of the tree when it had `len` leaves. Appending never changes a complete
+    /// subtree, so its nodes are still in `layers`.
+    fn frontier_at(&self, len: usize) -> Frontier<F, H> {
+        let nodes = (0..=self.depth)
+            .map(|level| match (len >> level) & 1 {
+                1 => self.layers[level][(len >> level) - 1],
+                _ => self.zeros[level],
+            })
+            .collect();
+        Frontier {
+            len,
+            nodes,
+            zeros: self.zeros.clone(),
+            _marker: PhantomData,
+        }
+    }
+
+    /// The leaves appended since the tree had `len` leaves, or `None` if it never had fewer
+    /// than `len`
+    pub fn delta_since(&self, len: usize) -> Option<TreeDelta>
+    where
+        F: PrimeField<Repr = [u8; 32]>,
+    {
+        let leaves = self.leaves().get(len..)?;
+        Some(TreeDelta {
+            from_len: len as u64,
+            from_root: self.frontier_at(len).root().to_repr(),
+            leaves: leaves.iter().map(|leaf| leaf.to_repr()).collect(),
+            to_root: self.root().to_repr(),
+        })
+    }
+
+    /// Appends the leaves of `delta`. The tree is left unchanged unless it is at the delta's
+    /// start and the leaves reproduce its root.
+    pub fn apply(&mut self, delta: &TreeDelta) -> Result<(), Error>
+    where
+        F: PrimeField<Repr = [u8; 32]>,
+    {
+        // checked on the frontier, which can't be rolled back, rather than on the layers
+        let mut frontier = self.frontier();
+        let leaves = frontier.apply_leaves(delta)?;
+        self.insert_batch(&leaves)?;
+        Ok(())
+    }
+}
+
+/// The right edge of an append-only tree: its leaf count, and the root of each complete left
+/// subtree not yet hashed into a larger one. That is all the tree needs to compute its root and
+/// append leaves, as Tornado's contract does with `filledSubtrees`, but it can't prove the
+/// leaves it has forgotten.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Frontier<F, H = ProductHasher> {
+    len: usize,
+    // nodes[i] is the node of layer i at position (len >> i) - 1 when bit i of len is set, i.e.
+    // a complete subtree of 2^i leaves whose right sibling is still incomplete, and zeros[i]
+    // otherwise. The leaves are split into those subtrees like len into its bits.
+    nodes: Vec<F>,
+    zeros: Vec<F>,
+    _marker: PhantomData<H>,
+}
+
+impl<F: PrimeField, H: Hasher<F>> Frontier<F, H> {
+    /// Frontier of an empty tree
+    pub fn new(depth: usize) -> Self {
+        let zeros = zeros::<F, H>(depth);
+        Self {
+            len: 0,
+            nodes: zeros.clone(),
+            zeros,
+            _marker: PhantomData,
+        }
+    }
+
+    pub fn depth(&self) -> usize {
+        self.zeros.len() - 1
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    pub fn root(&self) -> F {
+        let depth = self.depth();
+        if self.len >> depth == 1 {
+            // the tree is full, and one complete subtree
+            return self.nodes[depth];
+        }
+        // the node above the next leaf, whose leaves on the left are the complete subtrees
+        (0..depth).fold(self.zeros[0], |node, level| match (self.len >> level) & 1 {
+            1 => H::hash(self.nodes[level], node),
+            _ => H::hash(node, self.zeros[level]),
+        })
+    }
+
+    /// Returns the index of the appended leaf. Complete subtrees merge like the carries of
+    /// `len + 1`.
+    pub fn insert(&mut self, leaf: F) -> Result<usize, Error> {
+        let index = self.len;
+        if index >> self.depth() == 1 {
+            return Err(Error::TreeFull);
+        }
+        let mut node = leaf;
+        let mut level = 0;
+        while (index >> level) & 1 == 1 {
+            node = H::hash(self.nodes[level], node);
+            self.nodes[level] = self.zeros[level];
+            level += 1;
+        }
+        self.nodes[level] = node;
+        self.len += 1;
+        Ok(index)
+    }
+
+    /// Appends the leaves of `delta`, as `IncrementalMerkleTree::apply`
+    pub fn apply(&mut self, delta: &TreeDelta) -> Result<(), Error>
+    where
+        F: PrimeField<Repr = [u8; 32]>,
+    {
+        let mut frontier = self.clone();
+        frontier.apply_leaves(delta)?;
+        *self = frontier;
+        Ok(())
+    }
+
+    /// Appends the leaves of `delta` once checked to start from this frontier, and returns them
+    /// if they reproduce its root. On error, `self` is partially updated.
+    fn apply_leaves(&mut self, delta: &TreeDelta) -> Result<Vec<F>, Error>
+    where
+        F: PrimeField<Repr = [u8; 32]>,
+    {
+        let field = |repr: &[u8; 32]| Option::from(F::from_repr(*repr)).ok_or(Error::InvalidDelta);
+        if delta.from_len != self.len as u64 || field(&delta.from_root)? != self.root() {
+            return Err(Error::DeltaMismatch);
+        }
+        let to_root = field(&delta.to_root)?;
+        let leaves = delta
+            .leaves
+            .iter()
+            .map(field)
+            .collect::<Result<Vec<_>, _>>()?;
+        for leaf in leaves.iter() {
+            self.insert(*leaf)?;
+        }
+        if self.root() != to_root {
+            return Err(Error::RootMismatch);
+        }
+        Ok(leaves)
+    }
+}
+
+/// Where a deposit scanner stopped: the last block it synced and the tree's frontier after it,
+/// so the next scan only reads the events of the blocks after `block`. Field elements are
+/// stored as their repr, as in `checkpoint::TreeSnapshot`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TreeCheckpoint {
+    pub block: u64,
+    pub root: [u8; 32],
+    /// Leaves in the tree
+    pub len: u64,
+    /// One node per layer, from the leaves up to the root, see `Frontier`
+    pub frontier: Vec<[u8; 32]>,
+}
+
+impl TreeCheckpoint {
+    pub fn new<F, H>(block: u64, frontier: &Frontier<F, H>) -> Self
+    where
+        F: PrimeField<Repr = [u8; 32]>,
+        H: Hasher<F>,
+    {
+        Self {
+            block,
+            root: frontier.root().to_repr(),
+            len: frontier.len as u64,
+            frontier: frontier.nodes.iter().map(|node| node.to_repr()).collect(),
+        }
+    }
+
+    /// The checkpointed frontier, once checked to reproduce the checkpointed root
+    pub fn frontier<F, H>(&self) -> Result<Frontier<F, H>, Error>
+    where
+        F: PrimeField<Repr = [u8; 32]>,
+        H: Hasher<F>,
+    {
+        let field =
+            |repr: &[u8; 32]| Option::from(F::from_repr(*repr)).ok_or(Error::InvalidFrontier);
+        let depth = self
+            .frontier
+            .len()
+            .checked_sub(1)
+            .ok_or(Error::InvalidFrontier)?;
+        let len = usize::try_from(self.len).map_err(|_| Error::InvalidFrontier)?;
+        if depth >= usize::BITS as usize || len > 1 << depth {
+            return Err(Error::InvalidFrontier);
+        }
+
+        let mut frontier = Frontier::<F, H>::new(depth);
+        frontier.len = len;
+        for (level, node) in self.frontier.iter().enumerate() {
+            let node = field(node)?;
+            // a node off the frontier must be the empty subtree, so a checkpoint has exactly
+            // one encoding
+            if (len >> level) & 1 == 0 && node != frontier.zeros[level] {
+                return Err(Error::InvalidFrontier);
+            }
+            frontier.nodes[level] = node;
+        }
+        if frontier.root() != field(&self.root)? {
+            return Err(Error::RootMismatch);
+        }
+        Ok(frontier)
+    }
+}
+
+/// The leaves appended to a tree between two of its roots, for a light client to follow the
+/// pool from a tree (or frontier) it already holds rather than from a new snapshot. Field
+/// elements are stored as their repr, as in `TreeCheckpoint`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TreeDelta {
+    /// Leaves in the tree before the delta, i.e. the index of its first leaf
+    pub from_len: u64,
+    pub from_root: [u8; 32],
+    pub leaves: Vec<[u8; 32]>,
+    /// Root once the leaves are appended
+    pub to_root: [u8; 32],
+}
+
+/// Number of recent roots a withdrawal may be proven against, as in Tornado's
+/// `MerkleTreeWithHistory`
+pub const ROOT_HISTORY_SIZE: usize = 30;
+
+/// Ring buffer of the last `N` roots. Its slots, in order, are the root history public inputs
+/// of `TornadoHistoryCircuit`, whose fixed-denomination, single-asset pool keeps a tree of its
+/// own.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RootHistory<F, const N: usize = ROOT_HISTORY_SIZE> {
+    roots: [F; N],
+    current: usize,
+}
+
+impl<F: PrimeField, const N: usize> RootHistory<F, N> {
+    /// Every slot starts as `initial_root` (the empty tree root), rather than `0` as on-chain:
+    /// the slots are all public inputs, and `0` must not become a provable root.
+    pub fn new(initial_root: F) -> Self {
+        assert!(N > 0, "root history can't be empty");
+        Self {
+            roots: [initial_root; N],
+            current: 0,
+        }
+    }
+
+    /// Overwrites the oldest root
+    pub fn push(&mut self, root: F) {
+        self.current = (self.current + 1) % N;
+        self.roots[self.current] = root;
+    }
+
+    pub fn current(&self) -> F {
+        self.roots[self.current]
+    }
+
+    pub fn contains(&self, root: &F) -> bool {
+        self.roots.contains(root)
+    }
+
+    /// Slots in storage order, not from newest to oldest
+    pub fn roots(&self) -> &[F; N] {
+        &self.roots
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        zeros, Error, Frontier, Hasher, IncrementalMerkleTree, ProductHasher, RootHistory,
+        Sha256Hasher, TreeCheckpoint, TreeDelta, ZERO_VALUE,
+    };
+    use crate::{
+        circuits::{merkle::MerklePathWitness, tornado::TornadoCircuit},
+        codec::from_hex,
+        ext_data::asset_id,
+        note,
+    };
+    use halo2_proofs::{
+        circuit::Value,
+        dev::MockProver,
+        halo2curves::{bn256::Fr, ff::PrimeField, pasta::Fp},
+    };
+
+    #[test]
+    fn test_sha256_hasher() {
+        // sha256 of the encodings of 1 and 2 is above the modulus
+        let expected: Fr =
+            from_hex("0x1529595e73ccab6b37ea20aa9971bf903130a7d57d4ee0e3f58b038854e4116b").unwrap();
+        assert_eq!(Sha256Hasher::hash(Fr::from(1), Fr::from(2)), expected);
+
+        let mut tree = IncrementalMerkleTree::<Fr, Sha256Hasher>::new(1);
+        tree.insert(Fr::from(1)).unwrap();
+        tree.insert(Fr::from(2)).unwrap();
+        assert_eq!(tree.root(), expected);
+    }
+
+    #[test]
+    fn test_insert() {
+        let zero = Fp::from(ZERO_VALUE);
+        let mut tree = IncrementalMerkleTree::new(2);
+        assert_eq!(tree.root(), zero * zero * zero * zero);
+
+        assert_eq!(tree.insert(Fp::from(3)).unwrap(), 0);
+        assert_eq!(tree.insert(Fp::from(5)).unwrap(), 1);
+        assert_eq!(tree.insert(Fp::from(7)).unwrap(), 2);
+        assert_eq!(tree.root(), Fp::from(3 * 5 * 7) * zero);
+
+        tree.insert(Fp::from(11)).unwrap();
+        assert_eq!(tree.root(), Fp::from(3 * 5 * 7 * 11));
+        assert_eq!(tree.insert(Fp::from(13)), Err(Error::TreeFull));
+    }
+
+    #[test]
+    fn test_sparse_storage() {
+        let zero = Fp::from(ZERO_VALUE);
+        let table = zeros::<Fp, ProductHasher>(20);
+        assert_eq!(table.len(), 21);
+        assert_eq!(table[2], zero * zero * zero * zero);
+
+        let mut tree = IncrementalMerkleTree::new(20);
+        assert_eq!(tree.root(), table[20]);
+        for leaf in [3, 5, 7] {
+            tree.insert(Fp::from(leaf)).unwrap();
+        }
+        // 3 leaves, 2 nodes on level 1, then a single node per level up to the root
+        let stored: usize = tree.layers.iter().map(Vec::len).sum();
+        assert_eq!(stored, 3 + 2 + 19);
+
+        let (path_elements, _) = tree.proof(2).unwrap();
+        assert_eq!(path_elements[0], table[0]);
+        assert_eq!(path_elements[2..], table[2..20]);
+    }
+
+    #[test]
+    fn test_insert_batch() {
+        let leaves: Vec<Fp> = (1..=37).map(Fp::from).collect();
+
+        let mut sequential = IncrementalMerkleTree::new(6);
+        for leaf in leaves.iter() {
+            sequential.insert(*leaf).unwrap();
+        }
+
+        let mut batched = IncrementalMerkleTree::new(6);
+        assert_eq!(batched.insert_batch(&leaves[..5]).unwrap(), 0);
+        assert_eq!(batched.insert_batch(&[]).unwrap(), 5);
+        assert_eq!(batched.insert_batch(&leaves[5..20]).unwrap(), 5);
+        assert_eq!(batched.insert_batch(&leaves[20..]).unwrap(), 20);
+
+        assert_eq!(batched.root(), sequential.root());
+        for index in 0..leaves.len() {
+            assert_eq!(batched.proof(index), sequential.proof(index));
+        }
+        assert_eq!(
+            batched.insert_batch(&vec![Fp::from(1); 28]),
+            Err(Error::TreeFull)
+        );
+    }
+
+    #[test]
+    fn test_frontier() {
+        let mut tree = IncrementalMerkleTree::new(5);
+        let mut frontier = Frontier::<Fp, ProductHasher>::new(5);
+        assert_eq!(frontier.root(), tree.root());
+        for leaf in 1..=32 {
+            let index = tree.insert(Fp::from(leaf)).unwrap();
+            assert_eq!(frontier.insert(Fp::from(leaf)), Ok(index));
+            assert_eq!(frontier, tree.frontier());
+            assert_eq!(frontier.root(), tree.root());
+        }
+        assert_eq!(frontier.insert(Fp::from(1)), Err(Error::TreeFull));
+    }
+
+    #[test]
+    fn test_tree_checkpoint() {
+        let leaves: Vec<Fp> = (1..=20).map(Fp::from).collect();
+        let mut tree = IncrementalMerkleTree::new(6);
+        tree.insert_batch(&leaves[..13]).unwrap();
+
+        let checkpoint = TreeCheckpoint::new(1000, &tree.frontier());
+        let json = serde_json::to_string(&checkpoint).unwrap();
+        let checkpoint: TreeCheckpoint = serde_json::from_str(&json).unwrap();
+        assert_eq!((checkpoint.block, checkpoint.len), (1000, 13));
+
+        // resume from the checkpoint, with the leaves after it only
+        let mut frontier = checkpoint.frontier::<Fp, ProductHasher>().unwrap();
+        tree.insert_batch(&leaves[13..]).unwrap();
+        for leaf in leaves[13..].iter() {
+            frontier.insert(*leaf).unwrap();
+        }
+        assert_eq!(frontier.root(), tree.root());
+
+        let mut wrong_root = checkpoint.clone();
+        wrong_root.root = Fp::from(7).to_repr();
+        assert_eq!(
+            wrong_root.frontier::<Fp, ProductHasher>(),
+            Err(Error::RootMismatch)
+        );
+        // 13 = 0b1101: layers 0, 2 and 3 are on the frontier, layer 1 isn't
+        let mut wrong_node = checkpoint.clone();
+        wrong_node.frontier[2] = Fp::from(7).to_repr();
+        assert_eq!(
+            wrong_node.frontier::<Fp, ProductHasher>(),
+            Err(Error::RootMismatch)
+        );
+        let mut off_frontier = checkpoint.clone();
+        off_frontier.frontier[1] = Fp::from(7).to_repr();
+        assert_eq!(
+            off_frontier.frontier::<Fp, ProductHasher>(),
+            Err(Error::InvalidFrontier)
+        );
+        let mut too_many_leaves = checkpoint;
+        too_many_leaves.len = 65;
+        assert_eq!(
+            too_many_leaves.frontier::<Fp, ProductHasher>(),
+            Err(Error::InvalidFrontier)
+        );
+    }
+
+    #[test]
+    fn test_tree_delta() {
+        let leaves: Vec<Fp> = (1..=20).map(Fp::from).collect();
+        let mut pool = IncrementalMerkleTree::new(6);
+        pool.insert_batch(&leaves[..13]).unwrap();
+        let mut light_client = pool.clone();
+        let mut frontier = pool.frontier();
+        pool.insert_batch(&leaves[13..]).unwrap();
+
+        let delta = pool.delta_since(13).unwrap();
+        let json = serde_json::to_string(&delta).unwrap();
+        let delta: TreeDelta = serde_json::from_str(&json).unwrap();
+        assert_eq!((delta.from_len, delta.leaves.len()), (13, 7));
+        assert_eq!(delta.from_root, light_client.root().to_repr());
+        light_client.apply(&delta).unwrap();
+        assert_eq!(light_client.root(), pool.root());
+        assert_eq!(light_client.leaves(), pool.leaves());
+        frontier.apply(&delta).unwrap();
+        assert_eq!(frontier, pool.frontier());
+        assert_eq!(pool.delta_since(20).unwrap().leaves, Vec::<[u8; 32]>::new());
+        assert_eq!(pool.delta_since(21), None);
+
+        // a delta applies once, from its start only, and leaves the tree unchanged otherwise
+        let before = light_client.clone();
+        assert_eq!(light_client.apply(&delta), Err(Error::DeltaMismatch));
+        let mut wrong_start = pool.delta_since(12).unwrap();
+        wrong_start.from_len = 13;
+        let mut behind = IncrementalMerkleTree::new(6);
+        behind.insert_batch(&leaves[..13]).unwrap();
+        assert_eq!(behind.apply(&wrong_start), Err(Error::DeltaMismatch));
+        let mut wrong_leaf = delta.clone();
+        wrong_leaf.leaves[3] = Fp::from(7).to_repr();
+        assert_eq!(behind.apply(&wrong_leaf), Err(Error::RootMismatch));
+        let mut non_canonical = delta.clone();
+        non_canonical.leaves[0] = [0xff; 32];
+        assert_eq!(behind.apply(&non_canonical), Err(Error::InvalidDelta));
+        assert_eq!(behind.leaves(), &leaves[..13]);
+        assert_eq!(light_client.leaves(), before.leaves());
+
+        let mut too_many = delta;
+        too_many.leaves.extend(vec![Fp::from(1).to_repr(); 51]);
+        assert_eq!(behind.apply(&too_many), Err(Error::TreeFull));
+    }
+
+    #[test]
+    fn test_root_history() {
+        let mut tree = IncrementalMerkleTree::<Fp>::new(4);
+        let mut history = RootHistory::<Fp, 3>::new(tree.root());
+        let mut roots = vec![tree.root()];
+        for leaf in [3, 5, 7, 11] {
+            tree.insert(Fp::from(leaf)).unwrap();
+            history.push(tree.root());
+            roots.push(tree.root());
+        }
+
+        assert_eq!(history.current(), tree.root());
+        assert!(roots[2..].iter().all(|root| history.contains(root)));
+        assert!(!history.contains(&roots[0]));
+        assert!(!history.contains(&roots[1]));
+    }
+
+    #[test]
+    fn test_proof_matches_root() {
+        let mut tree = IncrementalMerkleTree::new(3);
+        for leaf in [3, 5, 7, 11, 13] {
+            tree.insert(Fp::from(leaf)).unwrap();
+        }
+
+        for index in 0..tree.len() {
+            let (path_elements, path_indices) = tree.proof(index).unwrap();
+            let mut node = tree.leaves()[index];
+            for (level, (element, bit)) in path_elements.iter().zip(path_indices).enumerate() {
+                node *= element;
+                // the path indices are the bits of the leaf index
+                assert_eq!(bit, Fp::from((index >> level) as u64 & 1));
+            }
+            assert_eq!(node, tree.root());
+        }
+        assert!(tree.proof(tree.len()).is_none());
+    }
+
+    #[test]
+    fn test_update() {
+        let mut tree = IncrementalMerkleTree::new(3);
+        for leaf in [3, 5, 7, 11, 13] {
+            tree.insert(Fp::from(leaf)).unwrap();
+        }
+        assert_eq!(tree.update(4, Fp::from(17)), Some(Fp::from(13)));
+        assert_eq!(tree.update(1, Fp::from(19)), Some(Fp::from(5)));
+        assert_eq!(tree.update(5, Fp::from(23)), None);
+
+        let mut rebuilt = IncrementalMerkleTree::new(3);
+        for leaf in [3, 19, 7, 11, 17] {
+            rebuilt.insert(Fp::from(leaf)).unwrap();
+        }
+        assert_eq!(tree.layers, rebuilt.layers);
+    }
+
+    #[test]
+    fn test_circuit_accepts_tree_proof() {
+        let nullifier = Fp::from(0x456);
+        let secret = Fp::from(0xabc);
+        let amount = Fp::from(100_000_000);
+        let asset_id = asset_id::<Fp>("eth");
+        let commitment = note::commitment(nullifier, secret, amount, asset_id);
+
+        let mut tree = IncrementalMerkleTree::new(5);
+        for leaf in [3, 5, 7] {
+            tree.insert(Fp::from(leaf)).unwrap();
+        }
+        let index = tree.insert(commitment).unwrap();
+        tree.insert(Fp::from(11)).unwrap();
+
+        let circuit = TornadoCircuit::<Fp, 5>::new(
+            Value::known(nullifier),
+            Value::known(secret),
+            MerklePathWitness::from_tree(&tree, index).unwrap(),
+            Value::known(Fp::from(0)),
+            Value::known(Fp::from(0x11)),
+            Value::known(Fp::from(0)),
+            Value::known(Fp::from(0)),
+            Value::known(Fp::from(0)),
+            Value::known(amount),
+            Value::known(asset_id),
+        );
+        let mut public_input = vec![nullifier * nullifier, tree.root(), Fp::from(0)];
+        public_input.extend([
+            Fp::from(0x11),
+            Fp::from(0),
+            Fp::from(0),
+            Fp::from(0),
+            amount,
+            asset_id,
+        ]);
+        let prover = MockProver::run(10, &circuit, vec![public_input]).unwrap();
+        assert!(prover.verify().is_ok());
+    }
+}
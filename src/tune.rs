@@ -0,0 +1,181 @@
+//! Search over layout presets for a Merkle path of a given anonymity set size.
+//!
+//! Each candidate is laid out with `dev::stats` to find its minimum `k`; candidates fitting the
+//! budget are then keygen'd and proven once to time them and measure the peak memory of the
+//! proof. The best preset (smallest `k`, then fastest proof) is meant to be written out as a
+//! JSON config. The chips have a fixed number of
+//! columns and one region per layer, so the knobs searched are the floor planner and the tree
+//! arity.
+use crate::{
+    bench_circuits::{DeepTreeCircuit, QuadTreeCircuit},
+    dev,
+    error::TornadoError,
+    prover::{self, ProverOptions},
+};
+use halo2_proofs::{
+    circuit::{floor_planner::V1, FloorPlanner, SimpleFloorPlanner},
+    halo2curves::bn256::{Bn256, Fr},
+    plonk::Circuit,
+    poly::kzg::commitment::ParamsKZG,
+};
+use rand_core::OsRng;
+use serde::{Deserialize, Serialize};
+use std::{fs, time::Instant};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum FloorPlannerKind {
+    Simple,
+    V1,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Arity {
+    Binary,
+    Quad,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Preset {
+    pub floor_planner: FloorPlannerKind,
+    pub arity: Arity,
+    pub k: u32,
+    /// Keygen plus one proof, `None` for a dry run
+    pub prove_ms: Option<u128>,
+    /// Peak resident memory of the proof, keys and params included, in bytes. `None` for a dry
+    /// run, or where `peak_rss` can't measure it.
+    #[serde(default)]
+    pub peak_rss: Option<u64>,
+}
+
+/// Peak resident memory of the process since it started or since the last `reset_peak_rss`,
+/// in bytes. Only measured on Linux, from `VmHWM` in `/proc/self/status`.
+pub fn peak_rss() -> Option<u64> {
+    let status = fs::read_to_string("/proc/self/status").ok()?;
+    let kb = status
+        .lines()
+        .find_map(|line| line.strip_prefix("VmHWM:"))?;
+    let kb: u64 = kb.trim().strip_suffix("kB")?.trim().parse().ok()?;
+    Some(kb * 1024)
+}
+
+/// Lowers the peak of `peak_rss` to the current resident memory (Linux 4.0 and later)
+fn reset_peak_rss() {
+    // not fatal: the peak then includes whatever ran before
+    let _ = fs::write("/proc/self/clear_refs", "5");
+}
+
+fn benchmark<C: Circuit<Fr> + Send>(
+    circuit: C,
+    k: u32,
+    options: &ProverOptions,
+) -> Result<(u128, Option<u64>), TornadoError> {
+    let params = ParamsKZG::<Bn256>::setup(k, OsRng);
+    let start = Instant::now();
+    let pk = prover::keygen_with_options(&params, &circuit, options)?;
+    reset_peak_rss();
+    prover::prove_with_options(&params, &pk, circuit, &[vec![]], options)?;
+    Ok((start.elapsed().as_millis(), peak_rss()))
+}
+
+fn candidate<C: Circuit<Fr> + Send>(
+    circuit: C,
+    floor_planner: FloorPlannerKind,
+    arity: Arity,
+    max_k: u32,
+    bench: Option<&ProverOptions>,
+) -> Result<Option<Preset>, TornadoError> {
+    let k = dev::stats(&circuit)?.min_k;
+    if k > max_k {
+        return Ok(None);
+    }
+    let (prove_ms, peak_rss) = match bench {
+        Some(options) => {
+            let (prove_ms, peak_rss) = benchmark(circuit, k, options)?;
+            (Some(prove_ms), peak_rss)
+        }
+        None => (None, None),
+    };
+    Ok(Some(Preset {
+        floor_planner,
+        arity,
+        k,
+        prove_ms,
+        peak_rss,
+    }))
+}
+
+fn candidates_for<P: FloorPlanner, const DEPTH: usize, const QUAD_DEPTH: usize>(
+    floor_planner: FloorPlannerKind,
+    max_k: u32,
+    bench: Option<&ProverOptions>,
+) -> Result<Vec<Preset>, TornadoError> {
+    let binary = candidate(
+        DeepTreeCircuit::<Fr, P, DEPTH>::new(),
+        floor_planner,
+        Arity::Binary,
+        max_k,
+        bench,
+    )?;
+    let quad = candidate(
+        QuadTreeCircuit::<Fr, P, QUAD_DEPTH>::new(),
+        floor_planner,
+        Arity::Quad,
+        max_k,
+        bench,
+    )?;
+    Ok(binary.into_iter().chain(quad).collect())
+}
+
+/// Every preset fitting in `max_k` for a binary tree of `DEPTH` levels (or the quad tree of
+/// `QUAD_DEPTH = DEPTH / 2` levels with the same number of leaves), best first.
+/// With `bench`, each preset is also keygen'd and proven once with these options, e.g.
+/// `ProverOptions::low_memory` to measure the memory a constrained device needs.
+pub fn tune<const DEPTH: usize, const QUAD_DEPTH: usize>(
+    max_k: u32,
+    bench: Option<&ProverOptions>,
+) -> Result<Vec<Preset>, TornadoError> {
+    assert_eq!(
+        DEPTH,
+        2 * QUAD_DEPTH,
+        "both trees must have the same number of leaves"
+    );
+
+    let mut presets = candidates_for::<SimpleFloorPlanner, DEPTH, QUAD_DEPTH>(
+        FloorPlannerKind::Simple,
+        max_k,
+        bench,
+    )?;
+    presets.extend(candidates_for::<V1, DEPTH, QUAD_DEPTH>(
+        FloorPlannerKind::V1,
+        max_k,
+        bench,
+    )?);
+    presets.sort_by_key(|preset| (preset.k, preset.prove_ms));
+    Ok(presets)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{peak_rss, tune, Preset};
+
+    #[test]
+    fn test_tune_dry_run() {
+        let presets = tune::<8, 4>(12, None).unwrap();
+        assert_eq!(presets.len(), 4);
+        assert!(presets.windows(2).all(|pair| pair[0].k <= pair[1].k));
+
+        let json = serde_json::to_string(&presets[0]).unwrap();
+        assert_eq!(serde_json::from_str::<Preset>(&json).unwrap(), presets[0]);
+
+        assert!(tune::<8, 4>(4, None).unwrap().is_empty());
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn test_peak_rss() {
+        assert!(peak_rss().unwrap() > 0);
+        // configs written before peak_rss was measured still parse
+        let json = r#"{"floor_planner":"V1","arity":"Binary","k":9,"prove_ms":null}"#;
+        assert_eq!(serde_json::from_str::<Preset>(json).unwrap().peak_rss, None);
+    }
+}
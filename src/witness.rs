@@ -0,0 +1,268 @@
+//! JSON files driving the withdrawal prover, so tooling that doesn't link Rust (indexers,
+//! frontends) can produce them.
+//!
+//! `witness.json` holds the private inputs:
+//!
+//! ```json
+//! {
+//!   "nullifier": "0x…",
+//!   "secret": "0x…",
+//!   "pathElements": ["0x…", "0x…"],
+//!   "leafIndex": 12
+//! }
+//! ```
+//!
+//! `public.json` holds the public inputs, as passed to the pool contract:
+//!
+//! ```json
+//! {
+//!   "root": "0x…",
+//!   "nullifierHash": "0x…",
+//!   "extDataHash": "0x…",
+//!   "recipient": "0x<40 hex digits>",
+//!   "relayer": "0x<40 hex digits>",
+//!   "fee": "50000000000000000",
+//!   "refund": "0",
+//!   "amount": "100000000",
+//!   "assetId": "0x…"
+//! }
+//! ```
+//!
+//! Field elements are big-endian hex (see `codec::from_hex`), `pathElements` goes from the
+//! leaf up to the root, `leafIndex` is the position of the deposit in the tree, `fee` and
+//! `refund` are decimal amounts in wei, `amount` is the note's amount in units of 10^-9 of the
+//! asset (see `note::denomination_to_amount`) and `assetId` is the pool's asset (see
+//! `ext_data::asset_id`). Unknown keys are rejected.
+use crate::{
+    circuits::tornado::TornadoPublicInputs,
+    codec::{self, from_decimal, from_hex},
+    ext_data::address_to_field,
+};
+use halo2_proofs::halo2curves::ff::PrimeField;
+use serde::{Deserialize, Serialize};
+use std::{fmt, fs, io, path::Path};
+
+#[derive(Debug)]
+pub enum Error {
+    Io(io::Error),
+    Json(serde_json::Error),
+    InvalidValue {
+        key: &'static str,
+        error: codec::Error,
+    },
+    InvalidAddress(&'static str),
+    /// `pathElements` doesn't have one element per layer of the tree
+    WrongDepth {
+        expected: usize,
+        found: usize,
+    },
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::Io(e) => write!(f, "io error: {e}"),
+            Error::Json(e) => write!(f, "invalid json: {e}"),
+            Error::InvalidValue { key, error } => write!(f, "invalid {key}: {error}"),
+            Error::InvalidAddress(key) => {
+                write!(f, "{key} is not a 0x-prefixed, 40-digit address")
+            }
+            Error::WrongDepth { expected, found } => {
+                write!(f, "expected {expected} path elements, found {found}")
+            }
+        }
+    }
+}
+
+impl std::error::Error for Error {}
+
+impl From<io::Error> for Error {
+    fn from(e: io::Error) -> Self {
+        Error::Io(e)
+    }
+}
+
+impl From<serde_json::Error> for Error {
+    fn from(e: serde_json::Error) -> Self {
+        Error::Json(e)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", deny_unknown_fields)]
+pub struct WitnessFile {
+    pub nullifier: String,
+    pub secret: String,
+    pub path_elements: Vec<String>,
+    pub leaf_index: u64,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", deny_unknown_fields)]
+pub struct PublicFile {
+    pub root: String,
+    pub nullifier_hash: String,
+    pub ext_data_hash: String,
+    pub recipient: String,
+    pub relayer: String,
+    pub fee: String,
+    pub refund: String,
+    pub amount: String,
+    pub asset_id: String,
+}
+
+/// Private inputs of a withdrawal, parsed
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Witness<F> {
+    pub nullifier: F,
+    pub secret: F,
+    pub path_elements: Vec<F>,
+    pub leaf_index: u64,
+}
+
+fn field<F: PrimeField<Repr = [u8; 32]>>(key: &'static str, value: &str) -> Result<F, Error> {
+    from_hex(value).map_err(|error| Error::InvalidValue { key, error })
+}
+
+fn amount<F: PrimeField<Repr = [u8; 32]>>(key: &'static str, value: &str) -> Result<F, Error> {
+    from_decimal(value).map_err(|error| Error::InvalidValue { key, error })
+}
+
+fn address<F: PrimeField<Repr = [u8; 32]>>(key: &'static str, value: &str) -> Result<F, Error> {
+    let mut bytes = [0u8; 20];
+    value
+        .strip_prefix("0x")
+        .and_then(|digits| hex::decode_to_slice(digits, &mut bytes).ok())
+        .ok_or(Error::InvalidAddress(key))?;
+    Ok(address_to_field(&bytes))
+}
+
+impl WitnessFile {
+    pub fn read(path: impl AsRef<Path>) -> Result<Self, Error> {
+        Ok(serde_json::from_slice(&fs::read(path)?)?)
+    }
+
+    pub fn parse<F: PrimeField<Repr = [u8; 32]>>(&self) -> Result<Witness<F>, Error> {
+        Ok(Witness {
+            nullifier: field("nullifier", &self.nullifier)?,
+            secret: field("secret", &self.secret)?,
+            path_elements: self
+                .path_elements
+                .iter()
+                .map(|element| field("pathElements", element))
+                .collect::<Result<_, _>>()?,
+            leaf_index: self.leaf_index,
+        })
+    }
+}
+
+impl PublicFile {
+    pub fn read(path: impl AsRef<Path>) -> Result<Self, Error> {
+        Ok(serde_json::from_slice(&fs::read(path)?)?)
+    }
+
+    /// The public inputs of `TornadoCircuit`
+    pub fn public_inputs<F: PrimeField<Repr = [u8; 32]>>(
+        &self,
+    ) -> Result<TornadoPublicInputs<F>, Error> {
+        Ok(TornadoPublicInputs {
+            nullifier_hash: field("nullifierHash", &self.nullifier_hash)?,
+            root: field("root", &self.root)?,
+            ext_data_hash: field("extDataHash", &self.ext_data_hash)?,
+            recipient: address("recipient", &self.recipient)?,
+            relayer: address("relayer", &self.relayer)?,
+            fee: amount("fee", &self.fee)?,
+            refund: amount("refund", &self.refund)?,
+            amount: amount("amount", &self.amount)?,
+            asset_id: field("assetId", &self.asset_id)?,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Error, PublicFile, WitnessFile};
+    use crate::{circuits::tornado::TornadoPublicInputs, ext_data::address_to_field};
+    use halo2_proofs::halo2curves::bn256::Fr;
+
+    const WITNESS: &str = r#"{
+        "nullifier": "0x456",
+        "secret": "0xabc",
+        "pathElements": ["0x2", "0x5", "0x7"],
+        "leafIndex": 6
+    }"#;
+
+    const PUBLIC: &str = r#"{
+        "root": "0x1",
+        "nullifierHash": "0x2",
+        "extDataHash": "0x3",
+        "recipient": "0x1111111111111111111111111111111111111111",
+        "relayer": "0x0000000000000000000000000000000000000000",
+        "fee": "50",
+        "refund": "0",
+        "amount": "100000000",
+        "assetId": "0x4"
+    }"#;
+
+    #[test]
+    fn test_parse_witness() {
+        let file: WitnessFile = serde_json::from_str(WITNESS).unwrap();
+        let witness = file.parse::<Fr>().unwrap();
+        assert_eq!(witness.nullifier, Fr::from(0x456));
+        assert_eq!(witness.secret, Fr::from(0xabc));
+        assert_eq!(
+            witness.path_elements,
+            vec![Fr::from(2), Fr::from(5), Fr::from(7)]
+        );
+        assert_eq!(witness.leaf_index, 6);
+
+        let json = serde_json::to_string(&file).unwrap();
+        assert_eq!(serde_json::from_str::<WitnessFile>(&json).unwrap(), file);
+    }
+
+    #[test]
+    fn test_public_inputs() {
+        let file: PublicFile = serde_json::from_str(PUBLIC).unwrap();
+        assert_eq!(
+            file.public_inputs::<Fr>().unwrap(),
+            TornadoPublicInputs {
+                nullifier_hash: Fr::from(2),
+                root: Fr::from(1),
+                ext_data_hash: Fr::from(3),
+                recipient: address_to_field(&[0x11; 20]),
+                relayer: Fr::from(0),
+                fee: Fr::from(50),
+                refund: Fr::from(0),
+                amount: Fr::from(100_000_000),
+                asset_id: Fr::from(4),
+            }
+        );
+    }
+
+    #[test]
+    fn test_invalid_files() {
+        let unknown_key = PUBLIC.replace("\"refund\"", "\"refnud\"");
+        assert!(serde_json::from_str::<PublicFile>(&unknown_key).is_err());
+
+        let mut file: PublicFile = serde_json::from_str(PUBLIC).unwrap();
+        file.recipient = "0x1111".to_string();
+        assert!(matches!(
+            file.public_inputs::<Fr>(),
+            Err(Error::InvalidAddress("recipient"))
+        ));
+
+        let mut file: PublicFile = serde_json::from_str(PUBLIC).unwrap();
+        file.fee = "0x32".to_string();
+        assert!(matches!(
+            file.public_inputs::<Fr>(),
+            Err(Error::InvalidValue { key: "fee", .. })
+        ));
+
+        let mut file: WitnessFile = serde_json::from_str(WITNESS).unwrap();
+        file.secret = "not hex".to_string();
+        assert!(matches!(
+            file.parse::<Fr>(),
+            Err(Error::InvalidValue { key: "secret", .. })
+        ));
+    }
+}
@@ -0,0 +1,133 @@
+//! Native computation of the values `TornadoCircuit` assigns, ahead of the layouter.
+//!
+//! The floor planners run each region closure twice (once to measure it, once to assign
+//! it), and a chip computing its digest in the closure would hash every value twice. With a
+//! real hash, a deep tree makes the witness the bottleneck, so `synthesize` computes every
+//! digest once here and the chips only copy them in.
+use crate::{
+    chips::tornado::WithdrawalInputs,
+    circuits::{merkle::MerklePathWitness, tornado::TornadoCircuit},
+    tree::{Hasher, ProductHasher},
+};
+use halo2_proofs::{circuit::Value, halo2curves::ff::PrimeField};
+use std::marker::PhantomData;
+
+/// Every digest `TornadoCircuit` assigns
+#[derive(Debug, Clone, Copy)]
+pub struct TornadoWitness<F, const DEPTH: usize> {
+    /// `H(nullifier, nullifier)`
+    pub nullifier_hash: Value<F>,
+    /// `H(nullifier, secret)`, then hashed with the amount, then with the asset id: the last
+    /// one is the commitment
+    pub commitment: [Value<F>; 3],
+    /// Digest of each layer of the Merkle path, from the leaf's parent up to the root
+    pub path_digests: [Value<F>; DEPTH],
+}
+
+/// Computes a `TornadoWitness` with `H`, which must be the hash the `HashChip` gate
+/// constrains. The nullifier hash is computed on the rayon pool alongside the commitment and
+/// the Merkle path, which are a chain: each digest is an input of the next.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct WitnessCalculator<H = ProductHasher> {
+    _marker: PhantomData<H>,
+}
+
+impl<H> WitnessCalculator<H> {
+    pub fn compute<F: PrimeField, const DEPTH: usize, P>(
+        circuit: &TornadoCircuit<F, DEPTH, P>,
+    ) -> TornadoWitness<F, DEPTH>
+    where
+        H: Hasher<F>,
+    {
+        Self::compute_inputs(&circuit.to_inputs())
+    }
+
+    /// The digests `TornadoGadget::assert_valid_withdrawal` assigns. With a binding, the path
+    /// digests are those of the unbound commitment, and go unused.
+    pub fn compute_inputs<F: PrimeField, const DEPTH: usize>(
+        inputs: &WithdrawalInputs<F, DEPTH>,
+    ) -> TornadoWitness<F, DEPTH>
+    where
+        H: Hasher<F>,
+    {
+        let hash = |left: Value<F>, right: Value<F>| left.zip(right).map(|(l, r)| H::hash(l, r));
+        let (nullifier_hash, (commitment, path_digests)) = rayon::join(
+            || hash(inputs.nullifier, inputs.nullifier),
+            || {
+                let inner = hash(inputs.nullifier, inputs.secret);
+                let with_amount = hash(inner, inputs.amount);
+                let with_asset_id = hash(with_amount, inputs.asset_id);
+                let path_digests = Self::path_digests(with_asset_id, &inputs.path);
+                ([inner, with_amount, with_asset_id], path_digests)
+            },
+        );
+        TornadoWitness {
+            nullifier_hash,
+            commitment,
+            path_digests,
+        }
+    }
+
+    /// The digests `MerkleChip::prove_tree_root` computes: the node is on the right of its
+    /// sibling where the bit of `leaf_index`, least significant first, is set
+    fn path_digests<F: PrimeField, const DEPTH: usize>(
+        leaf: Value<F>,
+        path: &MerklePathWitness<F, DEPTH>,
+    ) -> [Value<F>; DEPTH]
+    where
+        H: Hasher<F>,
+    {
+        let mut node = leaf;
+        let mut z = path.leaf_index;
+        path.path_elements.map(|sibling| {
+            let bit = z.map(|z| bool::from(z.is_odd()));
+            z = z
+                .zip(bit)
+                .map(|(z, bit)| (z - F::from(bit as u64)) * F::TWO_INV);
+            node = node.zip(sibling).zip(bit).map(|((node, sibling), bit)| {
+                if bit {
+                    H::hash(sibling, node)
+                } else {
+                    H::hash(node, sibling)
+                }
+            });
+            node
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::WitnessCalculator;
+    use crate::{
+        circuits::tornado::TornadoCircuit,
+        note::Note,
+        tree::{IncrementalMerkleTree, MerkleProof, ProductHasher},
+    };
+    use halo2_proofs::halo2curves::pasta::Fp;
+
+    #[test]
+    fn test_witness_calculator() {
+        let note = Note::<Fp>::new(Fp::from(0x456), Fp::from(0xabc), "eth", "0.1", 1).unwrap();
+        let mut tree = IncrementalMerkleTree::new(5);
+        for leaf in [3, 5, 7, 11, 13] {
+            tree.insert(Fp::from(leaf)).unwrap();
+        }
+        let index = tree.insert(note.commitment()).unwrap();
+        let merkle_proof: MerkleProof<Fp> = tree.proof(index).unwrap().into();
+        let circuit = TornadoCircuit::<Fp, 5>::builder(&note, merkle_proof)
+            .build()
+            .unwrap();
+
+        let witness = WitnessCalculator::<ProductHasher>::compute(&circuit);
+        witness
+            .nullifier_hash
+            .assert_if_known(|hash| *hash == note.nullifier_hash());
+        witness.commitment[2].assert_if_known(|commitment| *commitment == note.commitment());
+        witness.path_digests[4].assert_if_known(|root| *root == tree.root());
+
+        let unknown =
+            WitnessCalculator::<ProductHasher>::compute(&TornadoCircuit::<Fp, 5>::default());
+        unknown.path_digests[4].assert_if_known(|_| false);
+    }
+}